@@ -0,0 +1,89 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mini_async_http::RequestParser;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Allocator counting the allocations, to follow the ones made by the parser
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn request_with_body(size: usize) -> Vec<u8> {
+    let mut request = format!(
+        "POST /bench HTTP/1.1\r\nHost: localhost:8080\r\nUser-Agent: criterion\r\nAccept: */*\r\nContent-Length: {}\r\n\r\n",
+        size
+    )
+    .into_bytes();
+    request.extend(vec![b'a'; size]);
+
+    request
+}
+
+fn parse_sizes(c: &mut Criterion) {
+    let parser = RequestParser::new();
+    let mut group = c.benchmark_group("parse_u8");
+
+    for size in [0, 1024, 16 * 1024, 256 * 1024].iter() {
+        let input = request_with_body(*size);
+
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| parser.parse_u8(input).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+/// Report the number of allocations needed to parse a request, criterion only measures the time
+fn parse_allocations(_: &mut Criterion) {
+    let parser = RequestParser::new();
+    let input = request_with_body(64);
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let request = parser.parse_u8(&input).unwrap();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    drop(request);
+
+    println!("parse_u8/allocations: {} per request", allocations);
+}
+
+fn parse_pipelined(c: &mut Criterion) {
+    let parser = RequestParser::new();
+    let mut group = c.benchmark_group("parse_u8_pipelined");
+
+    for count in [1, 16, 128].iter() {
+        let input: Vec<u8> = (0..*count).flat_map(|_| request_with_body(64)).collect();
+
+        group.throughput(Throughput::Elements(*count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &input, |b, input| {
+            b.iter(|| {
+                let mut offset = 0;
+                while offset < input.len() {
+                    let (_, consumed) = parser.parse_u8(&input[offset..]).unwrap();
+                    offset += consumed;
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse_allocations, parse_sizes, parse_pipelined);
+criterion_main!(benches);
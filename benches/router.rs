@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mini_async_http::{Method, RequestBuilder, ResponseBuilder, Route, Router, Version};
+
+fn router_with_routes(count: usize) -> Router {
+    let mut router = Router::new();
+
+    for i in 0..count {
+        let route = Route::new(&format!("/resource{}/{{id}}", i), Method::GET).unwrap();
+        router.add_route(route, |_, _| ResponseBuilder::empty_200().build().unwrap());
+    }
+
+    router
+}
+
+fn request(path: String) -> mini_async_http::Request {
+    RequestBuilder::new()
+        .method(Method::GET)
+        .path(path)
+        .version(Version::HTTP11)
+        .build()
+        .unwrap()
+}
+
+fn exec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("router_exec");
+
+    for count in [1, 10, 100, 1000].iter() {
+        let router = router_with_routes(*count);
+
+        let first = request(String::from("/resource0/42"));
+        let last = request(format!("/resource{}/42", count - 1));
+        let missing = request(String::from("/missing"));
+
+        group.bench_with_input(BenchmarkId::new("first", count), &first, |b, req| {
+            b.iter(|| router.exec(req))
+        });
+        group.bench_with_input(BenchmarkId::new("last", count), &last, |b, req| {
+            b.iter(|| router.exec(req))
+        });
+        group.bench_with_input(BenchmarkId::new("not_found", count), &missing, |b, req| {
+            b.iter(|| router.exec(req))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, exec);
+criterion_main!(benches);
@@ -0,0 +1,108 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mini_async_http::test::TestServer;
+use mini_async_http::ResponseBuilder;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const REQUEST: &[u8] = b"GET /bench HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+/// Send one request on the stream and return the length of the whole answer,
+/// responses of the bench server always have the same length
+fn response_len(stream: &mut TcpStream) -> usize {
+    stream.write_all(REQUEST).unwrap();
+
+    let mut buffer = [0; 1024];
+    let mut received = Vec::new();
+    loop {
+        let n = stream.read(&mut buffer).unwrap();
+        received.extend_from_slice(&buffer[..n]);
+
+        if received.ends_with(b"Hello") {
+            return received.len();
+        }
+    }
+}
+
+fn throughput(c: &mut Criterion) {
+    let server = TestServer::new(|_| {
+        ResponseBuilder::empty_200()
+            .body(b"Hello")
+            .header("Content-Length", "5")
+            .build()
+            .unwrap()
+    });
+
+    let mut group = c.benchmark_group("server");
+
+    for pipelined in [1, 16].iter() {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        let expected = response_len(&mut stream) * pipelined;
+        let requests: Vec<u8> = REQUEST.repeat(*pipelined);
+        let mut responses = vec![0; expected];
+
+        group.throughput(Throughput::Elements(*pipelined as u64));
+        group.bench_function(BenchmarkId::new("keep_alive", pipelined), |b| {
+            b.iter(|| {
+                stream.write_all(&requests).unwrap();
+                stream.read_exact(&mut responses).unwrap();
+            })
+        });
+    }
+
+    group.bench_function("new_connection", |b| {
+        b.iter(|| {
+            let mut stream = TcpStream::connect(server.addr()).unwrap();
+            response_len(&mut stream)
+        })
+    });
+
+    group.finish();
+}
+
+/// Latency of a single request while another connection keeps the server busy with pipelined requests
+fn fairness(c: &mut Criterion) {
+    let server = TestServer::new(|_| {
+        ResponseBuilder::empty_200()
+            .body(b"Hello")
+            .header("Content-Length", "5")
+            .build()
+            .unwrap()
+    });
+
+    let running = Arc::new(AtomicBool::new(true));
+    let mut flooder = TcpStream::connect(server.addr()).unwrap();
+    let expected = response_len(&mut flooder) * 128;
+    let flooding = running.clone();
+    let flood = std::thread::spawn(move || {
+        let requests = REQUEST.repeat(128);
+        let mut responses = vec![0; expected];
+
+        while flooding.load(Ordering::Relaxed) {
+            flooder.write_all(&requests).unwrap();
+            flooder.read_exact(&mut responses).unwrap();
+        }
+    });
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let len = response_len(&mut stream);
+    let mut response = vec![0; len];
+
+    c.bench_function("server/latency_under_pipelining", |b| {
+        b.iter(|| {
+            stream.write_all(REQUEST).unwrap();
+            stream.read_exact(&mut response).unwrap();
+        })
+    });
+
+    running.store(false, Ordering::Relaxed);
+    flood.join().unwrap();
+}
+
+criterion_group!(benches, throughput, fairness);
+criterion_main!(benches);
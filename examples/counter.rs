@@ -1,25 +1,29 @@
 extern crate mini_async_http;
 
-use mini_async_http::AIOServer;
-use mini_async_http::ResponseBuilder;
-use std::sync::{Arc, Mutex};
+use mini_async_http::sync::AsyncMutex;
+use mini_async_http::{AIOServer, AsyncMiddleware, ResponseBuilder};
+use std::sync::Arc;
 
 pub fn main() {
-    let counter = Arc::from(Mutex::from(0));
+    let counter = Arc::new(AsyncMutex::new(0));
 
-    let mut server = AIOServer::new("0.0.0.0:7878".parse().unwrap(), move |_request| {
-        let lock = counter.clone();
-        let mut counter = lock.lock().unwrap();
+    let mut server = AIOServer::new("0.0.0.0:7878".parse().unwrap(), |_request| {
+        ResponseBuilder::empty_404().build().unwrap()
+    });
 
-        let body = counter.to_string();
-        *counter += 1;
+    // The counter is awaited so the requests waiting for it do not block the workers
+    server.add_middleware(AsyncMiddleware::new(move |_request, _next| {
+        let counter = counter.clone();
 
-        ResponseBuilder::empty_200()
-            .body(body.as_bytes())
-            .content_type("text/plain")
-            .build()
-            .unwrap()
-    });
+        Box::pin(async move {
+            let mut counter = counter.lock().await;
+
+            let body = counter.to_string();
+            *counter += 1;
+
+            ResponseBuilder::empty_200().text(&body).build().unwrap()
+        })
+    }));
 
     server.start();
 }
@@ -5,11 +5,7 @@ use mini_async_http::ResponseBuilder;
 
 pub fn main() {
     let mut server = AIOServer::new("0.0.0.0:7878".parse().unwrap(), move |_request| {
-        ResponseBuilder::empty_200()
-            .body(b"Hello")
-            .content_type("text/plain")
-            .build()
-            .unwrap()
+        ResponseBuilder::empty_200().text("Hello").build().unwrap()
     });
 
     server.start();
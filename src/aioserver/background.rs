@@ -0,0 +1,54 @@
+use crate::io::context;
+
+use futures::channel::oneshot;
+use futures::future::{BoxFuture, FutureExt};
+
+use std::sync::Arc;
+
+/// Job run in the background while the server is running, see [`AIOServer::add_background_job`]
+pub(crate) type Job = Arc<dyn Send + Sync + 'static + Fn() -> BoxFuture<'static, ()>>;
+
+/// Background jobs started with the server, each can be stopped at its next await
+pub(crate) struct RunningJobs {
+    jobs: Vec<(oneshot::Sender<()>, oneshot::Receiver<()>)>,
+}
+
+impl RunningJobs {
+    /// Spawn a new run of every job on the executor
+    pub(crate) fn start(jobs: &[Job]) -> RunningJobs {
+        let jobs = jobs
+            .iter()
+            .map(|job| {
+                let (stop, stopped) = oneshot::channel::<()>();
+                let (done, finished) = oneshot::channel();
+                let job = job();
+
+                context::spawn(async move {
+                    futures::select! {
+                        _ = job.fuse() => {},
+                        _ = stopped.fuse() => {},
+                    }
+                    let _ = done.send(());
+                });
+
+                (stop, finished)
+            })
+            .collect();
+
+        RunningJobs { jobs }
+    }
+
+    /// Stop the jobs still running and wait for them to be dropped
+    pub(crate) async fn stop(self) {
+        let finished: Vec<_> = self
+            .jobs
+            .into_iter()
+            .map(|(stop, finished)| {
+                let _ = stop.send(());
+                finished
+            })
+            .collect();
+
+        futures::future::join_all(finished).await;
+    }
+}
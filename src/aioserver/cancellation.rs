@@ -0,0 +1,228 @@
+use crate::io::delay::Delay;
+
+use futures::FutureExt;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+/// Signal telling a long running handler or stream to stop, checked with [`is_cancelled`] or awaited with [`cancelled`].
+///
+/// The server gives a token to each connection, cancelled when the server is shut down and when the connection
+/// is closed, for instance once the client is gone. While a request is handled, [`Request::cancellation`] returns
+/// the token of its connection, also cancelled once the deadline of the request is passed.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::CancellationToken;
+///
+/// let token = CancellationToken::new();
+/// let child = token.child_token();
+///
+/// token.cancel();
+/// assert!(child.is_cancelled());
+/// futures::executor::block_on(child.cancelled());
+/// ```
+///
+/// [`is_cancelled`]: #method.is_cancelled
+/// [`cancelled`]: #method.cancelled
+/// [`Request::cancellation`]: struct.Request.html#method.cancellation
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+    deadline: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    waiters: Mutex<Waiters>,
+}
+
+#[derive(Default)]
+struct Waiters {
+    wakers: Vec<Waker>,
+    children: Vec<Weak<Inner>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let waiters = std::mem::take(&mut *self.waiters.lock().unwrap());
+        waiters.wakers.into_iter().for_each(Waker::wake);
+        waiters
+            .children
+            .iter()
+            .filter_map(Weak::upgrade)
+            .for_each(|child| child.cancel());
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn poll_cancelled(&self, cx: &mut Context) -> Poll<()> {
+        if self.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut waiters = self.waiters.lock().unwrap();
+        // The token may be cancelled while the lock was taken
+        if self.is_cancelled() {
+            return Poll::Ready(());
+        }
+        if !waiters
+            .wakers
+            .iter()
+            .any(|waker| waker.will_wake(cx.waker()))
+        {
+            waiters.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancel the token and its children, the tasks awaiting them are woken
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    /// Return true if the token is cancelled or its deadline is passed
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+            || matches!(self.deadline, Some(deadline) if Instant::now() >= deadline)
+    }
+
+    /// Wait until the token is cancelled or its deadline is passed
+    pub async fn cancelled(&self) {
+        let cancelled = futures::future::poll_fn(|cx| self.inner.poll_cancelled(cx)).fuse();
+        futures::pin_mut!(cancelled);
+
+        match self.deadline {
+            Some(deadline) => {
+                let mut expired = Delay::until(deadline).fuse();
+                futures::select! {
+                    _ = cancelled => {},
+                    _ = expired => {},
+                }
+            }
+            None => cancelled.await,
+        }
+    }
+
+    /// Create a token cancelled with this one, which can also be cancelled on its own
+    pub fn child_token(&self) -> CancellationToken {
+        let child = Arc::new(Inner::default());
+
+        let mut waiters = self.inner.waiters.lock().unwrap();
+        if self.inner.is_cancelled() {
+            child.cancelled.store(true, Ordering::SeqCst);
+        } else {
+            // The children already dropped are forgotten so a long lived token does not grow
+            waiters.children.retain(|child| child.strong_count() > 0);
+            waiters.children.push(Arc::downgrade(&child));
+        }
+
+        CancellationToken {
+            inner: child,
+            deadline: self.deadline,
+        }
+    }
+
+    /// Return a token sharing the cancellation of this one that is also cancelled at the given instant,
+    /// unless its deadline is earlier
+    pub fn with_deadline(&self, deadline: Instant) -> CancellationToken {
+        CancellationToken {
+            inner: self.inner.clone(),
+            deadline: Some(
+                self.deadline
+                    .map_or(deadline, |current| current.min(deadline)),
+            ),
+        }
+    }
+
+    /// Return the instant the token is cancelled at, if any
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Cancel the token when the returned guard is dropped
+    pub(crate) fn drop_guard(self) -> CancelOnDrop {
+        CancelOnDrop(self)
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.inner.is_cancelled())
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+/// Cancel a token once dropped, such as when a connection task ends
+pub(crate) struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+    use std::time::Duration;
+
+    #[test]
+    fn cancel_children() {
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+
+        child.cancel();
+        assert!(!parent.is_cancelled());
+        assert!(grandchild.is_cancelled());
+
+        let waiting = parent.child_token();
+        let thread = std::thread::spawn(move || block_on(waiting.cancelled()));
+        std::thread::sleep(Duration::from_millis(20));
+        parent.cancel();
+        thread.join().unwrap();
+
+        assert!(parent.child_token().is_cancelled());
+
+        let token = CancellationToken::new();
+        drop(token.clone().drop_guard());
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn deadline() {
+        let token = CancellationToken::new();
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let limited = token.with_deadline(deadline);
+
+        assert!(!limited.is_cancelled());
+        block_on(limited.cancelled());
+        assert!(Instant::now() >= deadline);
+        assert!(limited.is_cancelled());
+        assert!(!token.is_cancelled());
+
+        let earlier = limited.with_deadline(deadline + Duration::from_secs(1));
+        assert_eq!(earlier.deadline(), Some(deadline));
+    }
+}
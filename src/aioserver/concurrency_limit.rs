@@ -0,0 +1,152 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Factor applied to the limit when the latency exceeds the target
+const BACKOFF: f64 = 0.9;
+
+/// Snapshot of the adaptive concurrency limit of a server, see [`ServerHandle::concurrency_stats`]
+///
+/// [`ServerHandle::concurrency_stats`]: struct.ServerHandle.html#method.concurrency_stats
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConcurrencyStats {
+    limit: usize,
+    in_flight: usize,
+    shed: u64,
+}
+
+impl ConcurrencyStats {
+    /// Return the number of handlers allowed to run at once
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Return the number of handlers running
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Return the number of requests answered with a 503 status code because the limit was reached
+    pub fn shed(&self) -> u64 {
+        self.shed
+    }
+}
+
+/// Limit of the handlers running at once, adjusted with their latency.
+/// The limit grows by one for every limit requests answered within the target and shrinks by 10%
+/// when a request is slower, at most once per target so a burst of slow requests does not collapse it.
+pub(crate) struct ConcurrencyLimiter {
+    target: Duration,
+    max: usize,
+    state: Mutex<LimitState>,
+}
+
+struct LimitState {
+    limit: f64,
+    in_flight: usize,
+    shed: u64,
+    backed_off: Instant,
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(target: Duration, max: usize) -> ConcurrencyLimiter {
+        let max = max.max(1);
+
+        ConcurrencyLimiter {
+            target,
+            max,
+            state: Mutex::new(LimitState {
+                limit: max as f64,
+                in_flight: 0,
+                shed: 0,
+                backed_off: Instant::now(),
+            }),
+        }
+    }
+
+    /// Count a new handler, None if the limit is reached
+    pub(crate) fn acquire(self: &Arc<Self>) -> Option<Permit> {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight >= state.limit as usize {
+            state.shed += 1;
+            return None;
+        }
+        state.in_flight += 1;
+
+        Some(Permit {
+            limiter: self.clone(),
+            start: Instant::now(),
+        })
+    }
+
+    fn release(&self, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+
+        if latency <= self.target {
+            state.limit = (state.limit + 1.0 / state.limit).min(self.max as f64);
+        } else if state.backed_off.elapsed() >= self.target {
+            state.limit = (state.limit * BACKOFF).max(1.0);
+            state.backed_off = Instant::now();
+        }
+    }
+
+    pub(crate) fn stats(&self) -> ConcurrencyStats {
+        let state = self.state.lock().unwrap();
+
+        ConcurrencyStats {
+            limit: state.limit as usize,
+            in_flight: state.in_flight,
+            shed: state.shed,
+        }
+    }
+}
+
+/// Count a handler against the limit until it is dropped, its latency adjusts the limit
+pub(crate) struct Permit {
+    limiter: Arc<ConcurrencyLimiter>,
+    start: Instant,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release(self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shed_over_limit() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(Duration::from_secs(1), 2));
+
+        let first = limiter.acquire().unwrap();
+        let _second = limiter.acquire().unwrap();
+        assert!(limiter.acquire().is_none());
+        assert_eq!(limiter.stats().shed(), 1);
+
+        drop(first);
+        assert_eq!(limiter.stats().in_flight(), 1);
+        assert!(limiter.acquire().is_some());
+    }
+
+    #[test]
+    fn adapt_to_latency() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(Duration::from_millis(10), 20));
+
+        let first = limiter.acquire().unwrap();
+        let second = limiter.acquire().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        drop(first);
+        assert_eq!(limiter.stats().limit(), 18);
+        // The requests slowed down at the same time only shrink the limit once
+        drop(second);
+        assert_eq!(limiter.stats().limit(), 18);
+
+        for _ in 0..60 {
+            drop(limiter.acquire().unwrap());
+        }
+        assert_eq!(limiter.stats().limit(), 20);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::aioserver::enhanced_stream::{
+    DEFAULT_BATCH_LIMIT, DEFAULT_MAX_HEADERS, DEFAULT_MAX_HEAD_SIZE,
+};
+use crate::http::Headers;
+
+use log::LevelFilter;
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Settings of a server that can be changed while it is running, applied with [`AIOServer::set_config`]
+/// and replaced at runtime with [`ServerHandle::reload_config`].
+///
+/// The limits of the request heads and of the pipelines apply to the connections accepted after a reload,
+/// the other settings to the requests received after it.
+/// With the `config` feature, the configuration can be read from a JSON file, see [`from_json`].
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::ServerConfig;
+/// use std::time::Duration;
+///
+/// let config = ServerConfig::new()
+///     .request_timeout(Duration::from_secs(30))
+///     .max_requests_per_connection(Some(1000))
+///     .default_header("Server", "mini-async-http");
+/// ```
+///
+/// [`AIOServer::set_config`]: struct.AIOServer.html#method.set_config
+/// [`ServerHandle::reload_config`]: struct.ServerHandle.html#method.reload_config
+/// [`from_json`]: #method.from_json
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub(crate) request_timeout: Option<Duration>,
+    pub(crate) slow_request: Option<Duration>,
+    pub(crate) pipeline_limit: usize,
+    pub(crate) max_requests: Option<usize>,
+    pub(crate) max_head_size: usize,
+    pub(crate) max_headers: usize,
+    pub(crate) default_headers: Headers,
+    pub(crate) log_level: Option<LevelFilter>,
+}
+
+impl ServerConfig {
+    /// Create the configuration of a server with the default settings
+    pub fn new() -> ServerConfig {
+        ServerConfig {
+            request_timeout: None,
+            slow_request: None,
+            pipeline_limit: DEFAULT_BATCH_LIMIT,
+            max_requests: None,
+            max_head_size: DEFAULT_MAX_HEAD_SIZE,
+            max_headers: DEFAULT_MAX_HEADERS,
+            default_headers: Headers::new(),
+            log_level: None,
+        }
+    }
+
+    /// See [`AIOServer::set_request_timeout`](struct.AIOServer.html#method.set_request_timeout)
+    pub fn request_timeout(mut self, timeout: Duration) -> ServerConfig {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`AIOServer::set_slow_request_threshold`](struct.AIOServer.html#method.set_slow_request_threshold)
+    pub fn slow_request_threshold(mut self, threshold: Duration) -> ServerConfig {
+        self.slow_request = Some(threshold);
+        self
+    }
+
+    /// See [`AIOServer::set_pipeline_limit`](struct.AIOServer.html#method.set_pipeline_limit)
+    pub fn pipeline_limit(mut self, limit: usize) -> ServerConfig {
+        self.pipeline_limit = limit.max(1);
+        self
+    }
+
+    /// See [`AIOServer::set_max_requests_per_connection`](struct.AIOServer.html#method.set_max_requests_per_connection)
+    pub fn max_requests_per_connection(mut self, max: Option<usize>) -> ServerConfig {
+        self.max_requests = max.map(|max| max.max(1));
+        self
+    }
+
+    /// See [`AIOServer::set_max_header_size`](struct.AIOServer.html#method.set_max_header_size)
+    pub fn max_header_size(mut self, size: usize) -> ServerConfig {
+        self.max_head_size = size;
+        self
+    }
+
+    /// See [`AIOServer::set_max_headers`](struct.AIOServer.html#method.set_max_headers)
+    pub fn max_headers(mut self, max: usize) -> ServerConfig {
+        self.max_headers = max;
+        self
+    }
+
+    /// See [`AIOServer::set_default_header`](struct.AIOServer.html#method.set_default_header)
+    pub fn default_header(mut self, name: &str, value: &str) -> ServerConfig {
+        self.default_headers.set_header(name, value);
+        self
+    }
+
+    /// Set the maximum level of the events logged through the `log` crate by the whole process,
+    /// left unchanged by default
+    pub fn log_level(mut self, level: LevelFilter) -> ServerConfig {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Read the configuration from JSON, the missing settings keep their default value.
+    /// The durations are given in milliseconds and the log level by name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::ServerConfig;
+    ///
+    /// let config = ServerConfig::from_json(r#"{
+    ///     "request_timeout_ms": 30000,
+    ///     "max_requests_per_connection": 1000,
+    ///     "default_headers": { "Server": "mini-async-http" },
+    ///     "log_level": "info"
+    /// }"#).unwrap();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn from_json(json: &str) -> Result<ServerConfig, ConfigError> {
+        let file: file::ConfigFile =
+            serde_json::from_str(json).map_err(|e| ConfigError::Parse(e.to_string()))?;
+
+        file.into_config()
+    }
+
+    /// Read the configuration from a JSON file, see [`from_json`]
+    ///
+    /// [`from_json`]: #method.from_json
+    #[cfg(feature = "config")]
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<ServerConfig, ConfigError> {
+        let json = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+
+        ServerConfig::from_json(&json)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig::new()
+    }
+}
+
+/// Error reading a [`ServerConfig`]
+///
+/// [`ServerConfig`]: struct.ServerConfig.html
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read
+    Io(std::io::Error),
+    /// The content is not a valid configuration
+    Parse(String),
+}
+
+#[cfg(feature = "config")]
+mod file {
+    use super::{ConfigError, ServerConfig};
+
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    /// Content of a configuration file, the settings are optional
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct ConfigFile {
+        request_timeout_ms: Option<u64>,
+        slow_request_threshold_ms: Option<u64>,
+        pipeline_limit: Option<usize>,
+        max_requests_per_connection: Option<usize>,
+        max_header_size: Option<usize>,
+        max_headers: Option<usize>,
+        #[serde(default)]
+        default_headers: BTreeMap<String, String>,
+        log_level: Option<String>,
+    }
+
+    impl ConfigFile {
+        pub(super) fn into_config(self) -> Result<ServerConfig, ConfigError> {
+            let mut config = ServerConfig::new();
+
+            if let Some(timeout) = self.request_timeout_ms {
+                config = config.request_timeout(Duration::from_millis(timeout));
+            }
+            if let Some(threshold) = self.slow_request_threshold_ms {
+                config = config.slow_request_threshold(Duration::from_millis(threshold));
+            }
+            if let Some(limit) = self.pipeline_limit {
+                config = config.pipeline_limit(limit);
+            }
+            if let Some(max) = self.max_requests_per_connection {
+                config = config.max_requests_per_connection(Some(max));
+            }
+            if let Some(size) = self.max_header_size {
+                config = config.max_header_size(size);
+            }
+            if let Some(max) = self.max_headers {
+                config = config.max_headers(max);
+            }
+            for (name, value) in &self.default_headers {
+                config = config.default_header(name, value);
+            }
+            if let Some(level) = self.log_level {
+                let level = level
+                    .parse()
+                    .map_err(|_| ConfigError::Parse(format!("unknown log level {}", level)))?;
+                config = config.log_level(level);
+            }
+
+            Ok(config)
+        }
+    }
+}
+
+/// Configuration of a running server, shared by its connections and its handle
+pub(crate) struct LiveConfig {
+    current: RwLock<Arc<ServerConfig>>,
+}
+
+impl LiveConfig {
+    pub(crate) fn new(config: ServerConfig) -> LiveConfig {
+        LiveConfig {
+            current: RwLock::new(Arc::new(config)),
+        }
+    }
+
+    /// Return the configuration in effect, the following reloads do not change it
+    pub(crate) fn get(&self) -> Arc<ServerConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replace the configuration, the log level is applied right away
+    pub(crate) fn set(&self, config: ServerConfig) {
+        if let Some(level) = config.log_level {
+            log::set_max_level(level);
+        }
+        *self.current.write().unwrap() = Arc::new(config);
+    }
+
+    /// Change a setting of the configuration
+    pub(crate) fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut ServerConfig),
+    {
+        let mut current = self.current.write().unwrap();
+        f(Arc::make_mut(&mut current));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reload() {
+        let live = LiveConfig::new(ServerConfig::new());
+        let before = live.get();
+
+        live.update(|config| config.pipeline_limit = 4);
+        live.set(ServerConfig::new().default_header("Server", "test"));
+
+        assert_eq!(before.pipeline_limit, DEFAULT_BATCH_LIMIT);
+        let after = live.get();
+        assert_eq!(after.pipeline_limit, DEFAULT_BATCH_LIMIT);
+        assert!(after.default_headers.get_header("Server").is_some());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn from_json() {
+        let config = ServerConfig::from_json(
+            r#"{"request_timeout_ms": 1500, "max_headers": 10, "default_headers": {"Server": "test"}, "log_level": "warn"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.request_timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(config.max_headers, 10);
+        assert_eq!(config.pipeline_limit, DEFAULT_BATCH_LIMIT);
+        assert_eq!(config.log_level, Some(LevelFilter::Warn));
+        assert!(config.default_headers.get_header("Server").is_some());
+
+        assert!(matches!(
+            ServerConfig::from_json(r#"{"log_level": "loud"}"#),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            ServerConfig::from_json(r#"{"timeout": 3}"#),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::executor::task_local;
+use crate::io::blocking;
+use crate::io::delay::Delay;
+use crate::response::{Response, ResponseBuilder};
+
+use futures::channel::oneshot;
+use futures::FutureExt;
+
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+
+/// Time limit of the request being handled, from the request timeout of the server or the timeout of the matching route.
+/// It is stored in the task locals and can be retrieved with [`task_local::get`] or [`Request::deadline`].
+///
+/// [`task_local::get`]: task_local/fn.get.html
+/// [`Request::deadline`]: struct.Request.html#method.deadline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadline {
+    received: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    pub(crate) fn new(received: Instant, timeout: Duration) -> Deadline {
+        Deadline { received, timeout }
+    }
+
+    /// Return the instant the response is due
+    pub fn at(&self) -> Instant {
+        self.received + self.timeout
+    }
+
+    /// Return the time left before the deadline, zero once it is reached.
+    /// Handlers can give it as timeout to their own downstream calls.
+    pub fn remaining(&self) -> Duration {
+        self.at().saturating_duration_since(Instant::now())
+    }
+
+    /// Return true if the deadline is passed, the response will be replaced by a 503 status code
+    pub fn expired(&self) -> bool {
+        Instant::now() > self.at()
+    }
+}
+
+/// Response sent instead of the one of a handler that missed its deadline
+pub(crate) fn timeout_response() -> Response {
+    ResponseBuilder::empty_503().build().unwrap()
+}
+
+/// Run a handler under the timeout of its route, which replaces the one of the server.
+/// The timeout counts from the reception of the request. The handler runs on a blocking thread,
+/// with the task locals of the request, raced against a timer: a 503 is answered once the deadline
+/// is reached, the handler is not interrupted and its response is dropped when it comes.
+pub(crate) async fn with_timeout<F>(timeout: Duration, handler: F) -> Response
+where
+    F: Send + 'static + FnOnce() -> Response,
+{
+    let previous = task_local::get::<Deadline>();
+    let received = previous.map_or_else(Instant::now, |deadline| deadline.received);
+    let deadline = Deadline::new(received, timeout);
+    task_local::set(deadline);
+
+    let (sender, receiver) = oneshot::channel();
+    let locals = task_local::current();
+    blocking::spawn(move || {
+        // The deadline was reached while every blocking thread was busy
+        if sender.is_canceled() {
+            return;
+        }
+
+        let handler = AssertUnwindSafe(handler);
+        let responded = std::panic::catch_unwind(move || match locals {
+            Some(ref locals) => task_local::enter(locals, handler.0),
+            None => (handler.0)(),
+        });
+        // The deadline may be reached already
+        let _ = sender.send(responded);
+    });
+
+    let mut responded = receiver.fuse();
+    let mut expired = Delay::until(deadline.at()).fuse();
+    let response = futures::select! {
+        responded = responded => match responded {
+            Ok(Ok(response)) => response,
+            // The panic goes on in the task, as if the handler had run in it
+            Ok(Err(panic)) => std::panic::resume_unwind(panic),
+            Err(_) => timeout_response(),
+        },
+        _ = expired => return timeout_response(),
+    };
+
+    if response.is_pass() {
+        // The next matching route runs under its own timeout or the one of the server
+        match previous {
+            Some(previous) => {
+                task_local::set(previous);
+            }
+            None => {
+                task_local::remove::<Deadline>();
+            }
+        }
+        return response;
+    }
+
+    if deadline.expired() {
+        return timeout_response();
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn expiration() {
+        let deadline = Deadline::new(Instant::now(), Duration::from_secs(60));
+        assert!(!deadline.expired());
+
+        let deadline = Deadline::new(
+            Instant::now() - Duration::from_secs(2),
+            Duration::from_secs(1),
+        );
+        assert!(deadline.expired());
+        assert_eq!(deadline.remaining(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn late_response() {
+        let response = block_on(with_timeout(Duration::from_millis(0), || {
+            std::thread::sleep(Duration::from_millis(5));
+            ResponseBuilder::empty_200().build().unwrap()
+        }));
+        assert_eq!(response.code(), 503);
+
+        let response = block_on(with_timeout(Duration::from_secs(60), || {
+            ResponseBuilder::empty_200().build().unwrap()
+        }));
+        assert_eq!(response.code(), 200);
+    }
+
+    #[test]
+    fn stop_waiting() {
+        let start = Instant::now();
+        let response = block_on(with_timeout(Duration::from_millis(50), || {
+            std::thread::sleep(Duration::from_secs(5));
+            ResponseBuilder::empty_200().build().unwrap()
+        }));
+
+        assert_eq!(response.code(), 503);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn bounded_threads() {
+        for _ in 0..300 {
+            let response = block_on(with_timeout(Duration::from_secs(30), || {
+                ResponseBuilder::empty_200().build().unwrap()
+            }));
+            assert_eq!(response.code(), 200);
+        }
+
+        assert!(blocking::threads() <= blocking::MAX_THREADS);
+    }
+
+    #[test]
+    #[should_panic(expected = "handler failed")]
+    fn handler_panic() {
+        block_on(with_timeout(Duration::from_secs(60), || -> Response {
+            panic!("handler failed")
+        }));
+    }
+}
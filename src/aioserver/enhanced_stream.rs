@@ -2,92 +2,308 @@ use mio::event::Source;
 use mio::net::TcpStream;
 use mio::{Interest, Registry, Token};
 
-use log::trace;
+use std::cell::RefCell;
 use std::io::prelude::*;
 use std::io::Error;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
 
+use futures::FutureExt;
+
+use crate::aioserver::events::{event, CONNECTION};
+use crate::aioserver::transcript::TranscriptRecorder;
+use crate::data::{discard, ObjectPool, ReadBuffer};
 use crate::http::parser::ParseError;
+use crate::http::Method;
+use crate::io::delay::Delay;
+use crate::io::send_file::{send_file, SendFile};
 use crate::request::request_parser::RequestParser;
 use crate::request::Request;
+use crate::request::RequestBuilder;
+use crate::response::file::FileBody;
+use crate::response::Response;
 
 const DEFAULT_BUF_SIZE: usize = 8 * 1024;
+/// Number of serialization buffers kept by each worker thread
+const WRITE_POOL_SIZE: usize = 256;
+/// Buffers grown beyond this size by a large body are not kept
+const MAX_POOLED_WRITE_SIZE: usize = 64 * 1024;
+/// Size of the responses kept back by a corked stream beyond which they are written anyway
+const MAX_CORKED_SIZE: usize = 16 * 1024;
+
+thread_local! {
+    static WRITE_BUFFERS: RefCell<ObjectPool<Vec<u8>>> = RefCell::new(ObjectPool::new(WRITE_POOL_SIZE));
+}
+
+/// Free space given to every read of the stream
+const MIN_READ_SIZE: usize = 4 * 1024;
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+pub(crate) const DEFAULT_BATCH_LIMIT: usize = 32;
+pub(crate) const DEFAULT_MAX_HEAD_SIZE: usize = 16 * 1024;
+pub(crate) const DEFAULT_MAX_HEADERS: usize = 64;
 
+/// Error stopping a client connection
 #[derive(Debug)]
-pub(crate) enum RequestError {
+pub enum RequestError {
+    /// The client closed the connection
     EOF,
+    /// The connection could not be read
     ReadError(Error),
+    /// The received bytes are not a valid http request
     ParseError(ParseError),
+    /// The response could not be written to the connection
+    WriteError(Error),
 }
 /// Wrapper for a stream to read data from.
-/// It will try and buffer the maximum data that can be read from the inner Read and store it into its inner buffer
+/// It will try and buffer the maximum data that can be read from the inner Read and store it into its inner buffer,
+/// the stream reads directly into the free space of the buffer
 ///
 /// Warning : the buffer size is not limited which can be a major security issue
 ///
 /// Once the stream is read it will try and parse http request, if no request can be parsed from the buffer, it will be left untouched
-/// Everytime a request is read from the buffer, the corresponding section of the buffer is cleared
+/// Everytime a request is read from the buffer, its bytes are consumed. Their space is reused once the buffer is full,
+/// so a body received over many reads or a long pipeline is only copied a bounded number of times
+///
+/// When the head of a request is parsed but its body is not entirely received, the head is kept aside along with
+/// the expected body length, so the following reads only have to wait for the body instead of parsing the head again
+///
+/// At most `batch_limit` requests are parsed at once. When a client pipelines more, the remaining ones are
+/// left in the buffer and the stream is not read again until they are all returned, so the client is slowed
+/// down by TCP flow control instead of filling the memory of the server
+///
+/// The head of a request, its request line and headers, can not be larger than `max_head_size`.
+/// The bytes of an incomplete head are not buffered beyond that size, a HeadTooLarge error is returned instead
+///
+/// The bytes read and written are counted, along with the size of the head of each returned request
+///
+/// While the stream is corked, the responses without file are kept back and written together in a single write
+/// once `flush` is called or `MAX_CORKED_SIZE` is reached. With TCP_NODELAY, enabled by default, every write
+/// is sent in its own segments, so answering a batch of pipelined requests with one write saves packets
+/// as well as system calls. Without it Nagle's algorithm merges the small writes instead, but holds them back
+/// until the previous segment is acknowledged.
+///
+/// With a recorder, the bytes read and written are also written to a transcript. The file bodies are then
+/// copied through the usual write path instead of being sent without copy.
 pub(crate) struct EnhancedStream<T> {
-    id: usize,
+    id: u64,
     stream: T,
     parser: RequestParser,
-    read: Vec<u8>,
-    buffer: [u8; DEFAULT_BUF_SIZE],
+    read: ReadBuffer,
+    pending: Option<(RequestBuilder, usize, usize)>,
+    batch_limit: usize,
+    full_batch: bool,
+    max_head_size: usize,
+    heads: Vec<usize>,
+    bytes_read: u64,
+    bytes_written: u64,
+    write: Option<Vec<u8>>,
+    corked: bool,
+    recorder: Option<TranscriptRecorder>,
 }
 
 impl<T> EnhancedStream<T> {
     fn parse_buf(&mut self) -> Result<Vec<Request>, RequestError> {
         let mut requests = Vec::new();
+        self.full_batch = false;
+        self.heads.clear();
 
         loop {
-            match self.parser.parse_u8(&self.read) {
-                Ok((req, n)) => {
-                    requests.push(req);
-                    self.read = self.read.split_off(n);
+            if requests.len() >= self.batch_limit {
+                self.full_batch = true;
+                break;
+            }
+
+            let (builder, head, length) = match self.pending.take() {
+                Some(pending) => pending,
+                None => match self.parser.parse_head(self.read.data()) {
+                    Ok((_, n, _)) if n > self.max_head_size => {
+                        return Err(RequestError::ParseError(ParseError::HeadTooLarge))
+                    }
+                    Ok((builder, n, None)) => {
+                        self.read.consume(n);
+                        match builder.build() {
+                            Ok(req) => {
+                                let switching = switches_protocol(&req);
+                                requests.push(req);
+                                self.heads.push(n);
+                                // The following bytes may belong to the protocol the client switches to
+                                if switching {
+                                    self.full_batch = self.read.len() > 0;
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                return Err(RequestError::ParseError(ParseError::BuilderError(e)))
+                            }
+                        }
+                        continue;
+                    }
+                    Ok((builder, n, Some(length))) => {
+                        self.read.consume(n);
+                        (builder, n, length)
+                    }
+                    Err(ParseError::UnexpectedEnd) if self.read.len() > self.max_head_size => {
+                        return Err(RequestError::ParseError(ParseError::HeadTooLarge))
+                    }
+                    Err(ParseError::UnexpectedEnd) => break,
+                    Err(e) => return Err(RequestError::ParseError(e)),
+                },
+            };
+
+            if self.read.len() < length {
+                self.pending = Some((builder, head, length));
+                break;
+            }
 
-                    if self.read.is_empty() {
+            let builder = builder.body(&self.read.data()[..length]);
+            self.read.consume(length);
+            match builder.build() {
+                Ok(req) => {
+                    let switching = switches_protocol(&req);
+                    requests.push(req);
+                    self.heads.push(head);
+                    if switching {
+                        self.full_batch = self.read.len() > 0;
                         break;
                     }
                 }
-                Err(ParseError::UnexpectedEnd) => break,
-                Err(e) => return Err(RequestError::ParseError(e)),
+                Err(e) => return Err(RequestError::ParseError(ParseError::BuilderError(e))),
             }
         }
 
         Ok(requests)
     }
 
-    pub fn new(id: usize, stream: T) -> EnhancedStream<T> {
+    /// Return the number of body bytes still expected for the request being received, if any
+    pub fn remaining_body(&self) -> Option<usize> {
+        self.pending
+            .as_ref()
+            .map(|(_, _, length)| length.saturating_sub(self.read.len()))
+    }
+
+    /// Return the size in bytes of the head of a request returned by the last read, from its index in the batch
+    pub fn head_size(&self, index: usize) -> usize {
+        self.heads.get(index).cloned().unwrap_or(0)
+    }
+
+    /// Return the number of bytes read from the inner stream
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Return the number of bytes written to the inner stream by `write_response`
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn new(id: u64, stream: T) -> EnhancedStream<T> {
         EnhancedStream {
             id,
             stream,
             parser: RequestParser::new(),
-            read: Vec::new(),
-            buffer: [0; DEFAULT_BUF_SIZE],
+            read: ReadBuffer::new(DEFAULT_BUF_SIZE),
+            pending: None,
+            batch_limit: DEFAULT_BATCH_LIMIT,
+            full_batch: false,
+            max_head_size: DEFAULT_MAX_HEAD_SIZE,
+            heads: Vec::new(),
+            bytes_read: 0,
+            bytes_written: 0,
+            write: None,
+            corked: false,
+            recorder: None,
+        }
+    }
+
+    /// Keep the following responses back until `flush` is called, instead of writing each one right away
+    pub fn set_corked(&mut self, corked: bool) {
+        self.corked = corked;
+    }
+
+    /// Set the maximum number of requests returned by one read, at least one request is returned
+    pub fn set_batch_limit(&mut self, limit: usize) {
+        self.batch_limit = limit.max(1);
+    }
+
+    /// Set the maximum size in bytes of the head of a request
+    pub fn set_max_head_size(&mut self, size: usize) {
+        self.max_head_size = size;
+    }
+
+    /// Set the maximum number of headers of a request
+    pub fn set_max_headers(&mut self, max: usize) {
+        self.parser = RequestParser::new().max_headers(max);
+    }
+
+    /// Record the bytes read and written from now on
+    pub fn set_recorder(&mut self, recorder: TranscriptRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Count the bytes just read into the buffer
+    fn filled(&mut self, n: usize) {
+        self.read.fill(n);
+        self.bytes_read += n as u64;
+        if let Some(recorder) = &mut self.recorder {
+            let data = self.read.data();
+            recorder.record(self.id, true, &data[data.len() - n..]);
         }
+        event!(trace, CONNECTION, [conn = self.id], "Read {} bytes", n);
     }
 }
 
-impl<T: Read> EnhancedStream<T> {
+impl<T> EnhancedStream<T> {
+    /// Return the inner stream along with the bytes read but not parsed yet
+    pub fn into_parts(self) -> (T, Vec<u8>) {
+        (self.stream, self.read.into_vec())
+    }
+
+    /// Return the bytes read but not parsed yet, including the request that failed to parse
+    pub fn buffered(&self) -> &[u8] {
+        self.read.data()
+    }
+
+    /// Return a reference to the inner stream
+    pub fn get_ref(&self) -> &T {
+        &self.stream
+    }
+
+    /// Return a mutable reference to the inner stream
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.stream
+    }
+
     /// return the id associated to the EnhancedStream instance
-    pub fn id(&self) -> usize {
+    pub fn id(&self) -> u64 {
         self.id
     }
+}
 
+impl<T: Read> EnhancedStream<T> {
     /// Read the inner Read struct and fill the buffer with the data
     /// If a request can be parsed from the inner buffer but is not finished will return an Unexpected End error
     /// Return an error if the inner Stream has reached EOF
     /// if the stream of byte received is not correctly formated, an error is returned and the stream is stopped
     pub fn requests(&mut self) -> Result<Vec<Request>, RequestError> {
-        match self.stream.read(&mut self.buffer) {
+        if self.full_batch {
+            return self.parse_buf();
+        }
+
+        match self.stream.read(self.read.spare(MIN_READ_SIZE)) {
             Ok(0) => {
-                trace!("Reached EOF for {}", self.id);
+                event!(trace, CONNECTION, [conn = self.id], "Reached EOF");
                 return Err(RequestError::EOF);
             }
-            Ok(n) => {
-                self.read.extend_from_slice(&self.buffer[0..n]);
-                trace!("Read {} bytes from {}", n, self.id);
-            }
+            Ok(n) => self.filled(n),
             Err(e) => {
-                trace!("Error {:?} when reading {}", e, self.id);
+                event!(
+                    trace,
+                    CONNECTION,
+                    [conn = self.id],
+                    "Could not read : {:?}",
+                    e
+                );
                 return Err(RequestError::ReadError(e));
             }
         }
@@ -101,17 +317,25 @@ where
     T: futures::AsyncReadExt + Unpin,
 {
     pub(crate) async fn poll_requests(&mut self) -> Result<Vec<Request>, RequestError> {
-        match self.stream.read(&mut self.buffer).await {
+        // The requests left by a full batch are returned before reading more
+        if self.full_batch {
+            return self.parse_buf();
+        }
+
+        match self.stream.read(self.read.spare(MIN_READ_SIZE)).await {
             Ok(0) => {
-                trace!("Reached EOF for {}", self.id);
+                event!(trace, CONNECTION, [conn = self.id], "Reached EOF");
                 return Err(RequestError::EOF);
             }
-            Ok(n) => {
-                self.read.extend_from_slice(&self.buffer[0..n]);
-                trace!("Read {} bytes from {}", n, self.id);
-            }
+            Ok(n) => self.filled(n),
             Err(e) => {
-                trace!("Error {:?} when reading {}", e, self.id);
+                event!(
+                    trace,
+                    CONNECTION,
+                    [conn = self.id],
+                    "Could not read : {:?}",
+                    e
+                );
                 return Err(RequestError::ReadError(e));
             }
         }
@@ -120,6 +344,203 @@ where
     }
 }
 
+impl<T> EnhancedStream<T>
+where
+    T: futures::AsyncWriteExt + SendFile + Unpin,
+{
+    /// Write the whole response after the ones kept back, waiting for the connection to be writable when its
+    /// buffer is full. A corked stream keeps the response back instead, unless it has a file body.
+    /// Partial and interrupted writes are retried, any other error means the connection is unusable.
+    /// Return the size of the response.
+    pub(crate) async fn write_response(
+        &mut self,
+        response: &Response,
+    ) -> Result<usize, RequestError> {
+        let mut bytes = match self.write.take() {
+            Some(bytes) => bytes,
+            None => WRITE_BUFFERS
+                .with(|buffers| buffers.borrow_mut().take())
+                .unwrap_or_default(),
+        };
+        let start = bytes.len();
+        response.write_to(&mut bytes);
+        let size = bytes.len() - start;
+        let kept = bytes.len();
+        self.write = Some(bytes);
+
+        let file = response.file_body();
+        if self.corked && file.is_none() && kept < MAX_CORKED_SIZE {
+            return Ok(size);
+        }
+
+        self.flush().await?;
+        let file = match file {
+            Some(file) => file,
+            None => return Ok(size),
+        };
+
+        match self.write_file(file).await {
+            Ok(written) => {
+                self.bytes_written += written as u64;
+                self.trace_written(written);
+                Ok(size + written)
+            }
+            Err(e) => Err(self.write_error(e)),
+        }
+    }
+
+    /// Write the responses kept back
+    pub(crate) async fn flush(&mut self) -> Result<(), RequestError> {
+        let bytes = match self.write.take() {
+            Some(bytes) => bytes,
+            None => return Ok(()),
+        };
+
+        let result = futures::AsyncWriteExt::write_all(&mut self.stream, &bytes).await;
+        if let (Ok(()), Some(recorder)) = (&result, &mut self.recorder) {
+            recorder.record(self.id, false, &bytes);
+        }
+        let written = bytes.len();
+        recycle_write_buffer(bytes);
+
+        match result {
+            Ok(()) => {
+                self.bytes_written += written as u64;
+                self.trace_written(written);
+                Ok(())
+            }
+            Err(e) => Err(self.write_error(e)),
+        }
+    }
+
+    fn trace_written(&self, written: usize) {
+        event!(
+            trace,
+            CONNECTION,
+            [conn = self.id],
+            "Wrote {} bytes",
+            written
+        );
+    }
+
+    fn write_error(&self, e: Error) -> RequestError {
+        event!(
+            trace,
+            CONNECTION,
+            [conn = self.id],
+            "Could not write : {:?}",
+            e
+        );
+        RequestError::WriteError(e)
+    }
+
+    /// Send the file without copying it when the stream supports it, otherwise copy it to the stream by chunks.
+    /// A file shorter than announced is an error as the client would wait for the missing bytes.
+    async fn write_file(&mut self, file: &FileBody) -> std::io::Result<usize> {
+        // The recorded bytes have to go through the memory
+        if self.recorder.is_none() {
+            if let Some(sent) = send_file(&mut self.stream, file.file(), file.len()).await? {
+                return Ok(sent as usize);
+            }
+        }
+
+        let mut reader = file.reader()?;
+        let mut chunk = vec![0; FILE_CHUNK_SIZE];
+        let mut written = 0;
+
+        loop {
+            let n = reader.read(&mut chunk)?;
+            if n == 0 {
+                break;
+            }
+
+            futures::AsyncWriteExt::write_all(&mut self.stream, &chunk[..n]).await?;
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(self.id, false, &chunk[..n]);
+            }
+            written += n;
+        }
+
+        if (written as u64) < file.len() {
+            return Err(Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "The file is shorter than its announced length",
+            ));
+        }
+
+        Ok(written)
+    }
+}
+
+impl<T> EnhancedStream<T>
+where
+    T: futures::AsyncRead + futures::AsyncWrite + Unpin,
+{
+    /// Close the write side of the stream then discard what the client still sends, until it closes its side
+    /// or `timeout` expires. Closing a connection with unread data resets it, and the client could lose
+    /// the last response before reading it. Nothing is awaited when no data is left unread.
+    pub(crate) async fn linger(&mut self, timeout: Duration) {
+        if futures::AsyncWriteExt::close(&mut self.stream)
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut buf = [0; MIN_READ_SIZE];
+        let stream = &mut self.stream;
+        let read = futures::future::poll_fn(|cx| {
+            match Pin::new(&mut *stream).poll_read(cx, &mut buf) {
+                // The client sent nothing more, the connection can be closed right away
+                Poll::Pending => Poll::Ready(Ok(0)),
+                poll => poll,
+            }
+        });
+        // The timer only starts once data has been discarded
+        let mut discarded = match read.await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        let mut expired = Delay::new(timeout).fuse();
+
+        loop {
+            let read = futures::AsyncReadExt::read(&mut self.stream, &mut buf);
+
+            futures::select! {
+                read = read.fuse() => match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => discarded += n,
+                },
+                _ = expired => break,
+            }
+        }
+
+        event!(
+            trace,
+            CONNECTION,
+            [conn = self.id],
+            "Discarded {} bytes before closing",
+            discarded
+        );
+    }
+}
+
+/// Return true if the connection may leave HTTP after the request, through a tunnel or an upgrade
+fn switches_protocol(request: &Request) -> bool {
+    *request.method() == Method::CONNECT || request.upgrade().is_some()
+}
+
+/// Give a serialization buffer back to the pool of the thread, which may not be the one it was taken from
+fn recycle_write_buffer(mut bytes: Vec<u8>) {
+    if bytes.capacity() > MAX_POOLED_WRITE_SIZE {
+        discard();
+        return;
+    }
+
+    bytes.clear();
+    WRITE_BUFFERS.with(|buffers| buffers.borrow_mut().give(bytes));
+}
+
 /// Implement Shutdown for the std implementation of TcpStream
 impl EnhancedStream<std::net::TcpStream> {
     pub fn shutdown(&mut self) -> std::io::Result<()> {
@@ -205,6 +626,33 @@ mod tests {
         }
     }
 
+    /// Record each write separately
+    #[derive(Default)]
+    struct TestWriter {
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl futures::AsyncWrite for TestWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, Error>> {
+            self.get_mut().writes.push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl SendFile for TestWriter {}
+
     fn get_ressource_reader(path: &str) -> std::io::Cursor<Vec<u8>> {
         let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         d.push("resources/test");
@@ -238,6 +686,69 @@ mod tests {
         assert_eq!(14, requests.len());
     }
 
+    #[test]
+    fn batch_limit() {
+        let reader = get_ressource_reader("multi_requests.txt");
+        let mut stream = EnhancedStream::new(0, reader);
+        stream.set_batch_limit(5);
+
+        assert_eq!(5, stream.requests().unwrap().len());
+        assert_eq!(5, stream.requests().unwrap().len());
+        assert_eq!(4, stream.requests().unwrap().len());
+        assert!(matches!(stream.requests(), Err(RequestError::EOF)));
+    }
+
+    #[test]
+    fn unparsed_bytes() {
+        let input = get_ressource_reader("multi_requests.txt").into_inner();
+        let mut stream = EnhancedStream::new(0, std::io::Cursor::new(input.clone()));
+        stream.set_batch_limit(5);
+
+        assert_eq!(5, stream.requests().unwrap().len());
+        let buffered = stream.buffered().to_vec();
+        assert!(!buffered.is_empty() && buffered.len() < input.len());
+        assert!(input.ends_with(&buffered));
+
+        assert_eq!(5, stream.requests().unwrap().len());
+        let (_, unparsed) = stream.into_parts();
+        assert!(unparsed.len() < buffered.len());
+        assert!(input.ends_with(&unparsed));
+    }
+
+    #[test]
+    fn head_too_large() {
+        let mut input = b"GET / HTTP/1.1\r\n".to_vec();
+        for i in 0..40 {
+            input.extend_from_slice(format!("X-Header-{}: {}\r\n", i, "a".repeat(500)).as_bytes());
+        }
+
+        let mut stream = EnhancedStream::new(0, std::io::Cursor::new(input.clone()));
+        let mut result = stream.requests();
+        while matches!(result, Ok(ref requests) if requests.is_empty()) {
+            result = stream.requests();
+        }
+        assert!(matches!(
+            result,
+            Err(RequestError::ParseError(ParseError::HeadTooLarge))
+        ));
+
+        input.extend_from_slice(b"\r\n");
+        let mut stream = EnhancedStream::new(0, std::io::Cursor::new(input));
+        stream.set_max_head_size(64 * 1024);
+        let mut requests = stream.requests().unwrap();
+        while requests.is_empty() {
+            requests = stream.requests().unwrap();
+        }
+        assert_eq!(
+            requests[0]
+                .headers()
+                .get_header("X-Header-39")
+                .unwrap()
+                .len(),
+            500
+        );
+    }
+
     #[test]
     fn multi_async_request() {
         let task = async {
@@ -253,4 +764,139 @@ mod tests {
 
         futures::executor::block_on(task);
     }
+
+    #[test]
+    fn progressive_body() {
+        let body = vec![b'a'; 3 * DEFAULT_BUF_SIZE];
+        let mut input =
+            format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+        input.extend_from_slice(&body);
+        input.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+
+        let mut stream = EnhancedStream::new(0, std::io::Cursor::new(input));
+
+        assert!(stream.requests().unwrap().is_empty());
+        let remaining = stream.remaining_body().unwrap();
+        assert!(remaining > 0 && remaining < body.len());
+
+        let mut requests = Vec::new();
+        while requests.is_empty() {
+            requests = stream.requests().unwrap();
+        }
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body().unwrap(), &body);
+        assert_eq!(stream.head_size(0), 42);
+        assert_eq!(stream.head_size(1), 18);
+        assert_eq!(stream.bytes_read(), 42 + body.len() as u64 + 18);
+        assert_eq!(*requests[1].method(), crate::Method::GET);
+        assert!(stream.remaining_body().is_none());
+    }
+
+    #[test]
+    fn corked_writes() {
+        let response = crate::ResponseBuilder::empty_200()
+            .text("ok")
+            .build()
+            .unwrap();
+        let large = crate::ResponseBuilder::empty_200()
+            .body(&vec![b'a'; MAX_CORKED_SIZE])
+            .build()
+            .unwrap();
+
+        futures::executor::block_on(async {
+            let mut stream = EnhancedStream::new(0, TestWriter::default());
+            let size = stream.write_response(&response).await.unwrap();
+            assert_eq!(stream.get_ref().writes.len(), 1);
+
+            stream.set_corked(true);
+            stream.write_response(&response).await.unwrap();
+            stream.write_response(&response).await.unwrap();
+            assert_eq!(stream.get_ref().writes.len(), 1);
+            assert_eq!(stream.bytes_written(), size as u64);
+
+            stream.flush().await.unwrap();
+            assert_eq!(stream.get_ref().writes.len(), 2);
+            assert_eq!(stream.get_ref().writes[1].len(), 2 * size);
+            stream.flush().await.unwrap();
+            assert_eq!(stream.get_ref().writes.len(), 2);
+
+            // A response too large to be kept back is written along with the ones before it
+            stream.write_response(&response).await.unwrap();
+            let large_size = stream.write_response(&large).await.unwrap();
+            assert_eq!(stream.get_ref().writes.len(), 3);
+            assert_eq!(stream.get_ref().writes[2].len(), size + large_size);
+            assert_eq!(stream.bytes_written(), (4 * size + large_size) as u64);
+        });
+    }
+
+    /// Give the reads in order, None being a read that would block, then block forever
+    struct LingeringClient {
+        reads: std::collections::VecDeque<Option<Vec<u8>>>,
+    }
+
+    impl AsyncRead for LingeringClient {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize, Error>> {
+            match self.get_mut().reads.pop_front() {
+                Some(Some(data)) => {
+                    buf[..data.len()].copy_from_slice(&data);
+                    Poll::Ready(Ok(data.len()))
+                }
+                _ => Poll::Pending,
+            }
+        }
+    }
+
+    impl futures::AsyncWrite for LingeringClient {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, Error>> {
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn linger(reads: Vec<Option<&[u8]>>, timeout: Duration) -> Duration {
+        let client = LingeringClient {
+            reads: reads
+                .into_iter()
+                .map(|read| read.map(<[u8]>::to_vec))
+                .collect(),
+        };
+        let mut stream = EnhancedStream::new(0, client);
+
+        let start = std::time::Instant::now();
+        futures::executor::block_on(stream.linger(timeout));
+        start.elapsed()
+    }
+
+    #[test]
+    fn linger_without_unread_data() {
+        assert!(linger(vec![None], Duration::from_secs(60)) < Duration::from_secs(5));
+        assert!(linger(vec![Some(b"")], Duration::from_secs(60)) < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn linger_with_unread_data() {
+        let reads = vec![Some(&b"GET"[..]), Some(b" / HTTP/1.1"), Some(b"")];
+        assert!(linger(reads, Duration::from_secs(60)) < Duration::from_secs(5));
+
+        // The client keeps the connection open, the timer ends the wait
+        let elapsed = linger(vec![Some(b"GET")], Duration::from_millis(50));
+        assert!(elapsed >= Duration::from_millis(50));
+        assert!(elapsed < Duration::from_secs(5));
+    }
 }
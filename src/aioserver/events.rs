@@ -0,0 +1,57 @@
+//! Targets and fields of the events logged by the server, documented for operators in the README.
+//! The fields are structured with the `tracing` feature and appended to the message as `name=value` with `log`.
+
+use std::fmt;
+
+pub(crate) const SERVER: &str = "mini_async_http::server";
+pub(crate) const ACCEPT: &str = "mini_async_http::accept";
+pub(crate) const CONNECTION: &str = "mini_async_http::connection";
+pub(crate) const REQUEST: &str = "mini_async_http::request";
+pub(crate) const SLOW_REQUEST: &str = "mini_async_http::slow_request";
+
+/// Log an event with its fields, through `tracing` when the feature is enabled and `log` otherwise
+///
+/// `event!(warn, CONNECTION, [peer = info.peer()], "Invalid request : {:?}", error)`
+macro_rules! event {
+    ($level:ident, $target:expr, [$($field:ident = $value:expr),* $(,)?], $($message:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!(target: $target, $($field = %$value,)* $($message)+);
+        #[cfg(not(feature = "tracing"))]
+        log::$level!(
+            target: $target,
+            "{}{}",
+            format_args!($($message)+),
+            $crate::aioserver::events::Fields(&[$((stringify!($field), &$value as &dyn std::fmt::Display)),*])
+        );
+    }};
+}
+
+pub(crate) use event;
+
+/// Fields of an event written after its message
+#[cfg_attr(feature = "tracing", allow(dead_code))]
+pub(crate) struct Fields<'a>(pub(crate) &'a [(&'static str, &'a dyn fmt::Display)]);
+
+impl fmt::Display for Fields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.0 {
+            write!(f, " {}={}", name, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn fields() {
+        let peer: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let fields = Fields(&[("peer", &peer), ("request", &3)]);
+
+        assert_eq!(fields.to_string(), " peer=127.0.0.1:80 request=3");
+    }
+}
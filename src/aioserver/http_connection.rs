@@ -0,0 +1,372 @@
+use crate::aioserver::enhanced_stream::{EnhancedStream, RequestError};
+use crate::aioserver::events::{event, CONNECTION};
+use crate::aioserver::observer::next_connection_id;
+use crate::aioserver::server::{connection_header, date_header, remove_forbidden_body};
+use crate::aioserver::transcript::TranscriptRecorder;
+use crate::http::header::{CLOSE_CONNECTION_HEADER, CONNECTION_HEADER};
+use crate::io::send_file::SendFile;
+use crate::request::Request;
+use crate::response::Response;
+
+use futures::{AsyncRead, AsyncWrite};
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// HTTP/1.1 server side of a connection over any transport, such as a TLS stream, a unix socket or an
+/// in-memory stream, for the programs running the protocol without the [`AIOServer`].
+///
+/// The requests are read one after the other with [`next_request`], each one answered with [`send_response`]
+/// in the order they were read. The connection parses the pipelined requests, sets the `Date` and
+/// `Connection` headers of the responses and removes the bodies that must not be sent. The responses to the
+/// requests already read are written together, and the connection ends after a response closing it.
+/// The routing, the timeouts and the limits of the server are left to the caller.
+///
+/// # Example
+///
+/// ```
+/// use futures::{AsyncRead, AsyncWrite};
+/// use mini_async_http::{HttpConnection, RequestError, ResponseBuilder};
+///
+/// async fn serve<T: AsyncRead + AsyncWrite + Unpin>(transport: T) -> Result<(), RequestError> {
+///     let mut connection = HttpConnection::new(transport).max_header_size(8 * 1024);
+///
+///     while let Some(request) = connection.next_request().await? {
+///         let response = ResponseBuilder::empty_200()
+///             .body(request.path().as_bytes())
+///             .build()
+///             .unwrap();
+///         connection.send_response(&request, response).await?;
+///     }
+///
+///     connection.close().await
+/// }
+/// ```
+///
+/// [`AIOServer`]: struct.AIOServer.html
+/// [`next_request`]: #method.next_request
+/// [`send_response`]: #method.send_response
+pub struct HttpConnection<T> {
+    stream: EnhancedStream<Transport<T>>,
+    requests: VecDeque<Request>,
+    merge_slashes: bool,
+    closed: bool,
+}
+
+impl<T> HttpConnection<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Create the connection over the given transport
+    pub fn new(transport: T) -> HttpConnection<T> {
+        HttpConnection {
+            stream: EnhancedStream::new(next_connection_id(), Transport(transport)),
+            requests: VecDeque::new(),
+            merge_slashes: true,
+            closed: false,
+        }
+    }
+
+    /// Set the maximum size in bytes of the request line and headers of a request, 16 KiB by default.
+    /// A larger request makes [`next_request`] return a `HeadTooLarge` parse error.
+    ///
+    /// [`next_request`]: #method.next_request
+    pub fn max_header_size(mut self, size: usize) -> HttpConnection<T> {
+        self.stream.set_max_head_size(size);
+        self
+    }
+
+    /// Set the maximum number of headers of a request, 64 by default.
+    /// A request with more headers makes [`next_request`] return a `TooManyHeaders` parse error.
+    ///
+    /// [`next_request`]: #method.next_request
+    pub fn max_headers(mut self, max: usize) -> HttpConnection<T> {
+        self.stream.set_max_headers(max);
+        self
+    }
+
+    /// Set the maximum number of pipelined requests read at once, 32 by default.
+    /// The transport is not read again until they are returned.
+    pub fn pipeline_limit(mut self, limit: usize) -> HttpConnection<T> {
+        self.stream.set_batch_limit(limit);
+        self
+    }
+
+    /// Merge the consecutive slashes of the request paths, enabled by default as with the server
+    pub fn merge_slashes(mut self, merge: bool) -> HttpConnection<T> {
+        self.merge_slashes = merge;
+        self
+    }
+
+    /// Record the bytes received and sent from now on to the given output, such as a file,
+    /// in the format read by [`Transcript`]
+    ///
+    /// [`Transcript`]: struct.Transcript.html
+    pub fn record<W: Write + Send + 'static>(mut self, output: W) -> HttpConnection<T> {
+        self.stream.set_recorder(TranscriptRecorder::new(output));
+        self
+    }
+
+    /// Return the identifier of the connection, the `conn` field of the events logged for it
+    pub fn id(&self) -> u64 {
+        self.stream.id()
+    }
+
+    /// Return the next request of the client, None once the client closed the connection or a response closed it.
+    /// The responses not written yet are written before the transport is read.
+    ///
+    /// An invalid request returns a `ParseError`, the connection can still answer it with [`send_error`]
+    /// but does not return any other request.
+    ///
+    /// [`send_error`]: #method.send_error
+    pub async fn next_request(&mut self) -> Result<Option<Request>, RequestError> {
+        loop {
+            if self.closed {
+                return Ok(None);
+            }
+            if let Some(request) = self.requests.pop_front() {
+                return Ok(Some(request));
+            }
+
+            // The client may wait for the responses before sending the next request
+            self.stream.flush().await?;
+            match self.stream.poll_requests().await {
+                Ok(requests) => {
+                    for mut request in requests {
+                        request.normalize(self.merge_slashes);
+                        self.requests.push_back(request);
+                    }
+                }
+                Err(RequestError::EOF) => {
+                    self.closed = true;
+                    return Ok(None);
+                }
+                Err(e) => {
+                    self.closed = true;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Write the response to a request returned by [`next_request`].
+    /// Return true if the connection stays open, false if the request or the response closes it.
+    ///
+    /// [`next_request`]: #method.next_request
+    pub async fn send_response(
+        &mut self,
+        request: &Request,
+        mut response: Response,
+    ) -> Result<bool, RequestError> {
+        date_header(&mut response);
+        let removed = remove_forbidden_body(request, &mut response);
+        if removed > 0 {
+            event!(
+                warn,
+                CONNECTION,
+                [conn = self.id()],
+                "Removed the body of {} bytes of the {} response to {} {}",
+                removed,
+                response.code(),
+                request.method().as_str(),
+                request.path()
+            );
+        }
+
+        let keep_alive = connection_header(request, &mut response);
+        if !keep_alive {
+            // The requests following the one closing the connection are not answered
+            self.closed = true;
+            self.requests.clear();
+        }
+
+        self.stream.set_corked(!self.requests.is_empty());
+        self.stream.write_response(&response).await?;
+
+        Ok(keep_alive)
+    }
+
+    /// Answer a request that could not be parsed, the connection is closed afterwards
+    pub async fn send_error(&mut self, mut response: Response) -> Result<(), RequestError> {
+        self.closed = true;
+        self.requests.clear();
+
+        date_header(&mut response);
+        response
+            .headers
+            .set_header(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER);
+        self.stream.set_corked(false);
+        self.stream.write_response(&response).await?;
+
+        Ok(())
+    }
+
+    /// Write the responses not written yet and close the write side of the transport
+    pub async fn close(&mut self) -> Result<(), RequestError> {
+        self.closed = true;
+        self.stream.flush().await?;
+
+        futures::AsyncWriteExt::close(&mut self.stream.get_mut().0)
+            .await
+            .map_err(RequestError::WriteError)
+    }
+
+    /// Return the transport along with the bytes received after the last request returned, for instance
+    /// to hand the connection over to another protocol after an upgrade.
+    /// The responses not written yet are lost, [`close`] or another response has to write them first.
+    ///
+    /// [`close`]: #method.close
+    pub fn into_inner(self) -> (T, Vec<u8>) {
+        let (transport, buffered) = self.stream.into_parts();
+
+        (transport.0, buffered)
+    }
+}
+
+/// Transport of a connection, without zero copy for the file bodies
+struct Transport<T>(T);
+
+impl<T: AsyncRead + Unpin> AsyncRead for Transport<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Transport<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_close(cx)
+    }
+}
+
+impl<T> SendFile for Transport<T> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::parser::ParseError;
+    use crate::ResponseBuilder;
+
+    use std::io::Read;
+
+    /// Read the given bytes and record each write separately
+    struct TestTransport {
+        input: io::Cursor<Vec<u8>>,
+        writes: Vec<Vec<u8>>,
+        closed: bool,
+    }
+
+    impl TestTransport {
+        fn new(input: &[u8]) -> TestTransport {
+            TestTransport {
+                input: io::Cursor::new(input.to_vec()),
+                writes: Vec::new(),
+                closed: false,
+            }
+        }
+    }
+
+    impl AsyncRead for TestTransport {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().input.read(buf))
+        }
+    }
+
+    impl AsyncWrite for TestTransport {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().writes.push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            self.get_mut().closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn serve(connection: &mut HttpConnection<TestTransport>) -> Vec<String> {
+        futures::executor::block_on(async {
+            let mut paths = Vec::new();
+            while let Some(request) = connection.next_request().await.unwrap() {
+                paths.push(request.path().clone());
+                let response = ResponseBuilder::empty_200()
+                    .body(request.path().as_bytes())
+                    .build()
+                    .unwrap();
+                connection.send_response(&request, response).await.unwrap();
+            }
+            connection.close().await.unwrap();
+
+            paths
+        })
+    }
+
+    #[test]
+    fn pipelined_requests() {
+        let mut connection = HttpConnection::new(TestTransport::new(
+            b"GET /a HTTP/1.1\r\n\r\nHEAD //b HTTP/1.1\r\n\r\nGET /c HTTP/1.1\r\nConnection: close\r\n\r\nGET /d HTTP/1.1\r\n\r\n",
+        ));
+
+        assert_eq!(serve(&mut connection), vec!["/a", "/b", "/c"]);
+
+        let (transport, _) = connection.into_inner();
+        assert!(transport.closed);
+        // The responses to the pipelined requests are written at once
+        assert_eq!(transport.writes.len(), 1);
+        let written = String::from_utf8(transport.writes[0].clone()).unwrap();
+        assert_eq!(written.matches("HTTP/1.1 200").count(), 3);
+        assert_eq!(written.matches("date: ").count(), 3);
+        assert!(!written.contains("/b"));
+        assert_eq!(written.matches("connection: close").count(), 1);
+        assert!(written.ends_with("\r\n\r\n/c"));
+    }
+
+    #[test]
+    fn invalid_request() {
+        let mut connection = HttpConnection::new(TestTransport::new(
+            b"GET /a HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n",
+        ))
+        .max_headers(1);
+
+        futures::executor::block_on(async {
+            match connection.next_request().await {
+                Err(RequestError::ParseError(ParseError::TooManyHeaders)) => {}
+                other => panic!("Unexpected result {:?}", other),
+            }
+            connection
+                .send_error(ResponseBuilder::empty_431().build().unwrap())
+                .await
+                .unwrap();
+            assert!(connection.next_request().await.unwrap().is_none());
+        });
+
+        let (transport, _) = connection.into_inner();
+        let written = String::from_utf8(transport.writes.concat()).unwrap();
+        assert!(written.starts_with("HTTP/1.1 431"));
+    }
+}
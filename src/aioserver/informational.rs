@@ -0,0 +1,148 @@
+//! Informational responses (1xx) sent before the final response of a request.
+//!
+//! A handler or a middleware can send them while the final response is still being computed,
+//! typically a 103 Early Hints listing with Link headers the resources the client can start loading.
+
+use crate::executor::task_local;
+use crate::http::Version;
+use crate::response::Response;
+
+use socket2::SockRef;
+
+use std::io::{self, Write};
+
+/// Error returned by [`send_informational`]
+///
+/// [`send_informational`]: fn.send_informational.html
+#[derive(Debug)]
+pub enum InformationalError {
+    /// Not called while the server is handling a request
+    NoConnection,
+    /// The status code is not an informational one, 101 is reserved for protocol upgrades
+    InvalidStatus(i32),
+    /// The client uses HTTP/1.0 which does not support informational responses
+    Unsupported,
+    /// The response could not be written to the connection
+    WriteError(io::Error),
+}
+
+/// Connection of the request being handled, stored in the task locals by the server.
+/// It is removed before the connection is closed so the socket is always valid.
+#[derive(Clone)]
+pub(crate) struct Connection {
+    socket: RawSocket,
+    version: Version,
+}
+
+impl Connection {
+    #[cfg(unix)]
+    pub(crate) fn new<S: std::os::unix::io::AsRawFd>(stream: &S, version: &Version) -> Connection {
+        Connection {
+            socket: RawSocket(stream.as_raw_fd()),
+            version: version.clone(),
+        }
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn new<S: std::os::windows::io::AsRawSocket>(
+        stream: &S,
+        version: &Version,
+    ) -> Connection {
+        Connection {
+            socket: RawSocket(stream.as_raw_socket()),
+            version: version.clone(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[derive(Clone, Copy)]
+struct RawSocket(std::os::unix::io::RawFd);
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for RawSocket {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.0
+    }
+}
+
+#[cfg(windows)]
+#[derive(Clone, Copy)]
+struct RawSocket(std::os::windows::io::RawSocket);
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawSocket for RawSocket {
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.0
+    }
+}
+
+/// Write an informational response to the client of the request being handled.
+/// It is sent right away, before the final response returned by the handler.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{send_informational, AIOServer, ResponseBuilder};
+///
+/// let server = AIOServer::new("127.0.0.1:7889".parse().unwrap(), |_| {
+///     let hints = ResponseBuilder::early_hints()
+///         .header("Link", "</style.css>; rel=preload; as=style")
+///         .build()
+///         .unwrap();
+///     let _ = send_informational(&hints);
+///
+///     ResponseBuilder::empty_200()
+///         .html("<link rel=\"stylesheet\" href=\"/style.css\">")
+///         .build()
+///         .unwrap()
+/// });
+/// ```
+pub fn send_informational(response: &Response) -> Result<(), InformationalError> {
+    let code = response.code();
+    if !(100..200).contains(&code) || code == 101 {
+        return Err(InformationalError::InvalidStatus(code));
+    }
+
+    let connection = match task_local::get::<Connection>() {
+        Some(connection) => connection,
+        None => return Err(InformationalError::NoConnection),
+    };
+
+    if connection.version == Version::HTTP10 {
+        return Err(InformationalError::Unsupported);
+    }
+
+    let socket = SockRef::from(&connection.socket);
+    let mut writer: &socket2::Socket = &socket;
+
+    writer
+        .write_all(response.to_string().as_bytes())
+        .map_err(InformationalError::WriteError)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ResponseBuilder;
+
+    #[test]
+    fn invalid_status() {
+        let response = ResponseBuilder::empty_200().build().unwrap();
+
+        match send_informational(&response) {
+            Err(InformationalError::InvalidStatus(200)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn no_connection() {
+        let response = ResponseBuilder::early_hints().build().unwrap();
+
+        match send_informational(&response) {
+            Err(InformationalError::NoConnection) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+/// What the server does with a connection over the limit of its client, see [`AIOServer::set_max_connections_per_ip`]
+///
+/// [`AIOServer::set_max_connections_per_ip`]: struct.AIOServer.html#method.set_max_connections_per_ip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitAction {
+    /// The connection is answered with a 503 status code and closed
+    Reject,
+    /// The connection is closed without answer, which costs the server less during a flood
+    Close,
+}
+
+/// Number of open connections of each client address
+pub(crate) struct IpLimiter {
+    max: usize,
+    allowed: Vec<IpAddr>,
+    open: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl IpLimiter {
+    pub(crate) fn new(max: usize, allowed: Vec<IpAddr>) -> IpLimiter {
+        IpLimiter {
+            max,
+            allowed,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a new connection from the address, None if the client has too many connections already.
+    /// The allowed addresses are never limited.
+    pub(crate) fn acquire(self: &Arc<Self>, ip: IpAddr) -> Option<IpGuard> {
+        if self.allowed.contains(&ip) {
+            return Some(IpGuard { limiter: None, ip });
+        }
+
+        let mut open = self.open.lock().unwrap();
+        let count = open.entry(ip).or_insert(0);
+        if *count >= self.max {
+            return None;
+        }
+        *count += 1;
+
+        Some(IpGuard {
+            limiter: Some(self.clone()),
+            ip,
+        })
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut open = self.open.lock().unwrap();
+
+        if let Some(count) = open.get_mut(&ip) {
+            *count -= 1;
+            // The addresses are forgotten once closed so the map does not grow with every client
+            if *count == 0 {
+                open.remove(&ip);
+            }
+        }
+    }
+}
+
+/// Count a connection against the limit of its client until it is dropped
+pub(crate) struct IpGuard {
+    limiter: Option<Arc<IpLimiter>>,
+    ip: IpAddr,
+}
+
+impl Drop for IpGuard {
+    fn drop(&mut self) {
+        if let Some(limiter) = &self.limiter {
+            limiter.release(self.ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limit() {
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let allowed: IpAddr = "10.0.0.2".parse().unwrap();
+        let limiter = Arc::new(IpLimiter::new(2, vec![allowed]));
+
+        let first = limiter.acquire(client).unwrap();
+        let _second = limiter.acquire(client).unwrap();
+        assert!(limiter.acquire(client).is_none());
+
+        drop(first);
+        assert!(limiter.acquire(client).is_some());
+
+        let guards: Vec<_> = (0..5).map(|_| limiter.acquire(allowed)).collect();
+        assert!(guards.iter().all(Option::is_some));
+        assert!(!limiter.open.lock().unwrap().contains_key(&allowed));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::aioserver::server::Handler;
+use crate::{Request, Response};
+
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+type AsyncMiddlewareFn =
+    Arc<dyn Send + Sync + 'static + for<'a> Fn(&'a Request, AsyncNext) -> BoxFuture<'a, Response>>;
+
+/// Middleware running asynchronously around the handler of a server, added with [`AIOServer::add_middleware`].
+/// It is given the request and the rest of the chain, and can await other futures such as a token introspection
+/// before calling [`AsyncNext::run`] or answering by itself.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{AsyncMiddleware, ResponseBuilder};
+///
+/// let auth = AsyncMiddleware::new(|req, next| {
+///     Box::pin(async move {
+///         match req.headers().get_header("Authorization") {
+///             Some(_) => next.run(req).await,
+///             None => ResponseBuilder::empty_403().build().unwrap(),
+///         }
+///     })
+/// });
+/// ```
+///
+/// [`AIOServer::add_middleware`]: struct.AIOServer.html#method.add_middleware
+/// [`AsyncNext::run`]: struct.AsyncNext.html#method.run
+#[derive(Clone)]
+pub struct AsyncMiddleware {
+    inner: AsyncMiddlewareFn,
+}
+
+impl AsyncMiddleware {
+    /// Create a middleware from a closure returning a boxed future
+    pub fn new<T>(middleware: T) -> AsyncMiddleware
+    where
+        T: Send + Sync + 'static + for<'a> Fn(&'a Request, AsyncNext) -> BoxFuture<'a, Response>,
+    {
+        AsyncMiddleware {
+            inner: Arc::new(middleware),
+        }
+    }
+
+    /// Return a handler running the middleware in front of the given one
+    pub(crate) fn wrap(&self, handler: Handler) -> Handler {
+        let middleware = self.inner.clone();
+
+        Arc::new(move |request| middleware(request, AsyncNext::new(handler.clone())))
+    }
+}
+
+/// Rest of the handling chain given to an [`AsyncMiddleware`]
+///
+/// [`AsyncMiddleware`]: struct.AsyncMiddleware.html
+pub struct AsyncNext {
+    handler: Handler,
+}
+
+impl AsyncNext {
+    fn new(handler: Handler) -> AsyncNext {
+        AsyncNext { handler }
+    }
+
+    /// Continue the chain with the given request
+    pub fn run(self, request: &Request) -> BoxFuture<'_, Response> {
+        (self.handler)(request)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ResponseBuilder;
+    use futures::future::FutureExt;
+
+    fn get(path: &str) -> Request {
+        crate::RequestBuilder::new()
+            .method(crate::Method::GET)
+            .path(String::from(path))
+            .version(crate::Version::HTTP11)
+            .build()
+            .unwrap()
+    }
+
+    fn handler() -> Handler {
+        Arc::new(|_| futures::future::ready(ResponseBuilder::empty_200().build().unwrap()).boxed())
+    }
+
+    #[test]
+    fn chain() {
+        let header = AsyncMiddleware::new(|req, next| {
+            async move {
+                let mut response = next.run(req).await;
+                response.headers.set_header("x-middleware", "outer");
+                response
+            }
+            .boxed()
+        });
+        let reject = AsyncMiddleware::new(|req, next| {
+            async move {
+                crate::yield_now().await;
+                match req.path().as_str() {
+                    "/private" => ResponseBuilder::empty_403().build().unwrap(),
+                    _ => next.run(req).await,
+                }
+            }
+            .boxed()
+        });
+        let handler = header.wrap(reject.wrap(handler()));
+
+        let request = get("/");
+        let response = futures::executor::block_on(handler(&request));
+        assert_eq!(response.code(), 200);
+        assert_eq!(
+            response.headers().get_header("x-middleware").unwrap(),
+            "outer"
+        );
+
+        let request = get("/private");
+        let response = futures::executor::block_on(handler(&request));
+        assert_eq!(response.code(), 403);
+    }
+}
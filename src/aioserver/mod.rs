@@ -1,5 +1,20 @@
+pub(crate) mod background;
+pub(crate) mod cancellation;
+pub(crate) mod concurrency_limit;
+pub(crate) mod config;
+pub(crate) mod deadline;
 pub(crate) mod enhanced_stream;
 pub(crate) mod event_channel;
+pub(crate) mod events;
+pub(crate) mod http_connection;
+pub(crate) mod informational;
+pub(crate) mod ip_limit;
+pub(crate) mod middleware;
+pub(crate) mod observer;
+pub(crate) mod request_id;
 pub(crate) mod server;
+pub(crate) mod transcript;
+pub(crate) mod tunnel;
+pub(crate) mod upgrade;
 
 pub use server::AIOServer;
@@ -0,0 +1,376 @@
+use crate::aioserver::enhanced_stream::RequestError;
+use crate::request::Request;
+use crate::response::Response;
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
+/// Return a new connection identifier, unique within the process
+pub(crate) fn next_connection_id() -> u64 {
+    NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Information about a client connection, given to a [`ConnectionObserver`].
+/// The information of the connection of the request being handled can be retrieved with [`task_local::get`].
+///
+/// [`ConnectionObserver`]: trait.ConnectionObserver.html
+/// [`task_local::get`]: task_local/fn.get.html
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    id: u64,
+    peer: SocketAddr,
+}
+
+impl ConnectionInfo {
+    pub(crate) fn new(peer: SocketAddr) -> ConnectionInfo {
+        ConnectionInfo {
+            id: next_connection_id(),
+            peer,
+        }
+    }
+
+    /// Return the identifier given by the server to the connection, unique within the process.
+    /// It is the `conn` field of the events logged for the connection.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Return the address of the remote end of the connection
+    pub fn peer(&self) -> &SocketAddr {
+        &self.peer
+    }
+}
+
+/// Statistics about a connection, given to [`ConnectionObserver::on_close`]
+///
+/// [`ConnectionObserver::on_close`]: trait.ConnectionObserver.html#method.on_close
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+    requests: usize,
+    opened: Instant,
+    bytes_read: u64,
+    bytes_written: u64,
+}
+
+impl ConnectionStats {
+    pub(crate) fn new() -> ConnectionStats {
+        ConnectionStats {
+            requests: 0,
+            opened: Instant::now(),
+            bytes_read: 0,
+            bytes_written: 0,
+        }
+    }
+
+    pub(crate) fn request_served(&mut self) {
+        self.requests += 1;
+    }
+
+    pub(crate) fn set_bytes(&mut self, read: u64, written: u64) {
+        self.bytes_read = read;
+        self.bytes_written = written;
+    }
+
+    /// Return the number of requests answered on the connection
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+
+    /// Return the time elapsed since the connection was accepted
+    pub fn duration(&self) -> Duration {
+        self.opened.elapsed()
+    }
+
+    /// Return the number of bytes received from the client, including the requests that were not answered
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Return the number of bytes sent to the client, a tunnel is not counted
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+}
+
+/// Snapshot of the reuse of the keep-alive connections closed since the server was created.
+/// Retrieved with [`ServerHandle::keep_alive_stats`].
+///
+/// Few requests per connection and a high churn mean the clients keep opening new connections,
+/// which the connection limits such as [`AIOServer::set_max_requests_per_connection`] may be causing.
+///
+/// [`ServerHandle::keep_alive_stats`]: struct.ServerHandle.html#method.keep_alive_stats
+/// [`AIOServer::set_max_requests_per_connection`]: struct.AIOServer.html#method.set_max_requests_per_connection
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KeepAliveStats {
+    connections: u64,
+    reused: u64,
+    requests: u64,
+    limited: u64,
+    elapsed: Duration,
+}
+
+impl KeepAliveStats {
+    /// Return the number of connections closed
+    pub fn connections(&self) -> u64 {
+        self.connections
+    }
+
+    /// Return the number of closed connections that served more than one request
+    pub fn reused_connections(&self) -> u64 {
+        self.reused
+    }
+
+    /// Return the number of requests answered on the closed connections
+    pub fn requests(&self) -> u64 {
+        self.requests
+    }
+
+    /// Return the number of connections closed because they reached the maximum number of requests
+    pub fn limited_connections(&self) -> u64 {
+        self.limited
+    }
+
+    /// Return the average number of requests answered on a connection, 0 if none is closed
+    pub fn requests_per_connection(&self) -> f64 {
+        ratio(self.requests, self.connections)
+    }
+
+    /// Return the fraction of the connections that served more than one request, 0 if none is closed
+    pub fn reuse_rate(&self) -> f64 {
+        ratio(self.reused, self.connections)
+    }
+
+    /// Return the number of connections closed per second since the server was created
+    pub fn churn(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.connections as f64 / secs,
+            _ => 0.0,
+        }
+    }
+}
+
+fn ratio(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+/// Counters of the connections closed by a server, shared with its handle
+pub(crate) struct ReuseCounters {
+    connections: AtomicU64,
+    reused: AtomicU64,
+    requests: AtomicU64,
+    limited: AtomicU64,
+    created: Instant,
+}
+
+impl ReuseCounters {
+    pub(crate) fn new() -> ReuseCounters {
+        ReuseCounters {
+            connections: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            limited: AtomicU64::new(0),
+            created: Instant::now(),
+        }
+    }
+
+    /// Count a closed connection
+    pub(crate) fn record(&self, stats: &ConnectionStats) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.requests
+            .fetch_add(stats.requests() as u64, Ordering::Relaxed);
+        if stats.requests() > 1 {
+            self.reused.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a connection closed because it reached the maximum number of requests
+    pub(crate) fn limit_reached(&self) {
+        self.limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> KeepAliveStats {
+        KeepAliveStats {
+            connections: self.connections.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            limited: self.limited.load(Ordering::Relaxed),
+            elapsed: self.created.elapsed(),
+        }
+    }
+}
+
+/// Size of a request and of its response, given to [`ConnectionObserver::on_response`]
+///
+/// [`ConnectionObserver::on_response`]: trait.ConnectionObserver.html#method.on_response
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TransferStats {
+    request_head: usize,
+    request_body: usize,
+    response_head: usize,
+    response_body: usize,
+}
+
+impl TransferStats {
+    pub(crate) fn new(request: (usize, usize), response: (usize, usize)) -> TransferStats {
+        TransferStats {
+            request_head: request.0,
+            request_body: request.1,
+            response_head: response.0,
+            response_body: response.1,
+        }
+    }
+
+    /// Return the size in bytes of the request line and headers as received
+    pub fn request_head(&self) -> usize {
+        self.request_head
+    }
+
+    /// Return the size in bytes of the request body
+    pub fn request_body(&self) -> usize {
+        self.request_body
+    }
+
+    /// Return the size in bytes of the status line and headers of the response
+    pub fn response_head(&self) -> usize {
+        self.response_head
+    }
+
+    /// Return the size in bytes of the response body
+    pub fn response_body(&self) -> usize {
+        self.response_body
+    }
+
+    /// Return the total size of the request
+    pub fn request_bytes(&self) -> usize {
+        self.request_head + self.request_body
+    }
+
+    /// Return the total size of the response
+    pub fn response_bytes(&self) -> usize {
+        self.response_head + self.response_body
+    }
+}
+
+/// Observe the lifecycle of the connections handled by a server.
+/// Every method has a default implementation doing nothing, implement only the ones you need.
+///
+/// # Example
+///
+/// Log every connection error
+///
+/// ```
+/// use mini_async_http::{AIOServer, ConnectionInfo, ConnectionObserver, RequestError, ResponseBuilder};
+///
+/// struct ErrorLogger;
+///
+/// impl ConnectionObserver for ErrorLogger {
+///     fn on_error(&self, info: &ConnectionInfo, error: &RequestError) {
+///         println!("{} : {:?}", info.peer(), error);
+///     }
+/// }
+///
+/// let mut server = AIOServer::new("127.0.0.1:7881".parse().unwrap(), |_| {
+///     ResponseBuilder::empty_200().build().unwrap()
+/// });
+/// server.set_observer(ErrorLogger);
+/// ```
+pub trait ConnectionObserver: Send + Sync {
+    /// Called when a new connection is accepted.
+    /// If false is returned the connection is closed right away, which can be used to block abusive clients.
+    fn on_open(&self, _info: &ConnectionInfo) -> bool {
+        true
+    }
+
+    /// Called for each request received on the connection, before it is handled
+    fn on_request(&self, _info: &ConnectionInfo, _request: &Request) {}
+
+    /// Called once the response to a request is written, with the number of bytes exchanged
+    fn on_response(
+        &self,
+        _info: &ConnectionInfo,
+        _request: &Request,
+        _response: &Response,
+        _transfer: &TransferStats,
+    ) {
+    }
+
+    /// Called when the connection stops because of an error (EOF, read, parse or write error)
+    fn on_error(&self, _info: &ConnectionInfo, _error: &RequestError) {}
+
+    /// Called once the connection is closed
+    fn on_close(&self, _info: &ConnectionInfo, _stats: &ConnectionStats) {}
+}
+
+pub(crate) struct DefaultObserver;
+
+impl ConnectionObserver for DefaultObserver {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stats_count() {
+        let mut stats = ConnectionStats::new();
+        assert_eq!(stats.requests(), 0);
+
+        stats.request_served();
+        stats.request_served();
+
+        assert_eq!(stats.requests(), 2);
+    }
+
+    #[test]
+    fn reuse_counters() {
+        let counters = ReuseCounters::new();
+        assert_eq!(counters.snapshot().requests_per_connection(), 0.0);
+
+        let mut stats = ConnectionStats::new();
+        stats.request_served();
+        counters.record(&stats);
+        stats.request_served();
+        stats.request_served();
+        counters.record(&stats);
+        counters.limit_reached();
+
+        let snapshot = counters.snapshot();
+        assert_eq!(snapshot.connections(), 2);
+        assert_eq!(snapshot.reused_connections(), 1);
+        assert_eq!(snapshot.limited_connections(), 1);
+        assert_eq!(snapshot.requests_per_connection(), 2.0);
+        assert_eq!(snapshot.reuse_rate(), 0.5);
+        assert!(snapshot.churn() > 0.0);
+    }
+
+    #[test]
+    fn transfer_totals() {
+        let transfer = TransferStats::new((40, 10), (60, 100));
+
+        assert_eq!(transfer.request_bytes(), 50);
+        assert_eq!(transfer.response_bytes(), 160);
+    }
+
+    #[test]
+    fn unique_ids() {
+        let peer = "127.0.0.1:80".parse().unwrap();
+
+        assert_ne!(
+            ConnectionInfo::new(peer).id(),
+            ConnectionInfo::new(peer).id()
+        );
+    }
+
+    #[test]
+    fn default_accepts() {
+        let info = ConnectionInfo::new("127.0.0.1:80".parse().unwrap());
+
+        assert!(DefaultObserver.on_open(&info));
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Unique identifier given by the server to every request it receives.
+/// The identifier of the request being handled can be retrieved with [`task_local::get`].
+///
+/// [`task_local::get`]: task_local/fn.get.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
+impl RequestId {
+    pub(crate) fn next() -> RequestId {
+        RequestId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Return the identifier as an integer
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unique() {
+        let first = RequestId::next();
+        let second = RequestId::next();
+
+        assert_ne!(first, second);
+    }
+}
@@ -1,31 +1,192 @@
-use crate::aioserver::enhanced_stream::EnhancedStream;
-use crate::data::AtomicTake;
+use crate::aioserver::background::{Job, RunningJobs};
+use crate::aioserver::cancellation::CancellationToken;
+use crate::aioserver::concurrency_limit::{ConcurrencyLimiter, ConcurrencyStats};
+use crate::aioserver::config::{LiveConfig, ServerConfig};
+use crate::aioserver::deadline::{timeout_response, Deadline};
+use crate::aioserver::enhanced_stream::{EnhancedStream, RequestError};
+use crate::aioserver::events::{event, ACCEPT, CONNECTION, REQUEST, SERVER, SLOW_REQUEST};
+use crate::aioserver::informational::Connection;
+use crate::aioserver::ip_limit::{IpGuard, IpLimiter, LimitAction};
+use crate::aioserver::middleware::AsyncMiddleware;
+use crate::aioserver::observer::{
+    ConnectionInfo, ConnectionObserver, ConnectionStats, DefaultObserver, KeepAliveStats,
+    ReuseCounters, TransferStats,
+};
+use crate::aioserver::request_id::RequestId;
+use crate::aioserver::transcript::TranscriptRecorder;
+use crate::aioserver::tunnel::Tunnel;
+use crate::aioserver::upgrade::Upgrade;
+use crate::data::{object_pool_stats, AtomicTake, Flag, ObjectPoolStats, TakeCell};
+use crate::executor::instrument::{ExecutorStats, Instrumentation, PoolStats};
+use crate::executor::task_local;
+use crate::executor::thread_pool::PoolHandle;
+use crate::executor::yield_now::yield_now;
+use crate::http::date::with_http_date_now;
+use crate::http::encoding;
 use crate::http::header::CLOSE_CONNECTION_HEADER;
 use crate::http::header::CONNECTION_HEADER;
+use crate::http::header::KEEP_ALIVE_CONNECTION_HEADER;
+use crate::http::parser::ParseError;
+use crate::http::{Method, Version};
 use crate::io::context;
+use crate::io::context::RuntimeConfig;
+use crate::io::delay::Delay;
+use crate::io::socket::SocketConfig;
+use crate::io::tcp_listener::{AcceptError, TcpListener};
+use crate::io::tcp_stream::TcpStream;
 use crate::request::Request;
-use crate::response::Response;
+use crate::response::error_page::{render_error, ErrorRenderer};
+use crate::response::{ErrorPage, Reason, Response, ResponseBuilder};
+use crate::router::host::host_name;
 
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::future::Future;
 use std::io::Write;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use std::ops::Drop;
 
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
 
 use futures::channel::oneshot;
-use futures::future::FutureExt;
+use futures::future::{BoxFuture, FutureExt};
 
-type Status = Arc<(Mutex<bool>, Condvar)>;
+pub(crate) type Handler =
+    Arc<dyn Send + Sync + 'static + for<'a> Fn(&'a Request) -> BoxFuture<'a, Response>>;
+type ConnectHandler = Arc<dyn Send + Sync + 'static + Fn(&Request, Tunnel)>;
+type PriorityClassifier = Arc<dyn Send + Sync + 'static + Fn(&Request) -> Priority>;
+type ParseErrorHandler =
+    Arc<dyn Send + Sync + 'static + Fn(&ParseError, &[u8]) -> Option<Response>>;
 pub(crate) type SafeStream<R> = Arc<Mutex<EnhancedStream<R>>>;
 
+const CONNECTION_ESTABLISHED: &[u8] = b"HTTP/1.1 200 Connection Established\r\n\r\n";
+
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(5);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+/// Time given to a client to close its side once the server closed the connection
+const LINGER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Everything needed to serve the requests of a connection
+#[derive(Clone)]
+pub(crate) struct Service {
+    handler: Handler,
+    priority: Option<PriorityClassifier>,
+    observer: Arc<dyn ConnectionObserver>,
+    trace: bool,
+    connect: Option<ConnectHandler>,
+    socket: SocketConfig,
+    config: Arc<LiveConfig>,
+    merge_slashes: bool,
+    transcripts: Option<Arc<PathBuf>>,
+    parse_error: ParseErrorHandler,
+    concurrent_pipeline: bool,
+    concurrency: Option<Arc<ConcurrencyLimiter>>,
+    error_renderer: Option<ErrorRenderer>,
+    length_mismatch: LengthMismatchPolicy,
+    draining: Arc<Flag>,
+    keep_alive: Arc<ReuseCounters>,
+}
+
+/// Behaviour of the server when the executor queue is full, see [`AIOServer::set_queue_capacity`]
+///
+/// [`AIOServer::set_queue_capacity`]: struct.AIOServer.html#method.set_queue_capacity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverloadPolicy {
+    /// New connections are answered with a 503 status code and closed
+    Reject,
+    /// The oldest queued connection not yet started is dropped to make room for the new one,
+    /// the connections already being served and the other tasks are kept
+    ShedOldest,
+}
+
+/// Class of the workers running the handler of a request, set on a route with [`Route::priority`]
+/// or for any request with [`AIOServer::set_priority_classifier`]
+///
+/// [`Route::priority`]: struct.Route.html#method.priority
+/// [`AIOServer::set_priority_classifier`]: struct.AIOServer.html#method.set_priority_classifier
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Priority {
+    /// The handler runs on the workers serving the connections, the default
+    #[default]
+    High,
+    /// The handler runs on the bulk workers set with [`AIOServer::set_bulk_workers`], so long uploads
+    /// and downloads do not keep the other requests waiting for a worker
+    ///
+    /// [`AIOServer::set_bulk_workers`]: struct.AIOServer.html#method.set_bulk_workers
+    Bulk,
+}
+
+/// Transition of the lifecycle of a server, delivered to the receivers of [`ServerHandle::subscribe`]
+///
+/// [`ServerHandle::subscribe`]: struct.ServerHandle.html#method.subscribe
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    /// The server listens on the given addresses and accepts connections
+    Ready { addrs: Vec<SocketAddr> },
+    /// A listener could not accept a connection because the process is out of resources, such as file
+    /// descriptors, or stopped accepting for good when `fatal` is true
+    AcceptError { message: String, fatal: bool },
+    /// The server stopped accepting and gives the open connections up to `timeout` to end
+    Draining {
+        connections: usize,
+        timeout: Duration,
+    },
+    /// The server stopped, `abandoned` connections were still open at the end of the drain timeout
+    Stopped {
+        reason: ShutdownReason,
+        abandoned: usize,
+    },
+}
+
+/// Cause of the stop of a server, see [`ServerEvent::Stopped`]
+///
+/// [`ServerEvent::Stopped`]: enum.ServerEvent.html#variant.Stopped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShutdownReason {
+    /// The server was stopped through its [`ServerHandle`]
+    ///
+    /// [`ServerHandle`]: struct.ServerHandle.html
+    Requested,
+    /// Every listener of the server failed
+    ListenersFailed,
+}
+
+/// Behaviour of the server when the "Content-Length" header of a response does not match its body,
+/// see [`AIOServer::set_length_mismatch_policy`]
+///
+/// [`AIOServer::set_length_mismatch_policy`]: struct.AIOServer.html#method.set_length_mismatch_policy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LengthMismatchPolicy {
+    /// The header is set to the length of the body
+    Correct,
+    /// The header is removed and the connection is closed after the body, which the client reads until the end
+    CloseDelimited,
+    /// The response is replaced by a 500 status code
+    InternalError,
+}
+
 /// Main struct of the crate, represent the http server
 pub struct AIOServer {
-    handler: Arc<dyn Send + Sync + 'static + Fn(&Request) -> Response>,
+    service: Service,
     handle: ServerHandle,
-    addr: SocketAddr,
+    addrs: Vec<SocketAddr>,
+    inherited: Vec<std::net::TcpListener>,
+    overload: Option<(usize, OverloadPolicy)>,
+    per_ip: Option<(usize, LimitAction)>,
+    allowed_ips: Vec<IpAddr>,
+    shed_accept: bool,
+    accept_per_worker: bool,
+    jobs: Vec<Job>,
+    runtime: RuntimeConfig,
 
-    stop_sender: Arc<AtomicTake<oneshot::Sender<()>>>,
+    stop_sender: Arc<AtomicTake<oneshot::Sender<Duration>>>,
 }
 
 impl AIOServer {
@@ -55,12 +216,50 @@ impl AIOServer {
     where
         H: Send + Sync + 'static + Fn(&Request) -> Response,
     {
-        let stop_sender = Arc::from(AtomicTake::<oneshot::Sender<()>>::new());
+        AIOServer::from_async(addr, move |request| {
+            futures::future::ready(handler(request)).boxed()
+        })
+    }
+
+    /// Create a server from a handler returning the response asynchronously
+    pub(crate) fn from_async<H>(addr: SocketAddr, handler: H) -> AIOServer
+    where
+        H: Send + Sync + 'static + for<'a> Fn(&'a Request) -> BoxFuture<'a, Response>,
+    {
+        let stop_sender = Arc::from(AtomicTake::<oneshot::Sender<Duration>>::new());
+        let draining = Arc::new(Flag::new(false));
+        let keep_alive = Arc::new(ReuseCounters::new());
+        let config = Arc::new(LiveConfig::new(ServerConfig::new()));
 
         AIOServer {
-            handler: Arc::from(handler),
-            handle: ServerHandle::new(stop_sender.clone()),
-            addr,
+            service: Service {
+                handler: Arc::from(handler),
+                priority: None,
+                observer: Arc::new(DefaultObserver),
+                trace: false,
+                connect: None,
+                socket: SocketConfig::default(),
+                config: config.clone(),
+                merge_slashes: true,
+                transcripts: None,
+                parse_error: Arc::new(default_parse_error),
+                concurrent_pipeline: false,
+                concurrency: None,
+                error_renderer: None,
+                length_mismatch: LengthMismatchPolicy::Correct,
+                draining: draining.clone(),
+                keep_alive: keep_alive.clone(),
+            },
+            handle: ServerHandle::new(stop_sender.clone(), draining, keep_alive, config),
+            addrs: vec![addr],
+            inherited: Vec::new(),
+            overload: None,
+            per_ip: None,
+            allowed_ips: Vec::new(),
+            shed_accept: false,
+            accept_per_worker: false,
+            jobs: Vec::new(),
+            runtime: RuntimeConfig::default(),
             stop_sender,
         }
     }
@@ -83,7 +282,65 @@ impl AIOServer {
     /// ```
     /// [`Router`]: struct.Router.html
     pub fn from_router(addr: SocketAddr, router: crate::Router) -> AIOServer {
-        AIOServer::new(addr, move |req| router.exec(req))
+        let router = Arc::new(router);
+        let classifier = router.clone();
+
+        let mut server = AIOServer::from_async(addr, move |req| {
+            let router = router.clone();
+            async move { router.respond(req).await }.boxed()
+        });
+        server.set_priority_classifier(move |req| classifier.priority(req));
+        server
+    }
+
+    /// Create a server answering every request with a redirection to its `https://` equivalent,
+    /// keeping the host and the path. GET and HEAD requests get a 301, the others a 308 so the client
+    /// does not turn them into a GET. It is meant to listen on the port 80 next to a TLS server,
+    /// the port of the redirections is only written when it is not 443.
+    /// A request without Host header gets a 400 response.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let mut redirect = mini_async_http::AIOServer::https_redirect("0.0.0.0:80".parse().unwrap(), 443);
+    /// std::thread::spawn(move || redirect.start());
+    /// ```
+    pub fn https_redirect(addr: SocketAddr, https_port: u16) -> AIOServer {
+        AIOServer::new(addr, move |request| {
+            https_redirect_response(request, https_port)
+        })
+    }
+
+    /// Create a server accepting the connections of an already open listener instead of binding an address.
+    /// Combined with [`ServerHandle::export_listener`], a new version of a program can take over the listener
+    /// of the running one so no connection is refused during the restart.
+    ///
+    /// # Example
+    ///
+    /// The listener is given by the previous process, here through the file descriptor 3
+    ///
+    /// ```no_run
+    /// use std::os::unix::io::FromRawFd;
+    ///
+    /// let listener = unsafe { std::net::TcpListener::from_raw_fd(3) };
+    /// let server = mini_async_http::AIOServer::from_listener(listener, move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// ```
+    ///
+    /// [`ServerHandle::export_listener`]: struct.ServerHandle.html#method.export_listener
+    pub fn from_listener<H>(listener: std::net::TcpListener, handler: H) -> AIOServer
+    where
+        H: Send + Sync + 'static + Fn(&Request) -> Response,
+    {
+        let addr = listener
+            .local_addr()
+            .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+        let mut server = AIOServer::new(addr, handler);
+        server.addrs.clear();
+        server.inherited.push(listener);
+
+        server
     }
 
     /// Start the event loop. This call is blocking but you can still interact with the server through the Handle
@@ -113,182 +370,2324 @@ impl AIOServer {
     ///
     /// ```
     pub fn start(&mut self) {
-        context::start();
+        self.handle.settled.set(false);
+        self.handle.running.set(true);
+        context::start(&self.runtime);
 
-        self.async_run();
+        let stopped = Arc::new(Mutex::new(None));
+        self.async_run(stopped.clone());
+        self.handle.set_listeners(Vec::new());
+        self.handle.set_pool(None);
+        context::stop();
 
-        self.handle.set_ready(false);
+        // After a failed start the waiters still get their answer until the server is started again
+        if self.handle.ready.get() {
+            self.handle.settled.set(false);
+        }
+        self.handle.ready.set(false);
+        self.handle.running.set(false);
+        let stopped = stopped.lock().unwrap().take();
+        if let Some(stopped) = stopped {
+            self.handle.emit(stopped);
+        }
     }
 
-    fn async_run(&mut self) {
-        let handler = self.handler.clone();
+    /// Run the server until it stops, the event describing the stop is stored in `stopped`
+    fn async_run(&mut self, stopped: Arc<Mutex<Option<ServerEvent>>>) {
+        let service = self.service.clone();
         let handle = self.handle();
-        let addr = self.addr;
+        let addrs = self.addrs.clone();
+        let inherited: Vec<TcpListener> = self
+            .inherited
+            .iter()
+            .filter_map(|listener| match listener.try_clone() {
+                Ok(listener) => TcpListener::from_std(listener).ok(),
+                Err(e) => {
+                    event!(
+                        error,
+                        SERVER,
+                        [],
+                        "Could not use the inherited listener : {}",
+                        e
+                    );
+                    None
+                }
+            })
+            .collect();
+        let overload = self.overload;
+        let shed_accept = self.shed_accept;
+        let accept_per_worker = self.accept_per_worker;
+        let jobs = self.jobs.clone();
+        let per_ip = self.per_ip.map(|(max, action)| {
+            (
+                Arc::new(IpLimiter::new(max, self.allowed_ips.clone())),
+                action,
+            )
+        });
 
-        let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+        let (stop_sender, stop_receiver) = oneshot::channel::<Duration>();
         self.stop_sender.store(stop_sender);
 
         let server = async move {
-            let listener = crate::io::tcp_listener::TcpListener::bind(addr);
-            handle.set_ready(true);
-
-            let receiver = stop_receiver.fuse();
-            futures::pin_mut!(receiver);
-
-            loop {
-                let accept = listener.accept().fuse();
-                futures::pin_mut!(accept);
+            // IPv6 listeners only take IPv6 connections so they can share their port with IPv4 listeners
+            let mut socket = service.socket.clone();
+            if addrs.len() > 1 {
+                socket.only_v6.get_or_insert(true);
+            }
+            socket.reuse_port |= accept_per_worker;
 
-                let connection = futures::select! {
-                    conn = accept => conn,
-                    _ = receiver => {return},
-                };
-                let connection = match connection {
-                    Ok((conn, _)) => conn,
-                    Err(_) => return,
-                };
+            let listeners = match bind_listeners(&addrs, &socket) {
+                Some(listeners) => listeners.into_iter().chain(inherited).collect::<Vec<_>>(),
+                None => {
+                    *stopped.lock().unwrap() = Some(ServerEvent::Stopped {
+                        reason: ShutdownReason::ListenersFailed,
+                        abandoned: 0,
+                    });
+                    handle.settled.set(true);
+                    return;
+                }
+            };
+            let bound: Vec<SocketAddr> = listeners
+                .iter()
+                .filter_map(|listener| listener.local_addr().ok())
+                .collect();
+            handle.set_local_addrs(bound.clone());
+            handle.set_listeners(
+                listeners
+                    .iter()
+                    .filter_map(|listener| listener.export().ok())
+                    .collect(),
+            );
+            let pool = context::pool().expect("Context not started");
 
-                let handler = handler.clone();
-                context::spawn(async move {
-                    let connection = crate::io::tcp_stream::TcpStream::from_stream(connection);
-                    let mut stream = EnhancedStream::new(0, connection);
-                    loop {
-                        let requests = match stream.poll_requests().await {
-                            Ok(reqs) => reqs,
-                            Err(_) => return,
-                        };
-
-                        for request in requests {
-                            let response = (handler)(&request);
-                            write!(stream, "{}", response).unwrap();
-
-                            if let Some(header) = request.headers().get_header(CONNECTION_HEADER) {
-                                if header == CLOSE_CONNECTION_HEADER {
-                                    return;
-                                }
-                            }
+            // The other workers listen on the addresses bound by the first one, their port included when picked by the system
+            let mut shards = vec![listeners];
+            if accept_per_worker && !addrs.is_empty() {
+                let shard_addrs: Vec<SocketAddr> =
+                    bound.iter().take(addrs.len()).copied().collect();
+                for _ in 1..pool.size() {
+                    match bind_listeners(&shard_addrs, &socket) {
+                        Some(listeners) => shards.push(listeners),
+                        None => {
+                            *stopped.lock().unwrap() = Some(ServerEvent::Stopped {
+                                reason: ShutdownReason::ListenersFailed,
+                                abandoned: 0,
+                            });
+                            handle.settled.set(true);
+                            return;
                         }
                     }
-                });
+                }
             }
+            if shed_accept {
+                shards.iter().flatten().for_each(TcpListener::reserve_fd);
+            }
+
+            handle.draining.set(false);
+            handle.set_pool(Some(pool.clone()));
+            let jobs = RunningJobs::start(&jobs);
+            handle.ready.set(true);
+            handle.settled.set(true);
+            handle.emit(ServerEvent::Ready { addrs: bound });
+
+            let shutdown = CancellationToken::new();
+            let acceptor = Acceptor {
+                service,
+                handle: handle.clone(),
+                pool: pool.clone(),
+                overload,
+                per_ip,
+                shutdown: shutdown.clone(),
+            };
+
+            let (reason, timeout) = if accept_per_worker {
+                let mut stops = Vec::new();
+                let mut loops = Vec::new();
+
+                for (index, listeners) in shards.into_iter().enumerate() {
+                    let (stop_sender, stop) = oneshot::channel();
+                    let (done, finished) = oneshot::channel();
+                    let accept = acceptor.clone().run(listeners, stop, Some(index));
+
+                    let spawned = pool.spawn_on(index, async move {
+                        let _ = done.send(accept.await);
+                    });
+                    if spawned.is_err() {
+                        event!(
+                            error,
+                            SERVER,
+                            [],
+                            "Could not start the accept loop of the worker {}",
+                            index
+                        );
+                        continue;
+                    }
+                    stops.push(stop_sender);
+                    loops.push(finished);
+                }
+
+                let timeout = stop_receiver.await.unwrap_or_default();
+                for stop in stops {
+                    let _ = stop.send(timeout);
+                }
+                futures::future::join_all(loops).await;
+                (ShutdownReason::Requested, timeout)
+            } else {
+                let listeners = shards.pop().unwrap_or_default();
+                match acceptor.run(listeners, stop_receiver, None).await {
+                    Some(timeout) => (ShutdownReason::Requested, timeout),
+                    // The server stops as with a shutdown without drain timeout
+                    None => (ShutdownReason::ListenersFailed, Duration::from_secs(0)),
+                }
+            };
+
+            // The long running handlers and streams are told to stop so the connections can be drained
+            shutdown.cancel();
+            handle.emit(ServerEvent::Draining {
+                connections: handle.connection_count(),
+                timeout,
+            });
+            handle.drain(timeout).await;
+            *stopped.lock().unwrap() = Some(ServerEvent::Stopped {
+                reason,
+                abandoned: handle.connection_count(),
+            });
+            // The jobs keep running while the connections are drained
+            jobs.stop().await;
         };
         context::block_on(server);
     }
 }
 
 impl AIOServer {
-    /// Get a [`ServerHandle`] to this server
+    /// Set the [`ConnectionObserver`] notified of the lifecycle of every connection handled by the server
     ///
-    /// [`ServerHandle`]: struct.ServerHandle.html
-    pub fn handle(&self) -> ServerHandle {
-        self.handle.clone()
+    /// [`ConnectionObserver`]: trait.ConnectionObserver.html
+    pub fn set_observer<O>(&mut self, observer: O)
+    where
+        O: ConnectionObserver + 'static,
+    {
+        self.service.observer = Arc::new(observer);
     }
-}
 
-impl Drop for AIOServer {
-    fn drop(&mut self) {
-        self.handle.shutdown();
+    /// Enable or disable the answer to TRACE requests.
+    /// When enabled, TRACE requests are not given to the handler, the server echoes them back in the
+    /// response body. Disabled by default.
+    pub fn set_trace_enabled(&mut self, enabled: bool) {
+        self.service.trace = enabled;
     }
-}
-/// Clonable handle to a server.
-/// Can only be retrieved from a Server instance.
-/// Used to wait for the server to be ready or to shut it down.
-#[derive(Clone)]
-pub struct ServerHandle {
-    ready: Status,
-    stop_sender: Arc<AtomicTake<oneshot::Sender<()>>>,
-}
 
-impl ServerHandle {
-    fn new(stop_sender: Arc<AtomicTake<oneshot::Sender<()>>>) -> Self {
-        ServerHandle {
-            ready: Arc::new((Mutex::from(false), Condvar::new())),
-            stop_sender,
-        }
+    /// Set the handler used for CONNECT requests.
+    /// Once the tunnel is established the handler is given the raw connection to the client and is
+    /// responsible for it. As the [`Tunnel`] is blocking, the handler is called on a thread dedicated to the
+    /// tunnel and can relay it until it is closed without holding up a worker.
+    ///
+    /// Without handler, CONNECT requests are answered with a 405 status code.
+    ///
+    /// [`Tunnel`]: struct.Tunnel.html
+    pub fn set_connect_handler<H>(&mut self, handler: H)
+    where
+        H: Send + Sync + 'static + Fn(&Request, Tunnel),
+    {
+        self.service.connect = Some(Arc::from(handler));
     }
 
-    fn set_ready(&self, ready_val: bool) {
-        let (lock, cvar) = &*self.ready;
-        let mut ready = lock.lock().unwrap();
-        *ready = ready_val;
+    /// Run the given middleware around the handler of the server, its future can await before and after the handler.
+    /// Every call wraps the current chain, so the middleware added last is the first to see the requests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{AIOServer, AsyncMiddleware, ResponseBuilder};
+    ///
+    /// let mut server = AIOServer::new("127.0.0.1:7894".parse().unwrap(), move |request|{
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.add_middleware(AsyncMiddleware::new(|req, next| {
+    ///     Box::pin(async move {
+    ///         let mut response = next.run(req).await;
+    ///         response.headers.set_header("x-powered-by", "mini_async_http");
+    ///         response
+    ///     })
+    /// }));
+    /// ```
+    pub fn add_middleware(&mut self, middleware: AsyncMiddleware) {
+        self.service.handler = middleware.wrap(self.service.handler.clone());
+    }
 
-        cvar.notify_all();
+    /// Set the handler answering the requests that can not be parsed, before the connection is closed.
+    /// It is given the parse error and the bytes received since the last valid request, and returns the
+    /// response to send or None to close the connection without answering.
+    ///
+    /// By default, requests with too many or too large headers are answered with a 431 status code and the other
+    /// invalid requests with a 400 status code. The errors are also reported to the [`ConnectionObserver`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{AIOServer, ParseError, ResponseBuilder};
+    ///
+    /// let mut server = AIOServer::new("127.0.0.1:7890".parse().unwrap(), |_| {
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_parse_error_handler(|error, _received| match error {
+    ///     ParseError::TooManyHeaders => Some(ResponseBuilder::empty_431().build().unwrap()),
+    ///     _ => Some(ResponseBuilder::empty_400().text("Invalid request").build().unwrap()),
+    /// });
+    /// ```
+    ///
+    /// [`ConnectionObserver`]: trait.ConnectionObserver.html
+    pub fn set_parse_error_handler<H>(&mut self, handler: H)
+    where
+        H: Send + Sync + 'static + Fn(&ParseError, &[u8]) -> Option<Response>,
+    {
+        self.service.parse_error = Arc::from(handler);
     }
 
-    /// Send a shutdown signal to the server and wait for it to stop.
-    /// If the server is not started, the function returns immediately.
+    /// Bound the number of connections waiting in the executor queue.
+    /// When the queue holds `capacity` tasks, new connections are handled according to the given [`OverloadPolicy`].
+    /// The queue is unbounded by default.
     ///
     /// # Example
     ///
-    /// Creates a server and starts it. From another thread we send the shutdown signal
-    /// causing the server to stop and the execution to end.
+    /// ```
+    /// use mini_async_http::{AIOServer, OverloadPolicy, ResponseBuilder};
     ///
+    /// let mut server = AIOServer::new("127.0.0.1:7885".parse().unwrap(), |_| {
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_queue_capacity(1024, OverloadPolicy::Reject);
     /// ```
-    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7880".parse().unwrap(), move |request|{
-    ///     mini_async_http::ResponseBuilder::empty_200()
-    ///         .body(b"Hello")
-    ///         .content_type("text/plain")
-    ///         .build()
-    ///         .unwrap()
+    ///
+    /// [`OverloadPolicy`]: enum.OverloadPolicy.html
+    pub fn set_queue_capacity(&mut self, capacity: usize, policy: OverloadPolicy) {
+        self.overload = Some((capacity, policy));
+    }
+
+    /// Instrument the executor: every poll of a task is timed and a warning is logged when one
+    /// lasts longer than the threshold, which usually means a handler is blocking its worker thread.
+    /// The statistics are available through [`ServerHandle::executor_stats`].
+    ///
+    /// [`ServerHandle::executor_stats`]: struct.ServerHandle.html#method.executor_stats
+    pub fn set_slow_poll_threshold(&mut self, threshold: Duration) {
+        self.runtime.instrumentation = Some(Instrumentation::new(
+            threshold,
+            self.handle.executor_stats.clone(),
+        ));
+    }
+
+    /// Log a warning for every request whose handler takes longer than the threshold to respond,
+    /// with the method, the path, the latency and the address of the client.
+    /// The warnings use the log target "mini_async_http::slow_request" so they can be kept apart from the other logs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7892".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
     /// });
-    /// let handle = server.handle();
+    /// server.set_slow_request_threshold(Duration::from_millis(500));
+    /// ```
+    pub fn set_slow_request_threshold(&mut self, threshold: Duration) {
+        self.service
+            .config
+            .update(|config| config.slow_request = Some(threshold));
+    }
+
+    /// Limit the time the handler has to respond to a request, counted from its reception.
+    /// A response produced after the limit is replaced by a 503 status code, the handler itself is not interrupted.
+    /// Routes can replace this limit with their own, see [`Route::timeout`].
     ///
-    /// std::thread::spawn(move || {
-    ///     handle.ready();
-    ///     handle.shutdown();
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7893".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
     /// });
+    /// server.set_request_timeout(Duration::from_secs(5));
+    /// ```
     ///
-    /// server.start();
+    /// [`Route::timeout`]: struct.Route.html#method.timeout
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.service
+            .config
+            .update(|config| config.request_timeout = Some(timeout));
+    }
+
+    /// Limit the number of handlers running at once, shedding the requests over the limit with a 503 status code.
+    /// The limit starts at `max` and adapts to the latency of the handlers: it shrinks by 10% when a handler
+    /// takes longer than `target` and grows back by one for every limit requests answered in time.
+    /// Under overload the server then answers part of the requests right away instead of making every request slow.
+    /// The current limit is available through [`ServerHandle::concurrency_stats`].
+    ///
+    /// # Example
     ///
     /// ```
-    pub fn shutdown(&self) {
-        let sender = match self.stop_sender.take() {
-            Some(val) => val,
-            None => return,
-        };
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7900".parse().unwrap(), |_| {
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_adaptive_concurrency(std::time::Duration::from_millis(200), 256);
+    /// ```
+    ///
+    /// [`ServerHandle::concurrency_stats`]: struct.ServerHandle.html#method.concurrency_stats
+    pub fn set_adaptive_concurrency(&mut self, target: Duration, max: usize) {
+        let limiter = Arc::new(ConcurrencyLimiter::new(target, max));
+        *self.handle.concurrency.lock().unwrap() = Some(limiter.clone());
+        self.service.concurrency = Some(limiter);
+    }
 
-        if sender.send(()).is_err() {
-            return;
-        }
+    /// Add a header to every response of the server, such as `Server` or `X-Frame-Options`, unless the handler sets it.
+    /// The headers of the routes and of the router take precedence over the ones of the server.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7897".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_default_header("Server", "mini-async-http");
+    /// ```
+    pub fn set_default_header(&mut self, name: &str, value: &str) {
+        self.service
+            .config
+            .update(|config| config.default_headers.set_header(name, value));
+    }
 
-        let (lock, cvar) = &*self.ready;
-        let mut started = lock.lock().unwrap();
+    /// Replace the timeouts, limits and default headers set so far with the given configuration,
+    /// which can be changed afterwards while the server runs with [`ServerHandle::reload_config`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::ServerConfig;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7905".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_config(
+    ///     ServerConfig::new()
+    ///         .request_timeout(Duration::from_secs(5))
+    ///         .default_header("Server", "mini-async-http"),
+    /// );
+    /// ```
+    ///
+    /// [`ServerHandle::reload_config`]: struct.ServerHandle.html#method.reload_config
+    pub fn set_config(&mut self, config: ServerConfig) {
+        self.service.config.set(config);
+    }
 
-        while *started {
-            started = cvar.wait(started).unwrap();
-        }
+    /// Choose what is done with a response whose "Content-Length" header does not match its body, the header is
+    /// corrected by default. Sent as is, the client would read a part of the body as the next response of the
+    /// connection, or wait for bytes that never come. Each mismatch is logged as a warning.
+    ///
+    /// The responses to HEAD requests and the ones without body by definition, such as 204 and 304,
+    /// are not checked as their header can describe a body that is not sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::LengthMismatchPolicy;
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7898".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_length_mismatch_policy(LengthMismatchPolicy::InternalError);
+    /// ```
+    pub fn set_length_mismatch_policy(&mut self, policy: LengthMismatchPolicy) {
+        self.service.length_mismatch = policy;
     }
 
-    /// Block untill the server is ready to receive requests
+    /// Give a body to the error responses sent without one, such as the 404 of the default not found handler,
+    /// the 405 answering CONNECT requests, the responses to invalid requests and the 500 replacing the response
+    /// of a handler that panicked. The renderer is given the status of the response and returns the body, in HTML
+    /// for the clients preferring it to JSON and in JSON otherwise. [`ErrorPage::render`] gives a default page.
     ///
     /// # Example
     ///
-    /// Creates a server and starts it in a separate thread.
-    /// The main thread waits for the server to be ready and then ends
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7899".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_404().build().unwrap()
+    /// });
+    /// server.set_error_renderer(|page| page.render());
+    /// ```
+    ///
+    /// [`ErrorPage::render`]: struct.ErrorPage.html#method.render
+    pub fn set_error_renderer<R>(&mut self, renderer: R)
+    where
+        R: Send + Sync + 'static + Fn(&ErrorPage) -> String,
+    {
+        self.service.error_renderer = Some(Arc::new(renderer));
+    }
+
+    /// Listen on another address in addition to the one given at creation, all the addresses share the same handler.
+    /// When several addresses are used, IPv6 listeners only accept IPv6 connections
+    /// so the server can listen on both `0.0.0.0` and `[::]` with the same port.
+    ///
+    /// # Example
     ///
     /// ```
-    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7880".parse().unwrap(), move |request|{
-    ///     mini_async_http::ResponseBuilder::empty_200()
-    ///         .body(b"Hello")
-    ///         .content_type("text/plain")
-    ///         .build()
-    ///         .unwrap()
+    /// let mut server = mini_async_http::AIOServer::new("0.0.0.0:8080".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
     /// });
-    /// let handle = server.handle();
+    /// server.add_address("[::]:8080".parse().unwrap());
+    /// ```
+    pub fn add_address(&mut self, addr: SocketAddr) {
+        self.addrs.push(addr);
+    }
+
+    /// Enable or disable TCP_NODELAY on the accepted connections, enabled by default.
+    /// When enabled small responses are sent right away instead of being delayed by Nagle's algorithm.
+    /// Each response is then sent in its own segments, except with a concurrent pipeline which writes
+    /// the responses ready at once together, see [`set_concurrent_pipeline`].
     ///
-    /// std::thread::spawn(move || {
-    ///     server.start();
+    /// [`set_concurrent_pipeline`]: #method.set_concurrent_pipeline
+    pub fn set_tcp_nodelay(&mut self, nodelay: bool) {
+        self.service.socket.nodelay = nodelay;
+    }
+
+    /// Enable TCP keepalive on the accepted connections.
+    /// Probes are sent after the connection has been idle for `time` and then every `interval`.
+    /// The interval is ignored on the platforms where it can not be set.
+    pub fn set_tcp_keepalive(&mut self, time: Duration, interval: Duration) {
+        self.service.socket.keepalive = Some((time, interval));
+    }
+
+    /// Set SO_LINGER on the accepted connections.
+    /// With a duration, closing a connection blocks until the pending data is sent or the duration is elapsed.
+    pub fn set_tcp_linger(&mut self, linger: Option<Duration>) {
+        self.service.socket.linger = linger;
+    }
+
+    /// Set SO_REUSEPORT on the listeners so other processes can listen on the same address.
+    /// The system then spreads the new connections between them, which lets a new version of a program
+    /// start before the old one is shut down. Ignored on the platforms without SO_REUSEPORT.
+    pub fn set_reuse_port(&mut self, reuse: bool) {
+        self.service.socket.reuse_port = reuse;
+    }
+
+    /// Set the maximum number of pending connections waiting to be accepted, 1024 by default
+    pub fn set_listen_backlog(&mut self, backlog: u32) {
+        self.service.socket.backlog = i32::try_from(backlog).unwrap_or(i32::MAX);
+    }
+
+    /// Set the maximum number of pipelined requests read at once from a connection, 32 by default.
+    /// The connection is not read again until they are answered, which slows down a client sending
+    /// requests faster than they are handled.
+    pub fn set_pipeline_limit(&mut self, limit: usize) {
+        self.service
+            .config
+            .update(|config| config.pipeline_limit = limit.max(1));
+    }
+
+    /// Handle the pipelined requests read at once from a connection concurrently on the worker threads,
+    /// instead of one after the other. The responses are still written in the order of the requests.
+    /// Disabled by default, as the handlers then have to cope with several requests of the same client at once.
+    /// Handlers run this way can not send informational responses.
+    ///
+    /// The responses without file body that are ready together are written in a single write, up to 16 KiB,
+    /// so a batch of small responses is sent in as few segments as possible even with TCP_NODELAY.
+    pub fn set_concurrent_pipeline(&mut self, enabled: bool) {
+        self.service.concurrent_pipeline = enabled;
+    }
+
+    /// Close the connections once they have answered this number of requests, the last response carrying
+    /// `Connection: close`. Unlimited by default. A limit moves the long lived clients between the instances
+    /// behind a load balancer, at the cost of new connections shown by [`ServerHandle::keep_alive_stats`].
+    ///
+    /// [`ServerHandle::keep_alive_stats`]: struct.ServerHandle.html#method.keep_alive_stats
+    pub fn set_max_requests_per_connection(&mut self, max: Option<usize>) {
+        self.service
+            .config
+            .update(|config| config.max_requests = max.map(|max| max.max(1)));
+    }
+
+    /// Set the maximum size in bytes of the request line and headers of a request, 16 KiB by default.
+    /// Larger requests are answered with a 431 status code and their connection is closed
+    /// before the rest of their headers is buffered.
+    pub fn set_max_header_size(&mut self, size: usize) {
+        self.service
+            .config
+            .update(|config| config.max_head_size = size);
+    }
+
+    /// Set the maximum number of headers of a request, 64 by default.
+    /// Requests with more headers are answered with a 431 status code and their connection is closed.
+    pub fn set_max_headers(&mut self, max: usize) {
+        self.service
+            .config
+            .update(|config| config.max_headers = max);
+    }
+
+    /// Merge the consecutive slashes of the request paths before they are routed, enabled by default.
+    /// The "." and ".." segments of the paths are always resolved, so a request like `/public/../admin`
+    /// reaches the routes of `/admin` and their guards instead of bypassing them.
+    pub fn set_merge_slashes(&mut self, merge: bool) {
+        self.service.merge_slashes = merge;
+    }
+
+    /// Record the bytes exchanged on every connection to a file of the directory named after the id
+    /// of the connection, such as `42.transcript`, to debug the clients talking to the server in unexpected ways.
+    /// The files are read and replayed with [`Transcript`]. Recording is meant for debugging: every read and
+    /// write of the connections is also written to their file, and the file bodies are copied instead of
+    /// being sent without copy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7906".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
     /// });
+    /// server.set_transcript_dir("/tmp/transcripts");
+    /// ```
     ///
-    /// handle.ready();
+    /// [`Transcript`]: struct.Transcript.html
+    pub fn set_transcript_dir<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.service.transcripts = Some(Arc::new(dir.into()));
+    }
+
+    /// Limit the number of connections open at once by each client address, to blunt connection floods.
+    /// The connections over the limit are handled according to the given [`LimitAction`] as soon as
+    /// they are accepted, the addresses added with [`add_allowed_ip`] are not limited.
+    ///
+    /// # Example
     ///
     /// ```
-    pub fn ready(&self) {
-        let (lock, cvar) = &*self.ready;
-        let mut started = lock.lock().unwrap();
+    /// use mini_async_http::{AIOServer, LimitAction, ResponseBuilder};
+    ///
+    /// let mut server = AIOServer::new("127.0.0.1:7901".parse().unwrap(), |_| {
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_max_connections_per_ip(32, LimitAction::Reject);
+    /// // The load balancer opens many connections
+    /// server.add_allowed_ip("10.0.0.1".parse().unwrap());
+    /// ```
+    ///
+    /// [`LimitAction`]: enum.LimitAction.html
+    /// [`add_allowed_ip`]: #method.add_allowed_ip
+    pub fn set_max_connections_per_ip(&mut self, max: usize, action: LimitAction) {
+        self.per_ip = Some((max, action));
+    }
 
-        while !*started {
-            started = cvar.wait(started).unwrap();
-        }
+    /// Exempt a client address from the limit set with [`set_max_connections_per_ip`]
+    ///
+    /// [`set_max_connections_per_ip`]: #method.set_max_connections_per_ip
+    pub fn add_allowed_ip(&mut self, ip: IpAddr) {
+        self.allowed_ips.push(ip);
+    }
+
+    /// Close new connections right away when the process runs out of file descriptors.
+    /// A file descriptor is kept in reserve to accept them, otherwise the pending connections wait
+    /// in the listen queue while the server retries accepting with an increasing delay.
+    pub fn set_accept_shedding(&mut self, shed: bool) {
+        self.shed_accept = shed;
+    }
+
+    /// Accept the connections on every worker instead of a single task handing them to the pool.
+    /// Each worker listens on the addresses of the server with its own SO_REUSEPORT listener, the system
+    /// spreads the new connections between them and every connection is handled by the worker that accepted it,
+    /// which keeps its data in the caches of one core. Disabled by default.
+    ///
+    /// The capacity set with [`set_queue_capacity`] does not apply as the connections skip the global queue.
+    /// Only the listeners of the first worker are exported by [`ServerHandle::export_listener`], and a listener
+    /// inherited with [`from_listener`] is only accepted on by the first worker. Only supported on the platforms
+    /// with SO_REUSEPORT, where the connections are balanced between the listeners as on Linux.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7902".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.set_accept_per_worker(true);
+    /// ```
+    ///
+    /// [`set_queue_capacity`]: #method.set_queue_capacity
+    /// [`ServerHandle::export_listener`]: struct.ServerHandle.html#method.export_listener
+    /// [`from_listener`]: #method.from_listener
+    pub fn set_accept_per_worker(&mut self, enabled: bool) {
+        self.accept_per_worker = enabled;
+    }
+
+    /// Run a job in the background on the executor of the server, such as a cache eviction or a flush of metrics.
+    /// The job is started with the server and stopped at its next await once the server is shut down and its
+    /// connections drained. A new run of the job is started every time the server is started.
+    ///
+    /// # Example
+    ///
+    /// Evict the expired entries of a cache every minute
+    ///
+    /// ```
+    /// use mini_async_http::runtime;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7903".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// server.add_background_job(|| async {
+    ///     let mut interval = runtime::interval(Duration::from_secs(60));
+    ///     loop {
+    ///         interval.tick().await;
+    ///         // Evict the expired entries ...
+    ///     }
+    /// });
+    /// ```
+    pub fn add_background_job<J, F>(&mut self, job: J)
+    where
+        J: Send + Sync + 'static + Fn() -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.jobs.push(Arc::new(move || job().boxed()));
+    }
+
+    /// Set the prefix of the names of the threads started by the server, "mini-async-http" by default.
+    /// Worker threads are named "{prefix}-worker-{index}" and the event loop thread "{prefix}-reactor".
+    pub fn set_thread_name(&mut self, prefix: &str) {
+        self.runtime.thread_name = prefix.to_string();
+    }
+
+    /// Set the stack size in bytes of the threads started by the server
+    pub fn set_thread_stack_size(&mut self, size: usize) {
+        self.runtime.stack_size = Some(size);
+    }
+
+    /// Pin each thread started by the server to a core.
+    /// The worker of index i runs on the core i and the event loop thread on the last core.
+    pub fn set_thread_affinity(&mut self, affinity: bool) {
+        self.runtime.affinity = affinity;
+    }
+
+    /// Start a separate pool of threads running the handlers of the [`Priority::Bulk`] requests, none by default.
+    /// As the handlers block the thread running them, large uploads and downloads would otherwise occupy the
+    /// workers while health checks and small API calls wait. The threads are named "{prefix}-bulk-{index}".
+    /// Without bulk workers every handler runs on the workers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{AIOServer, Method, Priority, ResponseBuilder, Route, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(Route::new("/health", Method::GET).unwrap(), |_, _| {
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// router.add_route(
+    ///     Route::new("/upload", Method::POST).unwrap().priority(Priority::Bulk),
+    ///     |_, _| ResponseBuilder::empty_200().build().unwrap(),
+    /// );
+    ///
+    /// let mut server = AIOServer::from_router("127.0.0.1:7899".parse().unwrap(), router);
+    /// server.set_bulk_workers(4);
+    /// ```
+    ///
+    /// [`Priority::Bulk`]: enum.Priority.html#variant.Bulk
+    pub fn set_bulk_workers(&mut self, workers: usize) {
+        self.runtime.bulk_workers = workers;
+    }
+
+    /// Set the function giving the [`Priority`] of each request, replacing the priorities of the routes
+    /// when the server is created from a router.
+    ///
+    /// [`Priority`]: enum.Priority.html
+    pub fn set_priority_classifier<F>(&mut self, classifier: F)
+    where
+        F: Send + Sync + 'static + Fn(&Request) -> Priority,
+    {
+        self.service.priority = Some(Arc::new(classifier));
+    }
+
+    pub(crate) fn service(&self) -> Service {
+        self.service.clone()
+    }
+
+    /// Get a [`ServerHandle`] to this server
+    ///
+    /// [`ServerHandle`]: struct.ServerHandle.html
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+}
+
+impl Service {
+    pub(crate) async fn respond(&self, request: &Request) -> Response {
+        match request.method() {
+            Method::TRACE if self.trace => ResponseBuilder::empty_200()
+                .content_type("message/http")
+                .body(request.to_string().as_bytes())
+                .build()
+                .unwrap(),
+            Method::CONNECT => ResponseBuilder::empty_405().build().unwrap(),
+            _ => match (&self.priority, context::bulk_pool()) {
+                (Some(priority), Some(bulk)) if priority(request) == Priority::Bulk => {
+                    self.respond_on(&bulk, request).await
+                }
+                _ => (self.handler)(request).await,
+            },
+        }
+    }
+
+    /// Run the handler on the given pool, with the task locals of the request.
+    /// The request is copied as it must outlive the current task.
+    async fn respond_on(&self, pool: &PoolHandle, request: &Request) -> Response {
+        let (sender, receiver) = oneshot::channel();
+        let handler = self.handler.clone();
+        let request = request.clone();
+
+        let task = task_local::inherit(async move {
+            let response = handler(&request).await;
+            // The connection may be closed already
+            let _ = sender.send(response);
+        });
+        #[cfg(feature = "tracing")]
+        let task = tracing::Instrument::in_current_span(task);
+
+        if pool.spawn(task).is_err() {
+            return ResponseBuilder::empty_503().build().unwrap();
+        }
+        // The receiver is canceled when the task is dropped without running, as when the pool stops
+        receiver
+            .await
+            .unwrap_or_else(|_| ResponseBuilder::empty_503().build().unwrap())
+    }
+}
+
+/// Everything needed to accept the connections of the server, shared by the accept loops
+#[derive(Clone)]
+struct Acceptor {
+    service: Service,
+    handle: ServerHandle,
+    pool: PoolHandle,
+    overload: Option<(usize, OverloadPolicy)>,
+    per_ip: Option<(Arc<IpLimiter>, LimitAction)>,
+    shutdown: CancellationToken,
+}
+
+impl Acceptor {
+    /// Accept the connections of the listeners until the server is stopped and return the shutdown timeout,
+    /// None if every listener failed. Given a worker, the connections are pinned to it.
+    /// The listeners are closed when the loop returns so the new connections go to the servers they were exported to.
+    async fn run(
+        self,
+        mut listeners: Vec<TcpListener>,
+        stop: oneshot::Receiver<Duration>,
+        worker: Option<usize>,
+    ) -> Option<Duration> {
+        let handle = &self.handle;
+        let accept_loop = worker.unwrap_or(0);
+        let receiver = stop.fuse();
+        futures::pin_mut!(receiver);
+        let mut backoff = MIN_ACCEPT_BACKOFF;
+        let mut next = 0;
+
+        let timeout = loop {
+            // While paused, the new connections wait in the backlog of the listeners
+            if handle.accepting.is_paused() {
+                let resumed = handle.accepting.wait(false, accept_loop).fuse();
+                futures::pin_mut!(resumed);
+
+                futures::select! {
+                    _ = resumed => continue,
+                    stop = receiver => break stop.unwrap_or_default(),
+                }
+            }
+
+            let (index, connection) = {
+                let accept = accept_any(&listeners, next).fuse();
+                let paused = handle.accepting.wait(true, accept_loop).fuse();
+                futures::pin_mut!(accept, paused);
+
+                // A pause is checked first so the connections of the backlog are not accepted after it
+                futures::select_biased! {
+                    _ = paused => continue,
+                    stop = receiver => break stop.unwrap_or_default(),
+                    conn = accept => conn,
+                }
+            };
+            next = index + 1;
+
+            let (connection, peer) = match connection {
+                Ok(conn) => {
+                    backoff = MIN_ACCEPT_BACKOFF;
+                    conn
+                }
+                Err(AcceptError::Connection(e)) => {
+                    event!(
+                        trace,
+                        ACCEPT,
+                        [],
+                        "Connection failed before being accepted : {}",
+                        e
+                    );
+                    continue;
+                }
+                Err(AcceptError::Resources(e)) => {
+                    handle.emit(ServerEvent::AcceptError {
+                        message: e.to_string(),
+                        fatal: false,
+                    });
+                    if listeners[index].shed() {
+                        event!(
+                            warn,
+                            ACCEPT,
+                            [],
+                            "Out of resources, closing a new connection : {}",
+                            e
+                        );
+                        continue;
+                    }
+
+                    event!(
+                        warn,
+                        ACCEPT,
+                        [],
+                        "Out of resources, accepting again in {:?} : {}",
+                        backoff,
+                        e
+                    );
+                    let delay = Delay::new(backoff).fuse();
+                    futures::pin_mut!(delay);
+                    backoff = std::cmp::min(backoff * 2, MAX_ACCEPT_BACKOFF);
+
+                    futures::select! {
+                        _ = delay => continue,
+                        stop = receiver => break stop.unwrap_or_default(),
+                    }
+                }
+                Err(AcceptError::Fatal(e)) => {
+                    event!(
+                        error,
+                        SERVER,
+                        [],
+                        "Could not accept connections anymore, closing the listener : {}",
+                        e
+                    );
+                    handle.emit(ServerEvent::AcceptError {
+                        message: e.to_string(),
+                        fatal: true,
+                    });
+                    listeners.remove(index);
+
+                    if listeners.is_empty() {
+                        return None;
+                    }
+                    continue;
+                }
+            };
+
+            let ip_guard = match &self.per_ip {
+                Some((limiter, action)) => match limiter.acquire(peer.ip()) {
+                    Some(guard) => Some(guard),
+                    None => {
+                        event!(
+                            warn,
+                            ACCEPT,
+                            [peer = peer],
+                            "Too many connections from the client, refusing a new one"
+                        );
+                        if *action == LimitAction::Reject {
+                            reject_connection(connection, &self.service.socket);
+                        }
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            let info = ConnectionInfo::new(peer);
+            if !self.service.observer.on_open(&info) {
+                continue;
+            }
+
+            // Connections go through the global queue so they are counted against the capacity
+            if let (None, Some((capacity, policy))) = (worker, self.overload) {
+                if self.pool.queued() >= capacity
+                    && (policy == OverloadPolicy::Reject || !self.pool.shed_oldest())
+                {
+                    event!(
+                        warn,
+                        ACCEPT,
+                        [peer = peer, conn = info.id()],
+                        "Executor queue full, rejecting the connection"
+                    );
+                    reject_connection(connection, &self.service.socket);
+                    continue;
+                }
+            }
+
+            let guard = ConnectionGuard::new(handle.connections.clone(), ip_guard);
+            let token = self.shutdown.child_token();
+            let conn = info.id();
+            let serve = serve_connection(connection, info, self.service.clone(), guard, token);
+            #[cfg(feature = "tracing")]
+            let serve = tracing::Instrument::instrument(
+                serve,
+                tracing::info_span!(target: CONNECTION, "connection", peer = %peer, conn),
+            );
+            let spawned = match (worker, self.overload) {
+                (Some(worker), _) => self.pool.spawn_on(worker, serve),
+                (None, Some(_)) => self.pool.spawn_sheddable(serve),
+                (None, None) => {
+                    context::spawn(serve);
+                    Ok(())
+                }
+            };
+            if spawned.is_err() {
+                event!(
+                    error,
+                    ACCEPT,
+                    [peer = peer, conn = conn],
+                    "Could not spawn the connection"
+                );
+            }
+        };
+
+        drop(listeners);
+        Some(timeout)
+    }
+}
+
+/// Bind a listener on every address, None if one of them can not be bound
+fn bind_listeners(addrs: &[SocketAddr], socket: &SocketConfig) -> Option<Vec<TcpListener>> {
+    addrs
+        .iter()
+        .map(|addr| match TcpListener::bind(*addr, socket) {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                event!(
+                    error,
+                    SERVER,
+                    [addr = addr],
+                    "Could not listen on the address : {}",
+                    e
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// Accept a connection on any of the listeners, starting with the listener of the given index so each one gets its turn.
+/// Return the index of the listener with the result.
+fn accept_any(
+    listeners: &[TcpListener],
+    first: usize,
+) -> impl Future<
+    Output = (
+        usize,
+        Result<(mio::net::TcpStream, SocketAddr), AcceptError>,
+    ),
+> + '_ {
+    futures::future::poll_fn(move |cx| {
+        for offset in 0..listeners.len() {
+            let index = (first + offset) % listeners.len();
+
+            if let Poll::Ready(result) = listeners[index].poll_accept(cx) {
+                return Poll::Ready((index, result));
+            }
+        }
+
+        Poll::Pending
+    })
+}
+
+/// Redirect the request to the same host and path over https, the host of an absolute target is preferred
+fn https_redirect_response(request: &Request, https_port: u16) -> Response {
+    let uri = request.uri();
+    let host = uri
+        .authority()
+        .or_else(|| request.headers().get_header("Host").map(String::as_str));
+    let host = match host {
+        Some(host) if !host.trim().is_empty() => host_name(host),
+        _ => return ResponseBuilder::empty_400().build().unwrap(),
+    };
+
+    let mut location = format!("https://{}", host);
+    if https_port != 443 {
+        location.push_str(&format!(":{}", https_port));
+    }
+    // The asterisk form of OPTIONS has no path to keep
+    match uri.path() {
+        path if path.starts_with('/') => location.push_str(path),
+        _ => location.push('/'),
+    }
+    if let Some(query) = uri.query() {
+        location.push('?');
+        location.push_str(query);
+    }
+
+    let location = encoding::location(&location);
+    let builder = match request.method() {
+        Method::GET | Method::HEAD => ResponseBuilder::empty_301(&location),
+        _ => ResponseBuilder::empty_308(&location),
+    };
+
+    builder.build().unwrap()
+}
+
+/// Answer a connection refused because of an overload or of the limit of its client and close it
+fn reject_connection(connection: mio::net::TcpStream, socket: &SocketConfig) {
+    let mut stream = TcpStream::from_stream(connection, socket);
+
+    // The answer is written by a task of its own so the accept loop does not wait for the client
+    context::spawn(async move {
+        let response = ResponseBuilder::empty_503()
+            .header(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER)
+            .build()
+            .unwrap();
+
+        if let Err(e) = futures::AsyncWriteExt::write_all(&mut stream, &response.to_bytes()).await {
+            event!(
+                trace,
+                ACCEPT,
+                [],
+                "Could not answer rejected connection : {:?}",
+                e
+            );
+            return;
+        }
+
+        // Closing with unread data would reset the connection before the client reads the answer,
+        // the request is discarded like on the other connections, without any timer when nothing is left unread
+        EnhancedStream::new(0, stream).linger(LINGER_TIMEOUT).await;
+    });
+}
+
+/// Count a connection as open until it is dropped, in total and for its client when connections are limited by address
+struct ConnectionGuard {
+    connections: Arc<AtomicUsize>,
+    _ip: Option<IpGuard>,
+}
+
+impl ConnectionGuard {
+    fn new(connections: Arc<AtomicUsize>, ip: Option<IpGuard>) -> ConnectionGuard {
+        connections.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard {
+            connections,
+            _ip: ip,
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+async fn serve_connection(
+    connection: mio::net::TcpStream,
+    info: ConnectionInfo,
+    service: Service,
+    _guard: ConnectionGuard,
+    token: CancellationToken,
+) {
+    // The handlers still running for the connection are told it is closed
+    let _cancel = token.clone().drop_guard();
+    let connection = TcpStream::from_stream(connection, &service.socket);
+    let mut stream = EnhancedStream::new(info.id(), connection);
+    // A reloaded configuration only changes the limits of the connections accepted after it
+    let config = service.config.get();
+    stream.set_batch_limit(config.pipeline_limit);
+    stream.set_max_head_size(config.max_head_size);
+    stream.set_max_headers(config.max_headers);
+    if let Some(dir) = &service.transcripts {
+        record_transcript(&mut stream, &info, dir);
+    }
+    let mut stats = ConnectionStats::new();
+
+    'connection: loop {
+        let mut requests = match stream.poll_requests().await {
+            Ok(reqs) => reqs,
+            Err(e) => {
+                service.observer.on_error(&info, &e);
+                if let RequestError::ParseError(ref error) = e {
+                    reject_request(&mut stream, &info, &service, error).await;
+                }
+                break;
+            }
+        };
+
+        for request in requests.iter_mut() {
+            request.normalize(service.merge_slashes);
+        }
+
+        let concurrent = service.concurrent_pipeline
+            && requests.len() > 1
+            && requests
+                .iter()
+                .all(|request| *request.method() != Method::CONNECT && request.upgrade().is_none());
+
+        if concurrent {
+            // The requests following one closing the connection would not be answered
+            if let Some(last) = requests.iter().position(|request| !request.keep_alive()) {
+                requests.truncate(last + 1);
+            }
+            if let Some(max) = service.config.get().max_requests {
+                requests.truncate(max.saturating_sub(stats.requests()));
+            }
+
+            let responses: Vec<_> = requests
+                .into_iter()
+                .map(|request| {
+                    service.observer.on_request(&info, &request);
+                    dispatch_request(&service, &info, request, &token)
+                })
+                .collect();
+
+            // The responses already computed are written together, the others are waited for once
+            // the ones before them are sent
+            stream.set_corked(true);
+            for (index, mut response) in responses.into_iter().enumerate() {
+                let response = match futures::poll!(&mut response) {
+                    Poll::Ready(response) => response,
+                    Poll::Pending => {
+                        if let Err(e) = stream.flush().await {
+                            service.observer.on_error(&info, &e);
+                            break 'connection;
+                        }
+                        response.await
+                    }
+                };
+
+                // The receiver is canceled when the task is dropped without running, as when the pool stops
+                let handled = match response {
+                    Ok(handled) => handled,
+                    Err(_) => break 'connection,
+                };
+
+                let sent = send_response(&mut stream, &info, &service, &mut stats, index, handled);
+                if !sent.await {
+                    break 'connection;
+                }
+            }
+            stream.set_corked(false);
+
+            if let Err(e) = stream.flush().await {
+                service.observer.on_error(&info, &e);
+                break;
+            }
+            continue;
+        }
+
+        // Informational responses are written right to the socket, so the stream is left uncorked
+
+        for (index, request) in requests.into_iter().enumerate() {
+            // Let the other connections progress between pipelined requests
+            if index > 0 {
+                yield_now().await;
+            }
+
+            service.observer.on_request(&info, &request);
+
+            if let (Method::CONNECT, Some(connect)) = (request.method(), &service.connect) {
+                stats.request_served();
+                stats.set_bytes(stream.bytes_read(), stream.bytes_written());
+                report_close(&service, &info, &stats);
+                open_tunnel(stream, &info, request, connect.clone());
+                return;
+            }
+
+            let connection = Connection::new(stream.get_ref(), request.version());
+            let mut response =
+                handle_request(&service, &info, &request, Some(connection), &token).await;
+
+            if let Some(upgrade) = response.take_upgrade() {
+                if response.code() == Reason::SWITCHINGPROTOCOLS101.code() {
+                    let handled = (request, response);
+                    upgrade_connection(stream, &info, &service, stats, index, handled, upgrade);
+                    return;
+                }
+            }
+
+            let handled = (request, response);
+            let sent = send_response(&mut stream, &info, &service, &mut stats, index, handled);
+            if !sent.await {
+                break 'connection;
+            }
+        }
+    }
+
+    // A response closing the connection may still be kept back
+    if let Err(e) = stream.flush().await {
+        service.observer.on_error(&info, &e);
+    }
+    // The requests the client is still sending are discarded so it can read the last response
+    stream.linger(LINGER_TIMEOUT).await;
+    stats.set_bytes(stream.bytes_read(), stream.bytes_written());
+    report_close(&service, &info, &stats);
+}
+
+/// Record the connection to a new file of the directory, the connection is served anyway when it fails
+fn record_transcript(stream: &mut EnhancedStream<TcpStream>, info: &ConnectionInfo, dir: &Path) {
+    let path = dir.join(format!("{}.transcript", info.id()));
+
+    match TranscriptRecorder::create(&path) {
+        Ok(recorder) => {
+            stream.set_recorder(recorder);
+            event!(
+                debug,
+                CONNECTION,
+                [peer = info.peer(), conn = info.id()],
+                "Recording the connection to {}",
+                path.display()
+            );
+        }
+        Err(e) => event!(
+            warn,
+            CONNECTION,
+            [peer = info.peer(), conn = info.id()],
+            "Could not create the transcript {} : {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+/// Report a closed connection to the observer and count it in the keep-alive statistics
+fn report_close(service: &Service, info: &ConnectionInfo, stats: &ConnectionStats) {
+    service.keep_alive.record(stats);
+    service.observer.on_close(info, stats);
+}
+
+/// Compute the response to a request in the current task.
+/// Without connection, the handler can not send informational responses.
+async fn handle_request(
+    service: &Service,
+    info: &ConnectionInfo,
+    request: &Request,
+    connection: Option<Connection>,
+    token: &CancellationToken,
+) -> Response {
+    let id = RequestId::next();
+    task_local::set(id);
+    task_local::set(info.clone());
+    // A shed request does not reach the handler
+    let permit = match &service.concurrency {
+        Some(limiter) => match limiter.acquire() {
+            Some(permit) => Some(permit),
+            None => {
+                event!(
+                    debug,
+                    REQUEST,
+                    [peer = info.peer(), conn = info.id(), request = id],
+                    "Concurrency limit reached, {} {} is shed",
+                    request.method().as_str(),
+                    request.path()
+                );
+                return ResponseBuilder::empty_503().build().unwrap();
+            }
+        },
+        None => None,
+    };
+    task_local::set(token.clone());
+    if let Some(connection) = connection {
+        task_local::set(connection);
+    }
+
+    let start = Instant::now();
+    if let Some(timeout) = service.config.get().request_timeout {
+        task_local::set(Deadline::new(start, timeout));
+    }
+    // A panicking handler is answered with a 500 status code instead of stopping the worker
+    let responded = std::panic::AssertUnwindSafe(respond_request(service, request, id))
+        .catch_unwind()
+        .await;
+    drop(permit);
+    let mut response = match responded {
+        Ok(response) => response,
+        Err(_) => {
+            event!(
+                error,
+                REQUEST,
+                [peer = info.peer(), conn = info.id(), request = id],
+                "Handler panicked on {} {}",
+                request.method().as_str(),
+                request.path()
+            );
+            ResponseBuilder::empty_500().build().unwrap()
+        }
+    };
+    task_local::remove::<Connection>();
+    task_local::remove::<CancellationToken>();
+    // The router replaces the deadline of the server when the route has its own timeout
+    if let Some(deadline) = task_local::remove::<Deadline>() {
+        if deadline.expired() {
+            response = timeout_response();
+        }
+    }
+
+    log_slow_request(service, info, request, id, start.elapsed());
+
+    response
+}
+
+/// Handle a pipelined request in its own task, the response is given back to the connection
+/// so the responses are still written in the order of the requests
+fn dispatch_request(
+    service: &Service,
+    info: &ConnectionInfo,
+    request: Request,
+    token: &CancellationToken,
+) -> oneshot::Receiver<(Request, Response)> {
+    let (sender, receiver) = oneshot::channel();
+    let service = service.clone();
+    let info = info.clone();
+    let token = token.clone();
+
+    let task = async move {
+        let response = handle_request(&service, &info, &request, None, &token).await;
+        // The connection may be closed already
+        let _ = sender.send((request, response));
+    };
+    // The request span stays inside the span of its connection
+    #[cfg(feature = "tracing")]
+    let task = tracing::Instrument::in_current_span(task);
+    context::spawn(task);
+
+    receiver
+}
+
+/// Write the response of a request and report it to the observer.
+/// Return true if the connection stays open.
+async fn send_response(
+    stream: &mut EnhancedStream<TcpStream>,
+    info: &ConnectionInfo,
+    service: &Service,
+    stats: &mut ConnectionStats,
+    index: usize,
+    (request, mut response): (Request, Response),
+) -> bool {
+    response
+        .headers
+        .merge_defaults(&service.config.get().default_headers);
+    date_header(&mut response);
+    suppress_body(info, &request, &mut response);
+    check_content_length(service, info, &request, &mut response);
+    if let Some(renderer) = &service.error_renderer {
+        render_error(renderer, &mut response, Some(&request), None);
+    }
+    if service.draining.get() {
+        response
+            .headers
+            .set_header(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER);
+    }
+    if matches!(service.config.get().max_requests, Some(max) if stats.requests() + 1 >= max) {
+        // The connection is only counted as limited when it would have stayed open otherwise
+        if request.keep_alive()
+            && !response
+                .headers()
+                .has_token(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER)
+        {
+            service.keep_alive.limit_reached();
+        }
+        response
+            .headers
+            .set_header(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER);
+    }
+    let keep_alive = connection_header(&request, &mut response);
+    // The client may be gone, the connection is closed without affecting the worker
+    let written = match stream.write_response(&response).await {
+        Ok(written) => written,
+        Err(e) => {
+            service.observer.on_error(info, &e);
+            return false;
+        }
+    };
+    stats.request_served();
+
+    let request_body = request.body().map_or(0, Vec::len);
+    let response_body = response.body_len();
+    let transfer = TransferStats::new(
+        (stream.head_size(index), request_body),
+        (written - response_body, response_body),
+    );
+    service
+        .observer
+        .on_response(info, &request, &response, &transfer);
+    request.recycle();
+
+    keep_alive
+}
+
+/// Set the "Date" header of a response, unless the handler did
+pub(crate) fn date_header(response: &mut Response) {
+    if response.headers().get_header("Date").is_none() {
+        with_http_date_now(|date| response.headers.set_header("Date", date));
+    }
+}
+
+/// Remove the body of a response that must not have one: a response to a HEAD request, or with a 1xx, 204 or 304
+/// status code. Sent anyway, the body would be read as the start of the next response of the connection.
+fn suppress_body(info: &ConnectionInfo, request: &Request, response: &mut Response) {
+    let len = remove_forbidden_body(request, response);
+
+    if len > 0 {
+        event!(
+            warn,
+            REQUEST,
+            [peer = info.peer(), conn = info.id()],
+            "Removed the body of {} bytes of the {} response to {} {}",
+            len,
+            response.code(),
+            request.method().as_str(),
+            request.path()
+        );
+    }
+}
+
+/// Remove the body of a response that must not have one, return the size of the removed body
+pub(crate) fn remove_forbidden_body(request: &Request, response: &mut Response) -> usize {
+    let head = *request.method() == Method::HEAD;
+    let bodiless = matches!(response.code(), 100..=199 | 204);
+    if !head && !bodiless && response.code() != 304 {
+        return 0;
+    }
+
+    let len = response.remove_body();
+    if bodiless {
+        response.headers.remove_header("Content-Length");
+    } else if head && len > 0 && response.headers().get_header("Content-Length").is_none() {
+        // The response to a HEAD request announces the length of the body it would have
+        response
+            .headers
+            .set_header("Content-Length", &len.to_string());
+    }
+
+    len
+}
+
+/// Apply the policy of the server to a response whose "Content-Length" header does not match its body
+fn check_content_length(
+    service: &Service,
+    info: &ConnectionInfo,
+    request: &Request,
+    response: &mut Response,
+) {
+    let bodiless = matches!(response.code(), 100..=199 | 204 | 304);
+    if bodiless || *request.method() == Method::HEAD {
+        return;
+    }
+
+    let len = response.body_len();
+    let announced = response.headers().get_header("Content-Length");
+    match announced {
+        Some(value) if value.parse() == Ok(len) => return,
+        None if len == 0 => return,
+        _ => {}
+    }
+
+    event!(
+        warn,
+        REQUEST,
+        [peer = info.peer(), conn = info.id()],
+        "Content-Length {:?} of the response to {} {} does not match its body of {} bytes",
+        announced,
+        request.method().as_str(),
+        request.path(),
+        len
+    );
+    match service.length_mismatch {
+        LengthMismatchPolicy::Correct => {
+            response
+                .headers
+                .set_header("Content-Length", &len.to_string());
+        }
+        LengthMismatchPolicy::CloseDelimited => {
+            response.headers.remove_header("Content-Length");
+            response
+                .headers
+                .set_header(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER);
+        }
+        LengthMismatchPolicy::InternalError => {
+            *response = ResponseBuilder::empty_500().body(b"").build().unwrap();
+        }
+    }
+}
+
+fn log_slow_request(
+    service: &Service,
+    info: &ConnectionInfo,
+    request: &Request,
+    id: RequestId,
+    latency: Duration,
+) {
+    match service.config.get().slow_request {
+        Some(threshold) if latency > threshold => event!(
+            warn,
+            SLOW_REQUEST,
+            [peer = info.peer(), conn = info.id(), request = id],
+            "Slow request {} {} : {:?}",
+            request.method().as_str(),
+            request.path(),
+            latency
+        ),
+        _ => {}
+    }
+}
+
+/// Status of the responses to invalid requests when no parse error handler is set
+pub(crate) fn default_parse_error(error: &ParseError, _received: &[u8]) -> Option<Response> {
+    let builder = match error {
+        ParseError::TooManyHeaders | ParseError::HeadTooLarge => ResponseBuilder::empty_431(),
+        _ => ResponseBuilder::empty_400(),
+    };
+
+    Some(builder.build().unwrap())
+}
+
+/// Answer a request that could not be parsed, the connection is closed afterwards
+async fn reject_request(
+    stream: &mut EnhancedStream<TcpStream>,
+    info: &ConnectionInfo,
+    service: &Service,
+    error: &ParseError,
+) {
+    event!(
+        warn,
+        CONNECTION,
+        [peer = info.peer(), conn = info.id()],
+        "Invalid request : {:?}",
+        error
+    );
+
+    if let Some(mut response) = (service.parse_error)(error, stream.buffered()) {
+        if let Some(renderer) = &service.error_renderer {
+            render_error(renderer, &mut response, None, Some(&error.to_string()));
+        }
+        response
+            .headers
+            .set_header(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER);
+        date_header(&mut response);
+
+        if let Err(e) = stream.write_response(&response).await {
+            event!(
+                trace,
+                CONNECTION,
+                [peer = info.peer(), conn = info.id()],
+                "Could not answer invalid request : {:?}",
+                e
+            );
+        }
+    }
+}
+
+/// Tell the client whether the connection stays open after the response.
+/// It is closed when the client or the handler asked for it, HTTP/1.0 clients get their keep-alive acknowledged.
+/// Return true if the connection stays open.
+pub(crate) fn connection_header(request: &Request, response: &mut Response) -> bool {
+    let keep_alive = request.keep_alive()
+        && !response
+            .headers()
+            .has_token(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER);
+
+    if !keep_alive {
+        response
+            .headers
+            .set_header(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER);
+    } else if *request.version() == Version::HTTP10 {
+        response
+            .headers
+            .set_header(CONNECTION_HEADER, KEEP_ALIVE_CONNECTION_HEADER);
+    }
+
+    keep_alive
+}
+
+#[cfg(not(feature = "tracing"))]
+async fn respond_request(service: &Service, request: &Request, _id: RequestId) -> Response {
+    service.respond(request).await
+}
+
+/// Respond to the request inside a span, the route field is filled by the router when a route is matching
+#[cfg(feature = "tracing")]
+async fn respond_request(service: &Service, request: &Request, id: RequestId) -> Response {
+    use tracing::Instrument;
+
+    let span = tracing::info_span!(
+        target: REQUEST,
+        "request",
+        id = id.get(),
+        method = request.method().as_str(),
+        path = request.path().as_str(),
+        route = tracing::field::Empty,
+    );
+    let start = std::time::Instant::now();
+
+    let response = service.respond(request).instrument(span.clone()).await;
+
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.code(),
+            latency_us = start.elapsed().as_micros() as u64,
+            "request handled"
+        )
+    });
+
+    response
+}
+
+fn open_tunnel(
+    stream: EnhancedStream<TcpStream>,
+    info: &ConnectionInfo,
+    request: Request,
+    connect: ConnectHandler,
+) {
+    hand_over(
+        stream,
+        info,
+        CONNECTION_ESTABLISHED.to_vec(),
+        move |tunnel| (connect)(&request, tunnel),
+    );
+}
+
+/// Send the `101 Switching Protocols` response and give the connection to the handler of the new protocol
+fn upgrade_connection(
+    stream: EnhancedStream<TcpStream>,
+    info: &ConnectionInfo,
+    service: &Service,
+    mut stats: ConnectionStats,
+    index: usize,
+    (request, mut response): (Request, Response),
+    upgrade: Upgrade,
+) {
+    response
+        .headers
+        .merge_defaults(&service.config.get().default_headers);
+    date_header(&mut response);
+    let head = response.to_bytes();
+
+    stats.request_served();
+    stats.set_bytes(
+        stream.bytes_read(),
+        stream.bytes_written() + head.len() as u64,
+    );
+    let request_body = request.body().map_or(0, Vec::len);
+    let transfer = TransferStats::new((stream.head_size(index), request_body), (head.len(), 0));
+    service
+        .observer
+        .on_response(info, &request, &response, &transfer);
+    report_close(service, info, &stats);
+
+    hand_over(stream, info, head, |tunnel| upgrade.run(tunnel));
+}
+
+/// Write the head of the new protocol and give the raw connection to the handler,
+/// along with the bytes the client already sent.
+/// The connection is blocking from then on, so the handler runs on a thread of its own instead of a worker.
+fn hand_over<H>(stream: EnhancedStream<TcpStream>, info: &ConnectionInfo, head: Vec<u8>, handler: H)
+where
+    H: Send + 'static + FnOnce(Tunnel),
+{
+    let (stream, buffered) = stream.into_parts();
+
+    let mut stream = match stream.into_std() {
+        Ok(stream) => stream,
+        Err(e) => {
+            event!(
+                error,
+                CONNECTION,
+                [peer = info.peer(), conn = info.id()],
+                "Could not hand over the connection : {:?}",
+                e
+            );
+            return;
+        }
+    };
+
+    let (peer, conn) = (*info.peer(), info.id());
+    let spawned = std::thread::Builder::new()
+        .name(String::from("mini-async-http-tunnel"))
+        .spawn(move || {
+            if let Err(e) = stream.write_all(&head) {
+                event!(
+                    error,
+                    CONNECTION,
+                    [peer = peer, conn = conn],
+                    "Could not hand over the connection : {:?}",
+                    e
+                );
+                return;
+            }
+
+            handler(Tunnel::new(stream, buffered));
+        });
+
+    if let Err(e) = spawned {
+        event!(
+            error,
+            CONNECTION,
+            [peer = peer, conn = conn],
+            "Could not start the thread of the connection : {:?}",
+            e
+        );
+    }
+}
+
+impl Drop for AIOServer {
+    fn drop(&mut self) {
+        self.handle.shutdown();
+    }
+}
+/// Clonable handle to a server.
+/// Can only be retrieved from a Server instance.
+/// Used to wait for the server to be ready, to query its state or to shut it down.
+#[derive(Clone)]
+pub struct ServerHandle {
+    ready: Arc<Flag>,
+    /// Set once the server is ready or failed to start, until it is started again
+    settled: Arc<Flag>,
+    running: Arc<Flag>,
+    pool: Arc<Mutex<Option<PoolHandle>>>,
+    concurrency: Arc<Mutex<Option<Arc<ConcurrencyLimiter>>>>,
+    executor_stats: Arc<PoolStats>,
+    keep_alive: Arc<ReuseCounters>,
+    config: Arc<LiveConfig>,
+    connections: Arc<AtomicUsize>,
+    local_addrs: Arc<Mutex<Vec<SocketAddr>>>,
+    listeners: Arc<Mutex<Vec<std::net::TcpListener>>>,
+    draining: Arc<Flag>,
+    accepting: Arc<AcceptSwitch>,
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<ServerEvent>>>>,
+    stop_sender: Arc<AtomicTake<oneshot::Sender<Duration>>>,
+}
+
+/// Whether the server takes new connections, watched by the accept loops only
+#[derive(Default)]
+struct AcceptSwitch {
+    paused: AtomicBool,
+    wakers: Mutex<HashMap<usize, Waker>>,
+}
+
+impl AcceptSwitch {
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+        self.wakers
+            .lock()
+            .unwrap()
+            .values()
+            .for_each(Waker::wake_by_ref);
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Wait for the switch to be in the given state, a waker is kept for each accept loop
+    async fn wait(&self, paused: bool, accept_loop: usize) {
+        futures::future::poll_fn(|cx| {
+            self.wakers
+                .lock()
+                .unwrap()
+                .insert(accept_loop, cx.waker().clone());
+
+            if self.is_paused() == paused {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl ServerHandle {
+    fn new(
+        stop_sender: Arc<AtomicTake<oneshot::Sender<Duration>>>,
+        draining: Arc<Flag>,
+        keep_alive: Arc<ReuseCounters>,
+        config: Arc<LiveConfig>,
+    ) -> Self {
+        ServerHandle {
+            ready: Arc::new(Flag::new(false)),
+            settled: Arc::new(Flag::new(false)),
+            running: Arc::new(Flag::new(false)),
+            pool: Arc::new(Mutex::from(None)),
+            concurrency: Arc::new(Mutex::from(None)),
+            executor_stats: Arc::new(PoolStats::default()),
+            keep_alive,
+            config,
+            connections: Arc::new(AtomicUsize::new(0)),
+            local_addrs: Arc::new(Mutex::from(Vec::new())),
+            listeners: Arc::new(Mutex::from(Vec::new())),
+            draining,
+            accepting: Arc::new(AcceptSwitch::default()),
+            subscribers: Arc::new(Mutex::from(Vec::new())),
+            stop_sender,
+        }
+    }
+
+    fn set_local_addrs(&self, addrs: Vec<SocketAddr>) {
+        *self.local_addrs.lock().unwrap() = addrs;
+    }
+
+    /// Return the address the server is listening on, once it is ready.
+    /// Useful when the server is bound to the port 0 to retrieve the port picked by the system.
+    /// When the server listens on several addresses, the first one is returned.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addrs.lock().unwrap().first().cloned()
+    }
+
+    /// Return all the addresses the server is listening on, once it is ready
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.local_addrs.lock().unwrap().clone()
+    }
+
+    fn set_listeners(&self, listeners: Vec<std::net::TcpListener>) {
+        *self.listeners.lock().unwrap() = listeners;
+    }
+
+    /// Return a duplicate of the listener of the running server, to be given to a new server with [`AIOServer::from_listener`].
+    /// On Unix the listener is inherited by the processes started afterwards, its file descriptor can be passed
+    /// to a new version of the program which then takes over the connections while this one is stopped with
+    /// [`shutdown_graceful`]. When the server listens on several addresses, the first listener is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::os::unix::io::AsRawFd;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7891".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     server.start();
+    /// });
+    ///
+    /// handle.ready();
+    /// let listener = handle.export_listener().unwrap();
+    /// std::process::Command::new("/usr/bin/server")
+    ///     .env("LISTEN_FD", listener.as_raw_fd().to_string())
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// handle.shutdown_graceful(Duration::from_secs(30));
+    /// ```
+    ///
+    /// [`AIOServer::from_listener`]: struct.AIOServer.html#method.from_listener
+    /// [`shutdown_graceful`]: #method.shutdown_graceful
+    pub fn export_listener(&self) -> Option<std::net::TcpListener> {
+        let listeners = self.listeners.lock().unwrap();
+        let listener = listeners.first()?;
+
+        match listener.try_clone() {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                event!(error, SERVER, [], "Could not export the listener : {}", e);
+                None
+            }
+        }
+    }
+
+    /// Stop taking new connections without closing the listeners or the established connections,
+    /// for instance while a dependency of the handlers is unavailable.
+    /// The clients connecting meanwhile wait in the backlog of the listeners until [`resume_accepting`] is called,
+    /// or are refused by the system once the backlog is full.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7895".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     handle.ready();
+    ///     handle.pause_accepting();
+    ///     assert!(handle.is_accepting_paused());
+    ///
+    ///     handle.resume_accepting();
+    ///     handle.shutdown();
+    /// });
+    ///
+    /// server.start();
+    /// ```
+    ///
+    /// [`resume_accepting`]: #method.resume_accepting
+    pub fn pause_accepting(&self) {
+        self.accepting.set_paused(true);
+    }
+
+    /// Take new connections again after [`pause_accepting`], starting with the ones waiting in the backlog
+    ///
+    /// [`pause_accepting`]: #method.pause_accepting
+    pub fn resume_accepting(&self) {
+        self.accepting.set_paused(false);
+    }
+
+    /// Return true if taking new connections is paused
+    pub fn is_accepting_paused(&self) -> bool {
+        self.accepting.is_paused()
+    }
+
+    /// Announce that the server is about to stop: every response sent from now on carries `Connection: close`
+    /// and ends its connection, so the clients of a load balancer move to other instances before the shutdown.
+    /// The server keeps accepting and serving connections, one request each.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7896".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     handle.ready();
+    ///     handle.start_draining();
+    ///     assert!(handle.is_draining());
+    ///
+    ///     handle.shutdown_graceful(std::time::Duration::from_secs(5));
+    /// });
+    ///
+    /// server.start();
+    /// ```
+    pub fn start_draining(&self) {
+        self.draining.set(true);
+    }
+
+    /// Keep the connections alive again after [`start_draining`]
+    ///
+    /// [`start_draining`]: #method.start_draining
+    pub fn stop_draining(&self) {
+        self.draining.set(false);
+    }
+
+    /// Return true if the responses close their connection, after [`start_draining`] or during a graceful shutdown
+    ///
+    /// [`start_draining`]: #method.start_draining
+    pub fn is_draining(&self) -> bool {
+        self.draining.get()
+    }
+
+    /// Return true if the server is bound and accepting connections, does not block
+    pub fn is_ready(&self) -> bool {
+        self.ready.get()
+    }
+
+    /// Return true from the moment the server is started until its event loop has stopped
+    pub fn is_running(&self) -> bool {
+        self.running.get()
+    }
+
+    fn set_pool(&self, pool: Option<PoolHandle>) {
+        *self.pool.lock().unwrap() = pool;
+    }
+
+    /// Return the number of tasks waiting in the executor global queue, 0 if the server is not running
+    pub fn queue_depth(&self) -> usize {
+        match *self.pool.lock().unwrap() {
+            Some(ref pool) => pool.queued(),
+            None => 0,
+        }
+    }
+
+    /// Return the statistics recorded by the executor since the server was created.
+    /// They stay empty unless [`AIOServer::set_slow_poll_threshold`] is used.
+    ///
+    /// [`AIOServer::set_slow_poll_threshold`]: struct.AIOServer.html#method.set_slow_poll_threshold
+    pub fn executor_stats(&self) -> ExecutorStats {
+        self.executor_stats.snapshot()
+    }
+
+    /// Return the counters of the objects recycled between requests, such as the header maps and the response buffers.
+    /// Each worker thread keeps its own objects, the counters are shared by all the servers of the process.
+    pub fn object_pool_stats(&self) -> ObjectPoolStats {
+        object_pool_stats()
+    }
+
+    /// Return the number of worker threads of the running server, 0 if the server is not running
+    pub fn pool_size(&self) -> usize {
+        match *self.pool.lock().unwrap() {
+            Some(ref pool) => pool.size(),
+            None => 0,
+        }
+    }
+
+    /// Return the number of worker threads restarted after a panic since the server started, 0 if it is not running.
+    /// A worker whose thread panics is replaced after a short delay so the pool keeps its size.
+    pub fn worker_restarts(&self) -> usize {
+        match *self.pool.lock().unwrap() {
+            Some(ref pool) => pool.restarts(),
+            None => 0,
+        }
+    }
+
+    /// Change the number of worker threads of the running server.
+    /// New workers are started right away while retired workers end once they are idle,
+    /// the connections they were serving are moved to the other workers.
+    /// Return false if the server is not running or the size is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7888".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     server.start();
+    /// });
+    ///
+    /// handle.ready();
+    /// assert!(handle.resize_pool(2));
+    /// assert_eq!(handle.pool_size(), 2);
+    /// handle.shutdown();
+    /// ```
+    pub fn resize_pool(&self, size: usize) -> bool {
+        match *self.pool.lock().unwrap() {
+            Some(ref pool) => pool.resize(size).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Return the statistics of the connections closed since the server was created,
+    /// such as the number of requests answered on each one
+    pub fn keep_alive_stats(&self) -> KeepAliveStats {
+        self.keep_alive.snapshot()
+    }
+
+    /// Return the state of the limit set with [`AIOServer::set_adaptive_concurrency`], None without limit
+    ///
+    /// [`AIOServer::set_adaptive_concurrency`]: struct.AIOServer.html#method.set_adaptive_concurrency
+    pub fn concurrency_stats(&self) -> Option<ConcurrencyStats> {
+        self.concurrency
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|limiter| limiter.stats())
+    }
+
+    /// Replace the configuration of the running server, without restarting it or closing its connections.
+    /// The requests received afterwards use the new timeouts, limits and default headers, while the limits
+    /// of the request heads and of the pipelines only apply to the connections accepted afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::ServerConfig;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7904".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     handle.ready();
+    ///     handle.reload_config(handle.config().request_timeout(Duration::from_secs(2)));
+    ///     handle.shutdown();
+    /// });
+    ///
+    /// server.start();
+    /// ```
+    pub fn reload_config(&self, config: ServerConfig) {
+        event!(info, SERVER, [], "Configuration reloaded");
+        self.config.set(config);
+    }
+
+    /// Return the configuration in effect
+    pub fn config(&self) -> ServerConfig {
+        ServerConfig::clone(&self.config.get())
+    }
+
+    /// Return the number of connections currently open on the server
+    pub fn connection_count(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Return a receiver of the lifecycle events of the server, from its next transition on,
+    /// so supervising code can react to them without parsing the logs.
+    /// The events are delivered to every receiver, a dropped receiver is forgotten.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{ServerEvent, ShutdownReason};
+    ///
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7907".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    /// let events = handle.subscribe();
+    ///
+    /// std::thread::spawn(move || {
+    ///     handle.ready();
+    ///     handle.shutdown();
+    /// });
+    /// server.start();
+    ///
+    /// let stopped = events.iter().find(|event| matches!(event, ServerEvent::Stopped { .. }));
+    /// assert_eq!(
+    ///     stopped,
+    ///     Some(ServerEvent::Stopped { reason: ShutdownReason::Requested, abandoned: 0 })
+    /// );
+    /// ```
+    pub fn subscribe(&self) -> mpsc::Receiver<ServerEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+
+        receiver
+    }
+
+    fn emit(&self, event: ServerEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Wait for the server to be ready without blocking the thread.
+    /// Return false if the server stopped instead, as when one of its addresses can not be bound.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7883".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     server.start();
+    /// });
+    ///
+    /// assert!(futures::executor::block_on(handle.ready_async()));
+    /// assert!(handle.is_ready());
+    /// ```
+    pub async fn ready_async(&self) -> bool {
+        self.settled.wait_async(true).await;
+        self.ready.get()
+    }
+
+    /// Send a shutdown signal to the server and wait for it to stop.
+    /// If the server is not started, the function returns immediately.
+    ///
+    /// # Example
+    ///
+    /// Creates a server and starts it. From another thread we send the shutdown signal
+    /// causing the server to stop and the execution to end.
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7880".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200()
+    ///         .body(b"Hello")
+    ///         .content_type("text/plain")
+    ///         .build()
+    ///         .unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     handle.ready();
+    ///     handle.shutdown();
+    /// });
+    ///
+    /// server.start();
+    ///
+    /// ```
+    pub fn shutdown(&self) {
+        self.shutdown_graceful(Duration::from_secs(0))
+    }
+
+    /// Stop accepting connections and give the open ones up to `timeout` to end before shutting the server down.
+    /// The connections are closed after their current response, the listener stays open in the servers
+    /// it was exported to.
+    pub fn shutdown_graceful(&self, timeout: Duration) {
+        let sender = match self.stop_sender.take() {
+            Some(val) => val,
+            None => return,
+        };
+
+        if sender.send(timeout).is_err() {
+            return;
+        }
+
+        self.wait();
+    }
+
+    /// Wait for the open connections to be closed, at most for the given duration
+    async fn drain(&self, timeout: Duration) {
+        self.draining.set(true);
+        self.set_listeners(Vec::new());
+
+        let start = Instant::now();
+        while self.connection_count() > 0 && start.elapsed() < timeout {
+            Delay::new(DRAIN_INTERVAL).await;
+        }
+    }
+
+    /// Block until the server has stopped, returns immediately if the server is not running.
+    /// Once stopped, the server can be started again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7884".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// let thread = std::thread::spawn(move || {
+    ///     server.start();
+    ///     server
+    /// });
+    ///
+    /// handle.ready();
+    /// handle.shutdown();
+    /// handle.wait();
+    ///
+    /// let mut server = thread.join().unwrap();
+    /// std::thread::spawn(move || {
+    ///     server.start();
+    /// });
+    ///
+    /// handle.ready();
+    /// ```
+    pub fn wait(&self) {
+        self.running.wait(false)
+    }
+
+    /// Wait for the server to stop without blocking the thread
+    pub async fn wait_async(&self) {
+        self.running.wait_async(false).await
+    }
+
+    /// Block untill the server is ready to receive requests.
+    /// Return false if the server stopped instead, as when one of its addresses can not be bound.
+    ///
+    /// # Example
+    ///
+    /// Creates a server and starts it in a separate thread.
+    /// The main thread waits for the server to be ready and then ends
+    ///
+    /// ```
+    /// let mut server = mini_async_http::AIOServer::new("127.0.0.1:7880".parse().unwrap(), move |request|{
+    ///     mini_async_http::ResponseBuilder::empty_200()
+    ///         .body(b"Hello")
+    ///         .content_type("text/plain")
+    ///         .build()
+    ///         .unwrap()
+    /// });
+    /// let handle = server.handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     server.start();
+    /// });
+    ///
+    /// handle.ready();
+    ///
+    /// ```
+    pub fn ready(&self) -> bool {
+        self.settled.wait(true);
+        self.ready.get()
     }
 }
@@ -0,0 +1,361 @@
+use crate::aioserver::enhanced_stream::RequestError;
+use crate::aioserver::events::{event, CONNECTION};
+use crate::aioserver::http_connection::HttpConnection;
+use crate::aioserver::server::default_parse_error;
+use crate::io::duplex::duplex;
+use crate::request::Request;
+use crate::response::Response;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Capacity of the in-memory connection a transcript is replayed on
+const REPLAY_CAPACITY: usize = 64 * 1024;
+
+/// Bytes received or sent at once on a connection, see [`Transcript`]
+///
+/// [`Transcript`]: struct.Transcript.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptEntry {
+    elapsed: Duration,
+    inbound: bool,
+    bytes: Vec<u8>,
+}
+
+impl TranscriptEntry {
+    /// Return the time elapsed between the start of the recording and the bytes
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Return true if the bytes were received from the client, false if they were sent to it
+    pub fn is_inbound(&self) -> bool {
+        self.inbound
+    }
+
+    /// Return the bytes received or sent
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Bytes exchanged on a connection along with their time, recorded with [`AIOServer::set_transcript_dir`]
+/// or [`HttpConnection::record`] to understand how a client talks to the server.
+///
+/// Every read and write of the connection is an entry of the file: a line with the microseconds elapsed since
+/// the connection was opened, `in` or `out` and the number of bytes, followed by the bytes themselves and a new line.
+/// The bytes exchanged after the connection switched to another protocol are not recorded.
+///
+/// A transcript is replayed against a handler with [`replay`], or against a running server with [`replay_to`]
+/// to reproduce the issue reported by a client without the client itself.
+///
+/// # Example
+///
+/// ```no_run
+/// use mini_async_http::{ResponseBuilder, Transcript};
+///
+/// let transcript = Transcript::load("/tmp/transcripts/42.transcript").unwrap();
+/// let sent = transcript.replay(|request| {
+///     println!("{:?}", request);
+///     ResponseBuilder::empty_200().build().unwrap()
+/// });
+/// ```
+///
+/// [`AIOServer::set_transcript_dir`]: struct.AIOServer.html#method.set_transcript_dir
+/// [`HttpConnection::record`]: struct.HttpConnection.html#method.record
+/// [`replay`]: #method.replay
+/// [`replay_to`]: #method.replay_to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// Read a transcript file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Transcript> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        Transcript::parse(&bytes)
+    }
+
+    /// Read a transcript from its content, an incomplete last entry is ignored as the recording may have
+    /// been interrupted while it was written
+    pub fn parse(mut bytes: &[u8]) -> io::Result<Transcript> {
+        let mut entries = Vec::new();
+
+        while let Some(end) = bytes.iter().position(|&b| b == b'\n') {
+            let line = std::str::from_utf8(&bytes[..end]).map_err(|_| invalid("Invalid entry"))?;
+            let mut fields = line.split(' ');
+            let (micros, direction, len) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(micros), Some(direction), Some(len)) => (micros, direction, len),
+                _ => return Err(invalid("Invalid entry")),
+            };
+
+            let elapsed =
+                Duration::from_micros(micros.parse().map_err(|_| invalid("Invalid time"))?);
+            let inbound = match direction {
+                "in" => true,
+                "out" => false,
+                _ => return Err(invalid("Invalid direction")),
+            };
+            let len: usize = len.parse().map_err(|_| invalid("Invalid length"))?;
+
+            // The entry ends with a new line after its bytes, a corrupt length must not overflow
+            let entry_len = len
+                .checked_add(1)
+                .ok_or_else(|| invalid("Invalid length"))?;
+            let rest = &bytes[end + 1..];
+            if rest.len() < entry_len {
+                break;
+            }
+            if rest[len] != b'\n' {
+                return Err(invalid("Invalid entry end"));
+            }
+
+            entries.push(TranscriptEntry {
+                elapsed,
+                inbound,
+                bytes: rest[..len].to_vec(),
+            });
+            bytes = &rest[entry_len..];
+        }
+
+        Ok(Transcript { entries })
+    }
+
+    /// Return the entries in the order they were recorded
+    pub fn entries(&self) -> &[TranscriptEntry] {
+        &self.entries
+    }
+
+    /// Return all the bytes received from the client
+    pub fn received(&self) -> Vec<u8> {
+        self.concat(true)
+    }
+
+    /// Return all the bytes sent to the client
+    pub fn sent(&self) -> Vec<u8> {
+        self.concat(false)
+    }
+
+    fn concat(&self, inbound: bool) -> Vec<u8> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.inbound == inbound)
+            .flat_map(|entry| entry.bytes.iter().cloned())
+            .collect()
+    }
+
+    /// Send the bytes received by the connection to the handler through an in-memory [`HttpConnection`],
+    /// in the same chunks but without waiting, and return the bytes it answers.
+    /// The invalid requests are answered as the server does by default.
+    ///
+    /// [`HttpConnection`]: struct.HttpConnection.html
+    pub fn replay<H>(&self, handler: H) -> Vec<u8>
+    where
+        H: Fn(&Request) -> Response,
+    {
+        use futures::{AsyncReadExt, AsyncWriteExt};
+
+        let (client, server) = duplex(REPLAY_CAPACITY);
+        let (mut reader, mut writer) = client.split();
+
+        let serve = async move {
+            let mut connection = HttpConnection::new(server);
+            loop {
+                match connection.next_request().await {
+                    Ok(Some(request)) => {
+                        let response = handler(&request);
+                        if connection.send_response(&request, response).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(RequestError::ParseError(error)) => {
+                        if let Some(response) = default_parse_error(&error, &[]) {
+                            let _ = connection.send_error(response).await;
+                        }
+                        break;
+                    }
+                    Ok(None) | Err(_) => break,
+                }
+            }
+            let _ = connection.close().await;
+        };
+
+        let send = async move {
+            for entry in self.entries.iter().filter(|entry| entry.inbound) {
+                // The connection was closed by a response
+                if writer.write_all(&entry.bytes).await.is_err() {
+                    break;
+                }
+            }
+            let _ = writer.close().await;
+        };
+
+        let receive = async move {
+            let mut sent = Vec::new();
+            let _ = reader.read_to_end(&mut sent).await;
+            sent
+        };
+
+        let (_, _, sent) =
+            futures::executor::block_on(futures::future::join3(serve, send, receive));
+        sent
+    }
+
+    /// Send the bytes received by the connection to a running server with their original timing,
+    /// and return the bytes it answers until it closes the connection
+    pub fn replay_to(&self, addr: SocketAddr) -> io::Result<Vec<u8>> {
+        let mut stream = TcpStream::connect(addr)?;
+        let mut reader = stream.try_clone()?;
+
+        let received = std::thread::spawn(move || {
+            let mut received = Vec::new();
+            reader.read_to_end(&mut received).map(|_| received)
+        });
+
+        let start = Instant::now();
+        for entry in self.entries.iter().filter(|entry| entry.inbound) {
+            if let Some(wait) = entry.elapsed.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+            stream.write_all(&entry.bytes)?;
+        }
+        stream.shutdown(Shutdown::Write)?;
+
+        received
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("The reader panicked")))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Write the bytes exchanged on a connection in the format of a [`Transcript`].
+/// Recording stops at the first error, as the connection itself is not affected.
+pub(crate) struct TranscriptRecorder {
+    output: Option<Box<dyn Write + Send>>,
+    start: Instant,
+}
+
+impl TranscriptRecorder {
+    pub(crate) fn new<W: Write + Send + 'static>(output: W) -> TranscriptRecorder {
+        TranscriptRecorder {
+            output: Some(Box::new(output)),
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn create(path: &Path) -> io::Result<TranscriptRecorder> {
+        Ok(TranscriptRecorder::new(File::create(path)?))
+    }
+
+    pub(crate) fn record(&mut self, id: u64, inbound: bool, bytes: &[u8]) {
+        let output = match self.output.as_mut() {
+            Some(output) => output,
+            None => return,
+        };
+
+        let head = format!(
+            "{} {} {}\n",
+            self.start.elapsed().as_micros(),
+            if inbound { "in" } else { "out" },
+            bytes.len()
+        );
+        // The entry is flushed right away so the transcript of a crashed server is complete
+        let written = output
+            .write_all(head.as_bytes())
+            .and_then(|_| output.write_all(bytes))
+            .and_then(|_| output.write_all(b"\n"))
+            .and_then(|_| output.flush());
+
+        if let Err(e) = written {
+            event!(
+                warn,
+                CONNECTION,
+                [conn = id],
+                "Could not record the transcript : {}",
+                e
+            );
+            self.output = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ResponseBuilder;
+
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_and_parse() {
+        let output = Output::default();
+        let mut recorder = TranscriptRecorder::new(output.clone());
+        recorder.record(0, true, b"GET / HTTP/1.1\r\n");
+        recorder.record(0, true, b"\r\n");
+        recorder.record(0, false, b"HTTP/1.1 200 OK\r\n\r\n");
+
+        let mut bytes = output.0.lock().unwrap().clone();
+        // An interrupted entry is ignored
+        bytes.extend_from_slice(b"1000 in 10\nGET");
+        let transcript = Transcript::parse(&bytes).unwrap();
+
+        assert_eq!(transcript.entries().len(), 3);
+        assert!(transcript.entries()[1].is_inbound());
+        assert!(!transcript.entries()[2].is_inbound());
+        assert_eq!(transcript.received(), b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(transcript.sent(), b"HTTP/1.1 200 OK\r\n\r\n");
+
+        assert!(Transcript::parse(b"10 sideways 2\nab\n").is_err());
+        assert!(Transcript::parse(b"10 in 2\nabc\n").is_err());
+
+        let corrupt = format!("10 in {}\nabc\n", usize::MAX);
+        let error = Transcript::parse(corrupt.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Invalid length");
+    }
+
+    #[test]
+    fn replay() {
+        let handler = |request: &Request| {
+            ResponseBuilder::empty_200()
+                .body(request.path().as_bytes())
+                .build()
+                .unwrap()
+        };
+
+        let transcript = Transcript::parse(
+            b"0 in 21\nGET /a HTTP/1.1\r\nHost\n5 in 45\n: a\r\n\r\nGET /b HTTP/1.1\r\nConnection: close\r\n\r\n\n",
+        )
+        .unwrap();
+        let sent = String::from_utf8(transcript.replay(handler)).unwrap();
+        assert_eq!(sent.matches("HTTP/1.1 200").count(), 2);
+        assert!(sent.contains("\r\n\r\n/a"));
+        assert!(sent.ends_with("\r\n\r\n/b"));
+
+        let transcript = Transcript::parse(b"0 in 10\nBROKEN\r\n\r\n\n").unwrap();
+        let sent = String::from_utf8(transcript.replay(handler)).unwrap();
+        assert!(sent.starts_with("HTTP/1.1 400"));
+    }
+}
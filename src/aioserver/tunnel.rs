@@ -0,0 +1,86 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+/// Raw connection handed over to the CONNECT handler once the tunnel is established.
+///
+/// The bytes the client sent after the CONNECT request and that were already read by the server
+/// are returned first by the `Read` implementation.
+pub struct Tunnel {
+    stream: TcpStream,
+    buffered: std::io::Cursor<Vec<u8>>,
+}
+
+impl Tunnel {
+    pub(crate) fn new(stream: TcpStream, buffered: Vec<u8>) -> Tunnel {
+        Tunnel {
+            stream,
+            buffered: std::io::Cursor::new(buffered),
+        }
+    }
+
+    /// Return the underlying blocking stream and the bytes already read from it
+    pub fn into_parts(self) -> (TcpStream, Vec<u8>) {
+        let position = self.buffered.position() as usize;
+        let mut buffered = self.buffered.into_inner();
+        buffered.drain(..position);
+
+        (self.stream, buffered)
+    }
+}
+
+impl Read for Tunnel {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.buffered.read(buf)? {
+            0 => self.stream.read(buf),
+            n => Ok(n),
+        }
+    }
+}
+
+impl Write for Tunnel {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn buffered_first() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let mut tunnel = Tunnel::new(server, b"early".to_vec());
+        client.write_all(b"late").unwrap();
+
+        let mut buf = [0; 5];
+        tunnel.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"early");
+
+        let mut buf = [0; 4];
+        tunnel.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"late");
+    }
+
+    #[test]
+    fn into_parts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let mut tunnel = Tunnel::new(server, b"early".to_vec());
+        let mut buf = [0; 2];
+        tunnel.read_exact(&mut buf).unwrap();
+
+        let (_, buffered) = tunnel.into_parts();
+        assert_eq!(buffered, b"rly");
+    }
+}
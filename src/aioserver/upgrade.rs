@@ -0,0 +1,79 @@
+use crate::aioserver::tunnel::Tunnel;
+
+use std::fmt;
+use std::sync::Mutex;
+
+/// Protocol the connection switches to once a `101 Switching Protocols` response is sent,
+/// set with [`ResponseBuilder::upgrade`].
+///
+/// Once the response is written the server stops reading the connection and gives it to the handler as a
+/// [`Tunnel`], which first returns the bytes of the new protocol the client sent along with its request.
+/// As the [`Tunnel`] is blocking, the handler is called on a thread dedicated to the connection
+/// and can serve the new protocol until the connection is closed without holding up a worker.
+///
+/// # Example
+///
+/// A protocol echoing back what the client sends
+///
+/// ```
+/// use mini_async_http::{Request, Response, ResponseBuilder};
+/// use std::io::{Read, Write};
+///
+/// fn handler(request: &Request) -> Response {
+///     if request.upgrade() != Some("echo") {
+///         return ResponseBuilder::empty_400().build().unwrap();
+///     }
+///
+///     ResponseBuilder::switching_protocols("echo")
+///         .upgrade(|mut tunnel| {
+///             let mut buf = [0; 1024];
+///             while let Ok(n) = tunnel.read(&mut buf) {
+///                 if n == 0 || tunnel.write_all(&buf[..n]).is_err() {
+///                     break;
+///                 }
+///             }
+///         })
+///         .build()
+///         .unwrap()
+/// }
+/// ```
+///
+/// [`ResponseBuilder::upgrade`]: struct.ResponseBuilder.html#method.upgrade
+/// [`Tunnel`]: struct.Tunnel.html
+pub struct Upgrade {
+    // The response is shared while it is written, the lock makes it Sync without requiring it of the handler
+    handler: Mutex<Box<dyn Send + 'static + FnOnce(Tunnel)>>,
+}
+
+impl Upgrade {
+    /// Create an upgrade giving the connection to the handler
+    pub fn new<H>(handler: H) -> Upgrade
+    where
+        H: Send + 'static + FnOnce(Tunnel),
+    {
+        Upgrade {
+            handler: Mutex::new(Box::new(handler)),
+        }
+    }
+
+    pub(crate) fn run(self, tunnel: Tunnel) {
+        let handler = self
+            .handler
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        handler(tunnel)
+    }
+}
+
+impl fmt::Debug for Upgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upgrade").finish()
+    }
+}
+
+/// The handlers can not be compared, an upgrade is only equal to itself
+impl PartialEq for Upgrade {
+    fn eq(&self, other: &Upgrade) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
@@ -1,3 +1,5 @@
+use crate::data::TakeCell;
+
 use crossbeam_utils::atomic::AtomicCell;
 
 pub(crate) struct AtomicTake<T> {
@@ -16,12 +18,14 @@ impl<T> AtomicTake<T> {
             inner: AtomicCell::new(Option::from(value)),
         }
     }
+}
 
-    pub(crate) fn take(&self) -> Option<T> {
+impl<T> TakeCell<T> for AtomicTake<T> {
+    fn take(&self) -> Option<T> {
         self.inner.take()
     }
 
-    pub(crate) fn store(&self, value: T) {
+    fn store(&self, value: T) {
         self.inner.store(Option::from(value));
     }
 }
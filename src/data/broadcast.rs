@@ -0,0 +1,312 @@
+//! Broadcast channel delivering every published value to all the subscribers, to fan out events
+//! such as notifications pushed to many connections.
+//!
+//! Each subscriber has its own buffer of `capacity` values. A subscriber that does not keep up with the
+//! publishers is handled according to the [`LagPolicy`] of the channel, so it never slows the others down.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::broadcast::{self, LagPolicy, RecvError};
+//!
+//! let publisher = broadcast::channel(16, LagPolicy::DropOldest);
+//! let mut first = publisher.subscribe();
+//! let mut second = publisher.subscribe();
+//!
+//! assert_eq!(publisher.publish("event"), 2);
+//! drop(publisher);
+//!
+//! futures::executor::block_on(async move {
+//!     assert_eq!(first.recv().await, Ok("event"));
+//!     assert_eq!(second.recv().await, Ok("event"));
+//!     // Every publisher is dropped and the buffer is empty
+//!     assert_eq!(first.recv().await, Err(RecvError::Closed));
+//! });
+//! ```
+//!
+//! [`LagPolicy`]: enum.LagPolicy.html
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// What happens to a subscriber whose buffer is full when a value is published
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LagPolicy {
+    /// The oldest value of the buffer is dropped, the subscriber is told how many it missed
+    DropOldest,
+    /// The subscriber is disconnected and its buffer dropped
+    Disconnect,
+}
+
+/// Error returned by [`Subscriber::recv`]
+///
+/// [`Subscriber::recv`]: struct.Subscriber.html#method.recv
+#[derive(Debug, PartialEq)]
+pub enum RecvError {
+    /// The subscriber lagged behind and missed the given number of values, the next ones can still be received
+    Lagged(u64),
+    /// The subscriber lagged behind and was disconnected
+    Disconnected,
+    /// Every publisher is dropped and all the values were received
+    Closed,
+}
+
+/// Error returned by [`Subscriber::try_recv`]
+///
+/// [`Subscriber::try_recv`]: struct.Subscriber.html#method.try_recv
+#[derive(Debug, PartialEq)]
+pub enum TryRecvError {
+    /// No value is waiting
+    Empty,
+    /// The subscriber can not receive values anymore, or missed some
+    Recv(RecvError),
+}
+
+struct Slot<T> {
+    queue: VecDeque<T>,
+    lagged: u64,
+    disconnected: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> Slot<T> {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct State<T> {
+    slots: HashMap<usize, Slot<T>>,
+    next_id: usize,
+    publishers: usize,
+    capacity: usize,
+    policy: LagPolicy,
+}
+
+/// Create a broadcast channel and return its first publisher.
+/// Each subscriber buffers at most `capacity` values, at least one.
+pub fn channel<T: Clone>(capacity: usize, policy: LagPolicy) -> Publisher<T> {
+    Publisher {
+        shared: Arc::new(Mutex::new(State {
+            slots: HashMap::new(),
+            next_id: 0,
+            publishers: 1,
+            capacity: capacity.max(1),
+            policy,
+        })),
+    }
+}
+
+/// Publishing half of a broadcast channel, it can be cloned to publish from several tasks
+pub struct Publisher<T> {
+    shared: Arc<Mutex<State<T>>>,
+}
+
+impl<T: Clone> Publisher<T> {
+    /// Give a copy of the value to every subscriber and return the number of subscribers it reached.
+    /// The value is dropped if there is no subscriber.
+    pub fn publish(&self, value: T) -> usize {
+        let mut state = self.shared.lock().unwrap();
+        let (capacity, policy) = (state.capacity, state.policy);
+        let mut reached = 0;
+
+        for slot in state.slots.values_mut().filter(|slot| !slot.disconnected) {
+            if slot.queue.len() >= capacity {
+                match policy {
+                    LagPolicy::DropOldest => {
+                        slot.queue.pop_front();
+                        slot.lagged += 1;
+                    }
+                    LagPolicy::Disconnect => {
+                        slot.queue.clear();
+                        slot.disconnected = true;
+                        slot.wake();
+                        continue;
+                    }
+                }
+            }
+
+            slot.queue.push_back(value.clone());
+            slot.wake();
+            reached += 1;
+        }
+
+        reached
+    }
+}
+
+impl<T> Publisher<T> {
+    /// Create a subscriber receiving the values published from now on
+    pub fn subscribe(&self) -> Subscriber<T> {
+        let mut state = self.shared.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.slots.insert(
+            id,
+            Slot {
+                queue: VecDeque::new(),
+                lagged: 0,
+                disconnected: false,
+                waker: None,
+            },
+        );
+
+        Subscriber {
+            id,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Return the number of subscribers, the disconnected ones excluded
+    pub fn subscribers(&self) -> usize {
+        let state = self.shared.lock().unwrap();
+        state
+            .slots
+            .values()
+            .filter(|slot| !slot.disconnected)
+            .count()
+    }
+}
+
+impl<T> Clone for Publisher<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().publishers += 1;
+
+        Publisher {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Publisher<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock().unwrap();
+        state.publishers -= 1;
+
+        if state.publishers == 0 {
+            state.slots.values_mut().for_each(Slot::wake);
+        }
+    }
+}
+
+/// Receiving half of a broadcast channel, with its own buffer of the published values
+pub struct Subscriber<T> {
+    id: usize,
+    shared: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Subscriber<T> {
+    /// Wait for the next value. A lag is reported once, before the values following it.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        futures::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Take the next value without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock().unwrap();
+
+        match Subscriber::next(&mut state, self.id) {
+            Some(result) => result.map_err(TryRecvError::Recv),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Poll for the next value, the task is woken when one is published or the channel is closed
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Result<T, RecvError>> {
+        let mut state = self.shared.lock().unwrap();
+
+        match Subscriber::next(&mut state, self.id) {
+            Some(result) => Poll::Ready(result),
+            None => {
+                if let Some(slot) = state.slots.get_mut(&self.id) {
+                    slot.waker = Some(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    fn next(state: &mut State<T>, id: usize) -> Option<Result<T, RecvError>> {
+        let publishers = state.publishers;
+        let slot = state.slots.get_mut(&id)?;
+
+        if slot.disconnected {
+            return Some(Err(RecvError::Disconnected));
+        }
+        if slot.lagged > 0 {
+            return Some(Err(RecvError::Lagged(std::mem::take(&mut slot.lagged))));
+        }
+
+        match slot.queue.pop_front() {
+            Some(value) => Some(Ok(value)),
+            None if publishers == 0 => Some(Err(RecvError::Closed)),
+            None => None,
+        }
+    }
+}
+
+impl<T> Drop for Subscriber<T> {
+    fn drop(&mut self) {
+        let slot = self.shared.lock().unwrap().slots.remove(&self.id);
+        // The buffered values are dropped once the lock is released
+        drop(slot);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn fan_out() {
+        let publisher = channel(4, LagPolicy::DropOldest);
+        assert_eq!(publisher.publish(0), 0);
+
+        let mut first = publisher.subscribe();
+        let mut second = publisher.subscribe();
+        let other = publisher.clone();
+
+        let thread = std::thread::spawn(move || block_on(second.recv()));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(other.publish(1), 2);
+        assert_eq!(thread.join().unwrap(), Ok(1));
+        assert_eq!(publisher.subscribers(), 1);
+
+        assert_eq!(first.try_recv(), Ok(1));
+        assert_eq!(first.try_recv(), Err(TryRecvError::Empty));
+
+        drop(publisher);
+        drop(other);
+        assert_eq!(block_on(first.recv()), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn lag_policies() {
+        let publisher = channel(2, LagPolicy::DropOldest);
+        let mut subscriber = publisher.subscribe();
+        for i in 0..5 {
+            publisher.publish(i);
+        }
+        assert_eq!(
+            subscriber.try_recv(),
+            Err(TryRecvError::Recv(RecvError::Lagged(3)))
+        );
+        assert_eq!(subscriber.try_recv(), Ok(3));
+        assert_eq!(subscriber.try_recv(), Ok(4));
+
+        let publisher = channel(2, LagPolicy::Disconnect);
+        let mut slow = publisher.subscribe();
+        let mut fast = publisher.subscribe();
+        for i in 0..3 {
+            publisher.publish(i);
+            assert_eq!(fast.try_recv(), Ok(i));
+        }
+        assert_eq!(block_on(slow.recv()), Err(RecvError::Disconnected));
+        assert_eq!(publisher.subscribers(), 1);
+        assert_eq!(publisher.publish(3), 1);
+    }
+}
@@ -0,0 +1,434 @@
+//! Asynchronous channels to communicate between the tasks of the server.
+//!
+//! The receiving task is woken by the executor of the server when a value is sent, so a handler or a
+//! middleware can await the values produced by another task or thread without blocking its worker.
+//! [`unbounded`] and [`bounded`] create channels with many senders and one receiver, [`oneshot`] a channel
+//! carrying a single value.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::channel;
+//!
+//! let (sender, mut receiver) = channel::unbounded();
+//!
+//! std::thread::spawn(move || {
+//!     for i in 0..3 {
+//!         sender.try_send(i).unwrap();
+//!     }
+//! });
+//!
+//! futures::executor::block_on(async move {
+//!     let mut sum = 0;
+//!     // None is returned once every sender is dropped
+//!     while let Some(i) = receiver.recv().await {
+//!         sum += i;
+//!     }
+//!     assert_eq!(sum, 3);
+//! });
+//! ```
+//!
+//! [`unbounded`]: fn.unbounded.html
+//! [`bounded`]: fn.bounded.html
+//! [`oneshot`]: fn.oneshot.html
+
+use futures::Stream;
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Error returned when sending to a channel whose receiver is dropped, the value is given back
+#[derive(Debug, PartialEq)]
+pub struct SendError<T>(pub T);
+
+/// Error returned by [`Sender::try_send`], the value is given back
+///
+/// [`Sender::try_send`]: struct.Sender.html#method.try_send
+#[derive(Debug, PartialEq)]
+pub enum TrySendError<T> {
+    /// The channel holds as many values as its capacity
+    Full(T),
+    /// The receiver is dropped
+    Closed(T),
+}
+
+/// Error returned by [`Receiver::try_recv`]
+///
+/// [`Receiver::try_recv`]: struct.Receiver.html#method.try_recv
+#[derive(Debug, PartialEq)]
+pub enum TryRecvError {
+    /// No value is waiting in the channel
+    Empty,
+    /// No value is waiting and every sender is dropped
+    Closed,
+}
+
+/// Error returned by a [`OneshotReceiver`] whose sender is dropped without sending a value
+///
+/// [`OneshotReceiver`]: struct.OneshotReceiver.html
+#[derive(Debug, PartialEq)]
+pub struct Canceled;
+
+struct State<T> {
+    queue: VecDeque<T>,
+    capacity: Option<usize>,
+    senders: usize,
+    closed: bool,
+    receiver: Option<Waker>,
+    blocked: Vec<Waker>,
+}
+
+impl<T> State<T> {
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.receiver.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_blocked(&mut self) {
+        self.blocked.drain(..).for_each(Waker::wake);
+    }
+}
+
+/// Create a channel without limit of the number of values waiting to be received
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    channel(None)
+}
+
+/// Create a channel holding at most `capacity` values waiting to be received, at least one.
+/// The senders then wait for the receiver to catch up.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    channel(Some(capacity.max(1)))
+}
+
+fn channel<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Mutex::new(State {
+        queue: VecDeque::new(),
+        capacity,
+        senders: 1,
+        closed: false,
+        receiver: None,
+        blocked: Vec::new(),
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// Sending half of a channel, it can be cloned to send from several tasks
+pub struct Sender<T> {
+    shared: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a value without waiting, failing if the channel is full or closed
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.shared.lock().unwrap();
+
+        if state.closed {
+            return Err(TrySendError::Closed(value));
+        }
+        if matches!(state.capacity, Some(capacity) if state.queue.len() >= capacity) {
+            return Err(TrySendError::Full(value));
+        }
+
+        state.queue.push_back(value);
+        state.wake_receiver();
+        Ok(())
+    }
+
+    /// Send a value, waiting for room in the channel when it is full
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = Some(value);
+
+        futures::future::poll_fn(|cx| {
+            let mut state = self.shared.lock().unwrap();
+            let item = value.take().expect("Send polled after completion");
+
+            if state.closed {
+                return Poll::Ready(Err(SendError(item)));
+            }
+            if matches!(state.capacity, Some(capacity) if state.queue.len() >= capacity) {
+                state.blocked.push(cx.waker().clone());
+                value = Some(item);
+                return Poll::Pending;
+            }
+
+            state.queue.push_back(item);
+            state.wake_receiver();
+            Poll::Ready(Ok(()))
+        })
+        .await
+    }
+
+    /// Return true if the receiver is dropped
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().unwrap().closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock().unwrap();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            state.wake_receiver();
+        }
+    }
+}
+
+/// Receiving half of a channel, also usable as a `Stream` of the values
+pub struct Receiver<T> {
+    shared: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Wait for the next value, None is returned once the channel is empty and every sender is dropped
+    pub async fn recv(&mut self) -> Option<T> {
+        futures::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Take the next value without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock().unwrap();
+
+        match state.queue.pop_front() {
+            Some(value) => {
+                state.wake_blocked();
+                Ok(value)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Poll for the next value, the task is woken when one is sent or the last sender is dropped
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        let mut state = self.shared.lock().unwrap();
+
+        match state.queue.pop_front() {
+            Some(value) => {
+                state.wake_blocked();
+                Poll::Ready(Some(value))
+            }
+            None if state.senders == 0 => Poll::Ready(None),
+            None => {
+                state.receiver = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock().unwrap();
+        state.closed = true;
+        state.wake_blocked();
+        // The values are dropped once the lock is released, their drop may use the channel
+        let queue = std::mem::take(&mut state.queue);
+        drop(state);
+        drop(queue);
+    }
+}
+
+struct OneshotState<T> {
+    value: Option<T>,
+    /// The sender sent its value or is dropped
+    finished: bool,
+    closed: bool,
+    receiver: Option<Waker>,
+}
+
+/// Create a channel carrying a single value, such as the answer to a request made to another task
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::channel;
+///
+/// let (sender, receiver) = channel::oneshot();
+/// std::thread::spawn(move || sender.send(42));
+///
+/// assert_eq!(futures::executor::block_on(receiver), Ok(42));
+/// ```
+pub fn oneshot<T>() -> (OneshotSender<T>, OneshotReceiver<T>) {
+    let shared = Arc::new(Mutex::new(OneshotState {
+        value: None,
+        finished: false,
+        closed: false,
+        receiver: None,
+    }));
+
+    (
+        OneshotSender {
+            shared: shared.clone(),
+        },
+        OneshotReceiver { shared },
+    )
+}
+
+/// Sending half of a oneshot channel
+pub struct OneshotSender<T> {
+    shared: Arc<Mutex<OneshotState<T>>>,
+}
+
+impl<T> OneshotSender<T> {
+    /// Send the value, it is given back if the receiver is dropped
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut state = self.shared.lock().unwrap();
+
+        if state.closed {
+            return Err(value);
+        }
+
+        state.value = Some(value);
+        state.finished = true;
+        if let Some(waker) = state.receiver.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Return true if the receiver is dropped
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().unwrap().closed
+    }
+}
+
+impl<T> Drop for OneshotSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock().unwrap();
+        state.finished = true;
+
+        if let Some(waker) = state.receiver.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Receiving half of a oneshot channel, a future resolved with the value or with [`Canceled`]
+/// if the sender is dropped without sending it
+///
+/// [`Canceled`]: struct.Canceled.html
+pub struct OneshotReceiver<T> {
+    shared: Arc<Mutex<OneshotState<T>>>,
+}
+
+impl<T> Future for OneshotReceiver<T> {
+    type Output = Result<T, Canceled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.shared.lock().unwrap();
+
+        match state.value.take() {
+            Some(value) => Poll::Ready(Ok(value)),
+            None if state.finished => Poll::Ready(Err(Canceled)),
+            None => {
+                state.receiver = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for OneshotReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().unwrap().closed = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    #[test]
+    fn unbounded_channel() {
+        let (sender, mut receiver) = unbounded();
+        let other = sender.clone();
+
+        sender.try_send(1).unwrap();
+        block_on(other.send(2)).unwrap();
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert_eq!(block_on(receiver.recv()), Some(2));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+        let thread = std::thread::spawn(move || {
+            for i in 3..6 {
+                other.try_send(i).unwrap();
+            }
+        });
+        drop(sender);
+        assert_eq!(
+            block_on(receiver.by_ref().collect::<Vec<_>>()),
+            vec![3, 4, 5]
+        );
+        thread.join().unwrap();
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn bounded_channel() {
+        let (sender, mut receiver) = bounded(1);
+
+        sender.try_send(1).unwrap();
+        assert_eq!(sender.try_send(2), Err(TrySendError::Full(2)));
+
+        // The sender waits until the receiver makes room
+        let thread = std::thread::spawn(move || {
+            block_on(sender.send(2)).unwrap();
+            sender
+        });
+        assert_eq!(block_on(receiver.recv()), Some(1));
+        let sender = thread.join().unwrap();
+        assert_eq!(block_on(receiver.recv()), Some(2));
+
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(block_on(sender.send(3)), Err(SendError(3)));
+    }
+
+    #[test]
+    fn oneshot_channel() {
+        let (sender, receiver) = oneshot();
+        std::thread::spawn(move || sender.send("done").unwrap());
+        assert_eq!(block_on(receiver), Ok("done"));
+
+        let (sender, receiver) = oneshot::<()>();
+        drop(sender);
+        assert_eq!(block_on(receiver), Err(Canceled));
+
+        let (sender, receiver) = oneshot();
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(1), Err(1));
+    }
+}
@@ -0,0 +1,161 @@
+use slab::Slab;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Boolean state that can be waited on, either by blocking the thread or from a future
+pub(crate) struct Flag {
+    value: Mutex<bool>,
+    cvar: Condvar,
+    /// Wakers of the futures waiting for the flag, one slot each until they complete or are dropped
+    wakers: Mutex<Slab<Waker>>,
+}
+
+impl Flag {
+    pub(crate) fn new(value: bool) -> Flag {
+        Flag {
+            value: Mutex::from(value),
+            cvar: Condvar::new(),
+            wakers: Mutex::from(Slab::new()),
+        }
+    }
+
+    pub(crate) fn get(&self) -> bool {
+        *self.value.lock().unwrap()
+    }
+
+    pub(crate) fn set(&self, value: bool) {
+        *self.value.lock().unwrap() = value;
+        self.cvar.notify_all();
+
+        self.wakers
+            .lock()
+            .unwrap()
+            .iter()
+            .for_each(|(_, waker)| waker.wake_by_ref());
+    }
+
+    /// Block until the flag holds the given value
+    pub(crate) fn wait(&self, expected: bool) {
+        let mut value = self.value.lock().unwrap();
+
+        while *value != expected {
+            value = self.cvar.wait(value).unwrap();
+        }
+    }
+
+    /// Wait for the flag to hold the given value without blocking the thread
+    pub(crate) fn wait_async(&self, expected: bool) -> WaitFlag<'_> {
+        WaitFlag {
+            flag: self,
+            expected,
+            slot: None,
+        }
+    }
+}
+
+/// Future returned by [`Flag::wait_async`]
+pub(crate) struct WaitFlag<'a> {
+    flag: &'a Flag,
+    expected: bool,
+    slot: Option<usize>,
+}
+
+impl WaitFlag<'_> {
+    fn unregister(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            self.flag.wakers.lock().unwrap().remove(slot);
+        }
+    }
+}
+
+impl Future for WaitFlag<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+
+        if this.flag.get() != this.expected {
+            let mut wakers = this.flag.wakers.lock().unwrap();
+            match this.slot {
+                Some(slot) if wakers[slot].will_wake(cx.waker()) => {}
+                Some(slot) => wakers[slot] = cx.waker().clone(),
+                None => this.slot = Some(wakers.insert(cx.waker().clone())),
+            }
+            drop(wakers);
+
+            // The value is checked again once the waker is registered so a concurrent set can not be missed
+            if this.flag.get() != this.expected {
+                return Poll::Pending;
+            }
+        }
+
+        this.unregister();
+        Poll::Ready(())
+    }
+}
+
+impl Drop for WaitFlag<'_> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn wait_set() {
+        let flag = Arc::new(Flag::new(false));
+        let setter = flag.clone();
+
+        std::thread::spawn(move || setter.set(true));
+
+        flag.wait(true);
+        assert!(flag.get());
+    }
+
+    #[test]
+    fn wait_async_set() {
+        let flag = Arc::new(Flag::new(true));
+        let setter = flag.clone();
+
+        std::thread::spawn(move || setter.set(false));
+
+        futures::executor::block_on(flag.wait_async(false));
+        assert!(!flag.get());
+    }
+
+    #[test]
+    fn wait_async_slots() {
+        let flag = Flag::new(false);
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // A flag already holding the value registers nothing
+        for _ in 0..3 {
+            assert!(Pin::new(&mut flag.wait_async(false))
+                .poll(&mut cx)
+                .is_ready());
+        }
+        assert!(flag.wakers.lock().unwrap().is_empty());
+
+        let mut wait = flag.wait_async(true);
+        for _ in 0..3 {
+            assert!(Pin::new(&mut wait).poll(&mut cx).is_pending());
+        }
+        assert_eq!(flag.wakers.lock().unwrap().len(), 1);
+        drop(wait);
+        assert!(flag.wakers.lock().unwrap().is_empty());
+
+        let mut wait = flag.wait_async(true);
+        assert!(Pin::new(&mut wait).poll(&mut cx).is_pending());
+        flag.set(true);
+        assert!(Pin::new(&mut wait).poll(&mut cx).is_ready());
+        assert!(flag.wakers.lock().unwrap().is_empty());
+    }
+}
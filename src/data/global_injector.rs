@@ -1,33 +1,29 @@
+use crate::data::{InjectorError, InjectorReceiver, InjectorSender};
+
 pub(crate) fn global_injector<T>() -> (Sender<T>, Receiver<T>) {
     let (sender, receiver) = crossbeam_channel::unbounded();
 
     (Sender { inner: sender }, Receiver { inner: receiver })
 }
 
-#[derive(Debug)]
-pub(crate) enum InjectorError<T> {
-    Send(T),
-    Recv,
-}
-
 #[derive(Debug)]
 pub(crate) struct Sender<T> {
     inner: crossbeam_channel::Sender<T>,
 }
 
-impl<T> Sender<T> {
-    pub(crate) fn send(&self, val: T) -> Result<(), InjectorError<T>> {
+impl<T> InjectorSender<T> for Sender<T> {
+    fn send(&self, val: T) -> Result<(), InjectorError<T>> {
         match self.inner.send(val) {
             Ok(_) => Ok(()),
             Err(send_error) => Err(InjectorError::Send(send_error.into_inner())),
         }
     }
 
-    pub(crate) fn len(&self) -> usize {
+    fn len(&self) -> usize {
         self.inner.len()
     }
 
-    pub(crate) fn is_empty(&self) -> bool {
+    fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
 }
@@ -45,26 +41,33 @@ pub(crate) struct Receiver<T> {
     inner: crossbeam_channel::Receiver<T>,
 }
 
-impl<T> Receiver<T> {
-    pub(crate) fn recv(&self) -> Result<T, InjectorError<T>> {
+impl<T> InjectorReceiver<T> for Receiver<T> {
+    fn recv(&self) -> Result<T, InjectorError<T>> {
         match self.inner.recv() {
             Ok(val) => Ok(val),
             Err(_) => Err(InjectorError::Recv),
         }
     }
 
-    pub(crate) fn try_recv(&self) -> Result<T, InjectorError<T>> {
+    fn recv_either(&self, other: &Receiver<T>) -> Result<T, InjectorError<T>> {
+        crossbeam_channel::select! {
+            recv(self.inner) -> val => val.map_err(|_| InjectorError::Recv),
+            recv(other.inner) -> val => val.map_err(|_| InjectorError::Recv),
+        }
+    }
+
+    fn try_recv(&self) -> Result<T, InjectorError<T>> {
         match self.inner.try_recv() {
             Ok(val) => Ok(val),
             Err(_) => Err(InjectorError::Recv),
         }
     }
 
-    pub(crate) fn len(&self) -> usize {
+    fn len(&self) -> usize {
         self.inner.len()
     }
 
-    pub(crate) fn is_empty(&self) -> bool {
+    fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
 }
@@ -122,6 +125,19 @@ mod test {
         assert!(receiver.is_empty());
     }
 
+    #[test]
+    fn recv_either() {
+        let (sender, receiver) = global_injector();
+        let (other_sender, other) = global_injector();
+
+        other_sender.send(1).expect("Error when sending");
+        assert_eq!(1, receiver.recv_either(&other).unwrap());
+
+        let handle = std::thread::spawn(move || receiver.recv_either(&other).unwrap());
+        sender.send(2).expect("Error when sending");
+        assert_eq!(2, handle.join().unwrap());
+    }
+
     #[test]
     fn multiple_send_recv() {
         let (sender, receiver) = global_injector();
@@ -1,46 +1,63 @@
-use std::cell::UnsafeCell;
+use crate::data::{QueueError, StealQueue, WorkQueue};
 
-const DEFAULT_SIZE: usize = u16::MAX as usize;
-
-#[derive(Debug)]
-pub(crate) enum QueueError<T> {
-    Push(T),
-    Empty,
-}
+use crossbeam_deque::{Steal, Stealer, Worker};
 
+/// FIFO queue owned by a single thread.
+/// Other threads can take values from it through a [`QueueStealer`].
 pub(crate) struct LocalQueue<T> {
-    inner: UnsafeCell<Vec<T>>,
+    inner: Worker<T>,
 }
 
 impl<T> LocalQueue<T> {
     pub(crate) fn new() -> LocalQueue<T> {
         LocalQueue {
-            inner: UnsafeCell::from(Vec::with_capacity(DEFAULT_SIZE)),
+            inner: Worker::new_fifo(),
         }
     }
+}
 
-    pub(crate) fn push(&self, val: T) -> Result<(), QueueError<T>> {
-        let inner: &mut Vec<T> = unsafe { &mut *self.inner.get() };
-        if inner.len() >= DEFAULT_SIZE {
-            return Err(QueueError::Push(val));
-        }
+impl<T> WorkQueue<T> for LocalQueue<T> {
+    type Stealer = QueueStealer<T>;
 
-        inner.push(val);
-        Ok(())
+    fn push(&self, val: T) {
+        self.inner.push(val);
     }
 
-    pub(crate) fn pop(&self) -> Result<T, QueueError<T>> {
-        let inner: &mut Vec<T> = unsafe { &mut *self.inner.get() };
+    fn pop(&self) -> Result<T, QueueError> {
+        self.inner.pop().ok_or(QueueError::Empty)
+    }
 
-        if let Some(val) = inner.pop() {
-            return Ok(val);
+    fn stealer(&self) -> QueueStealer<T> {
+        QueueStealer {
+            inner: self.inner.stealer(),
         }
+    }
+}
+
+/// Handle taking values from the front of a [`LocalQueue`] owned by another thread
+pub(crate) struct QueueStealer<T> {
+    inner: Stealer<T>,
+}
 
-        Err(QueueError::Empty)
+impl<T> StealQueue<T> for QueueStealer<T> {
+    fn steal(&self) -> Result<T, QueueError> {
+        loop {
+            match self.inner.steal() {
+                Steal::Success(val) => return Ok(val),
+                Steal::Empty => return Err(QueueError::Empty),
+                Steal::Retry => continue,
+            }
+        }
     }
 }
 
-unsafe impl<T> Sync for LocalQueue<T> {}
+impl<T> Clone for QueueStealer<T> {
+    fn clone(&self) -> Self {
+        QueueStealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
 
 #[cfg(test)]
 mod test {
@@ -58,20 +75,39 @@ mod test {
         let queue = LocalQueue::new();
         let val = 3;
 
-        queue.push(val).expect("Error when pushing on the queue");
+        queue.push(val);
 
         assert_eq!(val, queue.pop().expect("Missing Value in queue"));
         assert!(queue.pop().is_err());
     }
 
     #[test]
-    fn full_queue() {
+    fn fifo_order() {
         let queue = LocalQueue::new();
 
-        for _ in 0..DEFAULT_SIZE {
-            queue.push(3).expect("Error when pushing on the queue");
+        for i in 0..10 {
+            queue.push(i);
         }
 
-        assert!(queue.push(3).is_err());
+        for i in 0..10 {
+            assert_eq!(i, queue.pop().expect("Missing Value in queue"));
+        }
+    }
+
+    #[test]
+    fn steal() {
+        let queue = LocalQueue::new();
+        let stealer = queue.stealer();
+
+        queue.push(1);
+        queue.push(2);
+
+        let stolen = std::thread::spawn(move || stealer.steal().unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(1, stolen);
+        assert_eq!(2, queue.pop().unwrap());
+        assert!(queue.stealer().steal().is_err());
     }
 }
@@ -1,7 +1,30 @@
+#[cfg(not(loom))]
 mod atomic_take;
+pub mod broadcast;
+pub mod channel;
+mod flag;
+#[cfg(not(loom))]
 mod global_injector;
+#[cfg(not(loom))]
 mod local_queue;
+#[cfg(loom)]
+mod model;
+pub(crate) mod object_pool;
+mod primitives;
+mod read_buffer;
+pub mod sync;
 
+#[cfg(not(loom))]
 pub(crate) use atomic_take::AtomicTake;
+pub(crate) use flag::Flag;
+#[cfg(not(loom))]
 pub(crate) use global_injector::{global_injector, Receiver, Sender};
-pub(crate) use local_queue::{LocalQueue, QueueError};
+#[cfg(not(loom))]
+pub(crate) use local_queue::{LocalQueue, QueueStealer};
+#[cfg(loom)]
+pub(crate) use model::{global_injector, AtomicTake, LocalQueue, QueueStealer, Receiver, Sender};
+pub(crate) use object_pool::{discard, object_pool_stats, ObjectPool, ObjectPoolStats};
+pub(crate) use primitives::{
+    InjectorError, InjectorReceiver, InjectorSender, QueueError, StealQueue, TakeCell, WorkQueue,
+};
+pub(crate) use read_buffer::ReadBuffer;
@@ -0,0 +1,313 @@
+//! Implementations of the primitives on loom, compiled with `--cfg loom` in place of the crossbeam ones.
+//! They favour simplicity over speed, loom explores the interleavings of the atomic operations and the locks.
+
+use crate::data::{
+    InjectorError, InjectorReceiver, InjectorSender, QueueError, StealQueue, TakeCell, WorkQueue,
+};
+
+use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use loom::sync::{Arc, Mutex};
+
+use std::collections::VecDeque;
+
+pub(crate) struct AtomicTake<T> {
+    inner: AtomicPtr<T>,
+}
+
+impl<T> AtomicTake<T> {
+    pub(crate) fn new() -> AtomicTake<T> {
+        AtomicTake {
+            inner: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+
+    pub(crate) fn from(value: T) -> AtomicTake<T> {
+        AtomicTake {
+            inner: AtomicPtr::new(Box::into_raw(Box::new(value))),
+        }
+    }
+}
+
+impl<T> TakeCell<T> for AtomicTake<T> {
+    fn take(&self) -> Option<T> {
+        let value = self.inner.swap(std::ptr::null_mut(), Ordering::AcqRel);
+        if value.is_null() {
+            return None;
+        }
+
+        // The pointer was swapped out, no other thread can reach it
+        Some(*unsafe { Box::from_raw(value) })
+    }
+
+    fn store(&self, value: T) {
+        let previous = self
+            .inner
+            .swap(Box::into_raw(Box::new(value)), Ordering::AcqRel);
+        if !previous.is_null() {
+            drop(unsafe { Box::from_raw(previous) });
+        }
+    }
+}
+
+impl<T> Drop for AtomicTake<T> {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+// The value is only reached by the thread swapping it out
+unsafe impl<T: Send> Send for AtomicTake<T> {}
+unsafe impl<T: Send> Sync for AtomicTake<T> {}
+
+pub(crate) struct LocalQueue<T> {
+    inner: Arc<Mutex<VecDeque<T>>>,
+}
+
+impl<T> LocalQueue<T> {
+    pub(crate) fn new() -> LocalQueue<T> {
+        LocalQueue {
+            inner: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+
+impl<T> WorkQueue<T> for LocalQueue<T> {
+    type Stealer = QueueStealer<T>;
+
+    fn push(&self, val: T) {
+        self.inner.lock().unwrap().push_back(val);
+    }
+
+    fn pop(&self) -> Result<T, QueueError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(QueueError::Empty)
+    }
+
+    fn stealer(&self) -> QueueStealer<T> {
+        QueueStealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub(crate) struct QueueStealer<T> {
+    inner: Arc<Mutex<VecDeque<T>>>,
+}
+
+impl<T> StealQueue<T> for QueueStealer<T> {
+    fn steal(&self) -> Result<T, QueueError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(QueueError::Empty)
+    }
+}
+
+impl<T> Clone for QueueStealer<T> {
+    fn clone(&self) -> Self {
+        QueueStealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Queue shared by the senders and the receivers, along with their number to detect disconnections
+struct Channel<T> {
+    queue: Mutex<VecDeque<T>>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+}
+
+pub(crate) fn global_injector<T>() -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(Channel {
+        queue: Mutex::new(VecDeque::new()),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+    });
+
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+pub(crate) struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> InjectorSender<T> for Sender<T> {
+    fn send(&self, val: T) -> Result<(), InjectorError<T>> {
+        if self.channel.receivers.load(Ordering::Acquire) == 0 {
+            return Err(InjectorError::Send(val));
+        }
+        self.channel.queue.lock().unwrap().push_back(val);
+
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.channel.queue.lock().unwrap().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, Ordering::AcqRel);
+        Sender {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.channel.senders.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+pub(crate) struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Receiver<T> {
+    fn disconnected(&self) -> bool {
+        self.channel.senders.load(Ordering::Acquire) == 0
+    }
+}
+
+impl<T> InjectorReceiver<T> for Receiver<T> {
+    /// Spin until a value is sent, loom schedules the other threads at each yield
+    fn recv(&self) -> Result<T, InjectorError<T>> {
+        loop {
+            // The senders may queue a last value before they are all dropped
+            let disconnected = self.disconnected();
+            if let Ok(val) = self.try_recv() {
+                return Ok(val);
+            }
+            if disconnected {
+                return Err(InjectorError::Recv);
+            }
+            loom::thread::yield_now();
+        }
+    }
+
+    fn recv_either(&self, other: &Receiver<T>) -> Result<T, InjectorError<T>> {
+        loop {
+            let disconnected = self.disconnected() || other.disconnected();
+            if let Ok(val) = self.try_recv().or_else(|_| other.try_recv()) {
+                return Ok(val);
+            }
+            if disconnected {
+                return Err(InjectorError::Recv);
+            }
+            loom::thread::yield_now();
+        }
+    }
+
+    fn try_recv(&self) -> Result<T, InjectorError<T>> {
+        self.channel
+            .queue
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(InjectorError::Recv)
+    }
+
+    fn len(&self) -> usize {
+        self.channel.queue.lock().unwrap().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.channel.receivers.fetch_add(1, Ordering::AcqRel);
+        Receiver {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.receivers.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn take_once() {
+        loom::model(|| {
+            let take = Arc::new(AtomicTake::from(1));
+            let other = take.clone();
+
+            let thread = loom::thread::spawn(move || other.take());
+            let taken = take.take();
+
+            let taken = [taken, thread.join().unwrap()];
+            assert_eq!(taken.iter().flatten().count(), 1);
+        });
+    }
+
+    #[test]
+    fn store_hand_off() {
+        loom::model(|| {
+            let take = Arc::new(AtomicTake::new());
+            let other = take.clone();
+
+            let thread = loom::thread::spawn(move || other.store(1));
+            let taken = take.take();
+            thread.join().unwrap();
+
+            // The value is either taken or still stored, never lost
+            assert_eq!(taken.or_else(|| take.take()), Some(1));
+        });
+    }
+
+    #[test]
+    fn steal_once() {
+        loom::model(|| {
+            let queue = LocalQueue::new();
+            let stealer = queue.stealer();
+            queue.push(1);
+
+            let thread = loom::thread::spawn(move || stealer.steal().ok());
+            let popped = queue.pop().ok();
+
+            let taken = [popped, thread.join().unwrap()];
+            assert_eq!(taken.iter().flatten().count(), 1);
+        });
+    }
+
+    #[test]
+    fn injector_disconnect() {
+        loom::model(|| {
+            let (sender, receiver) = global_injector();
+
+            let thread = loom::thread::spawn(move || {
+                sender.send(1).unwrap();
+            });
+
+            // The value sent before the sender is dropped is received
+            assert_eq!(receiver.recv().unwrap(), 1);
+            assert!(receiver.recv().is_err());
+            thread.join().unwrap();
+        });
+    }
+}
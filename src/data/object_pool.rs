@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static REUSED: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static DISCARDED: AtomicU64 = AtomicU64::new(0);
+
+/// Counters of the objects recycled between requests, such as the header maps and the response buffers.
+/// Retrieved with [`ServerHandle::object_pool_stats`].
+///
+/// [`ServerHandle::object_pool_stats`]: struct.ServerHandle.html#method.object_pool_stats
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ObjectPoolStats {
+    reused: u64,
+    allocated: u64,
+    discarded: u64,
+}
+
+impl ObjectPoolStats {
+    /// Return the number of objects taken from a pool instead of being allocated
+    pub fn reused(&self) -> u64 {
+        self.reused
+    }
+
+    /// Return the number of objects allocated because the pool of the worker was empty
+    pub fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
+    /// Return the number of objects released instead of being kept, because the pool was full or the object too large
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+}
+
+/// Return the counters of all the pools of the process
+pub(crate) fn object_pool_stats() -> ObjectPoolStats {
+    ObjectPoolStats {
+        reused: REUSED.load(Ordering::Relaxed),
+        allocated: ALLOCATED.load(Ordering::Relaxed),
+        discarded: DISCARDED.load(Ordering::Relaxed),
+    }
+}
+
+/// Objects kept for reuse, each worker thread has its own pools so they are used without synchronization.
+/// At most `limit` objects are kept, the other ones are dropped.
+pub(crate) struct ObjectPool<T> {
+    free: Vec<T>,
+    limit: usize,
+}
+
+impl<T> ObjectPool<T> {
+    pub(crate) fn new(limit: usize) -> ObjectPool<T> {
+        ObjectPool {
+            free: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Take an object from the pool, None if it is empty and a new object has to be allocated
+    pub(crate) fn take(&mut self) -> Option<T> {
+        let object = self.free.pop();
+
+        match object {
+            Some(_) => REUSED.fetch_add(1, Ordering::Relaxed),
+            None => ALLOCATED.fetch_add(1, Ordering::Relaxed),
+        };
+
+        object
+    }
+
+    /// Give an object back to the pool, it is dropped if the pool is full
+    pub(crate) fn give(&mut self, object: T) {
+        if self.free.len() < self.limit {
+            self.free.push(object);
+        } else {
+            discard();
+        }
+    }
+}
+
+/// Count an object released instead of being given back
+pub(crate) fn discard() {
+    DISCARDED.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let mut pool = ObjectPool::new(1);
+
+        assert_eq!(pool.take(), None::<Vec<u8>>);
+
+        pool.give(Vec::with_capacity(16));
+        pool.give(Vec::with_capacity(32));
+
+        assert_eq!(pool.take().unwrap().capacity(), 16);
+        assert_eq!(pool.take(), None);
+
+        let stats = object_pool_stats();
+        assert!(stats.reused() >= 1);
+        assert!(stats.allocated() >= 2);
+        assert!(stats.discarded() >= 1);
+    }
+}
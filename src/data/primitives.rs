@@ -0,0 +1,70 @@
+//! Contracts of the concurrent primitives the executor and the reactor are built on.
+//!
+//! The regular implementations rely on crossbeam. When the crate is compiled with `--cfg loom`,
+//! the implementations of the `model` module take their place so loom can check the interleavings
+//! of the code using them:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib model
+//! ```
+
+#[derive(Debug)]
+pub(crate) enum QueueError {
+    Empty,
+}
+
+#[derive(Debug)]
+pub(crate) enum InjectorError<T> {
+    Send(T),
+    Recv,
+}
+
+/// Slot a value is stored in by a thread and taken out by another
+pub(crate) trait TakeCell<T> {
+    /// Take the value out, leaving the slot empty
+    fn take(&self) -> Option<T>;
+
+    /// Store a value, the previous one is dropped
+    fn store(&self, value: T);
+}
+
+/// FIFO queue owned by a single thread, the other threads take values from its front through its stealers
+pub(crate) trait WorkQueue<T> {
+    type Stealer: StealQueue<T>;
+
+    fn push(&self, value: T);
+
+    fn pop(&self) -> Result<T, QueueError>;
+
+    fn stealer(&self) -> Self::Stealer;
+}
+
+/// Handle taking values from the front of a [`WorkQueue`] owned by another thread
+pub(crate) trait StealQueue<T>: Clone {
+    fn steal(&self) -> Result<T, QueueError>;
+}
+
+/// Sending half of the unbounded queue shared by all the workers
+pub(crate) trait InjectorSender<T>: Clone {
+    /// Queue a value, failing once all the receivers are dropped
+    fn send(&self, value: T) -> Result<(), InjectorError<T>>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool;
+}
+
+/// Receiving half of the unbounded queue shared by all the workers
+pub(crate) trait InjectorReceiver<T>: Clone {
+    /// Wait for a value, failing once the queue is empty and all the senders are dropped
+    fn recv(&self) -> Result<T, InjectorError<T>>;
+
+    /// Wait for a value on this receiver or on the other one, whichever comes first
+    fn recv_either(&self, other: &Self) -> Result<T, InjectorError<T>>;
+
+    fn try_recv(&self) -> Result<T, InjectorError<T>>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool;
+}
@@ -0,0 +1,133 @@
+/// Growable buffer of the bytes read from a connection.
+/// The stream reads directly into the free space at the end of the buffer and the parser sees the unparsed bytes
+/// as a single slice. The parsed bytes are not removed one request at a time, their space is reused by moving
+/// the unparsed bytes back to the front when the end is full and at least as many bytes were parsed as are left,
+/// otherwise the buffer grows. Every byte is then moved a bounded number of times.
+pub(crate) struct ReadBuffer {
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    initial: usize,
+}
+
+/// Capacity kept by an empty buffer, beyond it the memory taken by a large body is released
+const MAX_IDLE_CAPACITY: usize = 64 * 1024;
+
+impl ReadBuffer {
+    pub(crate) fn new(capacity: usize) -> ReadBuffer {
+        ReadBuffer {
+            buf: vec![0; capacity],
+            start: 0,
+            end: 0,
+            initial: capacity,
+        }
+    }
+
+    /// Return the bytes read but not consumed yet
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.buf[self.start..self.end]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Mark the first `n` unconsumed bytes as parsed
+    pub(crate) fn consume(&mut self, n: usize) {
+        self.start = std::cmp::min(self.start + n, self.end);
+
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
+
+            if self.buf.len() > MAX_IDLE_CAPACITY {
+                self.buf.truncate(self.initial);
+                self.buf.shrink_to_fit();
+            }
+        }
+    }
+
+    /// Return the free space at the end of the buffer, at least `min` bytes long.
+    /// The bytes written in it are added to the data with `fill`.
+    pub(crate) fn spare(&mut self, min: usize) -> &mut [u8] {
+        if self.buf.len() - self.end < min {
+            if self.start > 0 && self.start >= self.len() {
+                self.buf.copy_within(self.start..self.end, 0);
+                self.end -= self.start;
+                self.start = 0;
+            }
+
+            if self.buf.len() - self.end < min {
+                let size = std::cmp::max(self.buf.len() * 2, self.end + min);
+                self.buf.resize(size, 0);
+            }
+        }
+
+        &mut self.buf[self.end..]
+    }
+
+    /// Add `n` bytes written in the space returned by `spare` to the data
+    pub(crate) fn fill(&mut self, n: usize) {
+        self.end = std::cmp::min(self.end + n, self.buf.len());
+    }
+
+    /// Return the unconsumed bytes
+    pub(crate) fn into_vec(mut self) -> Vec<u8> {
+        self.buf.truncate(self.end);
+        self.buf.drain(..self.start);
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(buffer: &mut ReadBuffer, bytes: &[u8]) {
+        buffer.spare(bytes.len())[..bytes.len()].copy_from_slice(bytes);
+        buffer.fill(bytes.len());
+    }
+
+    #[test]
+    fn fill_and_consume() {
+        let mut buffer = ReadBuffer::new(8);
+        write(&mut buffer, b"hello");
+        write(&mut buffer, b" world");
+
+        assert_eq!(buffer.data(), b"hello world");
+
+        buffer.consume(6);
+        assert_eq!(buffer.data(), b"world");
+        assert_eq!(buffer.len(), 5);
+
+        buffer.consume(5);
+        assert!(buffer.data().is_empty());
+        assert_eq!(buffer.end, 0);
+    }
+
+    #[test]
+    fn reuse_parsed_space() {
+        let mut buffer = ReadBuffer::new(8);
+        write(&mut buffer, b"abcdefgh");
+        buffer.consume(6);
+
+        write(&mut buffer, b"ijklmn");
+        assert_eq!(buffer.data(), b"ghijklmn");
+        assert_eq!(buffer.buf.len(), 8);
+    }
+
+    #[test]
+    fn release_large_buffer() {
+        let mut buffer = ReadBuffer::new(8);
+        write(&mut buffer, &vec![1; 2 * MAX_IDLE_CAPACITY]);
+        write(&mut buffer, b"rest");
+        buffer.consume(2 * MAX_IDLE_CAPACITY);
+
+        assert_eq!(buffer.into_vec(), b"rest");
+
+        let mut buffer = ReadBuffer::new(8);
+        write(&mut buffer, &vec![1; 2 * MAX_IDLE_CAPACITY]);
+        buffer.consume(2 * MAX_IDLE_CAPACITY);
+        assert_eq!(buffer.buf.len(), 8);
+    }
+}
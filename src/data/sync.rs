@@ -0,0 +1,537 @@
+//! Locks for the state shared by the handlers, awaited instead of blocking the worker threads.
+//!
+//! A task waiting for an [`AsyncMutex`] or an [`AsyncRwLock`] gives its worker back to the other tasks
+//! and is woken once the lock is released, while a `std::sync::Mutex` blocks the whole worker under contention.
+//! The guards can be held across `.await`.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::sync::AsyncMutex;
+//! use std::sync::Arc;
+//!
+//! let counter = Arc::new(AsyncMutex::new(0));
+//!
+//! futures::executor::block_on(async {
+//!     let mut count = counter.lock().await;
+//!     *count += 1;
+//! });
+//! assert_eq!(*counter.try_lock().unwrap(), 1);
+//! ```
+//!
+//! [`AsyncMutex`]: struct.AsyncMutex.html
+//! [`AsyncRwLock`]: struct.AsyncRwLock.html
+
+use std::cell::UnsafeCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+
+/// State of a lock shared by the mutex and the read-write lock, a mutex only takes it exclusively
+#[derive(Default)]
+struct RawLock {
+    state: Mutex<LockState>,
+}
+
+#[derive(Default)]
+struct LockState {
+    readers: usize,
+    writer: bool,
+    next_id: u64,
+    /// Tasks waiting for the lock, in the order they started waiting
+    waiters: VecDeque<Waiter>,
+}
+
+/// Slot of a task waiting for the lock, its waker is replaced when the task is polled again
+struct Waiter {
+    id: u64,
+    exclusive: bool,
+    waker: Waker,
+}
+
+impl RawLock {
+    fn try_acquire(&self, exclusive: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        RawLock::acquire(&mut state, exclusive)
+    }
+
+    fn acquire(state: &mut LockState, exclusive: bool) -> bool {
+        if state.writer || (exclusive && state.readers > 0) {
+            return false;
+        }
+
+        if exclusive {
+            state.writer = true;
+        } else {
+            state.readers += 1;
+        }
+        true
+    }
+
+    fn lock(&self, exclusive: bool) -> Acquire<'_> {
+        Acquire {
+            raw: self,
+            exclusive,
+            id: None,
+        }
+    }
+
+    /// Release the lock and wake the tasks which can take it
+    fn release(&self, exclusive: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        if exclusive {
+            state.writer = false;
+        } else {
+            state.readers -= 1;
+        }
+
+        RawLock::wake(&mut state);
+    }
+
+    /// Wake the first waiting writer if the lock is free, or all the waiting readers if the first task
+    /// waits for reading. The woken tasks leave the queue, they come back in front if the lock was taken
+    /// before they were polled.
+    fn wake(state: &mut LockState) {
+        let exclusive = match state.waiters.front() {
+            Some(waiter) => waiter.exclusive,
+            None => return,
+        };
+
+        if state.writer || (exclusive && state.readers > 0) {
+            return;
+        }
+
+        if exclusive {
+            if let Some(waiter) = state.waiters.pop_front() {
+                waiter.waker.wake();
+            }
+        } else {
+            let (readers, writers) = state
+                .waiters
+                .drain(..)
+                .partition(|waiter| !waiter.exclusive);
+            state.waiters = writers;
+            readers
+                .into_iter()
+                .for_each(|waiter: Waiter| waiter.waker.wake());
+        }
+    }
+}
+
+/// Future of a task waiting for the lock, it holds one slot in the queue however many times it is polled
+struct Acquire<'a> {
+    raw: &'a RawLock,
+    exclusive: bool,
+    /// Identifier of the slot once the task waited
+    id: Option<u64>,
+}
+
+impl Future for Acquire<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let mut state = this.raw.state.lock().unwrap();
+        let position = this
+            .id
+            .and_then(|id| state.waiters.iter().position(|waiter| waiter.id == id));
+
+        if RawLock::acquire(&mut state, this.exclusive) {
+            if let Some(position) = position {
+                state.waiters.remove(position);
+            }
+            this.id = None;
+            return Poll::Ready(());
+        }
+
+        match position {
+            Some(position) => {
+                let waiter = &mut state.waiters[position];
+                if !waiter.waker.will_wake(cx.waker()) {
+                    waiter.waker = cx.waker().clone();
+                }
+            }
+            None => {
+                let waiter = Waiter {
+                    id: state.next_id,
+                    exclusive: this.exclusive,
+                    waker: cx.waker().clone(),
+                };
+                state.next_id += 1;
+
+                // A woken task losing the lock to another one keeps its place in front
+                if this.id.replace(waiter.id).is_some() {
+                    state.waiters.push_front(waiter);
+                } else {
+                    state.waiters.push_back(waiter);
+                }
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let id = match self.id {
+            Some(id) => id,
+            None => return,
+        };
+
+        let mut state = self.raw.state.lock().unwrap();
+        match state.waiters.iter().position(|waiter| waiter.id == id) {
+            Some(position) => {
+                state.waiters.remove(position);
+            }
+            // The task was woken but dropped before taking the lock, the wake up is passed on
+            None => RawLock::wake(&mut state),
+        }
+    }
+}
+
+/// Mutual exclusion lock whose [`lock`] is awaited
+///
+/// [`lock`]: #method.lock
+pub struct AsyncMutex<T: ?Sized> {
+    raw: RawLock,
+    value: UnsafeCell<T>,
+}
+
+// The value is only reached through a guard, which the lock gives to one task at a time
+unsafe impl<T: ?Sized + Send> Send for AsyncMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for AsyncMutex<T> {}
+
+impl<T> AsyncMutex<T> {
+    /// Create an unlocked mutex holding the value
+    pub fn new(value: T) -> AsyncMutex<T> {
+        AsyncMutex {
+            raw: RawLock::default(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consume the mutex and return its value
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> AsyncMutex<T> {
+    /// Wait for the lock, the value is unlocked when the guard is dropped
+    pub async fn lock(&self) -> AsyncMutexGuard<'_, T> {
+        self.raw.lock(true).await;
+        AsyncMutexGuard { mutex: self }
+    }
+
+    /// Take the lock if it is free, without waiting
+    pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T>> {
+        if self.raw.try_acquire(true) {
+            Some(AsyncMutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    /// Return a mutable reference to the value, no lock is needed as the mutex is borrowed mutably
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for AsyncMutex<T> {
+    fn default() -> Self {
+        AsyncMutex::new(T::default())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for AsyncMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncMutex").finish()
+    }
+}
+
+/// Access to the value of a locked [`AsyncMutex`], which is unlocked when the guard is dropped
+///
+/// [`AsyncMutex`]: struct.AsyncMutex.html
+pub struct AsyncMutexGuard<'a, T: ?Sized> {
+    mutex: &'a AsyncMutex<T>,
+}
+
+// Sharing the guard shares the value, which the mutex alone does not require to be Sync
+unsafe impl<T: ?Sized + Send + Sync> Sync for AsyncMutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for AsyncMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AsyncMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.raw.release(true);
+    }
+}
+
+/// Read-write lock whose [`read`] and [`write`] are awaited.
+/// Readers share the lock, a writer holds it alone. The new readers are not held back by a waiting writer.
+///
+/// [`read`]: #method.read
+/// [`write`]: #method.write
+pub struct AsyncRwLock<T: ?Sized> {
+    raw: RawLock,
+    value: UnsafeCell<T>,
+}
+
+// Several readers may reach the value at once from different threads
+unsafe impl<T: ?Sized + Send> Send for AsyncRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AsyncRwLock<T> {}
+
+impl<T> AsyncRwLock<T> {
+    /// Create an unlocked read-write lock holding the value
+    pub fn new(value: T) -> AsyncRwLock<T> {
+        AsyncRwLock {
+            raw: RawLock::default(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consume the lock and return its value
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> AsyncRwLock<T> {
+    /// Wait until no writer holds the lock and share it with the other readers
+    pub async fn read(&self) -> AsyncRwLockReadGuard<'_, T> {
+        self.raw.lock(false).await;
+        AsyncRwLockReadGuard { lock: self }
+    }
+
+    /// Wait until the lock is free and hold it alone
+    pub async fn write(&self) -> AsyncRwLockWriteGuard<'_, T> {
+        self.raw.lock(true).await;
+        AsyncRwLockWriteGuard { lock: self }
+    }
+
+    /// Share the lock if no writer holds it, without waiting
+    pub fn try_read(&self) -> Option<AsyncRwLockReadGuard<'_, T>> {
+        if self.raw.try_acquire(false) {
+            Some(AsyncRwLockReadGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Hold the lock alone if it is free, without waiting
+    pub fn try_write(&self) -> Option<AsyncRwLockWriteGuard<'_, T>> {
+        if self.raw.try_acquire(true) {
+            Some(AsyncRwLockWriteGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Return a mutable reference to the value, no lock is needed as the lock is borrowed mutably
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for AsyncRwLock<T> {
+    fn default() -> Self {
+        AsyncRwLock::new(T::default())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for AsyncRwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncRwLock").finish()
+    }
+}
+
+/// Shared access to the value of an [`AsyncRwLock`], released when the guard is dropped
+///
+/// [`AsyncRwLock`]: struct.AsyncRwLock.html
+pub struct AsyncRwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+}
+
+impl<T: ?Sized> Deref for AsyncRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.raw.release(false);
+    }
+}
+
+/// Exclusive access to the value of an [`AsyncRwLock`], released when the guard is dropped
+///
+/// [`AsyncRwLock`]: struct.AsyncRwLock.html
+pub struct AsyncRwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+}
+
+impl<T: ?Sized> Deref for AsyncRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AsyncRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.raw.release(true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::task::ArcWake;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Waker counting how many times it is woken
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl ArcWake for Counter {
+        fn wake_by_ref(counter: &Arc<Self>) {
+            counter.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Counter {
+        fn woken(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn poll(future: &mut Acquire<'_>, counter: &Arc<Counter>) -> Poll<()> {
+        let waker = futures::task::waker(counter.clone());
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn mutex() {
+        let mutex = Arc::new(AsyncMutex::new(0));
+
+        let guard = mutex.try_lock().unwrap();
+        assert!(mutex.try_lock().is_none());
+
+        // The waiting threads are woken when the guard is dropped
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let mutex = mutex.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *block_on(mutex.lock()) += 1;
+                    }
+                })
+            })
+            .collect();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        drop(guard);
+
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+        assert_eq!(Arc::try_unwrap(mutex).unwrap().into_inner(), 400);
+    }
+
+    #[test]
+    fn rw_lock() {
+        let lock = Arc::new(AsyncRwLock::new(String::from("a")));
+
+        let first = lock.try_read().unwrap();
+        let second = block_on(lock.read());
+        assert!(lock.try_write().is_none());
+        assert_eq!(*first, *second);
+
+        let writer = {
+            let lock = lock.clone();
+            std::thread::spawn(move || block_on(lock.write()).push('b'))
+        };
+        drop(first);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(!writer.is_finished());
+        drop(second);
+        writer.join().unwrap();
+
+        let mut guard = lock.try_write().unwrap();
+        assert!(lock.try_read().is_none());
+        guard.push('c');
+        drop(guard);
+        assert_eq!(*lock.try_read().unwrap(), "abc");
+    }
+
+    #[test]
+    fn waiters() {
+        let lock = AsyncRwLock::new(());
+        let guard = lock.try_write().unwrap();
+        let counters: Vec<Arc<Counter>> = (0..4).map(|_| Arc::default()).collect();
+
+        // A task polled again keeps a single slot in the queue
+        let mut writer = lock.raw.lock(true);
+        for _ in 0..3 {
+            assert!(poll(&mut writer, &counters[0]).is_pending());
+        }
+        assert_eq!(lock.raw.state.lock().unwrap().waiters.len(), 1);
+
+        let mut other_writer = lock.raw.lock(true);
+        let mut readers = [lock.raw.lock(false), lock.raw.lock(false)];
+        assert!(poll(&mut other_writer, &counters[1]).is_pending());
+        assert!(poll(&mut readers[0], &counters[2]).is_pending());
+        assert!(poll(&mut readers[1], &counters[3]).is_pending());
+
+        // Only the first writer is woken
+        drop(guard);
+        let woken: Vec<usize> = counters.iter().map(|counter| counter.woken()).collect();
+        assert_eq!(woken, [1, 0, 0, 0]);
+
+        // Once it releases the lock, the next one is a writer again
+        assert!(poll(&mut writer, &counters[0]).is_ready());
+        lock.raw.release(true);
+        let woken: Vec<usize> = counters.iter().map(|counter| counter.woken()).collect();
+        assert_eq!(woken, [1, 1, 0, 0]);
+
+        // A woken task dropped without taking the lock passes the wake up on, to all the readers
+        drop(other_writer);
+        let woken: Vec<usize> = counters.iter().map(|counter| counter.woken()).collect();
+        assert_eq!(woken, [1, 1, 1, 1]);
+        assert!(readers
+            .iter_mut()
+            .zip(&counters[2..])
+            .all(|(reader, counter)| poll(reader, counter).is_ready()));
+        assert!(lock.raw.state.lock().unwrap().waiters.is_empty());
+    }
+}
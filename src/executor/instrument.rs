@@ -0,0 +1,154 @@
+use crate::executor::Task;
+
+use futures::future::BoxFuture;
+use log::{trace, warn};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Snapshot of the statistics recorded by an instrumented executor.
+/// Retrieved with [`ServerHandle::executor_stats`].
+///
+/// [`ServerHandle::executor_stats`]: struct.ServerHandle.html#method.executor_stats
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExecutorStats {
+    polls: u64,
+    slow_polls: u64,
+    busy: Duration,
+}
+
+impl ExecutorStats {
+    /// Return the number of times a task has been polled
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /// Return the number of polls that exceeded the slow poll threshold
+    pub fn slow_polls(&self) -> u64 {
+        self.slow_polls
+    }
+
+    /// Return the total time spent polling tasks
+    pub fn busy(&self) -> Duration {
+        self.busy
+    }
+}
+
+/// Counters shared by the workers of an instrumented pool
+#[derive(Default)]
+pub(crate) struct PoolStats {
+    polls: AtomicU64,
+    slow_polls: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+impl PoolStats {
+    pub(crate) fn snapshot(&self) -> ExecutorStats {
+        ExecutorStats {
+            polls: self.polls.load(Ordering::Relaxed),
+            slow_polls: self.slow_polls.load(Ordering::Relaxed),
+            busy: Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Counters of a single task
+#[derive(Default)]
+pub(crate) struct TaskStats {
+    polls: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+impl TaskStats {
+    fn record(&self, elapsed: Duration) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.busy_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn polls(&self) -> u64 {
+        self.polls.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn busy(&self) -> Duration {
+        Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Time every poll made by the workers and warn when one blocks its worker longer than the threshold
+#[derive(Clone)]
+pub(crate) struct Instrumentation {
+    threshold: Duration,
+    stats: Arc<PoolStats>,
+}
+
+impl Instrumentation {
+    pub(crate) fn new(threshold: Duration, stats: Arc<PoolStats>) -> Instrumentation {
+        Instrumentation { threshold, stats }
+    }
+
+    pub(crate) fn poll(
+        &self,
+        task: &Task,
+        future: &mut BoxFuture<'static, ()>,
+        cx: &mut Context,
+    ) -> Poll<()> {
+        let start = Instant::now();
+        let poll = task.poll(future, cx);
+        let elapsed = start.elapsed();
+
+        task.stats.record(elapsed);
+        self.stats.polls.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .busy_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+
+        if elapsed > self.threshold {
+            self.stats.slow_polls.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Task poll took {:?}, more than {:?} : a handler may be blocking the worker",
+                elapsed, self.threshold
+            );
+        }
+
+        if poll.is_ready() {
+            trace!(
+                "Task completed after {} polls and {:?} of execution",
+                task.stats.polls(),
+                task.stats.busy()
+            );
+        }
+
+        poll
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::executor::thread_pool::ThreadPoolBuilder;
+
+    #[test]
+    fn record_polls() {
+        let stats = Arc::new(PoolStats::default());
+        let pool = ThreadPoolBuilder::new()
+            .size(2)
+            .instrument(Instrumentation::new(
+                Duration::from_millis(10),
+                stats.clone(),
+            ))
+            .build();
+
+        pool.block_on(async {}).unwrap();
+        pool.block_on(async { std::thread::sleep(Duration::from_millis(20)) })
+            .unwrap();
+        pool.stop().unwrap();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.polls(), 2);
+        assert_eq!(snapshot.slow_polls(), 1);
+        assert!(snapshot.busy() >= Duration::from_millis(20));
+    }
+}
@@ -5,6 +5,7 @@ use {
     },
     std::{
         future::Future,
+        sync::atomic::{AtomicBool, AtomicU8, Ordering},
         sync::Arc,
         task::{Context, Poll},
     },
@@ -14,15 +15,23 @@ use log::error;
 
 use std::sync::mpsc;
 
-use crate::data::AtomicTake;
-use crate::data::{global_injector, Receiver, Sender};
+use crate::data::{
+    global_injector, InjectorError, InjectorReceiver, InjectorSender, Receiver, Sender,
+};
+use crate::data::{AtomicTake, TakeCell};
+use crate::executor::worker::PinnedQueue;
 
+pub mod instrument;
+pub mod task_local;
 pub mod thread_pool;
 pub mod worker;
+pub mod yield_now;
 
 #[derive(Clone)]
 pub(crate) enum ExecutorMessage {
     Task(Arc<Task>),
+    /// End the worker receiving it, the other workers keep running
+    Retire,
     Stop,
 }
 
@@ -37,17 +46,129 @@ pub struct Spawner {
     task_sender: Sender<ExecutorMessage>,
 }
 
+/// The task waits for a wake up, its future is stored in the task
+const IDLE: u8 = 0;
+/// The task is in a queue, waiting for a worker
+const SCHEDULED: u8 = 1;
+/// A worker holds the future of the task and polls it
+const RUNNING: u8 = 2;
+/// The task was woken while polled, the worker queues it again once the future is stored back
+const NOTIFIED: u8 = 3;
+/// The future completed or was dropped, the wake ups are ignored
+const COMPLETE: u8 = 4;
+
 /// A future that can reschedule itself to be polled by an `Executor`.
 pub struct Task {
     future: AtomicTake<BoxFuture<'static, ()>>,
 
+    /// Scheduling state, so the task is queued once at a time and never while a worker holds its future
+    state: AtomicU8,
+
     /// Handle to place the task itself back onto the task queue.
     task_sender: Sender<ExecutorMessage>,
 
+    /// Queue of the worker the task is pinned to, if any
+    home: Option<Arc<PinnedQueue>>,
+
     notify_queue: Option<mpsc::SyncSender<()>>,
+
+    /// Set on a connection the pool may drop to make room as long as it was never polled
+    sheddable: AtomicBool,
+
+    locals: Arc<task_local::TaskLocals>,
+
+    stats: instrument::TaskStats,
 }
 
 impl Task {
+    pub(crate) fn new(
+        future: BoxFuture<'static, ()>,
+        task_sender: Sender<ExecutorMessage>,
+        notify_queue: Option<mpsc::SyncSender<()>>,
+    ) -> Task {
+        Task {
+            future: AtomicTake::from(future),
+            state: AtomicU8::new(SCHEDULED),
+            task_sender,
+            home: None,
+            notify_queue,
+            sheddable: AtomicBool::new(false),
+            locals: Arc::new(task_local::TaskLocals::default()),
+            stats: instrument::TaskStats::default(),
+        }
+    }
+
+    /// Pin the task to a worker, it is then only polled by this worker as long as it is running
+    pub(crate) fn pinned(mut self, home: Arc<PinnedQueue>) -> Task {
+        self.home = Some(home);
+        self
+    }
+
+    /// Let the pool drop the task to make room while it waits for its first poll
+    pub(crate) fn sheddable(self) -> Task {
+        self.sheddable.store(true, Ordering::SeqCst);
+        self
+    }
+
+    /// Take the task out of the ones the pool may drop, return true if it was one of them
+    pub(crate) fn take_sheddable(&self) -> bool {
+        self.sheddable.swap(false, Ordering::SeqCst)
+    }
+
+    /// Queue the task to be polled, on the queue of its worker when it is pinned to one still running.
+    /// Return false if the task could not be queued.
+    pub(crate) fn schedule(self: &Arc<Self>) -> bool {
+        let mut message = ExecutorMessage::Task(self.clone());
+
+        if let Some(ref home) = self.home {
+            match home.send(message) {
+                Ok(()) => return true,
+                Err(InjectorError::Send(back)) => message = back,
+                Err(InjectorError::Recv) => return false,
+            }
+        }
+
+        self.task_sender.send(message).is_ok()
+    }
+
+    /// Take the future out of the task to poll it, None if the task is complete
+    pub(crate) fn start_poll(&self) -> Option<BoxFuture<'static, ()>> {
+        // Set before taking the future so a wake up can not be missed in between
+        self.state.store(RUNNING, Ordering::SeqCst);
+        self.sheddable.store(false, Ordering::SeqCst);
+
+        let future = self.future.take();
+        if future.is_none() {
+            self.state.store(COMPLETE, Ordering::SeqCst);
+        }
+        future
+    }
+
+    /// Store back the future of a pending task, the task is queued again if it was woken while polled
+    pub(crate) fn end_poll(self: &Arc<Self>, future: BoxFuture<'static, ()>) {
+        self.future.store(future);
+
+        let idle = self
+            .state
+            .compare_exchange(RUNNING, IDLE, Ordering::SeqCst, Ordering::SeqCst);
+        if idle.is_err() {
+            self.state.store(SCHEDULED, Ordering::SeqCst);
+            if !self.schedule() {
+                error!("Error when waking up request")
+            }
+        }
+    }
+
+    /// Mark the future of the task as completed
+    pub(crate) fn complete(&self) {
+        self.state.store(COMPLETE, Ordering::SeqCst);
+    }
+
+    /// Poll the future of the task with its locals set as the current ones
+    pub(crate) fn poll(&self, future: &mut BoxFuture<'static, ()>, cx: &mut Context) -> Poll<()> {
+        task_local::enter(&self.locals, || future.as_mut().poll(cx))
+    }
+
     pub(crate) fn notify(&self) {
         if let Some(ref queue) = self.notify_queue {
             if queue.send(()).is_err() {
@@ -58,7 +179,6 @@ impl Task {
 }
 
 pub fn new_executor_and_spawner() -> (Executor, Spawner) {
-    const MAX_QUEUED_TASKS: usize = 10_000;
     let (task_sender, ready_queue) = global_injector();
     (Executor { ready_queue }, Spawner { task_sender })
 }
@@ -66,11 +186,7 @@ pub fn new_executor_and_spawner() -> (Executor, Spawner) {
 impl Spawner {
     pub fn spawn(&self, future: impl Future<Output = ()> + 'static + Send) {
         let future = future.boxed();
-        let task = Arc::new(Task {
-            future: AtomicTake::from(future),
-            task_sender: self.task_sender.clone(),
-            notify_queue: None,
-        });
+        let task = Arc::new(Task::new(future, self.task_sender.clone(), None));
         if self.task_sender.send(ExecutorMessage::Task(task)).is_err() {
             error!("Error when spawning request");
         }
@@ -85,12 +201,24 @@ impl Spawner {
 
 impl ArcWake for Task {
     fn wake_by_ref(arc_self: &Arc<Self>) {
-        let cloned = arc_self.clone();
-        if arc_self
-            .task_sender
-            .send(ExecutorMessage::Task(cloned))
-            .is_err()
-        {
+        let state = &arc_self.state;
+        let mut current = state.load(Ordering::SeqCst);
+
+        loop {
+            let woken = match current {
+                IDLE => SCHEDULED,
+                // The worker polling the task queues it again once done
+                RUNNING => NOTIFIED,
+                _ => return,
+            };
+
+            match state.compare_exchange(current, woken, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+
+        if current == IDLE && !arc_self.schedule() {
             error!("Error when waking up request")
         }
     }
@@ -101,7 +229,7 @@ impl Executor {
         while let Ok(ExecutorMessage::Task(task)) = self.ready_queue.recv() {
             // Take the future, and if it has not yet completed (is still Some),
             // poll it in an attempt to complete it.
-            let future_slot = task.future.take();
+            let future_slot = task.start_poll();
             if let Some(mut future) = future_slot {
                 // Create a `LocalWaker` from the task itself
                 let waker = waker_ref(&task);
@@ -110,10 +238,12 @@ impl Executor {
                 // `Pin<Box<dyn Future<Output = T> + Send + 'static>>`.
                 // We can get a `Pin<&mut dyn Future + Send + 'static>`
                 // from it by calling the `Pin::as_mut` method.
-                if let Poll::Pending = future.as_mut().poll(context) {
+                if task.poll(&mut future, context).is_pending() {
                     // We're not done processing the future, so put it
                     // back in its task to be run again in the future.
-                    task.future.store(future);
+                    task.end_poll(future);
+                } else {
+                    task.complete();
                 }
             }
         }
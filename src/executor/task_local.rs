@@ -0,0 +1,204 @@
+//! Values attached to the task currently executed by the server.
+//!
+//! A value set while handling a request stays available every time the task is polled again,
+//! even if it is resumed on another worker thread. Outside of a task nothing can be stored.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::{task_local, AIOServer, RequestId, ResponseBuilder};
+//!
+//! let server = AIOServer::new("127.0.0.1:7886".parse().unwrap(), |_| {
+//!     // The server stores the identifier of the request being handled
+//!     let id = task_local::get::<RequestId>().unwrap();
+//!
+//!     ResponseBuilder::empty_200()
+//!         .body(id.to_string().as_bytes())
+//!         .build()
+//!         .unwrap()
+//! });
+//! ```
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+thread_local! {
+    static CURRENT : RefCell<Option<Arc<TaskLocals>>> = RefCell::from(None);
+}
+
+/// Storage owned by a task
+#[derive(Default)]
+pub(crate) struct TaskLocals {
+    values: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+/// Locals replaced by [`enter`], put back when the guard is dropped even if the closure panics
+struct Restore(Option<Arc<TaskLocals>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| current.replace(previous));
+    }
+}
+
+/// Execute the closure with the given locals as the current ones
+pub(crate) fn enter<R, F>(locals: &Arc<TaskLocals>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _restore = Restore(CURRENT.with(|current| current.replace(Some(locals.clone()))));
+    f()
+}
+
+/// Return the locals of the current task, to [`enter`] them from another thread
+pub(crate) fn current() -> Option<Arc<TaskLocals>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Wrap the future so it is polled with the locals of the current task, even once spawned as another task.
+/// Both tasks then share the same values.
+pub(crate) fn inherit<F>(future: F) -> impl Future<Output = F::Output>
+where
+    F: Future,
+{
+    let locals = current();
+    let mut future = Box::pin(future);
+
+    futures::future::poll_fn(move |cx| match locals {
+        Some(ref locals) => enter(locals, || future.as_mut().poll(cx)),
+        None => future.as_mut().poll(cx),
+    })
+}
+
+fn with_current<R, F>(f: F) -> Option<R>
+where
+    F: FnOnce(&mut HashMap<TypeId, Box<dyn Any + Send>>) -> R,
+{
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|locals| f(&mut locals.values.lock().unwrap()))
+    })
+}
+
+/// Store a value in the current task, replacing the previous value of the same type.
+/// Return false if called outside of a task.
+pub fn set<T: Send + 'static>(value: T) -> bool {
+    with_current(|values| values.insert(TypeId::of::<T>(), Box::new(value))).is_some()
+}
+
+/// Return a copy of the value of the given type stored in the current task
+pub fn get<T: Clone + Send + 'static>() -> Option<T> {
+    with_current(|values| {
+        values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    })
+    .flatten()
+}
+
+/// Remove the value of the given type from the current task and return it
+pub fn remove<T: Send + 'static>() -> Option<T> {
+    with_current(|values| {
+        values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast::<T>().ok())
+            .map(|value| *value)
+    })
+    .flatten()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::executor::thread_pool::ThreadPoolBuilder;
+
+    use futures::channel::oneshot;
+    use std::sync::mpsc;
+
+    #[test]
+    fn outside_task() {
+        assert!(!set(3));
+        assert_eq!(get::<i32>(), None);
+    }
+
+    #[test]
+    fn enter_scope() {
+        let locals = Arc::new(TaskLocals::default());
+
+        enter(&locals, || assert!(set(String::from("value"))));
+        assert_eq!(get::<String>(), None);
+
+        enter(&locals, || {
+            assert_eq!(get::<String>().unwrap(), "value");
+            assert_eq!(remove::<String>().unwrap(), "value");
+            assert_eq!(get::<String>(), None);
+        });
+    }
+
+    #[test]
+    fn restored_after_panic() {
+        let outer = Arc::new(TaskLocals::default());
+        let inner = Arc::new(TaskLocals::default());
+
+        enter(&outer, || {
+            set(1u8);
+            let result = std::panic::catch_unwind(|| enter(&inner, || panic!("The task panicked")));
+            assert!(result.is_err());
+            assert_eq!(get::<u8>(), Some(1));
+        });
+        assert_eq!(get::<u8>(), None);
+    }
+
+    #[test]
+    fn across_polls() {
+        let pool = ThreadPoolBuilder::new().size(4).build();
+        let (wake_sender, wake_receiver) = oneshot::channel::<()>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        pool.spawn(async move {
+            set(42u32);
+            wake_receiver.await.unwrap();
+            result_sender.send(get::<u32>()).unwrap();
+        })
+        .unwrap();
+
+        wake_sender.send(()).unwrap();
+        let value = result_receiver
+            .recv_timeout(std::time::Duration::from_secs(1))
+            .unwrap();
+        pool.stop().unwrap();
+
+        assert_eq!(value, Some(42));
+    }
+
+    #[test]
+    fn inherited() {
+        let pool = ThreadPoolBuilder::new().size(2).build();
+        let locals = Arc::new(TaskLocals::default());
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let task = enter(&locals, || {
+            set(42u32);
+            inherit(async move {
+                result_sender.send(get::<u32>()).unwrap();
+                set(String::from("child"));
+            })
+        });
+        pool.spawn(task).unwrap();
+
+        let value = result_receiver
+            .recv_timeout(std::time::Duration::from_secs(1))
+            .unwrap();
+        pool.stop().unwrap();
+
+        assert_eq!(value, Some(42));
+        enter(&locals, || assert_eq!(get::<String>().unwrap(), "child"));
+    }
+}
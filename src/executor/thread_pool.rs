@@ -1,18 +1,25 @@
+use std::any::Any;
 use std::future::Future;
-use std::sync::Arc;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use futures::FutureExt;
 
 use std::sync::mpsc;
 
-use crate::data::AtomicTake;
-use crate::data::{global_injector, Receiver, Sender};
-use crate::executor::worker::Worker;
+use crate::data::{
+    global_injector, InjectorReceiver, InjectorSender, LocalQueue, QueueStealer, Receiver, Sender,
+    TakeCell, WorkQueue,
+};
+use crate::executor::instrument::Instrumentation;
+use crate::executor::worker::{PinnedQueue, Worker};
 use crate::executor::ExecutorMessage;
 use crate::executor::Task;
 use crate::io::context;
 
-use log::trace;
+use log::{error, trace, warn};
 
 type Result = std::result::Result<(), PoolError>;
 
@@ -22,12 +29,27 @@ pub(crate) enum PoolError {
     Join,
     Block,
     Stop,
+    Resize,
 }
 
+/// Delay before a panicked worker is replaced
+const RESTART_BACKOFF: Duration = Duration::from_millis(10);
+/// Longest delay before a panicked worker is replaced
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+/// A panic following the previous one within this duration doubles the delay before the replacement
+const RESTART_STREAK: Duration = Duration::from_secs(5);
+
+/// Stealers of the local queues of the running workers, identified by the worker id
+pub(crate) type Stealers = Arc<RwLock<Vec<(usize, QueueStealer<Arc<Task>>)>>>;
+
 pub(crate) struct ThreadPoolBuilder {
     size: usize,
     start: Arc<dyn Fn(usize, PoolHandle) + Send + Sync + 'static>,
     stop: Arc<dyn Fn(usize) + Send + Sync + 'static>,
+    instrumentation: Option<Instrumentation>,
+    name: Option<String>,
+    stack_size: Option<usize>,
+    affinity: bool,
 }
 
 impl ThreadPoolBuilder {
@@ -40,6 +62,10 @@ impl ThreadPoolBuilder {
             stop: Arc::from(|id| {
                 trace!("Stopping thread {}", id);
             }),
+            instrumentation: None,
+            name: None,
+            stack_size: None,
+            affinity: false,
         }
     }
 
@@ -64,42 +90,92 @@ impl ThreadPoolBuilder {
         self
     }
 
+    /// Time the polls made by the workers, see [`Instrumentation`]
+    pub(crate) fn instrument(mut self, instrumentation: Instrumentation) -> Self {
+        self.instrumentation = Some(instrumentation);
+        self
+    }
+
+    /// Name the worker threads "{prefix}-{index}"
+    pub(crate) fn name(mut self, prefix: &str) -> Self {
+        self.name = Some(prefix.to_string());
+        self
+    }
+
+    /// Set the stack size of the worker threads in bytes
+    pub(crate) fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// Pin each worker thread to a core, the worker of index i runs on the core i modulo the number of cores
+    pub(crate) fn affinity(mut self, affinity: bool) -> Self {
+        self.affinity = affinity;
+        self
+    }
+
     pub(crate) fn build(self) -> PoolHandle {
         let (sender, ready_queue) = global_injector();
         let (handle_sender, handle_receiver) = global_injector();
+        let size = self.size;
 
         let handle = PoolHandle {
-            sender: sender.clone(),
+            sender,
+            receiver: Arc::new(Mutex::from(Some(ready_queue))),
             handles: handle_receiver,
+            shared: Arc::new(PoolShared {
+                builder: self,
+                handle_sender,
+                stealers: Arc::new(RwLock::new(Vec::new())),
+                pinned: RwLock::new(Vec::new()),
+                size: Mutex::from(0),
+                next_id: AtomicUsize::new(0),
+                restarts: AtomicUsize::new(0),
+                last_panic: Mutex::from(None),
+            }),
         };
 
-        for i in 0..self.size {
-            let ready_queue = ready_queue.clone();
-            let start = self.start.clone();
-            let stop = self.stop.clone();
-            let handle = handle.clone();
-            let worker = Worker::new(sender.clone(), ready_queue);
+        handle
+            .resize(size)
+            .expect("Issue when starting thread pool");
 
-            let handle = std::thread::spawn(move || {
-                (start)(i, handle);
-                context::set_worker(worker.clone());
+        handle
+    }
+}
 
-                worker.run();
+/// State needed to start new workers after the pool is built
+struct PoolShared {
+    builder: ThreadPoolBuilder,
+    handle_sender: Sender<std::thread::JoinHandle<()>>,
+    stealers: Stealers,
+    /// Queues of the tasks pinned to the running workers, in the order the workers were started
+    pinned: RwLock<Vec<(usize, Arc<PinnedQueue>)>>,
+    size: Mutex<usize>,
+    next_id: AtomicUsize,
+    restarts: AtomicUsize,
+    /// Time of the last worker panic and the number of panics that closely preceded it
+    last_panic: Mutex<Option<(Instant, u32)>>,
+}
 
-                (stop)(i);
-            });
-            handle_sender
-                .send(handle)
-                .expect("Issue when starting thread pool");
+/// Pin the current thread to the core of the given index, modulo the number of cores
+pub(crate) fn pin_current_thread(index: usize) {
+    match core_affinity::get_core_ids() {
+        Some(cores) if !cores.is_empty() => {
+            core_affinity::set_for_current(cores[index % cores.len()]);
         }
-
-        handle
+        _ => warn!(
+            "Could not retrieve the cores, thread {} is not pinned",
+            index
+        ),
     }
 }
+
 #[derive(Clone)]
 pub(crate) struct PoolHandle {
     sender: Sender<ExecutorMessage>,
+    receiver: Arc<Mutex<Option<Receiver<ExecutorMessage>>>>,
     handles: Receiver<std::thread::JoinHandle<()>>,
+    shared: Arc<PoolShared>,
 }
 
 impl PoolHandle {
@@ -108,11 +184,21 @@ impl PoolHandle {
         F: Future<Output = ()> + Send + 'static,
     {
         let future = future.boxed();
-        let task = Arc::new(Task {
-            future: AtomicTake::from(future),
-            task_sender: self.sender.clone(),
-            notify_queue: None,
-        });
+        let task = Arc::new(Task::new(future, self.sender.clone(), None));
+
+        match self.sender.send(ExecutorMessage::Task(task)) {
+            Ok(_) => Result::Ok(()),
+            Err(_) => Result::Err(PoolError::Spawn),
+        }
+    }
+
+    /// Spawn a task the pool may drop with [`shed_oldest`](#method.shed_oldest) until it is first polled,
+    /// such as a new connection
+    pub(crate) fn spawn_sheddable<F>(&self, future: F) -> Result
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let task = Arc::new(Task::new(future.boxed(), self.sender.clone(), None).sheddable());
 
         match self.sender.send(ExecutorMessage::Task(task)) {
             Ok(_) => Result::Ok(()),
@@ -120,6 +206,29 @@ impl PoolHandle {
         }
     }
 
+    /// Spawn a task pinned to the worker of the given index, modulo the number of workers.
+    /// The task and its wake ups are only handled by this worker, unless it is retired.
+    pub(crate) fn spawn_on<F>(&self, index: usize, future: F) -> Result
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let home = {
+            let pinned = self.shared.pinned.read().unwrap();
+            if pinned.is_empty() {
+                return Err(PoolError::Spawn);
+            }
+            pinned[index % pinned.len()].1.clone()
+        };
+
+        let task = Arc::new(Task::new(future.boxed(), self.sender.clone(), None).pinned(home));
+
+        if task.schedule() {
+            Ok(())
+        } else {
+            Err(PoolError::Spawn)
+        }
+    }
+
     pub(crate) fn block_on<F>(&self, future: F) -> Result
     where
         F: Future<Output = ()> + Send + 'static,
@@ -128,11 +237,7 @@ impl PoolHandle {
 
         let (sender, receiver) = mpsc::sync_channel(1);
 
-        let task = Arc::new(Task {
-            future: AtomicTake::from(future),
-            task_sender: self.sender.clone(),
-            notify_queue: Some(sender),
-        });
+        let task = Arc::new(Task::new(future, self.sender.clone(), Some(sender)));
 
         if self.sender.send(ExecutorMessage::Task(task)).is_err() {
             return Result::Err(PoolError::Spawn);
@@ -145,12 +250,209 @@ impl PoolHandle {
         Result::Ok(())
     }
 
+    /// Return the number of tasks waiting in the global queue
+    pub(crate) fn queued(&self) -> usize {
+        self.sender.len()
+    }
+
+    /// Drop the future of the oldest task spawned with [`spawn_sheddable`](#method.spawn_sheddable) still waiting
+    /// in the global queue for its first poll, to make room for a new one. The other tasks, such as woken connections
+    /// or background jobs, are kept. Return false if no task could be dropped.
+    pub(crate) fn shed_oldest(&self) -> bool {
+        let receiver = match self.receiver.lock().unwrap().clone() {
+            Some(receiver) => receiver,
+            None => return false,
+        };
+        let mut controls = Vec::new();
+        let mut tasks = Vec::new();
+        let mut shed = false;
+
+        // The whole queue is taken so the control messages can be sent back ahead of the tasks
+        while let Ok(message) = receiver.try_recv() {
+            match message {
+                ExecutorMessage::Task(task) if !shed && task.take_sheddable() => {
+                    match task.future.take() {
+                        Some(future) => {
+                            drop(future);
+                            shed = true;
+                        }
+                        None => tasks.push(ExecutorMessage::Task(task)),
+                    }
+                }
+                ExecutorMessage::Task(task) => tasks.push(ExecutorMessage::Task(task)),
+                control => controls.push(control),
+            }
+        }
+
+        for message in controls.into_iter().chain(tasks) {
+            if self.sender.send(message).is_err() {
+                return false;
+            }
+        }
+
+        shed
+    }
+
+    /// Return the number of workers the pool is running, retiring workers excluded
+    pub(crate) fn size(&self) -> usize {
+        *self.shared.size.lock().unwrap()
+    }
+
+    /// Start or retire workers until the pool has the given size.
+    /// A retiring worker ends once it has no task left to execute, its queued tasks are given back to the pool.
+    pub(crate) fn resize(&self, size: usize) -> Result {
+        if size == 0 {
+            return Err(PoolError::Resize);
+        }
+
+        let mut current = self.shared.size.lock().unwrap();
+
+        while *current > size {
+            if self.sender.send(ExecutorMessage::Retire).is_err() {
+                return Err(PoolError::Resize);
+            }
+            *current -= 1;
+        }
+
+        while *current < size {
+            self.start_worker()?;
+            *current += 1;
+        }
+
+        Ok(())
+    }
+
+    fn start_worker(&self) -> Result {
+        let ready_queue = match *self.receiver.lock().unwrap() {
+            Some(ref receiver) => receiver.clone(),
+            None => return Err(PoolError::Resize),
+        };
+        let builder = &self.shared.builder;
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let local = LocalQueue::new();
+        self.shared
+            .stealers
+            .write()
+            .unwrap()
+            .push((id, local.stealer()));
+        let pinned = Arc::new(PinnedQueue::new());
+        self.shared
+            .pinned
+            .write()
+            .unwrap()
+            .push((id, pinned.clone()));
+
+        let stealers = self.shared.stealers.clone();
+        let sender = self.sender.clone();
+        let instrumentation = builder.instrumentation.clone();
+        let start = builder.start.clone();
+        let stop = builder.stop.clone();
+        let handle = self.clone();
+        let affinity = builder.affinity;
+
+        let mut thread = std::thread::Builder::new();
+        if let Some(ref prefix) = builder.name {
+            thread = thread.name(format!("{}-{}", prefix, id));
+        }
+        if let Some(size) = builder.stack_size {
+            thread = thread.stack_size(size);
+        }
+
+        let thread = thread
+            .spawn(move || {
+                if affinity {
+                    pin_current_thread(id);
+                }
+
+                let worker = Worker::new(
+                    id,
+                    local,
+                    pinned,
+                    stealers,
+                    sender,
+                    ready_queue,
+                    instrumentation,
+                );
+
+                (start)(id, handle.clone());
+                context::set_worker(worker.clone());
+
+                let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| worker.run())).err();
+                if panicked.is_some() {
+                    worker.abandon();
+                }
+                handle
+                    .shared
+                    .pinned
+                    .write()
+                    .unwrap()
+                    .retain(|(worker, _)| *worker != id);
+
+                (stop)(id);
+
+                if let Some(payload) = panicked {
+                    handle.replace_worker(id, payload);
+                }
+            })
+            .map_err(|_| PoolError::Resize)?;
+
+        self.shared
+            .handle_sender
+            .send(thread)
+            .map_err(|_| PoolError::Resize)
+    }
+
+    /// Start a worker in place of the one whose thread panicked, after a delay growing with the panics
+    /// in a row so a worker panicking right away does not spin. The pool keeps its size.
+    fn replace_worker(&self, id: usize, payload: Box<dyn Any + Send>) {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message,
+            None => payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .unwrap_or("unknown payload"),
+        };
+
+        let delay = {
+            let mut last_panic = self.shared.last_panic.lock().unwrap();
+            let streak = match *last_panic {
+                Some((at, streak)) if at.elapsed() < RESTART_STREAK => streak + 1,
+                _ => 0,
+            };
+            *last_panic = Some((Instant::now(), streak));
+            (RESTART_BACKOFF * 2u32.pow(streak.min(10))).min(MAX_RESTART_BACKOFF)
+        };
+        error!(
+            "Worker {} panicked : {}, restarting it in {:?}",
+            id, message, delay
+        );
+
+        std::thread::sleep(delay);
+        self.shared.restarts.fetch_add(1, Ordering::Relaxed);
+        // The pool is stopping
+        if self.start_worker().is_err() {
+            self.shared.restarts.fetch_sub(1, Ordering::Relaxed);
+            warn!("Could not restart worker {}", id);
+        }
+    }
+
+    /// Return the number of workers started in place of a worker whose thread panicked
+    pub(crate) fn restarts(&self) -> usize {
+        self.shared.restarts.load(Ordering::Relaxed)
+    }
+
     pub(crate) fn stop(&self) -> Result {
         if self.handles.is_empty() {
             return Err(PoolError::Stop);
         }
 
-        for _ in 0..self.handles.len() {
+        // Once the workers are gone nothing may be queued anymore
+        self.receiver.lock().unwrap().take();
+
+        // Retiring workers are already counted by the retire messages left in the queue
+        let size = std::mem::replace(&mut *self.shared.size.lock().unwrap(), 0);
+        for _ in 0..size {
             if self.sender.send(ExecutorMessage::Stop).is_err() {
                 return Err(PoolError::Stop);
             }
@@ -185,6 +487,28 @@ mod test {
         assert_eq!(receiver.try_recv().unwrap(), 3);
     }
 
+    #[test]
+    fn thread_options() {
+        let pool = ThreadPoolBuilder::new()
+            .size(2)
+            .name("pool-test")
+            .stack_size(1 << 20)
+            .affinity(true)
+            .build();
+
+        let (sender, receiver) = mpsc::channel();
+
+        pool.block_on(async move {
+            let name = std::thread::current().name().map(String::from);
+            sender.send(name).unwrap();
+        })
+        .unwrap();
+        pool.stop().unwrap();
+
+        let name = receiver.try_recv().unwrap().unwrap();
+        assert!(name.starts_with("pool-test-"));
+    }
+
     #[test]
     fn spawn() {
         let size = 20;
@@ -260,6 +584,182 @@ mod test {
         }
     }
 
+    #[test]
+    fn shed_oldest() {
+        let pool = ThreadPoolBuilder::new().size(1).build();
+        let (sender, receiver) = mpsc::sync_channel(0);
+
+        // Keep the only worker busy so the following tasks stay queued
+        pool.spawn(async move {
+            receiver.recv().unwrap();
+        })
+        .unwrap();
+
+        let (dropped_sender, dropped) = mpsc::channel::<()>();
+        pool.spawn_sheddable(async move {
+            let _sender = dropped_sender;
+        })
+        .unwrap();
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(pool.queued(), 1);
+
+        assert!(pool.shed_oldest());
+        assert_eq!(dropped.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+        assert!(!pool.shed_oldest());
+
+        sender.send(()).unwrap();
+        pool.stop().unwrap();
+    }
+
+    #[test]
+    fn shed_keeps_control_messages_first() {
+        let pool = ThreadPoolBuilder::new().size(1).build();
+        let (sender, receiver) = mpsc::sync_channel(0);
+
+        // Keep the only worker busy so the following messages stay queued
+        pool.spawn(async move {
+            receiver.recv().unwrap();
+        })
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+
+        pool.spawn_sheddable(async {}).unwrap();
+        pool.spawn_sheddable(async {}).unwrap();
+        assert!(pool.sender.send(ExecutorMessage::Retire).is_ok());
+
+        assert!(pool.shed_oldest());
+        assert_eq!(pool.queued(), 2);
+
+        let queue = pool.receiver.lock().unwrap().clone().unwrap();
+        assert!(matches!(queue.try_recv(), Ok(ExecutorMessage::Retire)));
+        assert!(matches!(queue.try_recv(), Ok(ExecutorMessage::Task(_))));
+
+        sender.send(()).unwrap();
+        pool.stop().unwrap();
+    }
+
+    #[test]
+    fn shed_keeps_other_tasks() {
+        let pool = ThreadPoolBuilder::new().size(1).build();
+        let (sender, receiver) = mpsc::sync_channel(0);
+
+        // Keep the only worker busy so the following tasks stay queued
+        pool.spawn(async move {
+            receiver.recv().unwrap();
+        })
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+
+        // A background job queued before the connection
+        let (job_sender, job) = mpsc::channel();
+        pool.spawn(async move {
+            job_sender.send(()).unwrap();
+        })
+        .unwrap();
+        let (dropped_sender, dropped) = mpsc::channel::<()>();
+        pool.spawn_sheddable(async move {
+            let _sender = dropped_sender;
+        })
+        .unwrap();
+
+        assert!(pool.shed_oldest());
+        assert_eq!(dropped.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+        assert!(!pool.shed_oldest());
+
+        sender.send(()).unwrap();
+        assert!(job.recv_timeout(Duration::from_secs(5)).is_ok());
+        pool.stop().unwrap();
+    }
+
+    #[test]
+    fn resize() {
+        let (stop_sender, stopped) = mpsc::channel();
+        let pool = ThreadPoolBuilder::new()
+            .size(2)
+            .before_stop(move |id| stop_sender.send(id).unwrap())
+            .build();
+
+        pool.resize(4).unwrap();
+        assert_eq!(pool.size(), 4);
+
+        pool.resize(1).unwrap();
+        assert_eq!(pool.size(), 1);
+        for _ in 0..3 {
+            stopped.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+
+        // The remaining worker still executes the tasks
+        let (sender, receiver) = mpsc::channel();
+        for i in 0..10 {
+            let sender = sender.clone();
+            pool.spawn(async move { sender.send(i).unwrap() }).unwrap();
+        }
+        for _ in 0..10 {
+            receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+
+        assert!(pool.resize(0).is_err());
+        pool.stop().unwrap();
+        stopped.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(pool.resize(2).is_err());
+    }
+
+    #[test]
+    fn spawn_on() {
+        let pool = ThreadPoolBuilder::new().size(3).name("pinned").build();
+
+        let (sender, receiver) = mpsc::channel();
+        for i in 0..30 {
+            let sender = sender.clone();
+            pool.spawn_on(i, async move {
+                for _ in 0..3 {
+                    let name = std::thread::current().name().map(String::from);
+                    sender.send((i % 3, name)).unwrap();
+                    crate::executor::yield_now::yield_now().await;
+                }
+            })
+            .unwrap();
+        }
+
+        let mut threads = std::collections::HashMap::new();
+        for _ in 0..90 {
+            let (index, name) = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(*threads.entry(index).or_insert_with(|| name.clone()), name);
+        }
+        assert_eq!(threads.len(), 3);
+
+        // The tasks pinned to a retired worker are handled by the remaining ones
+        pool.resize(1).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        for i in 0..3 {
+            let sender = sender.clone();
+            pool.spawn_on(i, async move { sender.send(i).unwrap() })
+                .unwrap();
+        }
+        for _ in 0..3 {
+            receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+
+        pool.stop().unwrap();
+    }
+
+    #[test]
+    fn restart_panicked_worker() {
+        let pool = ThreadPoolBuilder::new().size(1).build();
+
+        pool.spawn(async { panic!("worker failure") }).unwrap();
+
+        // The task spawned after the panic is executed by the new worker
+        let (sender, receiver) = mpsc::channel();
+        pool.spawn(async move { sender.send(()).unwrap() }).unwrap();
+        receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        assert_eq!(pool.restarts(), 1);
+        assert_eq!(pool.size(), 1);
+        pool.stop().unwrap();
+    }
+
     #[test]
     fn double_stop() {
         let size = 20;
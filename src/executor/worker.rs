@@ -2,32 +2,82 @@ use futures::task::waker_ref;
 use futures::FutureExt;
 use std::future::Future;
 use std::task::Context;
-use std::task::Poll;
 
-use std::sync::Arc;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 
 use log::error;
 
-use crate::data::AtomicTake;
-use crate::data::{LocalQueue, QueueError, Receiver, Sender};
+use crate::data::{
+    global_injector, InjectorError, InjectorReceiver, InjectorSender, LocalQueue, Receiver, Sender,
+    StealQueue, WorkQueue,
+};
+use crate::executor::instrument::Instrumentation;
+use crate::executor::thread_pool::Stealers;
 use crate::executor::{ExecutorMessage, Task};
 
+/// Queue of the tasks pinned to a worker, they can not be stolen by the other workers.
+/// It is closed when the worker ends and its tasks are given back to the global queue.
+pub(crate) struct PinnedQueue {
+    sender: RwLock<Option<Sender<ExecutorMessage>>>,
+    receiver: Receiver<ExecutorMessage>,
+}
+
+impl PinnedQueue {
+    pub(crate) fn new() -> PinnedQueue {
+        let (sender, receiver) = global_injector();
+
+        PinnedQueue {
+            sender: RwLock::new(Some(sender)),
+            receiver,
+        }
+    }
+
+    /// Queue a message for the worker, it is given back once the queue is closed
+    pub(crate) fn send(
+        &self,
+        message: ExecutorMessage,
+    ) -> Result<(), InjectorError<ExecutorMessage>> {
+        match *self.sender.read().unwrap() {
+            Some(ref sender) => sender.send(message),
+            None => Err(InjectorError::Send(message)),
+        }
+    }
+
+    fn close(&self) {
+        self.sender.write().unwrap().take();
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct Worker {
-    local: Arc<LocalQueue<Arc<Task>>>,
+    id: usize,
+    local: Rc<LocalQueue<Arc<Task>>>,
+    pinned: Arc<PinnedQueue>,
+    siblings: Stealers,
     global_sender: Sender<ExecutorMessage>,
     global_receiver: Receiver<ExecutorMessage>,
+    instrumentation: Option<Instrumentation>,
 }
 
 impl Worker {
     pub(crate) fn new(
+        id: usize,
+        local: LocalQueue<Arc<Task>>,
+        pinned: Arc<PinnedQueue>,
+        siblings: Stealers,
         sender: Sender<ExecutorMessage>,
         receiver: Receiver<ExecutorMessage>,
+        instrumentation: Option<Instrumentation>,
     ) -> Worker {
         Worker {
-            local: Arc::from(LocalQueue::new()),
+            id,
+            local: Rc::new(local),
+            pinned,
+            siblings,
             global_sender: sender,
             global_receiver: receiver,
+            instrumentation,
         }
     }
 
@@ -35,49 +85,98 @@ impl Worker {
     where
         F: Future<Output = ()> + 'static + Send,
     {
-        let task = Arc::new(Task {
-            future: AtomicTake::from(future.boxed()),
-            task_sender: self.global_sender.clone(),
-            notify_queue: None,
-        });
+        let task = Arc::new(Task::new(future.boxed(), self.global_sender.clone(), None));
 
-        if let Err(QueueError::Push(task)) = self.local.push(task) {
-            if self
-                .global_sender
-                .send(ExecutorMessage::Task(task))
-                .is_err()
-            {
-                error!("Could not push task onto the global queue")
-            }
-        }
+        self.local.push(task);
     }
 
     pub(crate) fn run(&self) {
         while let Some(task) = self.pop_task() {
-            let future_slot = task.future.take();
+            let future_slot = task.start_poll();
             if let Some(mut future) = future_slot {
                 let waker = waker_ref(&task);
                 let context = &mut Context::from_waker(&*waker);
 
-                if let Poll::Pending = future.as_mut().poll(context) {
-                    task.future.store(future);
+                let poll = match self.instrumentation {
+                    Some(ref instrumentation) => instrumentation.poll(&task, &mut future, context),
+                    None => task.poll(&mut future, context),
+                };
+
+                if poll.is_pending() {
+                    task.end_poll(future);
                 } else {
+                    task.complete();
                     task.notify();
                 }
             }
         }
+
+        self.leave();
+    }
+
+    /// Give back the tasks of a worker whose thread panicked, the task it was polling is lost
+    pub(crate) fn abandon(&self) {
+        self.retire();
+        self.leave();
     }
 
+    /// Stop stealing from the worker and give back the tasks pinned to it
+    fn leave(&self) {
+        self.siblings
+            .write()
+            .unwrap()
+            .retain(|(id, _)| *id != self.id);
+
+        // Once closed no task can be pinned to the worker anymore, the queued ones go to the other workers
+        self.pinned.close();
+        while let Ok(message) = self.pinned.receiver.try_recv() {
+            if self.global_sender.send(message).is_err() {
+                error!("Could not give back a task pinned to an ending worker");
+            }
+        }
+    }
+
+    /// Give the tasks of the local queue back to the pool before the worker ends
+    fn retire(&self) {
+        while let Ok(task) = self.local.pop() {
+            if self
+                .global_sender
+                .send(ExecutorMessage::Task(task))
+                .is_err()
+            {
+                error!("Could not give back a task of a retiring worker");
+            }
+        }
+    }
+
+    /// Take the oldest local task, then the oldest task pinned to the worker, otherwise steal one
+    /// from another worker before waiting on the global queue and on the pinned tasks
     fn pop_task(&self) -> Option<Arc<Task>> {
-        match self.local.pop() {
-            Ok(task) => Some(task),
-            Err(_) => {
-                if let Ok(ExecutorMessage::Task(task)) = self.global_receiver.recv() {
-                    Some(task)
-                } else {
-                    None
-                }
+        if let Ok(task) = self.local.pop() {
+            return Some(task);
+        }
+
+        if let Ok(ExecutorMessage::Task(task)) = self.pinned.receiver.try_recv() {
+            return Some(task);
+        }
+
+        if let Some(task) = self
+            .siblings
+            .read()
+            .unwrap()
+            .iter()
+            .find_map(|(_, stealer)| stealer.steal().ok())
+        {
+            return Some(task);
+        }
+
+        match self.global_receiver.recv_either(&self.pinned.receiver) {
+            Ok(ExecutorMessage::Task(task)) => Some(task),
+            Ok(ExecutorMessage::Retire) => {
+                self.retire();
+                None
             }
+            _ => None,
         }
     }
 }
@@ -0,0 +1,119 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Give the worker back to the other tasks once before continuing.
+/// The task is woken immediately and queued behind the tasks already waiting to run.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::yield_now;
+///
+/// async fn long_computation() {
+///     for _ in 0..1000 {
+///         // Some work ...
+///         yield_now().await;
+///     }
+/// }
+/// ```
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// Future returned by [`yield_now`]
+///
+/// [`yield_now`]: fn.yield_now.html
+#[must_use = "futures do nothing unless polled"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::executor::thread_pool::ThreadPoolBuilder;
+
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn pending_once() {
+        let mut future = yield_now();
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut future).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn interleave_tasks() {
+        let pool = ThreadPoolBuilder::new().size(1).build();
+        let order = Arc::new(Mutex::from(Vec::new()));
+        let (sender, receiver) = mpsc::channel();
+
+        let tasks = order.clone();
+        pool.block_on(async move {
+            for id in 1..=2 {
+                let order = tasks.clone();
+                let sender = sender.clone();
+                crate::io::context::spawn(async move {
+                    for _ in 0..3 {
+                        order.lock().unwrap().push(id);
+                        yield_now().await;
+                    }
+                    sender.send(()).unwrap();
+                });
+            }
+        })
+        .unwrap();
+
+        for _ in 0..2 {
+            receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+        pool.stop().unwrap();
+
+        assert_eq!(*order.lock().unwrap(), vec![1, 2, 1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn yield_multiple_workers() {
+        let pool = ThreadPoolBuilder::new().size(4).build();
+        let (sender, receiver) = mpsc::channel();
+
+        pool.spawn(async move {
+            for _ in 0..10 {
+                let mut yielded = yield_now();
+                futures::future::poll_fn(|cx| match Pin::new(&mut yielded).poll(cx) {
+                    Poll::Pending => {
+                        // The task is already queued again while this worker still polls it
+                        std::thread::sleep(Duration::from_millis(10));
+                        Poll::Pending
+                    }
+                    ready => ready,
+                })
+                .await;
+            }
+            sender.send(()).unwrap();
+        })
+        .unwrap();
+
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        pool.stop().unwrap();
+    }
+}
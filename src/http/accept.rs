@@ -0,0 +1,135 @@
+/// A media range of the Accept header, such as `text/*;q=0.8`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MediaRange {
+    media_type: String,
+    subtype: String,
+    quality: f32,
+}
+
+impl MediaRange {
+    fn parse(range: &str) -> Option<MediaRange> {
+        let mut params = range.split(';');
+        let (media_type, subtype) = params.next()?.trim().split_once('/')?;
+
+        let quality = params
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+            .filter(|quality| (0.0..=1.0).contains(quality))
+            .unwrap_or(1.0);
+
+        Some(MediaRange {
+            media_type: media_type.trim().to_ascii_lowercase(),
+            subtype: subtype.trim().to_ascii_lowercase(),
+            quality,
+        })
+    }
+
+    /// Return how precisely the range matches the media type: 2 for the exact type,
+    /// 1 for `type/*`, 0 for `*/*` and None if it does not match
+    fn precision(&self, media_type: &str, subtype: &str) -> Option<u8> {
+        match (self.media_type.as_str(), self.subtype.as_str()) {
+            ("*", "*") => Some(0),
+            (range_type, "*") if range_type == media_type => Some(1),
+            (range_type, range_subtype) if range_type == media_type && range_subtype == subtype => {
+                Some(2)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse the value of an Accept header, the invalid ranges are left out
+pub(crate) fn parse_accept(value: &str) -> Vec<MediaRange> {
+    value.split(',').filter_map(MediaRange::parse).collect()
+}
+
+/// Return the quality given to the media type by the most precise matching range
+/// along with the precision of the match, None if no range matches
+pub(crate) fn quality(ranges: &[MediaRange], media: &str) -> Option<(f32, u8)> {
+    let media = media
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    let (media_type, subtype) = media.split_once('/')?;
+
+    ranges
+        .iter()
+        .filter_map(|range| {
+            range
+                .precision(media_type, subtype)
+                .map(|precision| (range.quality, precision))
+        })
+        .max_by_key(|(_, precision)| *precision)
+}
+
+/// Pick the media type the client prefers among the offered ones.
+/// Without Accept header the first offer is returned, the offers not accepted are never returned.
+/// When several offers have the same quality, the first one wins.
+pub(crate) fn negotiate<'a>(accept: Option<&str>, offers: &[&'a str]) -> Option<&'a str> {
+    let ranges = match accept {
+        Some(accept) => parse_accept(accept),
+        None => return offers.first().cloned(),
+    };
+
+    let mut best: Option<(&str, f32)> = None;
+    for offer in offers {
+        let quality = match quality(&ranges, offer) {
+            Some((quality, _)) if quality > 0.0 => quality,
+            _ => continue,
+        };
+
+        match best {
+            Some((_, best)) if quality <= best => {}
+            _ => best = Some((offer, quality)),
+        }
+    }
+
+    best.map(|(offer, _)| offer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_ranges() {
+        let ranges = parse_accept("text/html, application/json;q=0.5, invalid, */*;q=0.1");
+
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[1].subtype, "json");
+        assert_eq!(ranges[1].quality, 0.5);
+    }
+
+    #[test]
+    fn most_precise_quality() {
+        let ranges = parse_accept("text/*;q=0.3, text/html;q=0.7, */*;q=0.1");
+
+        assert_eq!(quality(&ranges, "text/html"), Some((0.7, 2)));
+        assert_eq!(
+            quality(&ranges, "text/plain; charset=utf-8"),
+            Some((0.3, 1))
+        );
+        assert_eq!(quality(&ranges, "image/png"), Some((0.1, 0)));
+        assert_eq!(quality(&parse_accept("text/html"), "image/png"), None);
+    }
+
+    #[test]
+    fn negotiate_offers() {
+        let offers = ["application/json", "text/html"];
+
+        assert_eq!(negotiate(None, &offers), Some("application/json"));
+        assert_eq!(
+            negotiate(Some("text/html, application/json;q=0.9"), &offers),
+            Some("text/html")
+        );
+        assert_eq!(negotiate(Some("*/*"), &offers), Some("application/json"));
+        assert_eq!(
+            negotiate(Some("application/json;q=0, text/*"), &offers),
+            Some("text/html")
+        );
+        assert_eq!(negotiate(Some("image/png"), &offers), None);
+    }
+}
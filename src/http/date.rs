@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+thread_local! {
+    /// Current second and its formatted date, each worker formats it at most once per second
+    static NOW: RefCell<Option<(u64, String)>> = RefCell::from(None);
+}
+
+/// Call the function with the current time formatted as an HTTP date, for the "Date" header of the responses.
+/// The date is formatted again only once the second changed.
+pub(crate) fn with_http_date_now<R>(f: impl FnOnce(&str) -> R) -> R {
+    let now = SystemTime::now();
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+
+    NOW.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        match &*cached {
+            Some((second, _)) if *second == secs => {}
+            _ => *cached = Some((secs, format_http_date(now))),
+        }
+
+        f(cached.as_ref().map_or("", |(_, date)| date))
+    })
+}
+
+/// Format a time as an HTTP date (IMF-fixdate), for instance "Sun, 06 Nov 1994 08:49:37 GMT".
+/// Times before the epoch are formatted as the epoch.
+pub(crate) fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+
+    let days = secs / 86400;
+    let (year, month, day) = civil_from_days(days as i64);
+    let seconds = secs % 86400;
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Convert a number of days since the epoch to a (year, month, day) date of the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn format_date() {
+        assert_eq!(
+            format_http_date(UNIX_EPOCH),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        let time = UNIX_EPOCH + Duration::from_secs(951782400);
+        assert_eq!(format_http_date(time), "Tue, 29 Feb 2000 00:00:00 GMT");
+    }
+
+    #[test]
+    fn cached_date() {
+        let before = format_http_date(SystemTime::now());
+        let first = with_http_date_now(|date| date.to_string());
+        let second = with_http_date_now(|date| date.to_string());
+        let after = format_http_date(SystemTime::now());
+
+        assert!(first == before || first == after);
+        assert!(second == first || second == after);
+    }
+}
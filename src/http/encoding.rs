@@ -0,0 +1,164 @@
+//! Percent-encoding of the strings put in URLs and headers.
+//!
+//! User supplied strings must be encoded before being inserted in a path, a query string or a `Location`
+//! header, otherwise a '/' or a '?' changes the meaning of the URL and a line break injects headers.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::encoding;
+//!
+//! let name = "Tom & Jerry/2";
+//! let url = format!(
+//!     "/shows/{}?title={}",
+//!     encoding::encode_path_segment(name),
+//!     encoding::encode_query_value(name)
+//! );
+//! assert_eq!(url, "/shows/Tom%20&%20Jerry%2F2?title=Tom%20%26%20Jerry/2");
+//!
+//! assert_eq!(encoding::location("/next\r\nSet-Cookie: a=b"), "/next%0D%0ASet-Cookie:%20a=b");
+//! ```
+
+use std::fmt::Write;
+
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~".contains(&byte)
+}
+
+fn is_sub_delim(byte: u8) -> bool {
+    b"!$&'()*+,;=".contains(&byte)
+}
+
+/// Percent-encode the bytes for which `keep` returns false
+fn encode(input: &str, keep: impl Fn(u8) -> bool) -> String {
+    let mut encoded = String::with_capacity(input.len());
+
+    for &byte in input.as_bytes() {
+        if keep(byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+
+    encoded
+}
+
+/// Encode a string to be used as one segment of a path, '/' and '?' included
+pub fn encode_path_segment(segment: &str) -> String {
+    encode(segment, |byte| {
+        is_unreserved(byte) || is_sub_delim(byte) || byte == b':' || byte == b'@'
+    })
+}
+
+/// Encode a string to be used as a name or a value of a query string, '&', '=' and '+' included
+pub fn encode_query_value(value: &str) -> String {
+    encode(value, |byte| {
+        is_unreserved(byte) || b"!$'()*,;:@/?".contains(&byte)
+    })
+}
+
+/// Encode name and value pairs as an "application/x-www-form-urlencoded" body or query string,
+/// the spaces are encoded as '+'
+pub fn encode_form(pairs: &[(&str, &str)]) -> String {
+    let component = |input: &str| {
+        encode(input, |byte| {
+            byte.is_ascii_alphanumeric() || b"*-._ ".contains(&byte)
+        })
+        .replace(' ', "+")
+    };
+
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}={}", component(name), component(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Make a URL safe to send in a `Location` header.
+/// The characters that can not appear in a URL, line breaks and spaces included, are encoded,
+/// the delimiters and the existing escapes are kept so a valid URL is left unchanged.
+pub fn location(url: &str) -> String {
+    encode(url, |byte| {
+        byte.is_ascii_graphic() && !b"\"<>\\^`{|}".contains(&byte)
+    })
+}
+
+/// Decode the percent-encoded bytes of a string.
+/// None is returned if an escape is invalid or the result is not UTF-8.
+pub fn decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Decode an "application/x-www-form-urlencoded" body or query string in name and value pairs,
+/// None is returned if one of them can not be decoded
+pub fn decode_form(input: &str) -> Option<Vec<(String, String)>> {
+    let component = |input: &str| decode(&input.replace('+', " "));
+
+    input
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((component(name)?, component(value)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_components() {
+        assert_eq!(encode_path_segment("a b/c?d#e%"), "a%20b%2Fc%3Fd%23e%25");
+        assert_eq!(encode_path_segment("été"), "%C3%A9t%C3%A9");
+        assert_eq!(encode_query_value("a=b&c+d"), "a%3Db%26c%2Bd");
+        assert_eq!(
+            encode_form(&[("name", "Tom & Jerry"), ("q", "1+1=2")]),
+            "name=Tom+%26+Jerry&q=1%2B1%3D2"
+        );
+    }
+
+    #[test]
+    fn safe_location() {
+        let url = "https://example.com/a%20b?c=d&e=f#top";
+        assert_eq!(location(url), url);
+        assert_eq!(location("/a b\r\n<x>"), "/a%20b%0D%0A%3Cx%3E");
+    }
+
+    #[test]
+    fn decode_components() {
+        assert_eq!(decode("a%20b%2Fc").unwrap(), "a b/c");
+        assert_eq!(decode(&encode_path_segment("été / ?")).unwrap(), "été / ?");
+        assert!(decode("a%2").is_none());
+        assert!(decode("%zz").is_none());
+        assert!(decode("%FF").is_none());
+
+        assert_eq!(
+            decode_form("name=Tom+%26+Jerry&empty&q=1%2B1").unwrap(),
+            vec![
+                (String::from("name"), String::from("Tom & Jerry")),
+                (String::from("empty"), String::new()),
+                (String::from("q"), String::from("1+1")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+macro_rules! standard_headers {
+    ($($variant:ident => $name:literal,)+) => {
+        /// Name of an HTTP header.
+        /// The common names are variants of the enum so they are stored and compared without allocation,
+        /// the other ones are kept lowercase in `Custom`.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use mini_async_http::HeaderName;
+        ///
+        /// assert_eq!(HeaderName::from("Content-Type"), HeaderName::ContentType);
+        /// assert_eq!(HeaderName::from("X-Custom").as_str(), "x-custom");
+        /// ```
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum HeaderName {
+            $(
+                #[doc = $name]
+                $variant,
+            )+
+            /// Any other header, the name has to be lowercase to match the parsed headers
+            Custom(String),
+        }
+
+        const STANDARD_HEADERS: &[(&str, HeaderName)] = &[
+            $(($name, HeaderName::$variant),)+
+        ];
+
+        impl HeaderName {
+            /// Return the lowercase name of the header
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(HeaderName::$variant => $name,)+
+                    HeaderName::Custom(name) => name,
+                }
+            }
+        }
+    };
+}
+
+standard_headers! {
+    Accept => "accept",
+    AcceptCharset => "accept-charset",
+    AcceptEncoding => "accept-encoding",
+    AcceptLanguage => "accept-language",
+    AcceptRanges => "accept-ranges",
+    Authorization => "authorization",
+    CacheControl => "cache-control",
+    Connection => "connection",
+    ContentDisposition => "content-disposition",
+    ContentEncoding => "content-encoding",
+    ContentLanguage => "content-language",
+    ContentLength => "content-length",
+    ContentLocation => "content-location",
+    ContentRange => "content-range",
+    ContentType => "content-type",
+    Cookie => "cookie",
+    Date => "date",
+    ETag => "etag",
+    Expect => "expect",
+    Expires => "expires",
+    Forwarded => "forwarded",
+    Host => "host",
+    IfMatch => "if-match",
+    IfModifiedSince => "if-modified-since",
+    IfNoneMatch => "if-none-match",
+    IfRange => "if-range",
+    IfUnmodifiedSince => "if-unmodified-since",
+    KeepAlive => "keep-alive",
+    LastModified => "last-modified",
+    Location => "location",
+    Origin => "origin",
+    Pragma => "pragma",
+    Range => "range",
+    Referer => "referer",
+    RetryAfter => "retry-after",
+    Server => "server",
+    SetCookie => "set-cookie",
+    Te => "te",
+    Trailer => "trailer",
+    TransferEncoding => "transfer-encoding",
+    Upgrade => "upgrade",
+    UserAgent => "user-agent",
+    Vary => "vary",
+    Via => "via",
+    WwwAuthenticate => "www-authenticate",
+    XForwardedFor => "x-forwarded-for",
+    XForwardedProto => "x-forwarded-proto",
+    XRequestId => "x-request-id",
+}
+
+impl HeaderName {
+    /// Return the name as a String, the name of a custom header is returned without copy
+    pub fn into_string(self) -> String {
+        match self {
+            HeaderName::Custom(name) => name,
+            name => name.as_str().to_string(),
+        }
+    }
+}
+
+impl From<&str> for HeaderName {
+    /// Return the variant of a standard header without allocating, whatever the case of the name
+    fn from(name: &str) -> Self {
+        STANDARD_HEADERS
+            .iter()
+            .find(|(standard, _)| {
+                standard.len() == name.len() && standard.eq_ignore_ascii_case(name)
+            })
+            .map(|(_, header)| header.clone())
+            .unwrap_or_else(|| HeaderName::Custom(name.to_ascii_lowercase()))
+    }
+}
+
+impl From<String> for HeaderName {
+    fn from(mut name: String) -> Self {
+        match STANDARD_HEADERS
+            .iter()
+            .find(|(standard, _)| standard.eq_ignore_ascii_case(&name))
+        {
+            Some((_, header)) => header.clone(),
+            None => {
+                name.make_ascii_lowercase();
+                HeaderName::Custom(name)
+            }
+        }
+    }
+}
+
+impl fmt::Display for HeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn standard_names() {
+        assert_eq!(
+            HeaderName::from("content-length"),
+            HeaderName::ContentLength
+        );
+        assert_eq!(HeaderName::from("HOST"), HeaderName::Host);
+        assert_eq!(HeaderName::ETag.as_str(), "etag");
+
+        for (name, header) in STANDARD_HEADERS {
+            assert_eq!(name, &header.as_str());
+            assert_eq!(name.to_ascii_lowercase(), *name);
+        }
+    }
+
+    #[test]
+    fn custom_names() {
+        let name = HeaderName::from("X-Custom-Header");
+
+        assert_eq!(name, HeaderName::Custom(String::from("x-custom-header")));
+        assert_eq!(name.to_string(), "x-custom-header");
+        assert_eq!(name.into_string(), "x-custom-header");
+    }
+}
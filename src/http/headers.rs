@@ -1,8 +1,22 @@
+use crate::data::{discard, ObjectPool};
+use crate::http::HeaderName;
+
+use std::cell::RefCell;
 use std::collections::hash_map;
 use std::collections::HashMap;
 
+/// Number of header maps kept by each worker thread
+const POOL_SIZE: usize = 256;
+/// Maps grown beyond this number of entries are not kept
+const MAX_POOLED_CAPACITY: usize = 128;
+
+thread_local! {
+    static MAPS: RefCell<ObjectPool<HashMap<HeaderName, String>>> = RefCell::new(ObjectPool::new(POOL_SIZE));
+}
+
 /// The HTTP header map.
-/// All the names are not case sensitive.
+/// All the names are not case sensitive, the common ones are stored as a [`HeaderName`] variant without allocation.
+/// The values are kept as given, as some of them like URLs, cookies or keys are case sensitive.
 ///
 /// # Example
 ///
@@ -17,9 +31,16 @@ use std::collections::HashMap;
 /// headers.set_header("Content-type","application/json");
 /// assert_eq!(headers.get_header("content-type").unwrap(),"application/json");
 /// ```
+///
+/// [`HeaderName`]: enum.HeaderName.html
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "HashMap<String, String>", into = "HashMap<String, String>")
+)]
 pub struct Headers {
-    map: HashMap<String, String>,
+    map: HashMap<HeaderName, String>,
 }
 
 impl Headers {
@@ -30,19 +51,95 @@ impl Headers {
         }
     }
 
+    /// Return an empty map able to hold `capacity` headers, reusing a recycled map of the thread when there is one
+    pub(crate) fn with_capacity(capacity: usize) -> Headers {
+        let map = match MAPS.with(|maps| maps.borrow_mut().take()) {
+            Some(mut map) => {
+                map.reserve(capacity);
+                map
+            }
+            None => HashMap::with_capacity(capacity),
+        };
+
+        Headers { map }
+    }
+
+    /// Give the map back to the pool of the thread once its request is answered
+    pub(crate) fn recycle(mut self) {
+        if self.map.capacity() > MAX_POOLED_CAPACITY {
+            discard();
+            return;
+        }
+
+        self.map.clear();
+        MAPS.with(|maps| maps.borrow_mut().give(self.map));
+    }
+
     /// Set the given header name to the given value. If the key already exists overwrite the value.
     pub fn set_header(&mut self, name: &str, value: &str) {
-        let name = name.to_ascii_lowercase();
-        let value = value.to_ascii_lowercase();
-
-        self.map.insert(name, value);
+        self.insert(HeaderName::from(name), value);
     }
 
     /// Retrieve the value at the given key
     pub fn get_header(&self, name: &str) -> Option<&String> {
-        let name = name.to_ascii_lowercase();
+        self.get(&HeaderName::from(name))
+    }
+
+    /// Remove the header and return its value
+    pub fn remove_header(&mut self, name: &str) -> Option<String> {
+        self.map.remove(&HeaderName::from(name))
+    }
+
+    /// Set the header to the given value, overwriting the previous one
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{HeaderName, Headers};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.insert(HeaderName::ContentType, "text/plain");
+    ///
+    /// assert_eq!(headers.get(&HeaderName::ContentType).unwrap(), "text/plain");
+    /// assert_eq!(headers.get_header("Content-Type").unwrap(), "text/plain");
+    /// ```
+    pub fn insert(&mut self, name: HeaderName, value: &str) {
+        self.map.insert(name, value.to_string());
+    }
 
-        self.map.get(&name)
+    /// Retrieve the value of the header, without converting the name
+    pub fn get(&self, name: &HeaderName) -> Option<&String> {
+        self.map.get(name)
+    }
+
+    /// Return true if the header is a comma separated list containing the token.
+    /// The tokens are compared without case, as for the Connection header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut headers = mini_async_http::Headers::new();
+    /// headers.set_header("Connection", "TE, Close");
+    ///
+    /// assert!(headers.has_token("connection", "close"));
+    /// assert!(!headers.has_token("connection", "keep-alive"));
+    /// ```
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        match self.get_header(name) {
+            Some(value) => value
+                .split(',')
+                .any(|item| item.trim().eq_ignore_ascii_case(token)),
+            None => false,
+        }
+    }
+
+    /// Add the headers of `defaults` which are not set yet, the values already set are kept
+    pub(crate) fn merge_defaults(&mut self, defaults: &Headers) {
+        for (name, value) in defaults.map.iter() {
+            if !self.map.contains_key(name) {
+                self.map.insert(name.clone(), value.clone());
+            }
+        }
     }
 
     /// Return an iterator over all the headers. All keys are lowercase
@@ -65,7 +162,7 @@ impl PartialEq for Headers {
 
         self.map
             .iter()
-            .map(|(key, value)| match other.get_header(key) {
+            .map(|(key, value)| match other.get(key) {
                 Some(val) => {
                     if val != value {
                         return false;
@@ -80,6 +177,24 @@ impl PartialEq for Headers {
     }
 }
 
+impl From<HashMap<String, String>> for Headers {
+    fn from(map: HashMap<String, String>) -> Self {
+        let mut headers = Headers::new();
+
+        for (name, value) in map.iter() {
+            headers.set_header(name, value);
+        }
+
+        headers
+    }
+}
+
+impl From<Headers> for HashMap<String, String> {
+    fn from(headers: Headers) -> Self {
+        headers.into_iter().collect()
+    }
+}
+
 impl Default for Headers {
     fn default() -> Self {
         Headers::new()
@@ -88,22 +203,29 @@ impl Default for Headers {
 
 impl IntoIterator for Headers {
     type Item = (String, String);
-    type IntoIter = hash_map::IntoIter<String, String>;
+    type IntoIter = std::iter::Map<
+        hash_map::IntoIter<HeaderName, String>,
+        fn((HeaderName, String)) -> (String, String),
+    >;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.map.into_iter()
+        self.map
+            .into_iter()
+            .map(|(name, value)| (name.into_string(), value))
     }
 }
 
 pub struct HeaderIterator<'a> {
-    inner: hash_map::Iter<'a, String, String>,
+    inner: hash_map::Iter<'a, HeaderName, String>,
 }
 
 impl<'a> Iterator for HeaderIterator<'a> {
-    type Item = (&'a String, &'a String);
+    type Item = (&'a str, &'a String);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next()
+        self.inner
+            .next()
+            .map(|(name, value)| (name.as_str(), value))
     }
 }
 
@@ -162,6 +284,29 @@ mod test {
         assert_ne!(a, b)
     }
 
+    #[test]
+    fn from_map() {
+        let mut map = HashMap::new();
+        map.insert(String::from("Content-Type"), String::from("text/plain"));
+
+        let headers = Headers::from(map);
+
+        assert_eq!(headers.get_header("content-type").unwrap(), "text/plain");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_map() {
+        let mut headers = Headers::new();
+        headers.set_header("Content-Type", "text/plain");
+
+        let json = serde_json::to_string(&headers).unwrap();
+        assert_eq!(json, r#"{"content-type":"text/plain"}"#);
+
+        let headers: Headers = serde_json::from_str(r#"{"Accept":"*/*"}"#).unwrap();
+        assert_eq!(headers.get_header("accept").unwrap(), "*/*");
+    }
+
     #[test]
     fn not_eq_val() {
         let mut a = Headers::new();
@@ -0,0 +1,270 @@
+//! Conversions with the types of the [`http`](https://docs.rs/http) crate, enabled with the `http` feature.
+//!
+//! Conversions from the `http` types can not fail, except for the requests as their target must also be a
+//! valid [`Uri`] for this crate. Conversions to the `http` types are fallible as the crate types do not
+//! validate header names, header values and extension methods.
+//!
+//! [`Uri`]: struct.Uri.html
+
+use crate::http::{BuildError, Headers, Method, Version};
+use crate::request::{Request, RequestBuilder};
+use crate::response::{Response, ResponseBuilder};
+
+use log::warn;
+
+use std::convert::TryFrom;
+
+impl From<::http::Method> for Method {
+    fn from(method: ::http::Method) -> Self {
+        match method.as_str().parse() {
+            Ok(method) => method,
+            Err(_) => Method::Extension(String::from(method.as_str())),
+        }
+    }
+}
+
+impl TryFrom<Method> for ::http::Method {
+    type Error = ::http::Error;
+
+    fn try_from(method: Method) -> Result<Self, Self::Error> {
+        Ok(::http::Method::from_bytes(method.as_str().as_bytes())?)
+    }
+}
+
+impl From<::http::Version> for Version {
+    fn from(version: ::http::Version) -> Self {
+        match version {
+            ::http::Version::HTTP_09 | ::http::Version::HTTP_10 => Version::HTTP10,
+            _ => Version::HTTP11,
+        }
+    }
+}
+
+impl From<Version> for ::http::Version {
+    fn from(version: Version) -> Self {
+        match version {
+            Version::HTTP10 => ::http::Version::HTTP_10,
+            Version::HTTP11 => ::http::Version::HTTP_11,
+        }
+    }
+}
+
+/// The values of a repeated header are joined with ", " as the crate holds one value per name.
+/// Set-Cookie values can not be joined, only the last one is kept and the others are dropped with a warning.
+impl From<::http::HeaderMap> for Headers {
+    fn from(map: ::http::HeaderMap) -> Self {
+        let mut headers = Headers::new();
+
+        for name in map.keys() {
+            let values: Vec<_> = map
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                .collect();
+
+            let value = if name == ::http::header::SET_COOKIE {
+                if values.len() > 1 {
+                    warn!(
+                        "Only the last of the {} Set-Cookie headers is kept",
+                        values.len()
+                    );
+                }
+                values
+                    .last()
+                    .map(|value| value.to_string())
+                    .unwrap_or_default()
+            } else {
+                values.join(", ")
+            };
+
+            headers.set_header(name.as_str(), &value);
+        }
+
+        headers
+    }
+}
+
+impl TryFrom<Headers> for ::http::HeaderMap {
+    type Error = ::http::Error;
+
+    fn try_from(headers: Headers) -> Result<Self, Self::Error> {
+        let mut map = ::http::HeaderMap::with_capacity(headers.iter().len());
+
+        for (name, value) in headers {
+            let name = ::http::header::HeaderName::from_bytes(name.as_bytes())?;
+            let value = ::http::header::HeaderValue::from_str(&value)?;
+            map.insert(name, value);
+        }
+
+        Ok(map)
+    }
+}
+
+/// The conversion fails if the path is not a valid [`Uri`], for example with an invalid percent escape
+///
+/// [`Uri`]: struct.Uri.html
+impl TryFrom<::http::Request<Vec<u8>>> for Request {
+    type Error = BuildError;
+
+    fn try_from(request: ::http::Request<Vec<u8>>) -> Result<Self, Self::Error> {
+        let (parts, body) = request.into_parts();
+
+        let path = match parts.uri.path_and_query() {
+            Some(path) => String::from(path.as_str()),
+            None => String::from("/"),
+        };
+
+        let mut builder = RequestBuilder::new()
+            .method(Method::from(parts.method))
+            .path(path)
+            .version(Version::from(parts.version))
+            .headers(Headers::from(parts.headers));
+
+        if !body.is_empty() {
+            builder = builder.body(&body);
+        }
+
+        builder.build()
+    }
+}
+
+impl TryFrom<Request> for ::http::Request<Vec<u8>> {
+    type Error = ::http::Error;
+
+    fn try_from(request: Request) -> Result<Self, Self::Error> {
+        let mut builder = ::http::Request::builder()
+            .method(::http::Method::try_from(request.method().clone())?)
+            .uri(request.path().as_str())
+            .version(::http::Version::from(request.version().clone()));
+
+        if let Some(headers) = builder.headers_mut() {
+            *headers = ::http::HeaderMap::try_from(request.headers().clone())?;
+        }
+
+        builder.body(request.body().cloned().unwrap_or_default())
+    }
+}
+
+impl From<::http::Response<Vec<u8>>> for Response {
+    fn from(response: ::http::Response<Vec<u8>>) -> Self {
+        let (parts, body) = response.into_parts();
+
+        let reason = parts.status.canonical_reason().unwrap_or_default();
+        let mut builder = ResponseBuilder::new()
+            .code(i32::from(parts.status.as_u16()))
+            .reason(String::from(reason))
+            .version(Version::from(parts.version))
+            .headers(Headers::from(parts.headers));
+
+        if !body.is_empty() {
+            builder = builder.body(&body);
+        }
+
+        builder.build().unwrap()
+    }
+}
+
+impl TryFrom<Response> for ::http::Response<Vec<u8>> {
+    type Error = ::http::Error;
+
+    fn try_from(response: Response) -> Result<Self, Self::Error> {
+        // A file body is read in memory as the converted response can not stream it
+        let file = response.file_body().and_then(|file| file.read_all().ok());
+        let mut builder = ::http::Response::builder()
+            .status(response.code as u16)
+            .version(::http::Version::from(response.version));
+
+        if let Some(headers) = builder.headers_mut() {
+            *headers = ::http::HeaderMap::try_from(response.headers)?;
+        }
+
+        builder.body(response.body.or(file).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn method() {
+        assert_eq!(Method::from(::http::Method::PATCH), Method::PATCH);
+        assert_eq!(
+            ::http::Method::try_from(Method::Extension(String::from("PROPFIND"))).unwrap(),
+            "PROPFIND"
+        );
+        assert!(::http::Method::try_from(Method::Extension(String::from("BAD METHOD"))).is_err());
+    }
+
+    #[test]
+    fn repeated_headers() {
+        let mut map = ::http::HeaderMap::new();
+        map.append("Accept", "text/html".parse().unwrap());
+        map.append("Accept", "application/json".parse().unwrap());
+        map.append("Set-Cookie", "a=1".parse().unwrap());
+        map.append("Set-Cookie", "b=2".parse().unwrap());
+
+        let headers = Headers::from(map);
+        assert_eq!(
+            headers.get_header("Accept").unwrap(),
+            "text/html, application/json"
+        );
+        assert_eq!(headers.get_header("Set-Cookie").unwrap(), "b=2");
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let mut headers = Headers::new();
+        headers.set_header("Content-Type", "text/plain");
+
+        let request = RequestBuilder::new()
+            .method(Method::POST)
+            .path(String::from("/path?query=value"))
+            .version(Version::HTTP11)
+            .headers(headers)
+            .body(b"body")
+            .build()
+            .unwrap();
+
+        let converted = ::http::Request::<Vec<u8>>::try_from(request).unwrap();
+        assert_eq!(converted.method(), ::http::Method::POST);
+        assert_eq!(converted.uri().query(), Some("query=value"));
+        assert_eq!(converted.headers()["content-type"], "text/plain");
+        assert_eq!(converted.body(), b"body");
+
+        let back = Request::try_from(converted).unwrap();
+        assert_eq!(back.path(), "/path?query=value");
+        assert_eq!(back.body().unwrap(), b"body");
+        assert_eq!(
+            back.headers().get_header("content-type").unwrap(),
+            "text/plain"
+        );
+    }
+
+    #[test]
+    fn invalid_request_path() {
+        let request = ::http::Request::get("/a%zz").body(Vec::new()).unwrap();
+
+        assert!(matches!(
+            Request::try_from(request),
+            Err(BuildError::InvalidUri)
+        ));
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let response = ResponseBuilder::empty_404()
+            .body(b"missing")
+            .build()
+            .unwrap();
+
+        let converted = ::http::Response::<Vec<u8>>::try_from(response).unwrap();
+        assert_eq!(converted.status(), ::http::StatusCode::NOT_FOUND);
+        assert_eq!(converted.headers()["content-length"], "7");
+
+        let back = Response::from(converted);
+        assert_eq!(back.code(), 404);
+        assert_eq!(back.reason(), "Not Found");
+        assert_eq!(back.body().unwrap(), b"missing");
+    }
+}
@@ -1,33 +1,63 @@
 use std::str::FromStr;
 
+/// HTTP request method.
+///
+/// Methods not defined by the HTTP specification (WebDAV, custom methods...) are represented by
+/// the `Extension` variant as long as they are valid tokens.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Method {
     GET,
+    HEAD,
     POST,
     PUT,
     DELETE,
+    CONNECT,
+    OPTIONS,
+    TRACE,
+    PATCH,
+    Extension(String),
 }
 
 impl Method {
     pub fn as_str(&self) -> &str {
         match self {
             Method::GET => "GET",
+            Method::HEAD => "HEAD",
             Method::POST => "POST",
             Method::PUT => "PUT",
             Method::DELETE => "DELETE",
+            Method::CONNECT => "CONNECT",
+            Method::OPTIONS => "OPTIONS",
+            Method::TRACE => "TRACE",
+            Method::PATCH => "PATCH",
+            Method::Extension(method) => method.as_str(),
         }
     }
 }
 
+/// Check that the given char is allowed in an HTTP token (RFC 7230 section 3.2.6)
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
 impl FromStr for Method {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "GET" => Ok(Method::GET),
+            "HEAD" => Ok(Method::HEAD),
             "POST" => Ok(Method::POST),
-            "DELETE" => Ok(Method::DELETE),
             "PUT" => Ok(Method::PUT),
+            "DELETE" => Ok(Method::DELETE),
+            "CONNECT" => Ok(Method::CONNECT),
+            "OPTIONS" => Ok(Method::OPTIONS),
+            "TRACE" => Ok(Method::TRACE),
+            "PATCH" => Ok(Method::PATCH),
+            _ if !s.is_empty() && s.chars().all(is_token_char) => {
+                Ok(Method::Extension(String::from(s)))
+            }
             _ => Err(()),
         }
     }
@@ -43,5 +73,27 @@ mod test {
         assert_eq!(Method::PUT.as_str(), "PUT");
         assert_eq!(Method::DELETE.as_str(), "DELETE");
         assert_eq!(Method::POST.as_str(), "POST");
+        assert_eq!(Method::PATCH.as_str(), "PATCH");
+        assert_eq!(
+            Method::Extension(String::from("PROPFIND")).as_str(),
+            "PROPFIND"
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Method::from_str("TRACE").unwrap(), Method::TRACE);
+        assert_eq!(Method::from_str("CONNECT").unwrap(), Method::CONNECT);
+        assert_eq!(
+            Method::from_str("MKCOL").unwrap(),
+            Method::Extension(String::from("MKCOL"))
+        );
+    }
+
+    #[test]
+    fn from_str_invalid() {
+        assert!(Method::from_str("").is_err());
+        assert!(Method::from_str("GE T").is_err());
+        assert!(Method::from_str("GET\r\n").is_err());
     }
 }
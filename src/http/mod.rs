@@ -1,14 +1,24 @@
+pub(crate) mod accept;
+pub(crate) mod date;
+pub mod encoding;
+mod header_name;
 mod headers;
+#[cfg(feature = "http")]
+mod interop;
 mod method;
 pub(crate) mod parser;
+mod uri;
 mod version;
 
+pub use header_name::HeaderName;
 pub use headers::Headers;
 pub use method::Method;
 pub use parser::BuildError;
+pub use uri::Uri;
 pub use version::Version;
 
 pub(crate) mod header {
     pub const CONNECTION_HEADER: &str = "Connection";
     pub const CLOSE_CONNECTION_HEADER: &str = "close";
+    pub const KEEP_ALIVE_CONNECTION_HEADER: &str = "keep-alive";
 }
@@ -1,8 +1,22 @@
 use std::convert::From;
+use std::fmt;
 
 #[derive(Debug)]
 pub enum BuildError {
     Incomplete,
+    /// The target of the request is not a valid [`Uri`]
+    ///
+    /// [`Uri`]: struct.Uri.html
+    InvalidUri,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Incomplete => f.write_str("incomplete request"),
+            BuildError::InvalidUri => f.write_str("invalid request target"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -16,7 +30,39 @@ pub enum ParseError {
     Status,
     Token,
     TooManyHeaders,
+    /// The request line and the headers are larger than the limit of the server
+    HeadTooLarge,
     Version,
+    /// The request has both Transfer-Encoding and Content-Length headers, or Content-Length values that differ,
+    /// so its body could be delimited differently by a proxy in front of the server
+    ConflictingLength,
+    /// The body is sent with a Transfer-Encoding, which is not supported
+    TransferEncoding,
+    /// A header value is continued on the next line, obsolete line folding is refused
+    ObsoleteFold,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ParseError::UnexpectedEnd => "unexpected end of the request",
+            ParseError::BuilderError(e) => return e.fmt(f),
+            ParseError::LengthParse => "invalid Content-Length header",
+            ParseError::HeaderName => "invalid header name",
+            ParseError::HeaderValue => "invalid header value",
+            ParseError::NewLine => "invalid new line",
+            ParseError::Status => "invalid status",
+            ParseError::Token => "invalid token",
+            ParseError::TooManyHeaders => "too many headers",
+            ParseError::HeadTooLarge => "request line and headers too large",
+            ParseError::Version => "invalid HTTP version",
+            ParseError::ConflictingLength => "conflicting body length headers",
+            ParseError::TransferEncoding => "unsupported Transfer-Encoding",
+            ParseError::ObsoleteFold => "obsolete line folding in the headers",
+        };
+
+        f.write_str(message)
+    }
 }
 
 impl From<httparse::Error> for ParseError {
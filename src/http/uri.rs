@@ -0,0 +1,357 @@
+use crate::http::parser::BuildError;
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
+/// Target of a request, split in its components and validated when the request is parsed or built.
+/// The four forms of request targets are supported: a path like `/users?id=1`, an absolute URL sent to
+/// proxies like `http://example.com/users`, an authority like `example.com:443` for CONNECT requests
+/// and `*` for server wide OPTIONS requests.
+///
+/// The components are returned as received, they are decoded with [`encoding::decode`].
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::Uri;
+/// use std::convert::TryFrom;
+///
+/// let uri = Uri::try_from("http://example.com/users/42?fields=name&sort").unwrap();
+///
+/// assert_eq!(uri.scheme(), Some("http"));
+/// assert_eq!(uri.authority(), Some("example.com"));
+/// assert_eq!(uri.path(), "/users/42");
+/// assert_eq!(uri.segments().collect::<Vec<_>>(), vec!["users", "42"]);
+/// assert_eq!(uri.query_pairs().collect::<Vec<_>>(), vec![("fields", "name"), ("sort", "")]);
+/// ```
+///
+/// [`encoding::decode`]: encoding/fn.decode.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct Uri {
+    target: String,
+    scheme: Option<Range<usize>>,
+    authority: Option<Range<usize>>,
+    path: Range<usize>,
+    query: Option<Range<usize>>,
+}
+
+impl Uri {
+    /// Return the scheme of an absolute URL
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.clone().map(|range| &self.target[range])
+    }
+
+    /// Return the host and port of an absolute URL or of the target of a CONNECT request
+    pub fn authority(&self) -> Option<&str> {
+        self.authority.clone().map(|range| &self.target[range])
+    }
+
+    /// Return the path, "/" for an absolute URL without path and empty for an authority
+    pub fn path(&self) -> &str {
+        let path = &self.target[self.path.clone()];
+
+        if path.is_empty() && self.scheme.is_some() {
+            "/"
+        } else {
+            path
+        }
+    }
+
+    /// Return the query string, the part after the '?'
+    pub fn query(&self) -> Option<&str> {
+        self.query.clone().map(|range| &self.target[range])
+    }
+
+    /// Return an iterator over the segments of the path, the empty ones are skipped
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.path().split('/').filter(|segment| !segment.is_empty())
+    }
+
+    /// Return an iterator over the name and value pairs of the query string.
+    /// A parameter without '=' has an empty value.
+    pub fn query_pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+    }
+
+    /// Return the target as received
+    pub fn as_str(&self) -> &str {
+        &self.target
+    }
+
+    pub(crate) fn as_string(&self) -> &String {
+        &self.target
+    }
+
+    fn parse(target: String) -> Option<Uri> {
+        // Fragments are never sent in a request target, other characters must be percent-encoded
+        if target.is_empty()
+            || target
+                .bytes()
+                .any(|b| b <= b' ' || b >= 0x7f || b == b'#' || b == b'\\')
+            || !valid_escapes(&target)
+        {
+            return None;
+        }
+
+        if target == "*" || target.starts_with('/') {
+            return Some(Uri::with_path(target, None, None, 0));
+        }
+
+        if let Some(end) = target.find("://") {
+            let mut scheme = target[..end].chars();
+            let valid_scheme = matches!(scheme.next(), Some(c) if c.is_ascii_alphabetic())
+                && scheme.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+            if !valid_scheme {
+                return None;
+            }
+
+            let start = end + 3;
+            let authority_end = target[start..]
+                .find(['/', '?'])
+                .map_or(target.len(), |i| start + i);
+            if authority_end == start {
+                return None;
+            }
+
+            return Some(Uri::with_path(
+                target,
+                Some(0..end),
+                Some(start..authority_end),
+                authority_end,
+            ));
+        }
+
+        // Authority form, as sent with CONNECT
+        if target.contains(['/', '?']) {
+            return None;
+        }
+        let len = target.len();
+
+        Some(Uri {
+            target,
+            scheme: None,
+            authority: Some(0..len),
+            path: len..len,
+            query: None,
+        })
+    }
+
+    /// Remove the dot segments of the path and merge its consecutive slashes if asked,
+    /// so every spelling of a path reaches the same route. A target without path is left as is.
+    pub(crate) fn normalize(&mut self, merge_slashes: bool) {
+        let path = &self.target[self.path.clone()];
+        if !path.starts_with('/') || !needs_normalization(path, merge_slashes) {
+            return;
+        }
+
+        let mut target = String::with_capacity(self.target.len());
+        target.push_str(&self.target[..self.path.start]);
+        target.push_str(&remove_dot_segments(path, merge_slashes));
+        target.push_str(&self.target[self.path.end..]);
+
+        let (scheme, authority) = (self.scheme.clone(), self.authority.clone());
+        *self = Uri::with_path(target, scheme, authority, self.path.start);
+    }
+
+    /// Split the part of the target starting at `start` in a path and a query
+    fn with_path(
+        target: String,
+        scheme: Option<Range<usize>>,
+        authority: Option<Range<usize>>,
+        start: usize,
+    ) -> Uri {
+        let (path, query) = match target[start..].find('?') {
+            Some(i) => (start..start + i, Some(start + i + 1..target.len())),
+            None => (start..target.len(), None),
+        };
+
+        Uri {
+            target,
+            scheme,
+            authority,
+            path,
+            query,
+        }
+    }
+}
+
+/// Check that every '%' starts an escape of two hexadecimal digits
+fn valid_escapes(target: &str) -> bool {
+    let bytes = target.as_bytes();
+
+    bytes.iter().enumerate().all(|(i, &b)| {
+        b != b'%'
+            || matches!(bytes.get(i + 1..i + 3), Some(hex) if hex.iter().all(u8::is_ascii_hexdigit))
+    })
+}
+
+fn needs_normalization(path: &str, merge_slashes: bool) -> bool {
+    path.contains("/.")
+        || path.contains("%2e")
+        || path.contains("%2E")
+        || (merge_slashes && path.contains("//"))
+}
+
+/// Return 1 for a "." segment and 2 for a ".." segment, their dots may be percent-encoded
+fn dot_segment(segment: &str) -> Option<usize> {
+    let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+
+    match segment.as_str() {
+        "." => Some(1),
+        ".." => Some(2),
+        _ => None,
+    }
+}
+
+/// Resolve the "." and ".." segments of a path starting with '/', a ".." at the root is dropped.
+/// The path keeps its trailing slash, also when it ends with a dot segment.
+fn remove_dot_segments(path: &str, merge_slashes: bool) -> String {
+    let segments: Vec<&str> = path[1..].split('/').collect();
+    let last = segments.len() - 1;
+    let mut output: Vec<&str> = Vec::with_capacity(segments.len());
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        match dot_segment(segment) {
+            Some(dots) => {
+                if dots == 2 {
+                    output.pop();
+                }
+                if i == last {
+                    output.push("");
+                }
+            }
+            None if segment.is_empty() && merge_slashes && i != last => {}
+            None => output.push(segment),
+        }
+    }
+
+    format!("/{}", output.join("/"))
+}
+
+impl TryFrom<String> for Uri {
+    type Error = BuildError;
+
+    fn try_from(target: String) -> Result<Self, Self::Error> {
+        Uri::parse(target).ok_or(BuildError::InvalidUri)
+    }
+}
+
+impl TryFrom<&str> for Uri {
+    type Error = BuildError;
+
+    fn try_from(target: &str) -> Result<Self, Self::Error> {
+        Uri::try_from(target.to_string())
+    }
+}
+
+impl From<Uri> for String {
+    fn from(uri: Uri) -> Self {
+        uri.target
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.target)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn uri(target: &str) -> Uri {
+        Uri::try_from(target).unwrap()
+    }
+
+    #[test]
+    fn origin_form() {
+        let uri = uri("/users//42/?id=1&&name=a=b");
+
+        assert_eq!(uri.scheme(), None);
+        assert_eq!(uri.authority(), None);
+        assert_eq!(uri.path(), "/users//42/");
+        assert_eq!(uri.segments().collect::<Vec<_>>(), vec!["users", "42"]);
+        assert_eq!(
+            uri.query_pairs().collect::<Vec<_>>(),
+            vec![("id", "1"), ("name", "a=b")]
+        );
+        assert_eq!(uri.as_str(), "/users//42/?id=1&&name=a=b");
+    }
+
+    #[test]
+    fn other_forms() {
+        let absolute = uri("https://example.com:8443?q");
+        assert_eq!(absolute.scheme(), Some("https"));
+        assert_eq!(absolute.authority(), Some("example.com:8443"));
+        assert_eq!(absolute.path(), "/");
+        assert_eq!(absolute.query(), Some("q"));
+
+        let authority = uri("example.com:443");
+        assert_eq!(authority.authority(), Some("example.com:443"));
+        assert_eq!(authority.path(), "");
+
+        assert_eq!(uri("*").path(), "*");
+    }
+
+    #[test]
+    fn normalize() {
+        let normalized = |target: &str, merge_slashes| {
+            let mut uri = uri(target);
+            uri.normalize(merge_slashes);
+            uri.as_str().to_string()
+        };
+
+        assert_eq!(normalized("/a/./b/../c?x=/../y", true), "/a/c?x=/../y");
+        assert_eq!(normalized("/a/b/..", true), "/a/");
+        assert_eq!(normalized("/../../a", true), "/a");
+        assert_eq!(normalized("/a/%2e%2E/b/.%2e", true), "/");
+        assert_eq!(normalized("//a//b/", true), "/a/b/");
+        assert_eq!(normalized("//a//b/", false), "//a//b/");
+        assert_eq!(normalized("/a//../b", false), "/a/b");
+        assert_eq!(normalized("/a/.b/c..", true), "/a/.b/c..");
+        assert_eq!(normalized("http://host/a/../b?q", true), "http://host/b?q");
+        assert_eq!(normalized("http://host", true), "http://host");
+        assert_eq!(normalized("*", true), "*");
+
+        let uri = {
+            let mut uri = uri("/a/../b?q=1");
+            uri.normalize(true);
+            uri
+        };
+        assert_eq!(uri.path(), "/b");
+        assert_eq!(uri.query(), Some("q=1"));
+    }
+
+    #[test]
+    fn invalid() {
+        for target in &[
+            "",
+            "/a b",
+            "/a#fragment",
+            "1http://host/",
+            "http:///path",
+            "host/path",
+            "/a\\b",
+            "/caf\u{e9}",
+            "/a%2",
+            "/a%zz",
+        ] {
+            assert!(
+                Uri::try_from(*target).is_err(),
+                "{} should be invalid",
+                target
+            );
+        }
+    }
+}
@@ -1,13 +1,19 @@
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+/// Version of the HTTP protocol of a request or a response.
+/// Other versions may be supported later, a `match` on it needs a wildcard arm.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Version {
+    HTTP10,
     HTTP11,
 }
 
 impl Version {
     pub fn as_str(&self) -> &str {
         match self {
+            Version::HTTP10 => "HTTP/1.0",
             Version::HTTP11 => "HTTP/1.1",
         }
     }
@@ -18,6 +24,7 @@ impl FromStr for Version {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "HTTP/1.0" => Ok(Version::HTTP10),
             "HTTP/1.1" => Ok(Version::HTTP11),
             _ => Err(()),
         }
@@ -30,6 +37,7 @@ mod test {
 
     #[test]
     fn as_str() {
+        assert_eq!(Version::HTTP10.as_str(), "HTTP/1.0");
         assert_eq!(Version::HTTP11.as_str(), "HTTP/1.1")
     }
 
@@ -37,8 +45,8 @@ mod test {
     fn from_str() {
         let version = Version::from_str("HTTP/1.1").unwrap();
 
-        match version {
-            Version::HTTP11 => {}
-        }
+        assert_eq!(version, Version::HTTP11);
+        assert_eq!(Version::from_str("HTTP/1.0").unwrap(), Version::HTTP10);
+        assert!(Version::from_str("HTTP/2").is_err());
     }
 }
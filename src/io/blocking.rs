@@ -0,0 +1,124 @@
+//! Threads running the blocking calls the tasks can not make on a worker, such as the handlers of the routes with a timeout.
+//!
+//! A thread is started when no thread is idle to take a call, up to `MAX_THREADS`, and stops after
+//! `IDLE_TIMEOUT` without work. Once every thread is busy the calls wait in a queue.
+
+use log::error;
+
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+pub(crate) const MAX_THREADS: usize = 64;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Job = Box<dyn Send + 'static + FnOnce()>;
+
+static POOL: Mutex<Pool> = Mutex::new(Pool {
+    queue: VecDeque::new(),
+    threads: 0,
+    idle: 0,
+});
+/// Signaled when a call is queued
+static QUEUED: Condvar = Condvar::new();
+
+struct Pool {
+    queue: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+/// Run the call on a blocking thread
+pub(crate) fn spawn<F>(call: F)
+where
+    F: Send + 'static + FnOnce(),
+{
+    let mut pool = POOL.lock().unwrap();
+    pool.queue.push_back(Box::new(call));
+
+    if pool.queue.len() <= pool.idle {
+        QUEUED.notify_one();
+        return;
+    }
+    if pool.threads >= MAX_THREADS {
+        return;
+    }
+
+    let started = std::thread::Builder::new()
+        .name(String::from("mini-async-http-blocking"))
+        .spawn(run);
+    match started {
+        Ok(_) => pool.threads += 1,
+        Err(e) => error!("Could not start a blocking thread : {}", e),
+    }
+}
+
+/// Return the number of blocking threads running
+pub(crate) fn threads() -> usize {
+    POOL.lock().unwrap().threads
+}
+
+fn run() {
+    let mut pool = POOL.lock().unwrap();
+
+    loop {
+        if let Some(call) = pool.queue.pop_front() {
+            drop(pool);
+            // A panicking call does not stop the thread
+            let _ = std::panic::catch_unwind(AssertUnwindSafe(call));
+            pool = POOL.lock().unwrap();
+            continue;
+        }
+
+        pool.idle += 1;
+        let (guard, waited) = QUEUED.wait_timeout(pool, IDLE_TIMEOUT).unwrap();
+        pool = guard;
+        pool.idle -= 1;
+
+        if waited.timed_out() && pool.queue.is_empty() {
+            pool.threads -= 1;
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn run_calls() {
+        let (sender, receiver) = mpsc::channel();
+
+        for i in 0..10 {
+            let sender = sender.clone();
+            spawn(move || sender.send(i).unwrap());
+        }
+
+        let mut values: Vec<i32> = receiver.iter().take(10).collect();
+        values.sort_unstable();
+        assert_eq!(values, (0..10).collect::<Vec<_>>());
+        assert!(threads() <= MAX_THREADS);
+    }
+
+    #[test]
+    fn bounded_threads() {
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..MAX_THREADS * 2 {
+            let sender = sender.clone();
+            spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                sender.send(()).unwrap();
+            });
+        }
+
+        assert!(threads() <= MAX_THREADS);
+        assert_eq!(
+            receiver.iter().take(MAX_THREADS * 2).count(),
+            MAX_THREADS * 2
+        );
+    }
+}
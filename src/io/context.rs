@@ -1,43 +1,132 @@
-use crate::executor::thread_pool::{PoolHandle, ThreadPoolBuilder};
+use crate::executor::instrument::Instrumentation;
+use crate::executor::thread_pool::{pin_current_thread, PoolHandle, ThreadPoolBuilder};
 use crate::executor::worker::Worker;
 use crate::io::reactor::Handle;
 use crate::io::reactor::Reactor;
 
+use log::error;
+
 use std::cell::RefCell;
 use std::future::Future;
+use std::thread::JoinHandle;
 
 thread_local! {
     static HANDLE : RefCell<Option<Handle>> = RefCell::from(None);
     static EXECUTOR : RefCell<Option<PoolHandle>> = RefCell::from(None);
+    static BULK : RefCell<Option<PoolHandle>> = RefCell::from(None);
     static WORKER : RefCell<Option<Worker>> = RefCell::from(None);
+    static REACTOR : RefCell<Option<JoinHandle<()>>> = RefCell::from(None);
+}
+
+/// Options of the runtime started by `start`
+#[derive(Clone)]
+pub(crate) struct RuntimeConfig {
+    pub(crate) instrumentation: Option<Instrumentation>,
+    pub(crate) thread_name: String,
+    pub(crate) stack_size: Option<usize>,
+    pub(crate) affinity: bool,
+    /// Number of threads of the pool running the bulk handlers, none if 0
+    pub(crate) bulk_workers: usize,
 }
 
-pub(crate) fn start() {
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            instrumentation: None,
+            thread_name: String::from("mini-async-http"),
+            stack_size: None,
+            affinity: false,
+            bulk_workers: 0,
+        }
+    }
+}
+
+pub(crate) fn start(config: &RuntimeConfig) {
     let mut reactor = Reactor::new();
 
     let reactor_handle = reactor.handle();
     set_handle(reactor_handle.try_clone().expect("Reactor could not start"));
 
-    std::thread::spawn(move || {
-        reactor.event_loop();
-    });
+    let mut thread = std::thread::Builder::new().name(format!("{}-reactor", config.thread_name));
+    if let Some(size) = config.stack_size {
+        thread = thread.stack_size(size);
+    }
+    let size = num_cpus::get_physical();
+    let affinity = config.affinity;
+
+    let reactor = thread
+        .spawn(move || {
+            // The workers take the first cores, the reactor shares the last one
+            if affinity {
+                pin_current_thread(size.saturating_sub(1));
+            }
+            reactor.event_loop();
+        })
+        .expect("Reactor could not start");
+    REACTOR.with(|ctx| ctx.replace(Some(reactor)));
+
+    // The bulk workers are not pinned, they would compete for the cores of the workers
+    let bulk = match config.bulk_workers {
+        0 => None,
+        workers => {
+            let reactor_handle = reactor_handle.try_clone().expect("Reactor could not start");
+            let mut builder = ThreadPoolBuilder::new()
+                .size(workers)
+                .name(&format!("{}-bulk", config.thread_name))
+                .after_start(move |_, handle| {
+                    set_pool(handle);
+                    set_handle(reactor_handle.try_clone().expect("Reactor could not start"));
+                });
+            if let Some(size) = config.stack_size {
+                builder = builder.stack_size(size);
+            }
+
+            Some(builder.build())
+        }
+    };
+    BULK.with(|ctx| ctx.replace(bulk.clone()));
 
-    let pool = ThreadPoolBuilder::new()
-        .size(num_cpus::get_physical())
+    let mut builder = ThreadPoolBuilder::new()
+        .size(size)
+        .name(&format!("{}-worker", config.thread_name))
+        .affinity(config.affinity)
         .after_start(move |_, handle| {
             set_pool(handle);
             set_handle(reactor_handle.try_clone().expect("Reactor could not start"));
-        })
-        .build();
+            BULK.with(|ctx| ctx.replace(bulk.clone()));
+        });
+
+    if let Some(size) = config.stack_size {
+        builder = builder.stack_size(size);
+    }
+
+    if let Some(ref instrumentation) = config.instrumentation {
+        builder = builder.instrument(instrumentation.clone());
+    }
+
+    let pool = builder.build();
 
     set_pool(pool);
 }
 
 pub(crate) fn handle() -> Option<Handle> {
-    HANDLE.with(|ctx| match *ctx.borrow() {
-        Some(ref handle) => handle.try_clone().ok(),
-        None => None,
-    })
+    // The context may already be destroyed when sources are dropped during a thread exit
+    HANDLE
+        .try_with(|ctx| match *ctx.borrow() {
+            Some(ref handle) => handle.try_clone().ok(),
+            None => None,
+        })
+        .ok()
+        .flatten()
+}
+
+pub(crate) fn pool() -> Option<PoolHandle> {
+    EXECUTOR.with(|ctx| ctx.borrow().clone())
+}
+
+/// Return the pool running the bulk handlers, if the runtime has one
+pub(crate) fn bulk_pool() -> Option<PoolHandle> {
+    BULK.with(|ctx| ctx.borrow().clone())
 }
 
 fn set_handle(handle: Handle) {
@@ -90,13 +179,35 @@ where
     });
 }
 
+/// Stop the worker pool and the reactor started by `start` and wait for their threads to end.
+/// The tasks waiting on a source are woken first with a shutdown error, so they end before the workers.
 pub(crate) fn stop() {
-    EXECUTOR.with(|ctx| match *ctx.borrow() {
-        Some(ref spawner) => {
+    HANDLE.with(|ctx| {
+        if let Some(ref handle) = *ctx.borrow() {
+            handle.cancel();
+        }
+    });
+
+    EXECUTOR.with(|ctx| match ctx.borrow_mut().take() {
+        Some(spawner) => {
             spawner.stop().expect("Unknown error when stopping context");
         }
         _ => panic!("Context not started : cannot stop"),
     });
+
+    if let Some(bulk) = BULK.with(|ctx| ctx.borrow_mut().take()) {
+        bulk.stop().expect("Unknown error when stopping context");
+    }
+
+    if let Some(handle) = HANDLE.with(|ctx| ctx.borrow_mut().take()) {
+        handle.shutdown();
+    }
+
+    if let Some(reactor) = REACTOR.with(|ctx| ctx.borrow_mut().take()) {
+        if reactor.join().is_err() {
+            error!("Reactor thread panicked");
+        }
+    }
 }
 
 #[cfg(test)]
@@ -110,13 +221,21 @@ mod test {
 
     #[test]
     fn start_context() {
-        start();
+        start(&RuntimeConfig::default());
         assert!(handle().is_some());
     }
 
+    #[test]
+    fn stop_context() {
+        start(&RuntimeConfig::default());
+        stop();
+
+        assert!(handle().is_none());
+    }
+
     #[test]
     fn start_multithread() {
-        start();
+        start(&RuntimeConfig::default());
         let h = handle().unwrap();
 
         std::thread::spawn(move || {
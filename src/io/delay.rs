@@ -0,0 +1,184 @@
+use log::error;
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Pending delays, ordered by deadline then by registration
+static TIMERS: Mutex<Timers> = Mutex::new(Timers {
+    entries: BTreeMap::new(),
+    next_id: 0,
+    started: false,
+});
+/// Signaled when a delay becomes the first one to expire
+static CHANGED: Condvar = Condvar::new();
+
+type Key = (Instant, u64);
+
+struct Timers {
+    entries: BTreeMap<Key, Waker>,
+    next_id: u64,
+    started: bool,
+}
+
+/// Future completing once the given duration has elapsed.
+/// Every delay is kept by a single timer thread, started with the first one, so the worker can execute
+/// other tasks meanwhile and a pending delay only costs an entry, removed when the delay is dropped.
+pub(crate) struct Delay {
+    deadline: Instant,
+    key: Option<Key>,
+}
+
+impl Delay {
+    pub(crate) fn new(duration: Duration) -> Delay {
+        Delay::until(Instant::now() + duration)
+    }
+
+    /// Complete once the given instant is reached
+    pub(crate) fn until(deadline: Instant) -> Delay {
+        Delay {
+            deadline,
+            key: None,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            if let Some(key) = self.key.take() {
+                TIMERS.lock().unwrap().entries.remove(&key);
+            }
+            return Poll::Ready(());
+        }
+
+        let mut timers = TIMERS.lock().unwrap();
+        let key = match self.key {
+            Some(key) => key,
+            None => {
+                timers.next_id += 1;
+                (self.deadline, timers.next_id)
+            }
+        };
+        self.key = Some(key);
+
+        match timers.entries.get_mut(&key) {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            Some(waker) => *waker = cx.waker().clone(),
+            None => {
+                timers.entries.insert(key, cx.waker().clone());
+                if !timers.started {
+                    start(&mut timers);
+                }
+                if timers.entries.keys().next() == Some(&key) {
+                    CHANGED.notify_one();
+                }
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            TIMERS.lock().unwrap().entries.remove(&key);
+        }
+    }
+}
+
+fn start(timers: &mut Timers) {
+    let started = std::thread::Builder::new()
+        .name(String::from("mini-async-http-timer"))
+        .spawn(run);
+
+    match started {
+        Ok(_) => timers.started = true,
+        Err(e) => error!("Could not start the timer thread : {}", e),
+    }
+}
+
+/// Wake the delays as they expire, sleeping until the first one
+fn run() {
+    let mut timers = TIMERS.lock().unwrap();
+
+    loop {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        while let Some(entry) = timers.entries.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            expired.push(entry.remove());
+        }
+
+        if !expired.is_empty() {
+            drop(timers);
+            expired.into_iter().for_each(Waker::wake);
+            timers = TIMERS.lock().unwrap();
+            continue;
+        }
+
+        timers = match timers.entries.keys().next() {
+            Some(&(deadline, _)) => {
+                CHANGED
+                    .wait_timeout(timers, deadline.saturating_duration_since(now))
+                    .unwrap()
+                    .0
+            }
+            None => CHANGED.wait(timers).unwrap(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wait_duration() {
+        let start = Instant::now();
+
+        futures::executor::block_on(Delay::new(Duration::from_millis(20)));
+
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn elapsed_delay() {
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut delay = Delay::new(Duration::from_millis(0));
+
+        assert!(Pin::new(&mut delay).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn dropped_delay() {
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut delay = Delay::new(Duration::from_secs(60));
+        assert!(Pin::new(&mut delay).poll(&mut cx).is_pending());
+        let key = delay.key.unwrap();
+        assert!(TIMERS.lock().unwrap().entries.contains_key(&key));
+
+        drop(delay);
+        assert!(!TIMERS.lock().unwrap().entries.contains_key(&key));
+    }
+
+    #[test]
+    fn many_delays() {
+        let delays = (0..100).map(|i| Delay::new(Duration::from_millis(i % 10)));
+        futures::executor::block_on(futures::future::join_all(delays));
+
+        assert!(TIMERS.lock().unwrap().started);
+    }
+}
@@ -0,0 +1,226 @@
+use futures::{AsyncRead, AsyncWrite};
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Create a pair of connected in-memory streams: the bytes written to one are read from the other.
+/// Each direction buffers up to `capacity` bytes, a write waits for the other end to read once it is full.
+///
+/// The streams implement the async traits of `futures`, to run an [`HttpConnection`] without socket,
+/// as well as the blocking `Read` and `Write` traits, to send requests with [`TestClient::from_stream`].
+/// Dropping or closing a stream ends the reads of the other one.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{duplex, HttpConnection, ResponseBuilder};
+/// use mini_async_http::test::TestClient;
+///
+/// let (client, server) = duplex(4096);
+///
+/// std::thread::spawn(move || {
+///     futures::executor::block_on(async {
+///         let mut connection = HttpConnection::new(server);
+///         while let Some(request) = connection.next_request().await.unwrap() {
+///             let response = ResponseBuilder::empty_200().body(b"Hello").build().unwrap();
+///             connection.send_response(&request, response).await.unwrap();
+///         }
+///     })
+/// });
+///
+/// let client = TestClient::from_stream(client);
+/// assert_eq!(client.get("/").send().body().unwrap(), b"Hello");
+/// // The second request is sent on the same connection
+/// assert_eq!(client.get("/").send().code(), 200);
+/// ```
+///
+/// [`HttpConnection`]: struct.HttpConnection.html
+/// [`TestClient::from_stream`]: test/struct.TestClient.html#method.from_stream
+pub fn duplex(capacity: usize) -> (DuplexStream, DuplexStream) {
+    let first = Arc::new(Mutex::new(Pipe::new(capacity.max(1))));
+    let second = Arc::new(Mutex::new(Pipe::new(capacity.max(1))));
+
+    (
+        DuplexStream {
+            read: first.clone(),
+            write: second.clone(),
+        },
+        DuplexStream {
+            read: second,
+            write: first,
+        },
+    )
+}
+
+/// One end of an in-memory connection created with [`duplex`]
+///
+/// [`duplex`]: fn.duplex.html
+pub struct DuplexStream {
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>,
+}
+
+/// Bytes going in one direction, along with the task waiting for them
+struct Pipe {
+    buffer: VecDeque<u8>,
+    capacity: usize,
+    /// The writing end is closed, the reads return the end of the stream once the buffer is empty
+    closed: bool,
+    /// The reading end is dropped, the writes fail
+    dropped: bool,
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+}
+
+impl Pipe {
+    fn new(capacity: usize) -> Pipe {
+        Pipe {
+            buffer: VecDeque::new(),
+            capacity,
+            closed: false,
+            dropped: false,
+            reader: None,
+            writer: None,
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.reader.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl AsyncRead for DuplexStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = self.read.lock().unwrap();
+
+        if pipe.buffer.is_empty() {
+            if pipe.closed || buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            pipe.reader = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let n = std::cmp::min(buf.len(), pipe.buffer.len());
+        for (byte, read) in buf.iter_mut().zip(pipe.buffer.drain(..n)) {
+            *byte = read;
+        }
+        if let Some(waker) = pipe.writer.take() {
+            waker.wake();
+        }
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for DuplexStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let mut pipe = self.write.lock().unwrap();
+
+        if pipe.dropped || pipe.closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let n = std::cmp::min(buf.len(), pipe.capacity - pipe.buffer.len());
+        if n == 0 {
+            pipe.writer = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        pipe.buffer.extend(&buf[..n]);
+        if let Some(waker) = pipe.reader.take() {
+            waker.wake();
+        }
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        self.write.lock().unwrap().close();
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Block the thread until the other end sent some bytes
+impl Read for DuplexStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        futures::executor::block_on(futures::AsyncReadExt::read(self, buf))
+    }
+}
+
+/// Block the thread until the other end made room for some bytes
+impl Write for DuplexStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        futures::executor::block_on(futures::AsyncWriteExt::write(self, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        self.write.lock().unwrap().close();
+
+        let mut pipe = self.read.lock().unwrap();
+        pipe.dropped = true;
+        if let Some(waker) = pipe.writer.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exchange() {
+        let (mut first, mut second) = duplex(4);
+
+        let writer = std::thread::spawn(move || {
+            futures::executor::block_on(async {
+                // Larger than the capacity, the write waits for the other end
+                futures::AsyncWriteExt::write_all(&mut first, b"request")
+                    .await
+                    .unwrap();
+                let mut response = [0; 8];
+                futures::AsyncReadExt::read_exact(&mut first, &mut response)
+                    .await
+                    .unwrap();
+                assert_eq!(&response, b"response");
+            })
+        });
+
+        let mut request = [0; 7];
+        second.read_exact(&mut request).unwrap();
+        assert_eq!(&request, b"request");
+        second.write_all(b"response").unwrap();
+
+        writer.join().unwrap();
+        // The other end is dropped
+        assert_eq!(second.read(&mut request).unwrap(), 0);
+        assert_eq!(
+            second.write(b"late").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
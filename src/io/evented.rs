@@ -0,0 +1,190 @@
+use crate::io::context;
+use crate::io::reactor::{Handle, IoWaker};
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Source of events registered with the reactor of the server, such as a pipe, an eventfd or a socket
+/// owned by the application, so the tasks of the server can await it without blocking their worker.
+///
+/// The source must be in non-blocking mode, its operations are retried each time the reactor reports
+/// an event until they stop failing with `WouldBlock`.
+/// It has to be created by a task of the server, from a handler or a middleware, as it needs its reactor.
+///
+/// # Example
+///
+/// Wait for a message on a socket given by another part of the application
+///
+/// ```
+/// use mini_async_http::mio::{event::Source, unix::SourceFd, Interest, Registry, Token};
+/// use mini_async_http::Evented;
+/// use std::io::{self, Read};
+/// use std::os::unix::io::AsRawFd;
+/// use std::os::unix::net::UnixStream;
+///
+/// struct Socket(UnixStream);
+///
+/// impl Source for Socket {
+///     fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+///         SourceFd(&self.0.as_raw_fd()).register(registry, token, interests)
+///     }
+///
+///     fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+///         SourceFd(&self.0.as_raw_fd()).reregister(registry, token, interests)
+///     }
+///
+///     fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+///         SourceFd(&self.0.as_raw_fd()).deregister(registry)
+///     }
+/// }
+///
+/// async fn read_message(socket: UnixStream) -> io::Result<String> {
+///     socket.set_nonblocking(true)?;
+///     let socket = Evented::new(Socket(socket), Interest::READABLE)?;
+///
+///     let mut buf = [0; 64];
+///     let read = socket.io(|socket| (&socket.0).read(&mut buf)).await?;
+///     Ok(String::from_utf8_lossy(&buf[..read]).to_string())
+/// }
+/// ```
+pub struct Evented<S: mio::event::Source> {
+    source: S,
+    waker: Arc<IoWaker>,
+    handle: Handle,
+}
+
+impl<S: mio::event::Source> Evented<S> {
+    /// Register the source for the given events with the reactor of the current task.
+    /// An error is returned outside of a task of the server or if the source can not be registered.
+    pub fn new(mut source: S, interest: mio::Interest) -> io::Result<Evented<S>> {
+        let handle = context::handle().ok_or_else(|| io::Error::other("Context not started"))?;
+        let waker = handle.try_register(&mut source, interest)?;
+
+        Ok(Evented {
+            source,
+            waker,
+            handle,
+        })
+    }
+
+    /// Return a reference to the source
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Return a mutable reference to the source
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Run an operation on the source, the task is woken by the next event of the source if it would block
+    pub fn poll_io<R, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<R>>
+    where
+        F: FnMut(&S) -> io::Result<R>,
+    {
+        self.waker.register(cx.waker());
+
+        match op(&self.source) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => self.waker.pending(),
+            result => Poll::Ready(result),
+        }
+    }
+
+    /// Run an operation on the source, waiting for its events as long as it would block
+    pub fn io<R, F>(&self, op: F) -> EventedIo<'_, S, F>
+    where
+        F: FnMut(&S) -> io::Result<R>,
+    {
+        EventedIo { evented: self, op }
+    }
+}
+
+impl<S: mio::event::Source> Drop for Evented<S> {
+    fn drop(&mut self) {
+        self.handle.deregister(&mut self.source, self.waker.clone());
+    }
+}
+
+/// Future returned by [`Evented::io`]
+///
+/// [`Evented::io`]: struct.Evented.html#method.io
+#[must_use = "futures do nothing unless polled"]
+pub struct EventedIo<'a, S: mio::event::Source, F> {
+    evented: &'a Evented<S>,
+    op: F,
+}
+
+impl<S, R, F> Future for EventedIo<'_, S, F>
+where
+    S: mio::event::Source,
+    F: FnMut(&S) -> io::Result<R> + Unpin,
+{
+    type Output = io::Result<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.evented.poll_io(cx, &mut this.op)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::io::context::RuntimeConfig;
+
+    use std::sync::mpsc;
+
+    #[test]
+    fn outside_context() {
+        let listener = mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+
+        assert!(Evented::new(listener, mio::Interest::READABLE).is_err());
+    }
+
+    #[test]
+    fn await_source() {
+        context::start(&RuntimeConfig::default());
+        let (sender, receiver) = mpsc::channel();
+
+        context::block_on(async move {
+            let listener = mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            let addr = listener.local_addr().unwrap();
+            let listener = Evented::new(listener, mio::Interest::READABLE).unwrap();
+
+            let client = std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                std::net::TcpStream::connect(addr).unwrap()
+            });
+
+            let (_, peer) = listener.io(|listener| listener.accept()).await.unwrap();
+            let client = client.join().unwrap();
+            sender.send(peer == client.local_addr().unwrap()).unwrap();
+        });
+        context::stop();
+
+        assert!(receiver.recv().unwrap());
+    }
+
+    #[test]
+    fn cancel_on_stop() {
+        context::start(&RuntimeConfig::default());
+        let (sender, receiver) = mpsc::channel();
+
+        context::spawn(async move {
+            let listener = mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            let listener = Evented::new(listener, mio::Interest::READABLE).unwrap();
+            sender.send(None).unwrap();
+
+            // No connection ever comes, the accept resolves once the runtime stops
+            let accepted = listener.io(|listener| listener.accept()).await;
+            sender.send(Some(accepted.is_err())).unwrap();
+        });
+        assert_eq!(receiver.recv().unwrap(), None);
+        context::stop();
+
+        assert_eq!(receiver.try_recv().unwrap(), Some(true));
+    }
+}
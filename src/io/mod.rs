@@ -1,4 +1,11 @@
+pub mod blocking;
 pub mod context;
+pub mod delay;
+pub mod duplex;
+pub mod evented;
 pub mod reactor;
+pub mod runtime;
+pub mod send_file;
+pub mod socket;
 pub mod tcp_listener;
 pub mod tcp_stream;
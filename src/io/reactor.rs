@@ -1,12 +1,18 @@
 use log::error;
 use slab::Slab;
 
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use std::task::Waker;
+use std::task::{Poll, Waker};
 
-use crate::data::AtomicTake;
-use crate::data::{global_injector, Receiver, Sender};
+#[cfg(loom)]
+use loom::{cell::UnsafeCell, sync::atomic::AtomicUsize};
+#[cfg(not(loom))]
+use {cell::UnsafeCell, std::sync::atomic::AtomicUsize};
+
+use crate::data::{global_injector, InjectorReceiver, InjectorSender, Receiver, Sender};
 
 const DEFAULT_SLAB_SIZE: usize = 16384;
 const DEFAULT_EVENTS_SIZE: usize = 16384;
@@ -15,13 +21,15 @@ pub(crate) struct Reactor {
     poll: mio::Poll,
     events: mio::Events,
 
-    io_wakers: Slab<Arc<IoWaker>>,
+    io_wakers: Arc<Slab<Arc<IoWaker>>>,
 
     id_sender: Sender<Arc<IoWaker>>,
     id_receiver: Receiver<Arc<IoWaker>>,
 
     waker: Arc<mio::Waker>,
     waker_token: usize,
+
+    stop: Arc<AtomicBool>,
 }
 
 impl Reactor {
@@ -31,16 +39,17 @@ impl Reactor {
 
         let mut io_wakers = Slab::with_capacity(DEFAULT_SLAB_SIZE);
         let (id_sender, id_receiver) = global_injector();
+        let cancelled = Arc::new(AtomicBool::new(false));
 
         let waker_entry = io_wakers.vacant_entry();
         let waker_token = waker_entry.key();
-        waker_entry.insert(Arc::from(IoWaker::new(waker_token)));
+        waker_entry.insert(Arc::from(IoWaker::new(waker_token, cancelled.clone())));
 
         let waker = Arc::new(mio::Waker::new(poll.registry(), mio::Token(waker_token)).unwrap());
 
         while io_wakers.len() < io_wakers.capacity() {
             let entry = io_wakers.vacant_entry();
-            let waker = Arc::from(IoWaker::new(entry.key()));
+            let waker = Arc::from(IoWaker::new(entry.key(), cancelled.clone()));
             entry.insert(waker.clone());
 
             if id_sender.send(waker).is_err() {
@@ -51,18 +60,30 @@ impl Reactor {
         Reactor {
             poll,
             events,
-            io_wakers,
+            io_wakers: Arc::new(io_wakers),
             id_sender,
             id_receiver,
             waker,
             waker_token,
+            stop: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Run the reactor until a shutdown is requested through one of its handles
     pub(crate) fn event_loop(&mut self) {
-        loop {
+        while !self.stop.load(Ordering::SeqCst) {
             self.turn();
         }
+
+        self.release_wakers();
+    }
+
+    /// Drop the wakers of the registered sources.
+    /// The tasks waiting on them can no longer be woken, dropping them closes their sources.
+    fn release_wakers(&self) {
+        for (_, waker) in self.io_wakers.iter() {
+            drop(waker.waker.take());
+        }
     }
 
     fn turn(&mut self) {
@@ -87,7 +108,10 @@ impl Reactor {
         Handle {
             id_receiver: self.id_receiver.clone(),
             id_sender: self.id_sender.clone(),
-            registry: self.poll.registry().try_clone().unwrap(),
+            registry: Arc::new(self.poll.registry().try_clone().unwrap()),
+            waker: self.waker.clone(),
+            stop: self.stop.clone(),
+            io_wakers: self.io_wakers.clone(),
         }
     }
 }
@@ -95,39 +119,86 @@ impl Reactor {
 pub(crate) struct Handle {
     id_receiver: Receiver<Arc<IoWaker>>,
     id_sender: Sender<Arc<IoWaker>>,
-    registry: mio::Registry,
+    registry: Arc<mio::Registry>,
+    waker: Arc<mio::Waker>,
+    stop: Arc<AtomicBool>,
+    io_wakers: Arc<Slab<Arc<IoWaker>>>,
 }
 
 impl Handle {
-    pub(crate) fn register(&self, source: &mut dyn mio::event::Source) -> Arc<IoWaker> {
+    pub(crate) fn register(
+        &self,
+        source: &mut dyn mio::event::Source,
+        interest: mio::Interest,
+    ) -> Arc<IoWaker> {
+        self.try_register(source, interest).unwrap()
+    }
+
+    /// Register a source, failing if the registry refuses it or no waker is left for it
+    pub(crate) fn try_register(
+        &self,
+        source: &mut dyn mio::event::Source,
+        interest: mio::Interest,
+    ) -> io::Result<Arc<IoWaker>> {
         let waker = match self.id_receiver.try_recv() {
             Ok(waker) => waker,
-            Err(_) => panic!("No waker available"),
+            Err(_) => return Err(io::Error::other("No waker available")),
         };
 
-        self.registry
-            .register(source, mio::Token(waker.key()), mio::Interest::READABLE)
-            .unwrap();
+        if let Err(e) = self
+            .registry
+            .register(source, mio::Token(waker.key()), interest)
+        {
+            let _ = self.id_sender.send(waker);
+            return Err(e);
+        }
 
-        waker
+        Ok(waker)
     }
 
     pub(crate) fn deregister(&self, source: &mut dyn mio::event::Source, waker: Arc<IoWaker>) {
-        self.registry.deregister(source).unwrap();
+        // A source closed before being dropped is already removed from the registry
+        if let Err(e) = self.registry.deregister(source) {
+            error!("Could not deregister a source : {}", e);
+        }
         if self.id_sender.send(waker).is_err() {
             error!("Could not put the waker back into the pool");
         }
     }
 
+    /// The registry is shared so cloning does not need a new file descriptor
     pub(crate) fn try_clone(&self) -> std::io::Result<Self> {
-        let registry = self.registry.try_clone()?;
-
         Ok(Handle {
             id_receiver: self.id_receiver.clone(),
             id_sender: self.id_sender.clone(),
-            registry,
+            registry: self.registry.clone(),
+            waker: self.waker.clone(),
+            stop: self.stop.clone(),
+            io_wakers: self.io_wakers.clone(),
         })
     }
+
+    /// Wake every task waiting on a source, the operations of the sources that would block fail from then on
+    /// with a shutdown error so the pending futures resolve instead of waiting forever.
+    pub(crate) fn cancel(&self) {
+        // All the wakers share the flag
+        if let Some((_, waker)) = self.io_wakers.iter().next() {
+            waker.cancelled.store(true, Ordering::SeqCst);
+        }
+
+        for (_, waker) in self.io_wakers.iter() {
+            waker.wake();
+        }
+    }
+
+    /// Ask the reactor event loop to stop
+    pub(crate) fn shutdown(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        if let Err(e) = self.waker.wake() {
+            error!("Could not wake the reactor up : {:?}", e);
+        }
+    }
 }
 
 enum CloneError {}
@@ -138,14 +209,17 @@ enum Message {
 
 pub(crate) struct IoWaker {
     key: usize,
-    waker: AtomicTake<Waker>,
+    waker: AtomicWaker,
+    /// Set for all the wakers of the reactor once the runtime shuts down
+    cancelled: Arc<AtomicBool>,
 }
 
 impl IoWaker {
-    fn new(key: usize) -> IoWaker {
+    fn new(key: usize, cancelled: Arc<AtomicBool>) -> IoWaker {
         IoWaker {
             key,
-            waker: AtomicTake::new(),
+            waker: AtomicWaker::new(),
+            cancelled,
         }
     }
 
@@ -154,17 +228,123 @@ impl IoWaker {
     }
 
     pub fn wake(&self) {
-        let waker = match self.waker.take() {
-            Some(waker) => waker,
-            None => return,
-        };
+        self.waker.wake();
+    }
 
-        waker.wake_by_ref();
-        self.set_waker(waker);
+    /// Register the task to wake on the next event, before running the operation on the source
+    /// so an event happening in between is not missed
+    pub fn register(&self, waker: &Waker) {
+        self.waker.register(waker);
     }
 
-    pub fn set_waker(&self, waker: Waker) {
-        self.waker.store(waker);
+    /// Wait for the next event of a source whose operation would block,
+    /// or fail with a shutdown error as no event is awaited anymore once the runtime shuts down
+    pub fn pending<T>(&self) -> Poll<io::Result<T>> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Poll::Ready(Err(io::Error::other("The runtime is shutting down")));
+        }
+
+        Poll::Pending
+    }
+}
+
+/// No task is registering or waking the waker
+const WAITING: usize = 0;
+/// A task is storing its waker
+const REGISTERING: usize = 0b01;
+/// The reactor is taking the waker out to wake it
+const WAKING: usize = 0b10;
+
+/// Slot holding the waker of the task waiting on a source.
+///
+/// A waker registered while the reactor wakes the previous one is not lost: the state tells the registering
+/// task that an event came meanwhile and the task wakes its new waker itself, instead of the reactor putting
+/// the previous waker back over it.
+pub(crate) struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// The waker is only reached by the thread that moved the state out of WAITING
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    pub(crate) fn new() -> AtomicWaker {
+        AtomicWaker {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Store the waker to wake on the next call to `wake`, replacing the previous one
+    pub(crate) fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .unwrap_or_else(|state| state)
+        {
+            WAITING => {
+                self.waker.with_mut(|slot| {
+                    let slot = unsafe { &mut *slot };
+                    if !matches!(slot, Some(current) if current.will_wake(waker)) {
+                        *slot = Some(waker.clone());
+                    }
+                });
+
+                // A wake came while registering, it could not take the waker so it is woken here
+                if self
+                    .state
+                    .compare_exchange(REGISTERING, WAITING, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    let woken = self.waker.with_mut(|slot| unsafe { (*slot).take() });
+                    self.state.swap(WAITING, Ordering::AcqRel);
+                    if let Some(woken) = woken {
+                        woken.wake();
+                    }
+                }
+            }
+            // The waker is being taken out, the task is woken right away to poll the source again
+            WAKING => waker.wake_by_ref(),
+            // Concurrent registrations are not supported, a source is polled by a single task at once
+            _ => {}
+        }
+    }
+
+    /// Wake the registered task, if any. The waker is taken out, the task registers again when it next waits.
+    pub(crate) fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    /// Take the registered waker out, unless a task is registering: it then wakes itself
+    pub(crate) fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, Ordering::AcqRel) {
+            WAITING => {
+                let waker = self.waker.with_mut(|slot| unsafe { (*slot).take() });
+                self.state.fetch_and(!WAKING, Ordering::Release);
+                waker
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `UnsafeCell` with the interface of the loom one, so the waker slot is checked by the loom models
+#[cfg(not(loom))]
+mod cell {
+    pub(crate) struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+    impl<T> UnsafeCell<T> {
+        pub(crate) fn new(value: T) -> UnsafeCell<T> {
+            UnsafeCell(std::cell::UnsafeCell::new(value))
+        }
+
+        pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+            f(self.0.get())
+        }
     }
 }
 
@@ -189,7 +369,7 @@ mod tests {
 
         let mut stream = mio::net::TcpListener::bind("0.0.0.0:29808".parse().unwrap()).unwrap();
 
-        let waker = handle.register(&mut stream);
+        let waker = handle.register(&mut stream, mio::Interest::READABLE);
 
         assert_eq!(DEFAULT_SLAB_SIZE - 2, reactor.id_receiver.len());
         assert_eq!(DEFAULT_SLAB_SIZE - 2, reactor.id_sender.len());
@@ -199,4 +379,174 @@ mod tests {
         assert_eq!(DEFAULT_SLAB_SIZE - 1, reactor.id_receiver.len());
         assert_eq!(DEFAULT_SLAB_SIZE - 1, reactor.id_sender.len());
     }
+
+    struct Woken(AtomicBool);
+
+    impl futures::task::ArcWake for Woken {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn cancel() {
+        let reactor = Reactor::new();
+        let handle = reactor.handle();
+
+        let mut stream = mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let waker = handle.register(&mut stream, mio::Interest::READABLE);
+        assert!(waker.pending::<()>().is_pending());
+
+        let woken = Arc::new(Woken(AtomicBool::new(false)));
+        waker.register(&futures::task::waker(woken.clone()));
+
+        handle.cancel();
+        assert!(woken.0.load(Ordering::SeqCst));
+        assert!(matches!(waker.pending::<()>(), Poll::Ready(Err(_))));
+    }
+
+    /// Waker of a task identified by its index, sending it once woken
+    struct Indexed(usize, std::sync::Mutex<std::sync::mpsc::Sender<usize>>);
+
+    impl futures::task::ArcWake for Indexed {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            let _ = arc_self.1.lock().unwrap().send(arc_self.0);
+        }
+    }
+
+    #[test]
+    fn no_lost_wakeup() {
+        const EVENTS: usize = 20_000;
+
+        let io = Arc::new(IoWaker::new(0, Arc::new(AtomicBool::new(false))));
+        let events = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (sender, woken) = std::sync::mpsc::channel();
+
+        let reactor = {
+            let (io, events) = (io.clone(), events.clone());
+            std::thread::spawn(move || {
+                for _ in 0..EVENTS {
+                    events.fetch_add(1, Ordering::SeqCst);
+                    io.wake();
+                }
+            })
+        };
+
+        // Each wait registers a new waker, only the last one registered is listened to
+        let mut seen = 0;
+        let mut index = 0;
+        while seen < EVENTS {
+            index += 1;
+            let waker = Arc::new(Indexed(index, std::sync::Mutex::new(sender.clone())));
+            io.register(&futures::task::waker(waker));
+
+            let current = events.load(Ordering::SeqCst);
+            if current > seen {
+                seen = current;
+                continue;
+            }
+            loop {
+                match woken.recv_timeout(std::time::Duration::from_secs(5)) {
+                    Ok(woken) if woken == index => break,
+                    Ok(_) => continue,
+                    Err(_) => panic!("Lost wake up after {} events", seen),
+                }
+            }
+        }
+
+        reactor.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown() {
+        let mut reactor = Reactor::new();
+        let handle = reactor.handle();
+
+        let thread = std::thread::spawn(move || reactor.event_loop());
+        handle.shutdown();
+
+        thread.join().unwrap();
+    }
+}
+
+#[cfg(all(test, loom))]
+mod model {
+    use super::*;
+
+    use loom::sync::atomic::{AtomicBool, AtomicUsize};
+
+    /// Task counting its wake ups
+    struct Task(AtomicUsize);
+
+    impl futures::task::ArcWake for Task {
+        fn wake_by_ref(arc_self: &std::sync::Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn task() -> (std::sync::Arc<Task>, Waker) {
+        let task = std::sync::Arc::new(Task(AtomicUsize::new(0)));
+        let waker = futures::task::waker(task.clone());
+        (task, waker)
+    }
+
+    fn io_waker() -> std::sync::Arc<IoWaker> {
+        std::sync::Arc::new(IoWaker::new(
+            0,
+            Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        ))
+    }
+
+    /// The task registers its waker then checks the source, the reactor marks the source ready then wakes
+    /// the task: either the task sees the source ready or it is woken.
+    #[test]
+    fn wake_after_register() {
+        loom::model(|| {
+            let io = io_waker();
+            let ready = std::sync::Arc::new(AtomicBool::new(false));
+            let (task, waker) = task();
+
+            let reactor = {
+                let (io, ready) = (io.clone(), ready.clone());
+                loom::thread::spawn(move || {
+                    ready.store(true, Ordering::SeqCst);
+                    io.wake();
+                })
+            };
+
+            io.register(&waker);
+            let seen = ready.load(Ordering::SeqCst);
+            reactor.join().unwrap();
+
+            assert!(seen || task.0.load(Ordering::SeqCst) > 0);
+        });
+    }
+
+    /// The task registers a new waker, for example once moved to another task, while the reactor wakes
+    /// the previous one: the next event must wake the new waker.
+    #[test]
+    fn register_during_wake() {
+        loom::model(|| {
+            let io = io_waker();
+            let ready = std::sync::Arc::new(AtomicBool::new(false));
+            let (_, previous) = task();
+            let (task, waker) = task();
+            io.register(&previous);
+
+            let reactor = {
+                let (io, ready) = (io.clone(), ready.clone());
+                loom::thread::spawn(move || {
+                    io.wake();
+                    ready.store(true, Ordering::SeqCst);
+                    io.wake();
+                })
+            };
+
+            io.register(&waker);
+            let seen = ready.load(Ordering::SeqCst);
+            reactor.join().unwrap();
+
+            assert!(seen || task.0.load(Ordering::SeqCst) > 0);
+        });
+    }
 }
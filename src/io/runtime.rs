@@ -0,0 +1,120 @@
+//! Timers for the tasks running on the executor of the server.
+//!
+//! The timers do not block the worker threads, the waiting is done by a timer thread shared by every timer.
+//! They are meant for coarse timings like periodic maintenance, see [`AIOServer::add_background_job`].
+//!
+//! [`AIOServer::add_background_job`]: ../struct.AIOServer.html#method.add_background_job
+
+use crate::io::delay::Delay;
+
+use futures::Stream;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Wait for the given duration
+pub async fn sleep(duration: Duration) {
+    Delay::new(duration).await
+}
+
+/// Create an [`Interval`] ticking every `period`, the first tick completes immediately
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::runtime;
+/// use std::time::Duration;
+///
+/// futures::executor::block_on(async {
+///     let mut interval = runtime::interval(Duration::from_millis(10));
+///     for _ in 0..3 {
+///         interval.tick().await;
+///         // Periodic work ...
+///     }
+/// });
+/// ```
+///
+/// [`Interval`]: struct.Interval.html
+pub fn interval(period: Duration) -> Interval {
+    Interval {
+        next: Instant::now(),
+        period,
+        delay: None,
+    }
+}
+
+/// Ticks at a fixed period, also usable as a `Stream` of the instants of the ticks.
+/// The ticks are scheduled from the first one so the period does not drift with the time taken by the work,
+/// when the work takes longer than the period the late tick completes immediately and the missed ones are skipped.
+pub struct Interval {
+    next: Instant,
+    period: Duration,
+    delay: Option<Delay>,
+}
+
+impl Interval {
+    /// Wait for the next tick and return the instant it was scheduled at
+    pub async fn tick(&mut self) -> Instant {
+        futures::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// Poll for the next tick, the task is woken when it is due
+    pub fn poll_tick(&mut self, cx: &mut Context) -> Poll<Instant> {
+        let next = self.next;
+        let delay = self.delay.get_or_insert_with(|| Delay::until(next));
+
+        if Pin::new(delay).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let now = Instant::now();
+        self.next += self.period;
+        while self.next <= now && self.period > Duration::from_secs(0) {
+            self.next += self.period;
+        }
+        self.delay = None;
+
+        Poll::Ready(next)
+    }
+
+    /// Return the period of the ticks
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Instant>> {
+        self.get_mut().poll_tick(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn ticks() {
+        let start = Instant::now();
+        let mut interval = interval(Duration::from_millis(100));
+
+        let first = block_on(interval.tick());
+        assert!(first.duration_since(start) < Duration::from_millis(100));
+
+        let second = block_on(interval.tick());
+        assert_eq!(second - first, Duration::from_millis(100));
+        assert!(Instant::now() >= second);
+
+        // The late tick completes immediately and the missed ones are skipped
+        std::thread::sleep(Duration::from_millis(230));
+        let third = block_on(interval.tick());
+        assert_eq!(third - second, Duration::from_millis(100));
+        let fourth = block_on(interval.tick());
+        assert_eq!(fourth - second, Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io;
+use std::task::{Context, Poll};
+
+/// Largest chunk given to the system at once, so other connections get their turn between chunks
+const MAX_SEND_SIZE: u64 = 1 << 20;
+
+/// Streams able to send a file without copying it in user space
+pub(crate) trait SendFile {
+    /// Send up to `count` bytes of the file starting at `offset`, the position of the file is not changed.
+    /// `Ready(Ok(None))` means zero copy is not available, the file has to be copied through the usual write path.
+    fn poll_send_file(
+        &mut self,
+        _cx: &mut Context,
+        _file: &File,
+        _offset: u64,
+        _count: usize,
+    ) -> Poll<io::Result<Option<usize>>> {
+        Poll::Ready(Ok(None))
+    }
+}
+
+/// Send the first `len` bytes of the file to the stream.
+/// Return None if the stream can not send the file, nothing is sent in that case.
+pub(crate) async fn send_file<S: SendFile>(
+    stream: &mut S,
+    file: &File,
+    len: u64,
+) -> io::Result<Option<u64>> {
+    let mut offset = 0;
+
+    while offset < len {
+        let count = std::cmp::min(len - offset, MAX_SEND_SIZE) as usize;
+        let sent = futures::future::poll_fn(|cx| stream.poll_send_file(cx, file, offset, count));
+
+        match sent.await? {
+            Some(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The file is shorter than its announced length",
+                ))
+            }
+            Some(n) => offset += n as u64,
+            None if offset == 0 => return Ok(None),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "Zero copy stopped in the middle of the file",
+                ))
+            }
+        }
+    }
+
+    Ok(Some(offset))
+}
+
+/// Zero copy through the sendfile system call
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn poll_sendfile(
+    socket: &impl std::os::unix::io::AsRawFd,
+    file: &File,
+    offset: u64,
+    count: usize,
+) -> Poll<io::Result<Option<usize>>> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        let mut position = offset as libc::off_t;
+        let sent =
+            unsafe { libc::sendfile(socket.as_raw_fd(), file.as_raw_fd(), &mut position, count) };
+
+        if sent >= 0 {
+            return Poll::Ready(Ok(Some(sent as usize)));
+        }
+
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EAGAIN) => Poll::Pending,
+            // The file can not be sent by the system, for example because of its file system
+            Some(libc::EINVAL) | Some(libc::ENOSYS) => Poll::Ready(Ok(None)),
+            _ => Poll::Ready(Err(e)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct NoZeroCopy;
+
+    impl SendFile for NoZeroCopy {}
+
+    #[test]
+    fn unsupported() {
+        let file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+        let sent = futures::executor::block_on(send_file(&mut NoZeroCopy, &file, 10)).unwrap();
+
+        assert_eq!(sent, None);
+    }
+}
@@ -0,0 +1,141 @@
+use mio::net;
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const DEFAULT_BACKLOG: i32 = 1024;
+
+/// TCP options of the sockets opened by the server
+#[derive(Debug, Clone)]
+pub(crate) struct SocketConfig {
+    pub(crate) nodelay: bool,
+    pub(crate) keepalive: Option<(Duration, Duration)>,
+    pub(crate) linger: Option<Duration>,
+    pub(crate) backlog: i32,
+    pub(crate) only_v6: Option<bool>,
+    pub(crate) reuse_port: bool,
+}
+
+impl Default for SocketConfig {
+    fn default() -> Self {
+        SocketConfig {
+            nodelay: true,
+            keepalive: None,
+            linger: None,
+            backlog: DEFAULT_BACKLOG,
+            only_v6: None,
+            reuse_port: false,
+        }
+    }
+}
+
+impl SocketConfig {
+    /// Create a non blocking listener bound to the address with the configured backlog
+    pub(crate) fn listen(&self, addr: SocketAddr) -> io::Result<net::TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(self.reuse_port)?;
+        if let (true, Some(only_v6)) = (addr.is_ipv6(), self.only_v6) {
+            socket.set_only_v6(only_v6)?;
+        }
+        socket.bind(&addr.into())?;
+        socket.listen(self.backlog)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(net::TcpListener::from_std(socket.into()))
+    }
+
+    /// Apply the options to an accepted connection
+    pub(crate) fn apply(&self, stream: &net::TcpStream) -> io::Result<()> {
+        let socket = SockRef::from(stream);
+
+        socket.set_nodelay(self.nodelay)?;
+        socket.set_linger(self.linger)?;
+
+        if let Some((time, interval)) = self.keepalive {
+            socket.set_tcp_keepalive(&keepalive(time, interval))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_vendor = "apple",
+    windows,
+))]
+fn keepalive(time: Duration, interval: Duration) -> TcpKeepalive {
+    TcpKeepalive::new().with_time(time).with_interval(interval)
+}
+
+/// The interval between probes can not be set on this platform, the system default is used
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_vendor = "apple",
+    windows,
+)))]
+fn keepalive(time: Duration, _interval: Duration) -> TcpKeepalive {
+    TcpKeepalive::new().with_time(time)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply_options() {
+        let config = SocketConfig {
+            nodelay: true,
+            keepalive: Some((Duration::from_secs(30), Duration::from_secs(5))),
+            linger: Some(Duration::from_secs(1)),
+            backlog: 16,
+            only_v6: None,
+            reuse_port: false,
+        };
+
+        let listener = config.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _client = std::net::TcpStream::connect(addr).unwrap();
+
+        let stream = loop {
+            match listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => panic!("{:?}", e),
+            }
+        };
+        config.apply(&stream).unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(socket.nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        assert_eq!(socket.linger().unwrap(), Some(Duration::from_secs(1)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reuse_port() {
+        let config = SocketConfig {
+            reuse_port: true,
+            ..SocketConfig::default()
+        };
+
+        let first = config.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = first.local_addr().unwrap();
+
+        assert!(config.listen(addr).is_ok());
+        assert!(SocketConfig::default().listen(addr).is_err());
+    }
+}
@@ -1,33 +1,144 @@
 use mio::net;
+use socket2::SockRef;
 
 use std::future::Future;
+use std::io;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::Context;
 use std::task::Poll;
 
 use crate::io::context;
+use crate::io::socket::SocketConfig;
 
 use crate::io::reactor::IoWaker;
 
 pub(crate) struct TcpListener {
     inner: net::TcpListener,
     waker: Arc<IoWaker>,
+    reserve: Mutex<Option<std::fs::File>>,
 }
 
 #[derive(Debug)]
 pub(crate) enum AcceptError {
-    Err,
+    /// The connection failed before being accepted, the next one can be accepted right away
+    Connection(io::Error),
+    /// The process or the system is out of resources, accepting should be retried later
+    Resources(io::Error),
+    /// The listener can not accept connections anymore
+    Fatal(io::Error),
+}
+
+impl From<io::Error> for AcceptError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::PermissionDenied => AcceptError::Connection(e),
+            _ if is_resource_error(&e) => AcceptError::Resources(e),
+            _ => AcceptError::Fatal(e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_resource_error(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        Some(code) => {
+            code == libc::EMFILE
+                || code == libc::ENFILE
+                || code == libc::ENOBUFS
+                || code == libc::ENOMEM
+        }
+        None => false,
+    }
+}
+
+#[cfg(windows)]
+fn is_resource_error(e: &io::Error) -> bool {
+    // WSAEMFILE and WSAENOBUFS
+    matches!(e.raw_os_error(), Some(10024) | Some(10055))
 }
 
 impl TcpListener {
-    pub(crate) fn bind(addr: std::net::SocketAddr) -> TcpListener {
-        let mut inner = net::TcpListener::bind(addr).unwrap();
+    pub(crate) fn bind(
+        addr: std::net::SocketAddr,
+        config: &SocketConfig,
+    ) -> io::Result<TcpListener> {
+        Ok(TcpListener::from_mio(config.listen(addr)?))
+    }
+
+    /// Take over a listener opened by another server, possibly in another process
+    pub(crate) fn from_std(listener: std::net::TcpListener) -> io::Result<TcpListener> {
+        listener.set_nonblocking(true)?;
 
+        Ok(TcpListener::from_mio(net::TcpListener::from_std(listener)))
+    }
+
+    fn from_mio(mut inner: net::TcpListener) -> TcpListener {
         let handle = context::handle().expect("Context not initialized");
-        let waker = handle.register(&mut inner);
+        let waker = handle.register(&mut inner, mio::Interest::READABLE);
+
+        TcpListener {
+            inner,
+            waker,
+            reserve: Mutex::from(None),
+        }
+    }
+
+    /// Keep a file descriptor open in reserve so [`shed`] can still accept connections once the process is out of them
+    ///
+    /// [`shed`]: #method.shed
+    pub(crate) fn reserve_fd(&self) {
+        *self.reserve.lock().unwrap() = open_reserve();
+    }
+
+    /// Release the reserved file descriptor to accept the pending connection and close it right away.
+    /// Return false if there is no reserve or nothing could be accepted.
+    pub(crate) fn shed(&self) -> bool {
+        let mut reserve = self.reserve.lock().unwrap();
+        if reserve.take().is_none() {
+            return false;
+        }
+
+        let shed = self.inner.accept().is_ok();
+        *reserve = open_reserve();
+
+        shed
+    }
 
-        TcpListener { inner, waker }
+    pub(crate) fn local_addr(&self) -> std::io::Result<std::net::SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Duplicate the listening socket so it can be handed to another server.
+    /// On Unix the duplicate is inherited by the child processes.
+    pub(crate) fn export(&self) -> io::Result<std::net::TcpListener> {
+        let socket = SockRef::from(&self.inner).try_clone()?;
+
+        #[cfg(unix)]
+        socket.set_cloexec(false)?;
+
+        Ok(socket.into())
+    }
+
+    /// Accept a connection, the task is woken once a new connection is pending
+    pub(crate) fn poll_accept(
+        &self,
+        cx: &mut Context,
+    ) -> Poll<Result<(net::TcpStream, std::net::SocketAddr), AcceptError>> {
+        self.waker.register(cx.waker());
+
+        match self.inner.accept() {
+            Ok(result) => Poll::Ready(Ok(result)),
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                self.waker.pending().map_err(AcceptError::Fatal)
+            }
+            Err(e) => Poll::Ready(Err(AcceptError::from(e))),
+        }
     }
 
     pub(crate) async fn accept(
@@ -50,16 +161,20 @@ impl Future for AcceptFuture<'_> {
     type Output = Result<(net::TcpStream, std::net::SocketAddr), AcceptError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        self.waker.set_waker(cx.waker().clone());
-
-        match self.listener.inner.accept() {
-            Ok(result) => Poll::Ready(Ok(result)),
-            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => Poll::Pending,
-            Err(_e) => Poll::Ready(Err(AcceptError::Err)),
-        }
+        self.listener.poll_accept(cx)
     }
 }
 
+#[cfg(unix)]
+fn open_reserve() -> Option<std::fs::File> {
+    std::fs::File::open("/dev/null").ok()
+}
+
+#[cfg(windows)]
+fn open_reserve() -> Option<std::fs::File> {
+    std::fs::File::open("NUL").ok()
+}
+
 impl Drop for TcpListener {
     fn drop(&mut self) {
         let handle = match context::handle() {
@@ -70,3 +185,29 @@ impl Drop for TcpListener {
         handle.deregister(&mut self.inner, self.waker.clone());
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classify_errors() {
+        let aborted = io::Error::from(io::ErrorKind::ConnectionAborted);
+        assert!(matches!(
+            AcceptError::from(aborted),
+            AcceptError::Connection(_)
+        ));
+
+        #[cfg(unix)]
+        {
+            let exhausted = io::Error::from_raw_os_error(libc::EMFILE);
+            assert!(matches!(
+                AcceptError::from(exhausted),
+                AcceptError::Resources(_)
+            ));
+        }
+
+        let invalid = io::Error::from(io::ErrorKind::InvalidInput);
+        assert!(matches!(AcceptError::from(invalid), AcceptError::Fatal(_)));
+    }
+}
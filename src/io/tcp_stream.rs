@@ -1,5 +1,5 @@
 use futures::io::Error;
-use futures::AsyncRead;
+use futures::{AsyncRead, AsyncWrite};
 use mio::net;
 
 use std::io::Read;
@@ -9,22 +9,84 @@ use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
 
+use log::warn;
+
 use crate::io::context;
+use crate::io::send_file::SendFile;
+use crate::io::socket::SocketConfig;
 
 use crate::io::reactor::IoWaker;
 
 pub struct TcpStream {
-    inner: net::TcpStream,
+    /// Only taken out by [`into_std`](#method.into_std), which consumes the stream
+    inner: Option<net::TcpStream>,
     waker: Arc<IoWaker>,
 }
 
 impl TcpStream {
-    pub(crate) fn from_stream(inner: net::TcpStream) -> TcpStream {
+    pub(crate) fn from_stream(inner: net::TcpStream, config: &SocketConfig) -> TcpStream {
         let mut inner = inner;
 
+        if let Err(e) = config.apply(&inner) {
+            warn!("Could not set the socket options of the connection : {}", e);
+        }
+
         let handle = context::handle().expect("Context not initialized");
-        let waker = handle.register(&mut inner);
-        TcpStream { inner, waker }
+        // A single task reads and writes the connection, both events wake it up
+        let waker = handle.register(
+            &mut inner,
+            mio::Interest::READABLE | mio::Interest::WRITABLE,
+        );
+        TcpStream {
+            inner: Some(inner),
+            waker,
+        }
+    }
+
+    fn inner(&self) -> &net::TcpStream {
+        self.inner.as_ref().expect("The stream was converted")
+    }
+
+    /// Deregister the stream from the reactor and turn it into a blocking std stream
+    pub(crate) fn into_std(mut self) -> std::io::Result<std::net::TcpStream> {
+        let mut inner = self.inner.take().expect("The stream was converted");
+
+        if let Some(handle) = context::handle() {
+            handle.deregister(&mut inner, self.waker.clone());
+        }
+
+        let stream = into_std_stream(inner);
+        stream.set_nonblocking(false)?;
+
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+fn into_std_stream(stream: net::TcpStream) -> std::net::TcpStream {
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+    unsafe { std::net::TcpStream::from_raw_fd(stream.into_raw_fd()) }
+}
+
+#[cfg(windows)]
+fn into_std_stream(stream: net::TcpStream) -> std::net::TcpStream {
+    use std::os::windows::io::{FromRawSocket, IntoRawSocket};
+
+    unsafe { std::net::TcpStream::from_raw_socket(stream.into_raw_socket()) }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.inner().as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawSocket for TcpStream {
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.inner().as_raw_socket()
     }
 }
 
@@ -34,33 +96,77 @@ impl AsyncRead for TcpStream {
         cx: &mut Context,
         buf: &mut [u8],
     ) -> Poll<Result<usize, Error>> {
-        self.waker.set_waker(cx.waker().clone());
+        self.waker.register(cx.waker());
+
+        let this = self.get_mut();
+        match this.inner().read(buf) {
+            Ok(n) => Poll::Ready(Ok(n)),
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => this.waker.pending(),
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        self.waker.register(cx.waker());
 
-        match self.get_mut().inner.read(buf) {
+        let this = self.get_mut();
+        match this.inner().write(buf) {
             Ok(n) => Poll::Ready(Ok(n)),
-            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => Poll::Pending,
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => this.waker.pending(),
             Err(e) => Poll::Ready(Err(e)),
         }
     }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Error>> {
+        Poll::Ready(self.get_mut().inner().flush())
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Error>> {
+        Poll::Ready(self.inner().shutdown(std::net::Shutdown::Write))
+    }
+}
+
+impl SendFile for TcpStream {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn poll_send_file(
+        &mut self,
+        cx: &mut Context,
+        file: &std::fs::File,
+        offset: u64,
+        count: usize,
+    ) -> Poll<Result<Option<usize>, Error>> {
+        self.waker.register(cx.waker());
+
+        match crate::io::send_file::poll_sendfile(self.inner(), file, offset, count) {
+            Poll::Pending => self.waker.pending(),
+            sent => sent,
+        }
+    }
 }
 
 impl Write for TcpStream {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.inner.write(buf)
+        self.inner().write(buf)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.inner.flush()
+        self.inner().flush()
     }
 }
 
 impl Drop for TcpStream {
     fn drop(&mut self) {
-        let handle = match context::handle() {
-            Some(handle) => handle,
-            None => return,
+        let (handle, inner) = match (context::handle(), self.inner.as_mut()) {
+            (Some(handle), Some(inner)) => (handle, inner),
+            _ => return,
         };
 
-        handle.deregister(&mut self.inner, self.waker.clone());
+        handle.deregister(inner, self.waker.clone());
     }
 }
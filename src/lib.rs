@@ -9,18 +9,70 @@ mod io;
 mod request;
 mod response;
 mod router;
+pub mod test;
+#[cfg(feature = "tower")]
+mod tower;
 
-pub use aioserver::server::ServerHandle;
+pub use aioserver::cancellation::CancellationToken;
+pub use aioserver::concurrency_limit::ConcurrencyStats;
+pub use aioserver::config::{ConfigError, ServerConfig};
+pub use aioserver::deadline::Deadline;
+pub use aioserver::enhanced_stream::RequestError;
+pub use aioserver::http_connection::HttpConnection;
+pub use aioserver::informational::{send_informational, InformationalError};
+pub use aioserver::ip_limit::LimitAction;
+pub use aioserver::middleware::{AsyncMiddleware, AsyncNext};
+pub use aioserver::observer::{
+    ConnectionInfo, ConnectionObserver, ConnectionStats, KeepAliveStats, TransferStats,
+};
+pub use aioserver::request_id::RequestId;
+pub use aioserver::server::{
+    LengthMismatchPolicy, OverloadPolicy, Priority, ServerEvent, ServerHandle, ShutdownReason,
+};
+pub use aioserver::transcript::{Transcript, TranscriptEntry};
+pub use aioserver::tunnel::Tunnel;
+pub use aioserver::upgrade::Upgrade;
 pub use aioserver::AIOServer;
+pub use data::broadcast;
+pub use data::channel;
+pub use data::object_pool::ObjectPoolStats;
+pub use data::sync;
+pub use executor::instrument::ExecutorStats;
+pub use executor::task_local;
+pub use executor::yield_now::{yield_now, YieldNow};
+pub use http::encoding;
 pub use http::parser::ParseError;
 pub use http::BuildError;
+pub use http::HeaderName;
 pub use http::Headers;
 pub use http::Method;
+pub use http::Uri;
 pub use http::Version;
+pub use io::duplex::{duplex, DuplexStream};
+pub use io::evented::{Evented, EventedIo};
+pub use io::runtime;
+/// Version of mio used by the reactor, to implement `mio::event::Source` for the sources given to [`Evented`]
+///
+/// [`Evented`]: struct.Evented.html
+pub use mio;
+pub use request::request_parser::RequestParser;
 pub use request::Request;
 pub use request::RequestBuilder;
+pub use request::{UploadError, UploadedFile, Uploads};
+pub use response::CacheControl;
+pub use response::FileError;
 pub use response::Reason;
 pub use response::Response;
 pub use response::ResponseBuilder;
-pub use router::route::Route;
+pub use response::StaticFiles;
+pub use response::{ErrorFormat, ErrorPage};
+pub use router::circuit_breaker;
+pub use router::circuit_breaker::{CircuitBreaker, CircuitError, CircuitState};
+pub use router::middleware::{Middleware, Next};
+pub use router::route::{Route, RouteDoc};
+#[cfg(feature = "session")]
+pub use router::session;
+pub use router::split::Split;
+pub use router::trace_context;
+pub use router::trace_context::TraceContext;
 pub use router::Router;
@@ -1,5 +1,7 @@
+pub(crate) mod multipart;
 mod request;
 pub(crate) mod request_parser;
 
+pub use multipart::{UploadError, UploadedFile, Uploads};
 pub use request::Request;
 pub use request::RequestBuilder;
@@ -0,0 +1,388 @@
+use crate::request::Request;
+use crate::{Response, ResponseBuilder};
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const MULTIPART_FORM: &str = "multipart/form-data";
+
+/// Number of the next file written, the names of the files are unique in the process
+static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// Save the files sent in a "multipart/form-data" body to a directory, with limits on their size.
+/// The files are written as `upload-<process id>-<number>`, the name given by the client is only kept
+/// in the [`UploadedFile`] so it can not be used to write outside of the directory.
+///
+/// The uploads are not streamed: the server reads the whole body in memory before the handler is called,
+/// so the limits only bound what is written to the directory, not the memory used by the request.
+/// Each part is written from the body without being copied again.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{ResponseBuilder, Uploads};
+///
+/// let uploads = Uploads::new(std::env::temp_dir()).max_file_size(10 * 1024 * 1024);
+///
+/// let server = mini_async_http::AIOServer::new("127.0.0.1:7900".parse().unwrap(), move |request| {
+///     match uploads.save(request) {
+///         Ok(files) => {
+///             let names: Vec<&str> = files.iter().map(|file| file.filename()).collect();
+///             ResponseBuilder::empty_200().text(&names.join(", ")).build().unwrap()
+///         }
+///         Err(e) => e.response(),
+///     }
+/// });
+/// ```
+///
+/// [`UploadedFile`]: struct.UploadedFile.html
+#[derive(Debug, Clone)]
+pub struct Uploads {
+    dir: PathBuf,
+    max_file_size: Option<usize>,
+    max_total_size: Option<usize>,
+}
+
+/// File of a multipart body saved by [`Uploads::save`]
+///
+/// [`Uploads::save`]: struct.Uploads.html#method.save
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadedFile {
+    field: String,
+    filename: String,
+    content_type: Option<String>,
+    path: PathBuf,
+    size: usize,
+}
+
+impl UploadedFile {
+    /// Return the name of the form field the file was sent with
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Return the name of the file on the client
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Return the Content-Type of the part, if the client sent one
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Return the path the file was saved to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the size of the file in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Error returned by [`Uploads::save`], no file is left in the directory
+///
+/// [`Uploads::save`]: struct.Uploads.html#method.save
+#[derive(Debug)]
+pub enum UploadError {
+    /// The body is not "multipart/form-data" or has no boundary
+    NotMultipart,
+    /// The body does not follow the multipart format
+    Malformed,
+    /// A file is larger than the limit set with [`Uploads::max_file_size`]
+    ///
+    /// [`Uploads::max_file_size`]: struct.Uploads.html#method.max_file_size
+    FileTooLarge,
+    /// The files are larger together than the limit set with [`Uploads::max_total_size`]
+    ///
+    /// [`Uploads::max_total_size`]: struct.Uploads.html#method.max_total_size
+    TooLarge,
+    /// A file could not be written
+    WriteError(io::Error),
+}
+
+impl UploadError {
+    /// Return the response to send instead of handling the upload.
+    /// A body which is not multipart gives a 415 status code, a malformed one a 400, files too large a 413
+    /// and the write errors a 500.
+    pub fn response(&self) -> Response {
+        let builder = match self {
+            UploadError::NotMultipart => ResponseBuilder::empty_415(),
+            UploadError::Malformed => ResponseBuilder::empty_400(),
+            UploadError::FileTooLarge | UploadError::TooLarge => ResponseBuilder::empty_413(),
+            UploadError::WriteError(_) => ResponseBuilder::empty_500(),
+        };
+
+        builder.build().unwrap()
+    }
+}
+
+/// Part of a multipart body
+struct Part<'a> {
+    name: Option<String>,
+    filename: Option<String>,
+    content_type: Option<String>,
+    data: &'a [u8],
+}
+
+impl Uploads {
+    /// Save the files in the given directory, which must exist
+    pub fn new<P: AsRef<Path>>(dir: P) -> Uploads {
+        Uploads {
+            dir: dir.as_ref().to_path_buf(),
+            max_file_size: None,
+            max_total_size: None,
+        }
+    }
+
+    /// Reject the uploads containing a file larger than `size` bytes
+    pub fn max_file_size(mut self, size: usize) -> Uploads {
+        self.max_file_size = Some(size);
+        self
+    }
+
+    /// Reject the uploads whose files are larger than `size` bytes together
+    pub fn max_total_size(mut self, size: usize) -> Uploads {
+        self.max_total_size = Some(size);
+        self
+    }
+
+    /// Write the file parts of the request to the directory and return them in the order they were sent.
+    /// The parts without filename are regular form fields and are not saved.
+    /// The limits are checked before anything is written.
+    pub fn save(&self, request: &Request) -> Result<Vec<UploadedFile>, UploadError> {
+        let boundary = boundary(request).ok_or(UploadError::NotMultipart)?;
+        let body = request.body().map(Vec::as_slice).unwrap_or_default();
+        let parts = parse_parts(body, boundary).ok_or(UploadError::Malformed)?;
+
+        let files: Vec<Part> = parts
+            .into_iter()
+            .filter(|part| part.filename.is_some())
+            .collect();
+
+        if let Some(max) = self.max_file_size {
+            if files.iter().any(|part| part.data.len() > max) {
+                return Err(UploadError::FileTooLarge);
+            }
+        }
+        if let Some(max) = self.max_total_size {
+            if files.iter().map(|part| part.data.len()).sum::<usize>() > max {
+                return Err(UploadError::TooLarge);
+            }
+        }
+
+        let mut saved: Vec<UploadedFile> = Vec::with_capacity(files.len());
+        for part in files {
+            match self.write(part) {
+                Ok(file) => saved.push(file),
+                Err(e) => {
+                    for file in saved {
+                        let _ = fs::remove_file(file.path);
+                    }
+                    return Err(UploadError::WriteError(e));
+                }
+            }
+        }
+
+        Ok(saved)
+    }
+
+    fn write(&self, part: Part) -> io::Result<UploadedFile> {
+        let name = format!(
+            "upload-{}-{}",
+            std::process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = self.dir.join(name);
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        if let Err(e) = file.write_all(part.data) {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+
+        Ok(UploadedFile {
+            field: part.name.unwrap_or_default(),
+            filename: part.filename.unwrap_or_default(),
+            content_type: part.content_type,
+            path,
+            size: part.data.len(),
+        })
+    }
+}
+
+/// Return the boundary of a multipart body, its case is kept as it is compared exactly
+fn boundary(request: &Request) -> Option<&str> {
+    if !request.content_type()?.eq_ignore_ascii_case(MULTIPART_FORM) {
+        return None;
+    }
+
+    request
+        .headers()
+        .get_header("Content-Type")?
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"'))
+        .filter(|boundary| !boundary.is_empty())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Split a multipart body in parts, None if it is malformed
+fn parse_parts<'a>(body: &'a [u8], boundary: &str) -> Option<Vec<Part<'a>>> {
+    let delimiter = format!("--{}", boundary);
+    let separator = format!("\r\n--{}", boundary);
+
+    let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+    let mut parts = Vec::new();
+
+    loop {
+        if rest.starts_with(b"--") {
+            return Some(parts);
+        }
+        rest = rest.strip_prefix(b"\r\n")?;
+
+        let head_end = find(rest, b"\r\n\r\n")?;
+        let head = std::str::from_utf8(&rest[..head_end]).ok()?;
+        rest = &rest[head_end + 4..];
+
+        let data_end = find(rest, separator.as_bytes())?;
+        let mut part = Part {
+            name: None,
+            filename: None,
+            content_type: None,
+            data: &rest[..data_end],
+        };
+        rest = &rest[data_end + separator.len()..];
+
+        for line in head.split("\r\n") {
+            let (name, value) = line.split_once(':')?;
+            let value = value.trim();
+
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                for (param, param_value) in value
+                    .split(';')
+                    .skip(1)
+                    .filter_map(|param| param.trim().split_once('='))
+                {
+                    let param_value = param_value.trim().trim_matches('"').to_string();
+                    match param.trim() {
+                        "name" => part.name = Some(param_value),
+                        "filename" => part.filename = Some(param_value),
+                        _ => {}
+                    }
+                }
+            } else if name.trim().eq_ignore_ascii_case("content-type") {
+                part.content_type = Some(value.to_string());
+            }
+        }
+
+        parts.push(part);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Headers, Method, RequestBuilder, Version};
+
+    const BODY: &[u8] = b"--XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Holidays\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"Beach.png\"\r\n\
+        Content-Type: image/png\r\n\r\n\
+        \x89PNG\r\n\x1a\n\r\n\
+        --XyZ--\r\n";
+
+    fn post(content_type: &str, body: &[u8]) -> Request {
+        let mut headers = Headers::new();
+        headers.set_header("Content-Type", content_type);
+
+        RequestBuilder::new()
+            .method(Method::POST)
+            .path(String::from("/upload"))
+            .version(Version::HTTP11)
+            .headers(headers)
+            .body(body)
+            .build()
+            .unwrap()
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("mini_async_http_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse() {
+        let parts = parse_parts(BODY, "XyZ").unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name.as_deref(), Some("title"));
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"Holidays");
+        assert_eq!(parts[1].filename.as_deref(), Some("Beach.png"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("image/png"));
+        assert_eq!(parts[1].data, b"\x89PNG\r\n\x1a\n");
+
+        assert!(parse_parts(b"--XyZ\r\nno head", "XyZ").is_none());
+        // The boundary is case sensitive, "--xyz" in the body is not a delimiter
+        assert!(parse_parts(BODY, "xyz").is_none());
+    }
+
+    #[test]
+    fn save() {
+        let dir = dir("save");
+        let request = post("multipart/form-data; boundary=XyZ", BODY);
+
+        let files = Uploads::new(&dir).save(&request).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].field(), "photo");
+        assert_eq!(files[0].filename(), "Beach.png");
+        assert_eq!(files[0].size(), 8);
+        assert!(files[0].path().starts_with(&dir));
+        assert_eq!(fs::read(files[0].path()).unwrap(), b"\x89PNG\r\n\x1a\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn limits() {
+        let dir = dir("limits");
+        let request = post("multipart/form-data; boundary=XyZ", BODY);
+
+        match Uploads::new(&dir).max_file_size(4).save(&request) {
+            Err(UploadError::FileTooLarge) => {}
+            other => panic!("Should be too large, got {:?}", other),
+        }
+        match Uploads::new(&dir).max_total_size(4).save(&request) {
+            Err(UploadError::TooLarge) => {}
+            other => panic!("Should be too large, got {:?}", other),
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let request = post("application/json", b"{}");
+        match Uploads::new(&dir).save(&request) {
+            Err(e @ UploadError::NotMultipart) => assert_eq!(e.response().code(), 415),
+            other => panic!("Should not be multipart, got {:?}", other),
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,16 +1,32 @@
+use crate::aioserver::cancellation::CancellationToken;
+use crate::aioserver::deadline::Deadline;
+use crate::executor::task_local;
+use crate::http::accept::{negotiate, parse_accept, quality};
+use crate::http::header::{
+    CLOSE_CONNECTION_HEADER, CONNECTION_HEADER, KEEP_ALIVE_CONNECTION_HEADER,
+};
 use crate::http::parser::BuildError;
 use crate::http::Headers;
 use crate::http::Method;
+use crate::http::Uri;
 use crate::http::Version;
 
 use std::convert::TryFrom;
 use std::fmt;
+use std::time::Instant;
+
+const HTML: &str = "text/html";
+const JSON: &str = "application/json";
+const FORM: &str = "application/x-www-form-urlencoded";
+const MULTIPART_FORM: &str = "multipart/form-data";
 
 /// Represent an http request.  
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Request {
     method: Method,
-    path: String,
+    #[cfg_attr(feature = "serde", serde(rename = "path"))]
+    uri: Uri,
     version: Version,
     headers: Headers,
     body: Option<Vec<u8>>,
@@ -22,9 +38,45 @@ impl Request {
         &self.method
     }
 
-    /// Return the target path of the request
+    /// Return the target of the request with its query string.
+    /// The server resolves the dot segments of its path before routing, see [`AIOServer::set_merge_slashes`].
+    ///
+    /// [`AIOServer::set_merge_slashes`]: struct.AIOServer.html#method.set_merge_slashes
     pub fn path(&self) -> &String {
-        &self.path
+        self.uri.as_string()
+    }
+
+    /// Normalize the path of the target before the request is routed, see [`Uri`]
+    ///
+    /// [`Uri`]: struct.Uri.html
+    pub(crate) fn normalize(&mut self, merge_slashes: bool) {
+        self.uri.normalize(merge_slashes);
+    }
+
+    /// Return the target of the request split in its components
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Method, RequestBuilder, Version};
+    ///
+    /// let request = RequestBuilder::new()
+    ///     .method(Method::GET)
+    ///     .path(String::from("/users/42?fields=name"))
+    ///     .version(Version::HTTP11)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(request.uri().path(), "/users/42");
+    /// assert_eq!(request.uri().query_pairs().collect::<Vec<_>>(), vec![("fields", "name")]);
+    /// ```
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Return the query string of the request, the part of the target after the '?'
+    pub fn query(&self) -> Option<&str> {
+        self.uri.query()
     }
 
     /// Return the HTTP version of the request
@@ -37,6 +89,192 @@ impl Request {
         &self.headers
     }
 
+    /// Return the length of the body announced by the Content-Length header.
+    /// None is returned if the header is missing or is not a valid length
+    pub fn content_length(&self) -> Option<usize> {
+        self.headers
+            .get_header("Content-Length")
+            .and_then(|length| length.parse().ok())
+    }
+
+    /// Return true if the client wants to keep the connection open after the response.
+    /// HTTP/1.1 connections are persistent unless the Connection header contains "close",
+    /// HTTP/1.0 connections are persistent only if it contains "keep-alive".
+    pub fn keep_alive(&self) -> bool {
+        match self.version {
+            Version::HTTP10 => self
+                .headers
+                .has_token(CONNECTION_HEADER, KEEP_ALIVE_CONNECTION_HEADER),
+            Version::HTTP11 => !self
+                .headers
+                .has_token(CONNECTION_HEADER, CLOSE_CONNECTION_HEADER),
+        }
+    }
+
+    /// Return the protocols the client asks to switch to, given by the Upgrade header when the
+    /// Connection header contains "upgrade". See [`ResponseBuilder::switching_protocols`] to accept it.
+    /// The value is kept as sent, the protocol names should be compared ignoring case.
+    ///
+    /// [`ResponseBuilder::switching_protocols`]: struct.ResponseBuilder.html#method.switching_protocols
+    pub fn upgrade(&self) -> Option<&str> {
+        if !self.headers.has_token(CONNECTION_HEADER, "upgrade") {
+            return None;
+        }
+
+        self.headers
+            .get_header("Upgrade")
+            .map(|protocols| protocols.trim())
+    }
+
+    /// Return the media type of the body given by the Content-Type header, without its parameters
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get_header("Content-Type")
+            .and_then(|content_type| content_type.split(';').next())
+            .map(str::trim)
+    }
+
+    /// Return true if the body is JSON, as "application/json" or a "+json" media type
+    pub fn is_json(&self) -> bool {
+        match self.content_type() {
+            Some(content_type) => {
+                content_type.eq_ignore_ascii_case(JSON)
+                    || content_type.to_ascii_lowercase().ends_with("+json")
+            }
+            None => false,
+        }
+    }
+
+    /// Return true if the body is an HTML form, url encoded or multipart
+    pub fn is_form(&self) -> bool {
+        matches!(self.content_type(), Some(content_type) if content_type.eq_ignore_ascii_case(FORM)
+            || content_type.eq_ignore_ascii_case(MULTIPART_FORM))
+    }
+
+    /// Return true if the client accepts the media type according to its Accept header.
+    /// Every media type is accepted when the header is missing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Headers, Method, RequestBuilder, Version};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set_header("Accept", "text/*, application/json;q=0");
+    ///
+    /// let request = RequestBuilder::new()
+    ///     .method(Method::GET)
+    ///     .path(String::from("/"))
+    ///     .version(Version::HTTP11)
+    ///     .headers(headers)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert!(request.accepts("text/csv"));
+    /// assert!(!request.accepts("application/json"));
+    /// ```
+    pub fn accepts(&self, media_type: &str) -> bool {
+        match self.headers.get_header("Accept") {
+            Some(accept) => {
+                matches!(quality(&parse_accept(accept), media_type), Some((q, _)) if q > 0.0)
+            }
+            None => true,
+        }
+    }
+
+    /// Return the media type the client prefers among the given ones according to its Accept header.
+    /// The first one is returned when the header is missing or when several are preferred equally,
+    /// None is returned if none is accepted.
+    pub fn preferred_type<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        negotiate(
+            self.headers.get_header("Accept").map(String::as_str),
+            offers,
+        )
+    }
+
+    /// Return true if the client prefers HTML to JSON, as browsers do.
+    /// HTML has to be accepted explicitly, accepting every media type with `*/*` is not enough.
+    pub fn wants_html(&self) -> bool {
+        let ranges = match self.headers.get_header("Accept") {
+            Some(accept) => parse_accept(accept),
+            None => return false,
+        };
+
+        match (quality(&ranges, HTML), quality(&ranges, JSON)) {
+            (Some((html, precision)), _) if precision == 0 || html <= 0.0 => false,
+            (Some((html, _)), Some((json, _))) => html >= json,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Return the value of the cookie with the given name sent in the Cookie header
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.headers
+            .get_header("Cookie")?
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.to_string())
+    }
+
+    /// Return the instant the response is due, set by the request timeout of the server or the timeout of the matching route.
+    /// None when no timeout applies or outside of the server, for example when the request is given to a router directly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Request, Response, ResponseBuilder};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// fn handler(request: &Request) -> Response {
+    ///     let budget = request
+    ///         .deadline()
+    ///         .map_or(Duration::from_secs(30), |deadline| deadline.saturating_duration_since(Instant::now()));
+    ///
+    ///     // Give the remaining budget to the downstream calls
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// }
+    /// ```
+    pub fn deadline(&self) -> Option<Instant> {
+        task_local::get::<Deadline>().map(|deadline| deadline.at())
+    }
+
+    /// Return the cancellation token of the request while it is handled by the server.
+    /// It is cancelled when the server is shut down, when the connection of the request is closed
+    /// and once the deadline of the request is passed, so long running handlers can stop their work.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Request, Response, ResponseBuilder};
+    ///
+    /// fn handler(request: &Request) -> Response {
+    ///     let token = request.cancellation();
+    ///     for _ in 0..100 {
+    ///         if matches!(&token, Some(token) if token.is_cancelled()) {
+    ///             return ResponseBuilder::empty_503().build().unwrap();
+    ///         }
+    ///         // Some work ...
+    ///     }
+    ///
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// }
+    /// ```
+    pub fn cancellation(&self) -> Option<CancellationToken> {
+        let token = task_local::get::<CancellationToken>()?;
+
+        Some(match task_local::get::<Deadline>() {
+            Some(deadline) => token.with_deadline(deadline.at()),
+            None => token,
+        })
+    }
+
+    /// Give the memory of the request back to the pools of the thread once it is answered
+    pub(crate) fn recycle(self) {
+        self.headers.recycle();
+    }
+
     /// Return the body of the request as byte vector
     pub fn body(&self) -> Option<&Vec<u8>> {
         self.body.as_ref()
@@ -62,7 +300,7 @@ impl fmt::Display for Request {
             format!(
                 "{} {} {}\r\n",
                 self.method.as_str(),
-                self.path,
+                self.uri,
                 self.version.as_str()
             )
             .as_str(),
@@ -147,15 +385,17 @@ impl RequestBuilder {
     }
 
     /// Build the request with provided informations.
-    /// If some informations are missing, BuildError will occur
+    /// If some informations are missing or the path is not a valid [`Uri`], BuildError will occur
+    ///
+    /// [`Uri`]: struct.Uri.html
     pub fn build(self) -> Result<Request, BuildError> {
         let method = match self.method {
             Some(val) => val,
             None => return Result::Err(BuildError::Incomplete),
         };
 
-        let path = match self.path {
-            Some(val) => val,
+        let uri = match self.path {
+            Some(val) => Uri::try_from(val)?,
             None => return Result::Err(BuildError::Incomplete),
         };
 
@@ -166,7 +406,7 @@ impl RequestBuilder {
 
         Result::Ok(Request {
             method,
-            path,
+            uri,
             version,
             headers: self.headers,
             body: self.body,
@@ -179,3 +419,57 @@ impl Default for RequestBuilder {
         RequestBuilder::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(name: &str, value: &str) -> Request {
+        let mut headers = Headers::new();
+        headers.set_header(name, value);
+
+        RequestBuilder::new()
+            .method(Method::POST)
+            .path(String::from("/"))
+            .version(Version::HTTP11)
+            .headers(headers)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn no_deadline_outside_server() {
+        assert_eq!(request("Accept", "*/*").deadline(), None);
+    }
+
+    #[test]
+    fn content_types() {
+        assert!(request("Content-Type", "application/json; charset=utf-8").is_json());
+        assert!(request("Content-Type", "application/problem+json").is_json());
+        assert!(request("Content-Type", "multipart/form-data; boundary=x").is_form());
+        assert!(!request("Content-Type", "text/plain").is_form());
+        assert!(!request("Accept", "application/json").is_json());
+    }
+
+    #[test]
+    fn wants_html() {
+        let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+        assert!(request("Accept", browser).wants_html());
+        assert!(!request("Accept", "*/*").wants_html());
+        assert!(!request("Accept", "application/json, text/html;q=0.5").wants_html());
+        assert!(!request("Content-Type", "text/html").wants_html());
+    }
+
+    #[test]
+    fn preferred_type() {
+        let request = request("Accept", "application/json;q=0.5, text/html");
+
+        assert_eq!(
+            request.preferred_type(&["application/json", "text/html"]),
+            Some("text/html")
+        );
+        assert_eq!(request.preferred_type(&["image/png"]), None);
+        assert!(request.accepts("application/json"));
+    }
+}
@@ -5,62 +5,79 @@ use crate::http::Version;
 use crate::request::Request;
 use crate::request::RequestBuilder;
 
-pub(crate) struct RequestParser {}
+/// Number of headers the parser can hold without allocating their storage
+const INLINE_HEADERS: usize = 64;
+
+/// Parse http requests out of raw bytes.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{Method, RequestParser};
+///
+/// let input = b"GET /first HTTP/1.1\r\n\r\nGET /second HTTP/1.1\r\n\r\n";
+/// let parser = RequestParser::new();
+///
+/// let (first, consumed) = parser.parse_u8(input).unwrap();
+/// let (second, _) = parser.parse_u8(&input[consumed..]).unwrap();
+///
+/// assert_eq!(first.path(), "/first");
+/// assert_eq!(second.path(), "/second");
+/// ```
+pub struct RequestParser {
+    max_headers: usize,
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        RequestParser::new()
+    }
+}
 
 impl RequestParser {
     pub fn new() -> RequestParser {
-        RequestParser {}
+        RequestParser {
+            max_headers: INLINE_HEADERS,
+        }
     }
 
-    pub fn parse_u8(&self, reader: &[u8]) -> Result<(Request, usize), ParseError> {
-        let mut headers = [httparse::EMPTY_HEADER; 64];
-        let mut req = httparse::Request::new(&mut headers);
-
-        let res = match req.parse(reader) {
-            Ok(httparse::Status::Partial) => return Err(ParseError::UnexpectedEnd),
-            Ok(httparse::Status::Complete(n)) => n,
-            Err(e) => return Err(ParseError::from(e)),
-        };
-
-        let mut builder = RequestBuilder::new()
-            .method(req.method.unwrap().parse().unwrap())
-            .path(String::from(req.path.unwrap()))
-            .version(Version::HTTP11);
-
-        let mut headers = Headers::new();
-
-        for header in req.headers {
-            let name = String::from(header.name);
-            let val = String::from_utf8(header.value.to_vec()).unwrap();
+    /// Set the maximum number of headers of a request, 64 by default. Requests with more headers are rejected
+    /// with a TooManyHeaders error. Up to 64 headers are parsed on the stack, the storage of a larger limit
+    /// is only allocated for the requests having more than 64 headers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::RequestParser;
+    ///
+    /// let parser = RequestParser::new().max_headers(128);
+    /// ```
+    pub fn max_headers(mut self, max: usize) -> RequestParser {
+        self.max_headers = max;
+        self
+    }
 
-            headers.set_header(&name, &val)
-        }
+    /// Parse the first request contained in the slice.
+    /// Return the request and the number of bytes consumed, so pipelined requests can be parsed one after the other.
+    pub fn parse_u8(&self, reader: &[u8]) -> Result<(Request, usize), ParseError> {
+        let (builder, res, length) = self.parse_head(reader)?;
 
-        let length = match headers.get_header(&String::from("Content-length")) {
-            Some(n) => n,
+        let length = match length {
+            Some(length) => length,
             None => {
-                builder = builder.headers(headers);
-                let request = match builder.build() {
-                    Ok(req) => req,
-                    Err(e) => return Err(ParseError::BuilderError(e)),
-                };
-
-                return Ok((request, res));
+                return match builder.build() {
+                    Ok(req) => Ok((req, res)),
+                    Err(e) => Err(ParseError::BuilderError(e)),
+                }
             }
         };
 
-        let length = match length.parse::<usize>() {
-            Ok(val) => val,
-            Err(_e) => return Err(ParseError::LengthParse),
-        };
-
         if reader.len() < res + length {
             return Err(ParseError::UnexpectedEnd);
         }
 
         let body = &reader[res..res + length];
-        let builder = builder.body(&body);
-        let builder = builder.headers(headers);
+        let builder = builder.body(body);
 
         let request = match builder.build() {
             Ok(req) => req,
@@ -69,6 +86,113 @@ impl RequestParser {
 
         Ok((request, res + length))
     }
+
+    /// Parse the request line and the headers of the first request contained in the slice.
+    /// Return a builder holding them, the number of bytes consumed and the body length announced by the Content-Length header.
+    /// The body itself does not need to be buffered yet so it can be read progressively.
+    pub(crate) fn parse_head(
+        &self,
+        reader: &[u8],
+    ) -> Result<(RequestBuilder, usize, Option<usize>), ParseError> {
+        let mut headers = [httparse::EMPTY_HEADER; INLINE_HEADERS];
+        let inline = std::cmp::min(self.max_headers, INLINE_HEADERS);
+
+        match parse_with(reader, &mut headers[..inline]) {
+            Err(ParseError::TooManyHeaders) if self.max_headers > INLINE_HEADERS => {
+                let mut headers = vec![httparse::EMPTY_HEADER; self.max_headers];
+                parse_with(reader, &mut headers)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Parse the head of a request using the given storage for the headers.
+/// The headers are borrowed from the input until they are added to the builder, where they are copied once.
+fn parse_with<'b>(
+    reader: &'b [u8],
+    storage: &mut [httparse::Header<'b>],
+) -> Result<(RequestBuilder, usize, Option<usize>), ParseError> {
+    let mut req = httparse::Request::new(storage);
+
+    let res = match req.parse(reader) {
+        Ok(httparse::Status::Partial) => return Err(ParseError::UnexpectedEnd),
+        Ok(httparse::Status::Complete(n)) => n,
+        Err(httparse::Error::HeaderName) if has_obsolete_fold(reader) => {
+            return Err(ParseError::ObsoleteFold)
+        }
+        Err(e) => return Err(ParseError::from(e)),
+    };
+
+    let method = match req.method.unwrap().parse() {
+        Ok(method) => method,
+        Err(_) => return Err(ParseError::Token),
+    };
+
+    let version = match req.version {
+        Some(0) => Version::HTTP10,
+        _ => Version::HTTP11,
+    };
+
+    let builder = RequestBuilder::new()
+        .method(method)
+        .path(String::from(req.path.unwrap()))
+        .version(version);
+
+    let mut headers = Headers::with_capacity(req.headers.len());
+    let mut length = None;
+    let mut transfer_encoding = false;
+
+    for header in req.headers.iter() {
+        let value = match std::str::from_utf8(header.value) {
+            Ok(value) => value,
+            Err(_) => return Err(ParseError::HeaderValue),
+        };
+
+        if header.name.eq_ignore_ascii_case("content-length") {
+            // Repeated values are accepted as long as they are all the same
+            for value in value.split(',') {
+                let value = parse_length(value.trim())?;
+                if matches!(length, Some(length) if length != value) {
+                    return Err(ParseError::ConflictingLength);
+                }
+                length = Some(value);
+            }
+        } else if header.name.eq_ignore_ascii_case("transfer-encoding") {
+            transfer_encoding = true;
+        }
+
+        headers.set_header(header.name, value)
+    }
+
+    // The server only delimits bodies with Content-Length, the body of another encoding would be read as the next request
+    match (transfer_encoding, length) {
+        (true, Some(_)) => return Err(ParseError::ConflictingLength),
+        (true, None) => return Err(ParseError::TransferEncoding),
+        _ => {}
+    }
+
+    Ok((builder.headers(headers), res, length))
+}
+
+/// Parse a Content-Length value, only digits are allowed so that no sign or space is interpreted differently by a proxy
+fn parse_length(value: &str) -> Result<usize, ParseError> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(ParseError::LengthParse);
+    }
+
+    value.parse().map_err(|_| ParseError::LengthParse)
+}
+
+/// Return true if a line of the head starts with a space or a tab, continuing the previous header
+fn has_obsolete_fold(reader: &[u8]) -> bool {
+    let head = match reader.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(end) => &reader[..end],
+        None => reader,
+    };
+
+    head.windows(2)
+        .any(|window| window[0] == b'\n' && (window[1] == b' ' || window[1] == b'\t'))
 }
 
 #[cfg(test)]
@@ -117,6 +241,19 @@ mod test {
         );
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let parser = RequestParser::new();
+        let input = get_resource_string("http_body.txt").as_bytes().to_vec();
+        let (request, _) = parser.parse_u8(&input).expect("Error when parsing");
+
+        let json = serde_json::to_string(&request).unwrap();
+        let back: Request = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(request, back);
+    }
+
     #[test]
     fn from_u8() {
         let parser = RequestParser::new();
@@ -184,6 +321,129 @@ mod test {
         }
     }
 
+    #[test]
+    fn head_without_body() {
+        let input = get_resource_string("http_body.txt");
+        let input = input.as_bytes();
+        let parser = RequestParser::new();
+
+        let head_len = input.len() - 16;
+        let (builder, n, length) = parser.parse_head(&input[..head_len]).unwrap();
+
+        assert_eq!(n, head_len);
+        assert_eq!(length, Some(16));
+
+        let request = builder.build().unwrap();
+        assert_eq!(request.content_length(), Some(16));
+        assert!(request.body().is_none());
+    }
+
+    #[test]
+    fn extension_method() {
+        let input = b"PROPFIND /dav HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let parser = RequestParser::new();
+
+        let (request, _) = parser.parse_u8(input).expect("Error when parsing");
+
+        assert_eq!(
+            *request.method(),
+            crate::Method::Extension(String::from("PROPFIND"))
+        );
+    }
+
+    #[test]
+    fn keep_alive() {
+        let parser = RequestParser::new();
+
+        let (request, _) = parser.parse_u8(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(*request.version(), Version::HTTP10);
+        assert!(!request.keep_alive());
+
+        let input = b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n";
+        let (request, _) = parser.parse_u8(input).unwrap();
+        assert!(request.keep_alive());
+
+        let input = b"GET / HTTP/1.1\r\nConnection: TE, Close\r\n\r\n";
+        let (request, _) = parser.parse_u8(input).unwrap();
+        assert!(!request.keep_alive());
+
+        let (request, _) = parser.parse_u8(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert!(request.keep_alive());
+    }
+
+    #[test]
+    fn max_headers() {
+        let mut input = b"GET / HTTP/1.1\r\n".to_vec();
+        for i in 0..100 {
+            input.extend_from_slice(format!("X-Header-{}: {}\r\n", i, i).as_bytes());
+        }
+        input.extend_from_slice(b"\r\n");
+
+        match RequestParser::new().parse_u8(&input) {
+            Err(ParseError::TooManyHeaders) => {}
+            other => panic!("Should have too many headers, got {:?}", other.map(|_| ())),
+        }
+
+        let (request, _) = RequestParser::new()
+            .max_headers(128)
+            .parse_u8(&input)
+            .unwrap();
+        assert_eq!(request.headers().get_header("x-header-99").unwrap(), "99");
+
+        match RequestParser::new().max_headers(10).parse_u8(&input) {
+            Err(ParseError::TooManyHeaders) => {}
+            other => panic!("Should have too many headers, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn invalid_header_value() {
+        let input = b"GET / HTTP/1.1\r\nX-Header: \xff\xfe\r\n\r\n";
+
+        match RequestParser::new().parse_u8(input) {
+            Err(ParseError::HeaderValue) => {}
+            other => panic!(
+                "Should have header value error, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+
+    #[test]
+    fn body_length_conflicts() {
+        let parser = RequestParser::new();
+        let parse = |input: &[u8]| parser.parse_u8(input).map(|(request, _)| request);
+
+        let request =
+            parse(b"POST / HTTP/1.1\r\nContent-Length: 2, 2\r\nContent-Length: 2\r\n\r\nab");
+        assert_eq!(request.unwrap().body().unwrap(), b"ab");
+
+        let conflicting: [&[u8]; 3] = [
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\nabc",
+            b"POST / HTTP/1.1\r\nContent-Length: 2, 3\r\n\r\nabc",
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\nabc",
+        ];
+        for input in conflicting.iter() {
+            assert!(matches!(parse(input), Err(ParseError::ConflictingLength)));
+        }
+
+        let chunked = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        assert!(matches!(parse(chunked), Err(ParseError::TransferEncoding)));
+
+        let signed = b"POST / HTTP/1.1\r\nContent-Length: +2\r\n\r\nab";
+        assert!(matches!(parse(signed), Err(ParseError::LengthParse)));
+    }
+
+    #[test]
+    fn obsolete_fold() {
+        let input = b"GET / HTTP/1.1\r\nX-Header: a\r\n b\r\n\r\n";
+
+        match RequestParser::new().parse_u8(input) {
+            Err(ParseError::ObsoleteFold) => {}
+            other => panic!("Should have fold error, got {:?}", other.map(|_| ())),
+        }
+    }
+
     #[test]
     fn first_line_error() {
         let input = b"zaezaexq\r\n";
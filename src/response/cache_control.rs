@@ -0,0 +1,144 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Directives of the Cache-Control header of a response, set with [`ResponseBuilder::cache_control`]
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{CacheControl, ResponseBuilder};
+/// use std::time::Duration;
+///
+/// let cache = CacheControl::new()
+///     .public()
+///     .max_age(Duration::from_secs(365 * 24 * 3600))
+///     .immutable();
+/// assert_eq!(cache.to_string(), "public, max-age=31536000, immutable");
+///
+/// let response = ResponseBuilder::empty_200().cache_control(cache).build().unwrap();
+/// ```
+///
+/// [`ResponseBuilder::cache_control`]: struct.ResponseBuilder.html#method.cache_control
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheControl {
+    public: bool,
+    private: bool,
+    no_cache: bool,
+    no_store: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+    must_revalidate: bool,
+    immutable: bool,
+}
+
+impl CacheControl {
+    /// Create an empty set of directives
+    pub fn new() -> CacheControl {
+        CacheControl::default()
+    }
+
+    /// The response can be stored by shared caches, removes `private`
+    pub fn public(mut self) -> CacheControl {
+        self.public = true;
+        self.private = false;
+        self
+    }
+
+    /// The response can only be stored by the browser, removes `public`
+    pub fn private(mut self) -> CacheControl {
+        self.private = true;
+        self.public = false;
+        self
+    }
+
+    /// The response must be validated with the server before being reused
+    pub fn no_cache(mut self) -> CacheControl {
+        self.no_cache = true;
+        self
+    }
+
+    /// The response must not be stored at all
+    pub fn no_store(mut self) -> CacheControl {
+        self.no_store = true;
+        self
+    }
+
+    /// The response stays fresh for the given duration, rounded down to the second
+    pub fn max_age(mut self, age: Duration) -> CacheControl {
+        self.max_age = Some(age.as_secs());
+        self
+    }
+
+    /// Freshness duration for shared caches, overriding `max-age` for them
+    pub fn s_maxage(mut self, age: Duration) -> CacheControl {
+        self.s_maxage = Some(age.as_secs());
+        self
+    }
+
+    /// A stale response must not be used without being validated
+    pub fn must_revalidate(mut self) -> CacheControl {
+        self.must_revalidate = true;
+        self
+    }
+
+    /// The response never changes while it is fresh, for instance a file with a versioned name
+    pub fn immutable(mut self) -> CacheControl {
+        self.immutable = true;
+        self
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut directives = Vec::new();
+
+        if self.public {
+            directives.push(String::from("public"));
+        }
+        if self.private {
+            directives.push(String::from("private"));
+        }
+        if self.no_cache {
+            directives.push(String::from("no-cache"));
+        }
+        if self.no_store {
+            directives.push(String::from("no-store"));
+        }
+        if let Some(age) = self.max_age {
+            directives.push(format!("max-age={}", age));
+        }
+        if let Some(age) = self.s_maxage {
+            directives.push(format!("s-maxage={}", age));
+        }
+        if self.must_revalidate {
+            directives.push(String::from("must-revalidate"));
+        }
+        if self.immutable {
+            directives.push(String::from("immutable"));
+        }
+
+        write!(f, "{}", directives.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn directives() {
+        assert_eq!(CacheControl::new().to_string(), "");
+        assert_eq!(CacheControl::new().no_store().to_string(), "no-store");
+        assert_eq!(
+            CacheControl::new()
+                .public()
+                .private()
+                .no_cache()
+                .max_age(Duration::from_millis(1500))
+                .s_maxage(Duration::from_secs(10))
+                .must_revalidate()
+                .to_string(),
+            "private, no-cache, max-age=1, s-maxage=10, must-revalidate"
+        );
+    }
+}
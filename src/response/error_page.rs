@@ -0,0 +1,224 @@
+use crate::{Method, Request, Response};
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+pub(crate) type ErrorRenderer = Arc<dyn Send + Sync + 'static + Fn(&ErrorPage) -> String>;
+
+const HTML: &str = "text/html; charset=utf-8";
+const JSON: &str = "application/json";
+
+/// Format of an error page, HTML when the client prefers it to JSON as browsers do, JSON otherwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// HTML document, sent as "text/html; charset=utf-8"
+    Html,
+    /// JSON object, sent as "application/json"
+    Json,
+}
+
+impl ErrorFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ErrorFormat::Html => HTML,
+            ErrorFormat::Json => JSON,
+        }
+    }
+}
+
+/// Error response given to the renderer set with [`AIOServer::set_error_renderer`] to produce its body
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{AIOServer, ErrorFormat, ResponseBuilder};
+///
+/// let mut server = AIOServer::new("127.0.0.1:7898".parse().unwrap(), |_| {
+///     ResponseBuilder::empty_404().build().unwrap()
+/// });
+/// server.set_error_renderer(|page| match page.format() {
+///     ErrorFormat::Html => format!("<h1>Oops, error {}</h1>", page.code()),
+///     ErrorFormat::Json => page.render(),
+/// });
+/// ```
+///
+/// [`AIOServer::set_error_renderer`]: struct.AIOServer.html#method.set_error_renderer
+#[derive(Debug)]
+pub struct ErrorPage<'a> {
+    code: i32,
+    reason: &'a str,
+    details: Option<&'a str>,
+    format: ErrorFormat,
+}
+
+impl<'a> ErrorPage<'a> {
+    /// Return the status code of the response
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Return the reason phrase of the response
+    pub fn reason(&self) -> &str {
+        self.reason
+    }
+
+    /// Return the description of the error, only known for the requests that could not be parsed
+    pub fn details(&self) -> Option<&str> {
+        self.details
+    }
+
+    /// Return the format expected by the client
+    pub fn format(&self) -> ErrorFormat {
+        self.format
+    }
+
+    /// Render the page in its format, a minimal HTML document or a JSON object like
+    /// `{"status":404,"reason":"Not Found"}` with a `details` field when they are known
+    pub fn render(&self) -> String {
+        match self.format {
+            ErrorFormat::Html => {
+                let title = format!("{} {}", self.code, escape_html(self.reason));
+                let mut page = format!(
+                    "<!DOCTYPE html><html><head><title>{}</title></head><body><h1>{}</h1>",
+                    title, title
+                );
+                if let Some(details) = self.details {
+                    let _ = write!(page, "<p>{}</p>", escape_html(details));
+                }
+                page.push_str("</body></html>");
+                page
+            }
+            ErrorFormat::Json => {
+                let mut page = format!(
+                    "{{\"status\":{},\"reason\":\"{}\"",
+                    self.code,
+                    escape_json(self.reason)
+                );
+                if let Some(details) = self.details {
+                    let _ = write!(page, ",\"details\":\"{}\"", escape_json(details));
+                }
+                page.push('}');
+                page
+            }
+        }
+    }
+}
+
+/// Give an error response without body the page produced by the renderer.
+/// Without request, as when it could not be parsed, the page is rendered as JSON.
+pub(crate) fn render_error(
+    renderer: &ErrorRenderer,
+    response: &mut Response,
+    request: Option<&Request>,
+    details: Option<&str>,
+) {
+    let head = matches!(request.map(Request::method), Some(Method::HEAD));
+    if response.code() < 400 || response.body_len() > 0 || head {
+        return;
+    }
+
+    let format = match request {
+        Some(request) if request.wants_html() => ErrorFormat::Html,
+        _ => ErrorFormat::Json,
+    };
+    let page = ErrorPage {
+        code: response.code,
+        reason: &response.reason,
+        details,
+        format,
+    };
+    let body = renderer(&page).into_bytes();
+
+    response
+        .headers
+        .set_header("Content-Type", format.content_type());
+    response
+        .headers
+        .set_header("Content-Length", &body.len().to_string());
+    response.body = Some(body);
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ResponseBuilder;
+
+    fn page(format: ErrorFormat, details: Option<&'static str>) -> ErrorPage<'static> {
+        ErrorPage {
+            code: 400,
+            reason: "Bad Request",
+            details,
+            format,
+        }
+    }
+
+    #[test]
+    fn render() {
+        assert_eq!(
+            page(ErrorFormat::Json, None).render(),
+            r#"{"status":400,"reason":"Bad Request"}"#
+        );
+        assert_eq!(
+            page(ErrorFormat::Json, Some("Invalid \"name\"\n")).render(),
+            r#"{"status":400,"reason":"Bad Request","details":"Invalid \"name\"\u000a"}"#
+        );
+
+        let html = page(ErrorFormat::Html, Some("<script>")).render();
+        assert!(html.contains("<h1>400 Bad Request</h1>"));
+        assert!(html.contains("<p>&lt;script&gt;</p>"));
+    }
+
+    #[test]
+    fn only_empty_errors() {
+        let renderer: ErrorRenderer = Arc::new(|page| page.render());
+
+        let mut response = ResponseBuilder::empty_404().build().unwrap();
+        render_error(&renderer, &mut response, None, None);
+        assert_eq!(
+            response.body_as_string().unwrap(),
+            r#"{"status":404,"reason":"Not Found"}"#
+        );
+        assert_eq!(response.headers().get_header("Content-Type").unwrap(), JSON);
+
+        let mut response = ResponseBuilder::empty_404()
+            .text("missing")
+            .build()
+            .unwrap();
+        render_error(&renderer, &mut response, None, None);
+        assert_eq!(response.body_as_string().unwrap(), "missing");
+
+        let mut response = ResponseBuilder::empty_200().build().unwrap();
+        render_error(&renderer, &mut response, None, None);
+        assert_eq!(response.body_len(), 0);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::response::{Response, ResponseBuilder};
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Content types of the files served with [`ResponseBuilder::file`], from their extension
+///
+/// [`ResponseBuilder::file`]: struct.ResponseBuilder.html#method.file
+const CONTENT_TYPES: [(&str, &str); 22] = [
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("xml", "application/xml"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("ico", "image/x-icon"),
+    ("pdf", "application/pdf"),
+    ("wasm", "application/wasm"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("mp4", "video/mp4"),
+    ("zip", "application/zip"),
+];
+
+/// Error returned by [`ResponseBuilder::file`] when the file can not be served
+///
+/// [`ResponseBuilder::file`]: struct.ResponseBuilder.html#method.file
+#[derive(Debug)]
+pub enum FileError {
+    /// The file does not exist or is not a regular file
+    NotFound,
+    /// The server is not allowed to read the file
+    PermissionDenied,
+    /// The file could not be opened
+    OpenError(io::Error),
+}
+
+impl FileError {
+    /// Return the response to send instead of the file.
+    /// A missing file gives a 404 status code, a forbidden one a 403 and the other errors a 500.
+    pub fn response(&self) -> Response {
+        let builder = match self {
+            FileError::NotFound => ResponseBuilder::empty_404(),
+            FileError::PermissionDenied => ResponseBuilder::empty_403(),
+            FileError::OpenError(_) => ResponseBuilder::empty_500(),
+        };
+
+        builder.build().unwrap()
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => FileError::NotFound,
+            io::ErrorKind::PermissionDenied => FileError::PermissionDenied,
+            _ => FileError::OpenError(e),
+        }
+    }
+}
+
+/// Body of a response read from a file when the response is written
+#[derive(Debug, Clone)]
+pub(crate) struct FileBody {
+    file: Arc<File>,
+    len: u64,
+}
+
+impl PartialEq for FileBody {
+    fn eq(&self, other: &FileBody) -> bool {
+        Arc::ptr_eq(&self.file, &other.file)
+    }
+}
+
+impl FileBody {
+    /// Open a regular file, its size is the length of the body
+    pub(crate) fn open(path: &Path) -> Result<FileBody, FileError> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+
+        if !metadata.is_file() {
+            return Err(FileError::NotFound);
+        }
+
+        Ok(FileBody {
+            file: Arc::new(file),
+            len: metadata.len(),
+        })
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub(crate) fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Return a reader over the body, from the start of the file
+    pub(crate) fn reader(&self) -> io::Result<impl Read + '_> {
+        let mut file = &*self.file;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(file.take(self.len))
+    }
+
+    /// Read the whole body in memory
+    pub(crate) fn read_all(&self) -> io::Result<Vec<u8>> {
+        let mut body = Vec::with_capacity(self.len as usize);
+        self.reader()?.read_to_end(&mut body)?;
+
+        Ok(body)
+    }
+}
+
+/// Return the content type of a file from its extension, "application/octet-stream" if it is unknown
+pub(crate) fn content_type(path: &Path) -> &'static str {
+    let extension = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => extension.to_ascii_lowercase(),
+        None => return DEFAULT_CONTENT_TYPE,
+    };
+
+    CONTENT_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map_or(DEFAULT_CONTENT_TYPE, |(_, content_type)| content_type)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn resource(name: &str) -> PathBuf {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("resources/test");
+        path.push(name);
+        path
+    }
+
+    #[test]
+    fn infer_content_type() {
+        assert_eq!(
+            content_type(Path::new("index.HTML")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(content_type(Path::new("logo.png")), "image/png");
+        assert_eq!(
+            content_type(Path::new("archive.tar.gz")),
+            DEFAULT_CONTENT_TYPE
+        );
+        assert_eq!(content_type(Path::new("README")), DEFAULT_CONTENT_TYPE);
+    }
+
+    #[test]
+    fn open_file() {
+        let body = FileBody::open(&resource("http_body.txt")).unwrap();
+        let expected = std::fs::read(resource("http_body.txt")).unwrap();
+
+        assert_eq!(body.len(), expected.len() as u64);
+        assert_eq!(body.read_all().unwrap(), expected);
+        assert_eq!(body.read_all().unwrap(), expected);
+    }
+
+    #[test]
+    fn missing_file() {
+        let error = FileBody::open(&resource("missing.txt")).unwrap_err();
+        assert!(matches!(error, FileError::NotFound));
+        assert_eq!(error.response().code(), 404);
+
+        let error = FileBody::open(&resource("")).unwrap_err();
+        assert!(matches!(error, FileError::NotFound));
+    }
+}
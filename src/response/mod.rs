@@ -1,7 +1,15 @@
+mod cache_control;
+pub(crate) mod error_page;
+pub(crate) mod file;
 mod reason;
 mod response;
-mod response_parser;
+pub(crate) mod response_parser;
+mod static_files;
 
+pub use cache_control::CacheControl;
+pub use error_page::{ErrorFormat, ErrorPage};
+pub use file::FileError;
 pub use reason::Reason;
 pub use response::Response;
 pub use response::ResponseBuilder;
+pub use static_files::StaticFiles;
@@ -1,26 +1,62 @@
 pub enum Reason {
+    SWITCHINGPROTOCOLS101,
+    EARLYHINTS103,
     OK200,
+    NOCONTENT204,
+    MOVEDPERMANENTLY301,
+    NOTMODIFIED304,
+    PERMANENTREDIRECT308,
     BADREQUEST400,
+    FORBIDDEN403,
     INTERNAL500,
     NOTFOUND404,
+    METHODNOTALLOWED405,
+    PAYLOADTOOLARGE413,
+    UNSUPPORTEDMEDIATYPE415,
+    REQUESTHEADERFIELDSTOOLARGE431,
+    SERVICEUNAVAILABLE503,
 }
 
 impl Reason {
     pub fn code(&self) -> i32 {
         match self {
+            Reason::SWITCHINGPROTOCOLS101 => 101,
+            Reason::EARLYHINTS103 => 103,
             Reason::BADREQUEST400 => 400,
+            Reason::FORBIDDEN403 => 403,
             Reason::INTERNAL500 => 500,
             Reason::OK200 => 200,
+            Reason::NOCONTENT204 => 204,
+            Reason::MOVEDPERMANENTLY301 => 301,
+            Reason::NOTMODIFIED304 => 304,
+            Reason::PERMANENTREDIRECT308 => 308,
             Reason::NOTFOUND404 => 404,
+            Reason::METHODNOTALLOWED405 => 405,
+            Reason::PAYLOADTOOLARGE413 => 413,
+            Reason::UNSUPPORTEDMEDIATYPE415 => 415,
+            Reason::REQUESTHEADERFIELDSTOOLARGE431 => 431,
+            Reason::SERVICEUNAVAILABLE503 => 503,
         }
     }
 
     pub fn reason(&self) -> String {
         String::from(match self {
+            Reason::SWITCHINGPROTOCOLS101 => "Switching Protocols",
+            Reason::EARLYHINTS103 => "Early Hints",
             Reason::BADREQUEST400 => "Bad Request",
+            Reason::FORBIDDEN403 => "Forbidden",
             Reason::INTERNAL500 => "Internal Server Error",
             Reason::OK200 => "Ok",
+            Reason::NOCONTENT204 => "No Content",
+            Reason::MOVEDPERMANENTLY301 => "Moved Permanently",
+            Reason::NOTMODIFIED304 => "Not Modified",
+            Reason::PERMANENTREDIRECT308 => "Permanent Redirect",
             Reason::NOTFOUND404 => "Not Found",
+            Reason::METHODNOTALLOWED405 => "Method Not Allowed",
+            Reason::PAYLOADTOOLARGE413 => "Payload Too Large",
+            Reason::UNSUPPORTEDMEDIATYPE415 => "Unsupported Media Type",
+            Reason::REQUESTHEADERFIELDSTOOLARGE431 => "Request Header Fields Too Large",
+            Reason::SERVICEUNAVAILABLE503 => "Service Unavailable",
         })
     }
 }
@@ -1,18 +1,35 @@
+use crate::aioserver::tunnel::Tunnel;
+use crate::aioserver::upgrade::Upgrade;
+use crate::http::date::format_http_date;
 use crate::http::parser::BuildError;
 use crate::http::Headers;
 use crate::http::Version;
-use crate::response::Reason;
+use crate::response::file::{content_type, FileBody};
+use crate::response::{CacheControl, FileError, Reason};
 
 use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+const HTML: &str = "text/html; charset=utf-8";
+const TEXT: &str = "text/plain; charset=utf-8";
 
 /// Represent an HTTP response
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Response {
     pub code: i32,
     pub reason: String,
     pub version: Version,
     pub headers: Headers,
     pub body: Option<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pass: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    file: Option<FileBody>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    upgrade: Option<Upgrade>,
 }
 
 impl fmt::Display for Response {
@@ -38,6 +55,102 @@ impl fmt::Display for Response {
 }
 
 impl Response {
+    /// Return a response telling the [`Router`] to try the next route matching the request.
+    /// When no other route is matching, the not found handler is used.
+    /// Outside of a router it is sent as a 404 Not Found response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Method, Response, ResponseBuilder, Route, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(Route::new("/{file}", Method::GET).unwrap(), |_, params| {
+    ///     match params.get("file").map(String::as_str) {
+    ///         Some("style.css") => ResponseBuilder::empty_200().text("body {}").build().unwrap(),
+    ///         _ => Response::pass(),
+    ///     }
+    /// });
+    /// router.add_route(Route::from_path("/{page}").unwrap(), |_, _| {
+    ///     ResponseBuilder::empty_200().html("<html></html>").build().unwrap()
+    /// });
+    /// ```
+    ///
+    /// [`Router`]: struct.Router.html
+    pub fn pass() -> Response {
+        let mut response = ResponseBuilder::empty_404().build().unwrap();
+        response.pass = true;
+        response
+    }
+
+    /// Return true if the response was created with [`Response::pass`]
+    ///
+    /// [`Response::pass`]: struct.Response.html#method.pass
+    pub fn is_pass(&self) -> bool {
+        self.pass
+    }
+
+    /// Return the file sent as body, set with [`ResponseBuilder::file`]
+    ///
+    /// [`ResponseBuilder::file`]: struct.ResponseBuilder.html#method.file
+    pub(crate) fn file_body(&self) -> Option<&FileBody> {
+        self.file.as_ref()
+    }
+
+    /// Take the protocol to switch to, set with [`ResponseBuilder::upgrade`]
+    ///
+    /// [`ResponseBuilder::upgrade`]: struct.ResponseBuilder.html#method.upgrade
+    pub(crate) fn take_upgrade(&mut self) -> Option<Upgrade> {
+        self.upgrade.take()
+    }
+
+    /// Remove the body, in memory or from a file, and return its length
+    pub(crate) fn remove_body(&mut self) -> usize {
+        let len = self.body_len();
+        self.body = None;
+        self.file = None;
+
+        len
+    }
+
+    /// Return the length of the body, read from memory or from a file
+    pub(crate) fn body_len(&self) -> usize {
+        match (&self.body, &self.file) {
+            (Some(body), _) => body.len(),
+            (None, Some(file)) => file.len() as usize,
+            (None, None) => 0,
+        }
+    }
+
+    /// Serialize the response as sent on the wire, the body is kept as is even if it is not utf 8
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes);
+
+        bytes
+    }
+
+    /// Serialize the response at the end of the buffer
+    pub(crate) fn write_to(&self, bytes: &mut Vec<u8>) {
+        // Writing to a Vec can not fail
+        let _ = write!(
+            bytes,
+            "{} {} {}\r\n",
+            self.version.as_str(),
+            self.code,
+            self.reason
+        );
+
+        for (key, value) in self.headers.iter() {
+            let _ = write!(bytes, "{}: {}\r\n", key, value);
+        }
+        bytes.extend_from_slice(b"\r\n");
+
+        if let Some(ref body) = self.body {
+            bytes.extend_from_slice(body);
+        }
+    }
+
     /// Return status code of the response
     pub fn code(&self) -> i32 {
         self.code
@@ -82,6 +195,8 @@ pub struct ResponseBuilder {
     version: Option<Version>,
     headers: Option<Headers>,
     body: Option<Vec<u8>>,
+    file: Option<FileBody>,
+    upgrade: Option<Upgrade>,
 }
 
 impl ResponseBuilder {
@@ -92,10 +207,43 @@ impl ResponseBuilder {
             version: Option::Some(Version::HTTP11),
             headers: Option::Some(Headers::new()),
             body: Option::None,
+            file: Option::None,
+            upgrade: Option::None,
         }
     }
 
-    /// Set the builer to build a response with an empty body and 500 status code
+    /// Set the builder to build a response with a 200 status code sending the given file.
+    /// The "Content-Type" header is inferred from the extension of the file and the "Content-Length" header
+    /// is its size. The file is read while the response is written instead of being loaded in memory,
+    /// on Linux it is sent by the system with sendfile without being copied by the server.
+    ///
+    /// An error is returned if the file can not be opened, [`FileError::response`] gives the response to send instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mini_async_http::ResponseBuilder;
+    ///
+    /// let response = match ResponseBuilder::file("static/index.html") {
+    ///     Ok(builder) => builder.build().unwrap(),
+    ///     Err(e) => e.response(),
+    /// };
+    /// ```
+    ///
+    /// [`FileError::response`]: enum.FileError.html#method.response
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<Self, FileError> {
+        let path = path.as_ref();
+        let file = FileBody::open(path)?;
+
+        let mut builder = ResponseBuilder::empty_200()
+            .content_type(content_type(path))
+            .header("Content-Length", &file.len().to_string());
+        builder.file = Some(file);
+
+        Ok(builder)
+    }
+
+    /// Set the builder to build a response with an empty body and 500 status code
     pub fn empty_500() -> Self {
         ResponseBuilder::new()
             .code(Reason::INTERNAL500.code())
@@ -103,7 +251,20 @@ impl ResponseBuilder {
             .version(Version::HTTP11)
     }
 
-    /// Set the builer to build a response with an empty body and 200 status code
+    /// Set the builder to build a `101 Switching Protocols` response to the given protocol,
+    /// the connection is given to the handler set with [`upgrade`] once the response is sent
+    ///
+    /// [`upgrade`]: #method.upgrade
+    pub fn switching_protocols(protocol: &str) -> Self {
+        ResponseBuilder::new()
+            .code(Reason::SWITCHINGPROTOCOLS101.code())
+            .reason(Reason::SWITCHINGPROTOCOLS101.reason())
+            .version(Version::HTTP11)
+            .header("Upgrade", protocol)
+            .header("Connection", "upgrade")
+    }
+
+    /// Set the builder to build a response with an empty body and 200 status code
     pub fn empty_200() -> Self {
         ResponseBuilder::new()
             .code(Reason::OK200.code())
@@ -111,7 +272,54 @@ impl ResponseBuilder {
             .version(Version::HTTP11)
     }
 
-    /// Set the builer to build a response with an empty body and 400 status code
+    /// Set the builder to build a response with an empty body and 103 status code,
+    /// to be sent with [`send_informational`] before the final response
+    ///
+    /// [`send_informational`]: fn.send_informational.html
+    pub fn early_hints() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::EARLYHINTS103.code())
+            .reason(Reason::EARLYHINTS103.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Set the builder to build a response with an empty body and 204 status code
+    pub fn no_content() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::NOCONTENT204.code())
+            .reason(Reason::NOCONTENT204.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Set the builder to build a response with an empty body and 301 status code, redirecting to the given location
+    pub fn empty_301(location: &str) -> Self {
+        ResponseBuilder::new()
+            .code(Reason::MOVEDPERMANENTLY301.code())
+            .reason(Reason::MOVEDPERMANENTLY301.reason())
+            .version(Version::HTTP11)
+            .header("Location", location)
+    }
+
+    /// Set the builder to build a response with an empty body and 308 status code, redirecting to the given location.
+    /// Unlike a 301, the client must repeat the request with the same method and body.
+    pub fn empty_308(location: &str) -> Self {
+        ResponseBuilder::new()
+            .code(Reason::PERMANENTREDIRECT308.code())
+            .reason(Reason::PERMANENTREDIRECT308.reason())
+            .version(Version::HTTP11)
+            .header("Location", location)
+    }
+
+    /// Set the builder to build a response with an empty body and 304 status code,
+    /// telling the client its cached copy is still valid
+    pub fn not_modified() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::NOTMODIFIED304.code())
+            .reason(Reason::NOTMODIFIED304.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Set the builder to build a response with an empty body and 400 status code
     pub fn empty_400() -> Self {
         ResponseBuilder::new()
             .code(Reason::BADREQUEST400.code())
@@ -119,6 +327,14 @@ impl ResponseBuilder {
             .version(Version::HTTP11)
     }
 
+    /// Set the builder to build a response with an empty body and 403 status code
+    pub fn empty_403() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::FORBIDDEN403.code())
+            .reason(Reason::FORBIDDEN403.reason())
+            .version(Version::HTTP11)
+    }
+
     pub fn empty_404() -> Self {
         ResponseBuilder::new()
             .code(Reason::NOTFOUND404.code())
@@ -126,6 +342,58 @@ impl ResponseBuilder {
             .version(Version::HTTP11)
     }
 
+    /// Set the builder to build a response with an empty body and 405 status code
+    pub fn empty_405() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::METHODNOTALLOWED405.code())
+            .reason(Reason::METHODNOTALLOWED405.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Set the builder to build a response with an empty body and 413 status code
+    pub fn empty_413() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::PAYLOADTOOLARGE413.code())
+            .reason(Reason::PAYLOADTOOLARGE413.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Set the builder to build a response with an empty body and 415 status code
+    pub fn empty_415() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::UNSUPPORTEDMEDIATYPE415.code())
+            .reason(Reason::UNSUPPORTEDMEDIATYPE415.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Set the builder to build a response with an empty body and 431 status code
+    pub fn empty_431() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::REQUESTHEADERFIELDSTOOLARGE431.code())
+            .reason(Reason::REQUESTHEADERFIELDSTOOLARGE431.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Set the builder to build a response with an empty body and 503 status code
+    pub fn empty_503() -> Self {
+        ResponseBuilder::new()
+            .code(Reason::SERVICEUNAVAILABLE503.code())
+            .reason(Reason::SERVICEUNAVAILABLE503.reason())
+            .version(Version::HTTP11)
+    }
+
+    /// Give the connection to the handler once the response is sent, see [`Upgrade`].
+    /// The handler is only run when the status code of the response is 101.
+    ///
+    /// [`Upgrade`]: struct.Upgrade.html
+    pub fn upgrade<H>(mut self, handler: H) -> Self
+    where
+        H: Send + 'static + FnOnce(Tunnel),
+    {
+        self.upgrade = Some(Upgrade::new(handler));
+        self
+    }
+
     /// Set the the status code of the response
     pub fn code(mut self, code: i32) -> Self {
         self.code = Option::Some(code);
@@ -172,14 +440,54 @@ impl ResponseBuilder {
         self.header("Content-Type", content_type)
     }
 
+    /// Set the "Cache-Control" header of the response
+    pub fn cache_control(self, cache_control: CacheControl) -> Self {
+        self.header("Cache-Control", &cache_control.to_string())
+    }
+
+    /// Set the "Expires" header of the response to the given time
+    pub fn expires(self, time: SystemTime) -> Self {
+        self.header("Expires", &format_http_date(time))
+    }
+
     /// Set the body as a byte slice of the response
     pub fn body(self, body: &[u8]) -> Self {
         let len = body.len();
         let mut builder = self.header("Content-Length", &len.to_string());
         builder.body = Option::Some(body.to_vec());
+        builder.file = Option::None;
         builder
     }
 
+    /// Set an HTML body, the "Content-Type" header is set to "text/html; charset=utf-8"
+    pub fn html(self, body: &str) -> Self {
+        self.bytes(HTML, body.as_bytes())
+    }
+
+    /// Set a plain text body, the "Content-Type" header is set to "text/plain; charset=utf-8"
+    pub fn text(self, body: &str) -> Self {
+        self.bytes(TEXT, body.as_bytes())
+    }
+
+    /// Set the body and its "Content-Type" header in one call
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::ResponseBuilder;
+    ///
+    /// let response = ResponseBuilder::empty_200()
+    ///     .bytes("application/json", b"{}")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(response.headers().get_header("Content-Type").unwrap(), "application/json");
+    /// assert_eq!(response.headers().get_header("Content-Length").unwrap(), "2");
+    /// ```
+    pub fn bytes(self, content_type: &str, body: &[u8]) -> Self {
+        self.body(body).content_type(content_type)
+    }
+
     /// Set the status of the response (code + reason phrase)
     pub fn status(mut self, status: Reason) -> Self {
         self.code = Some(status.code());
@@ -217,6 +525,9 @@ impl ResponseBuilder {
             version,
             headers,
             body: self.body,
+            pass: false,
+            file: self.file,
+            upgrade: self.upgrade,
         })
     }
 }
@@ -226,3 +537,79 @@ impl Default for ResponseBuilder {
         ResponseBuilder::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn text_html() {
+        let response = ResponseBuilder::empty_200().text("Hello").build().unwrap();
+        assert_eq!(response.headers().get_header("Content-Type").unwrap(), TEXT);
+        assert_eq!(
+            response.headers().get_header("Content-Length").unwrap(),
+            "5"
+        );
+        assert_eq!(response.body_as_string().unwrap(), "Hello");
+
+        let response = ResponseBuilder::empty_200()
+            .html("<p>Hello</p>")
+            .build()
+            .unwrap();
+        assert_eq!(response.headers().get_header("Content-Type").unwrap(), HTML);
+        assert_eq!(
+            response.headers().get_header("Content-Length").unwrap(),
+            "12"
+        );
+    }
+
+    #[test]
+    fn binary_body() {
+        let response = ResponseBuilder::empty_200()
+            .bytes("application/octet-stream", &[0, 159, 146, 150])
+            .build()
+            .unwrap();
+
+        assert!(response
+            .to_bytes()
+            .ends_with(&[b'\r', b'\n', 0, 159, 146, 150]));
+    }
+
+    #[test]
+    fn cache_headers() {
+        let response = ResponseBuilder::empty_200()
+            .cache_control(CacheControl::new().no_store())
+            .expires(std::time::UNIX_EPOCH)
+            .build()
+            .unwrap();
+
+        let headers = response.headers();
+        assert_eq!(headers.get_header("Cache-Control").unwrap(), "no-store");
+        assert_eq!(
+            headers.get_header("Expires").unwrap(),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn redirect_location_case() {
+        let response = ResponseBuilder::empty_301("https://Example.com/Docs?Token=AbC")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            response.headers().get_header("Location").unwrap(),
+            "https://Example.com/Docs?Token=AbC"
+        );
+    }
+
+    #[test]
+    fn no_content() {
+        let response = ResponseBuilder::no_content().build().unwrap();
+
+        assert_eq!(response.code(), 204);
+        assert_eq!(response.reason(), "No Content");
+        assert_eq!(response.body(), None);
+        assert_eq!(response.headers().get_header("Content-Length"), None);
+    }
+}
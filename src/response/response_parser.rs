@@ -96,4 +96,17 @@ mod test {
 
         assert_eq!(a, b);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let parser = ResponseParser::new();
+        let input = get_resource_string("response.txt").as_bytes().to_vec();
+        let (response, _) = parser.parse_u8(&input).unwrap();
+
+        let json = serde_json::to_string(&response).unwrap();
+        let back: Response = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(response, back);
+    }
 }
@@ -0,0 +1,836 @@
+use crate::http::encoding;
+use crate::http::Method;
+use crate::request::Request;
+use crate::response::error_page::escape_html;
+use crate::response::file::{content_type, FileError};
+use crate::response::{CacheControl, Response, ResponseBuilder};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Time during which a cached file is served without checking if it was modified
+const REVALIDATE_AFTER: Duration = Duration::from_secs(1);
+
+/// Content type of the directory listings
+const LISTING_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+
+/// File served for the requests to a directory by default
+const DEFAULT_INDEX: &str = "index.html";
+
+/// Precompressed variants looked for next to a cached file, as a content coding and the extension of the variant,
+/// in order of preference
+const VARIANTS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+/// Handler serving the files of a directory under a path prefix, with an `ETag` validated by `If-None-Match`.
+///
+/// A request to a directory is answered with its [`index_file`], `index.html` by default, or with the list of
+/// its files when [`directory_listing`] is enabled. The path of a directory must end with a '/' for the relative
+/// links of its page to work, the client is redirected when it does not. The `Cache-Control` header of the files
+/// can be set by extension with [`cache_control`].
+///
+/// The files are streamed from the disk by default. With [`cache`], the small files are kept in memory
+/// along with their precompressed variants, `style.css.br` and `style.css.gz` next to `style.css`, which are
+/// sent to the clients accepting them. A cached file is checked at most once per second and reloaded
+/// when its modification time or its size changes, the least recently used files are evicted first.
+///
+/// # Example
+///
+/// ```no_run
+/// use mini_async_http::{AIOServer, CacheControl, StaticFiles};
+/// use std::time::Duration;
+///
+/// // GET /assets/css/style.css is answered with the file public/css/style.css
+/// let files = StaticFiles::new("/assets", "public")
+///     .cache(16 * 1024 * 1024, 256 * 1024)
+///     .cache_control("css", CacheControl::new().public().max_age(Duration::from_secs(3600)))
+///     .directory_listing(true);
+///
+/// let server = AIOServer::new("127.0.0.1:7878".parse().unwrap(), move |request| {
+///     files.handle(request)
+/// });
+/// ```
+///
+/// [`index_file`]: #method.index_file
+/// [`directory_listing`]: #method.directory_listing
+/// [`cache_control`]: #method.cache_control
+/// [`cache`]: #method.cache
+pub struct StaticFiles {
+    prefix: String,
+    root: PathBuf,
+    index: Option<String>,
+    listing: bool,
+    dotfiles: bool,
+    cache_control: HashMap<String, CacheControl>,
+    fallback: Option<String>,
+    no_fallback: Vec<String>,
+    cache: Option<Mutex<FileCache>>,
+}
+
+impl StaticFiles {
+    /// Serve the files of the root directory for the requests whose path starts with the prefix
+    pub fn new<P: AsRef<Path>>(prefix: &str, root: P) -> StaticFiles {
+        StaticFiles {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            root: root.as_ref().to_path_buf(),
+            index: Some(DEFAULT_INDEX.to_string()),
+            listing: false,
+            dotfiles: false,
+            cache_control: HashMap::new(),
+            fallback: None,
+            no_fallback: Vec::new(),
+            cache: None,
+        }
+    }
+
+    /// Serve a single-page application from the root directory: its files are served under '/' and
+    /// the browsers navigating to any other page get its `index.html`, while the requests under the API
+    /// prefixes are still answered with a 404 status code. See [`fallback`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mini_async_http::{AIOServer, StaticFiles};
+    ///
+    /// let app = StaticFiles::single_page_app("dist", &["/api"]);
+    ///
+    /// let server = AIOServer::new("127.0.0.1:7878".parse().unwrap(), move |request| {
+    ///     app.handle(request)
+    /// });
+    /// ```
+    ///
+    /// [`fallback`]: #method.fallback
+    pub fn single_page_app<P: AsRef<Path>>(root: P, api_prefixes: &[&str]) -> StaticFiles {
+        api_prefixes.iter().fold(
+            StaticFiles::new("/", root).fallback(DEFAULT_INDEX),
+            |files, prefix| files.no_fallback(prefix),
+        )
+    }
+
+    /// Answer the GET and HEAD requests to a missing file with the given file of the root when they
+    /// accept HTML, as the navigations of a browser do, so the page can route them on the client.
+    /// The other requests, such as the ones for a missing script or image, are still not found.
+    pub fn fallback(mut self, file: &str) -> StaticFiles {
+        self.fallback = Some(file.to_string());
+        self
+    }
+
+    /// Never fall back for the paths under the prefix, such as the routes of an API
+    pub fn no_fallback(mut self, prefix: &str) -> StaticFiles {
+        self.no_fallback
+            .push(prefix.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Set the file served for the requests to a directory, None to serve none
+    pub fn index_file(mut self, name: Option<&str>) -> StaticFiles {
+        self.index = name.map(String::from);
+        self
+    }
+
+    /// Answer the requests to a directory without index file with the list of its files, disabled by default.
+    /// The hidden files, starting with a '.', are not listed.
+    pub fn directory_listing(mut self, enabled: bool) -> StaticFiles {
+        self.listing = enabled;
+        self
+    }
+
+    /// Serve the hidden files and directories, starting with a '.', disabled by default so files such as
+    /// `.env` or `.git/config` are not exposed. They are answered with a 404 status code when disabled.
+    pub fn serve_dotfiles(mut self, enabled: bool) -> StaticFiles {
+        self.dotfiles = enabled;
+        self
+    }
+
+    /// Send the `Cache-Control` header with the files of the given extension, compared without case
+    pub fn cache_control(mut self, extension: &str, cache_control: CacheControl) -> StaticFiles {
+        self.cache_control
+            .insert(extension.to_ascii_lowercase(), cache_control);
+        self
+    }
+
+    /// Keep the files up to `max_file_size` bytes in memory, up to `max_size` bytes in total
+    /// variants included. The larger files are still streamed from the disk.
+    pub fn cache(mut self, max_size: usize, max_file_size: usize) -> StaticFiles {
+        self.cache = Some(Mutex::new(FileCache::new(max_size, max_file_size)));
+        self
+    }
+
+    /// Answer a request with the file its path points to.
+    /// A missing file, or a path outside of the prefix or escaping the root, gives a 404 status code
+    /// and the methods other than GET and HEAD a 405.
+    pub fn handle(&self, request: &Request) -> Response {
+        if !matches!(request.method(), Method::GET | Method::HEAD) {
+            return ResponseBuilder::empty_405()
+                .header("Allow", "GET, HEAD")
+                .build()
+                .unwrap();
+        }
+
+        let target = request.uri().path();
+        let path = match self.resolve(target) {
+            Some(path) => path,
+            None => return FileError::NotFound.response(),
+        };
+
+        let served = if target.ends_with('/') {
+            self.serve_directory(&path, request)
+        } else {
+            match self.serve(&path, request) {
+                Err(FileError::NotFound) if path.is_dir() => Ok(redirect_to_directory(request)),
+                served => served,
+            }
+        };
+
+        match (served, &self.fallback) {
+            (Err(FileError::NotFound), Some(fallback)) if self.falls_back(target, request) => self
+                .serve(&self.root.join(fallback), request)
+                .unwrap_or_else(|e| e.response()),
+            (served, _) => served.unwrap_or_else(|e| e.response()),
+        }
+    }
+
+    /// Return true if the request for a missing file is answered with the fallback
+    fn falls_back(&self, target: &str, request: &Request) -> bool {
+        let excluded = self.no_fallback.iter().any(|prefix| {
+            matches!(target.strip_prefix(prefix.as_str()), Some(rest) if rest.is_empty() || rest.starts_with('/'))
+        });
+
+        !excluded && request.wants_html()
+    }
+
+    /// Send a file with the `Cache-Control` header of its extension
+    fn serve(&self, path: &Path, request: &Request) -> Result<Response, FileError> {
+        let mut response = match &self.cache {
+            Some(cache) => self.serve_cached(cache, path, request)?,
+            None => serve_file(path, request)?,
+        };
+
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        if let Some(cache_control) =
+            extension.and_then(|extension| self.cache_control.get(&extension.to_ascii_lowercase()))
+        {
+            response
+                .headers
+                .set_header("Cache-Control", &cache_control.to_string());
+        }
+
+        Ok(response)
+    }
+
+    /// Send the index file of a directory, or the list of its files
+    fn serve_directory(&self, path: &Path, request: &Request) -> Result<Response, FileError> {
+        if let Some(index) = &self.index {
+            match self.serve(&path.join(index), request) {
+                Err(FileError::NotFound) => {}
+                served => return served,
+            }
+        }
+
+        if !self.listing {
+            return Err(FileError::NotFound);
+        }
+
+        // The root has no parent to link to
+        let page = listing(path, request.uri().path(), path != self.root)?;
+        let builder = if *request.method() == Method::HEAD {
+            ResponseBuilder::empty_200()
+                .content_type(LISTING_CONTENT_TYPE)
+                .header("Content-Length", &page.len().to_string())
+        } else {
+            ResponseBuilder::empty_200().bytes(LISTING_CONTENT_TYPE, page.as_bytes())
+        };
+
+        Ok(builder.build().unwrap())
+    }
+
+    /// Return the file the path points to, None if it is outside of the prefix, tries to leave the root
+    /// or points to a hidden file while they are not served.
+    /// The segments containing a ':' are refused as they could name a drive on Windows.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let relative = path.strip_prefix(self.prefix.as_str())?;
+        if !relative.is_empty() && !relative.starts_with('/') {
+            return None;
+        }
+
+        let mut resolved = self.root.clone();
+        for segment in relative.split('/').filter(|segment| !segment.is_empty()) {
+            let segment = encoding::decode(segment)?;
+            if segment == "."
+                || segment == ".."
+                || (segment.starts_with('.') && !self.dotfiles)
+                || segment.contains(&['/', '\\', '\0', ':'][..])
+            {
+                return None;
+            }
+            resolved.push(segment);
+        }
+
+        Some(resolved)
+    }
+
+    fn serve_cached(
+        &self,
+        cache: &Mutex<FileCache>,
+        path: &Path,
+        request: &Request,
+    ) -> Result<Response, FileError> {
+        let cached = cache.lock().unwrap().get(path);
+        let metadata = match cached {
+            Some((file, checked)) if checked.elapsed() < REVALIDATE_AFTER => {
+                return Ok(file.respond(request));
+            }
+            Some((file, _)) => {
+                let metadata = fs::metadata(path);
+                if matches!(&metadata, Ok(metadata) if file.is_current(metadata)) {
+                    cache.lock().unwrap().checked(path);
+                    return Ok(file.respond(request));
+                }
+                // The file was modified or removed since it was cached
+                cache.lock().unwrap().remove(path);
+                metadata?
+            }
+            None => fs::metadata(path)?,
+        };
+
+        if !metadata.is_file() {
+            return Err(FileError::NotFound);
+        }
+        if metadata.len() > cache.lock().unwrap().max_file_size as u64 {
+            return serve_file(path, request);
+        }
+
+        let file = Arc::new(CachedFile::load(path, &metadata)?);
+        cache.lock().unwrap().insert(path, file.clone());
+
+        Ok(file.respond(request))
+    }
+}
+
+impl std::fmt::Debug for StaticFiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticFiles")
+            .field("prefix", &self.prefix)
+            .field("root", &self.root)
+            .field("cached", &self.cache.is_some())
+            .finish()
+    }
+}
+
+/// Redirect the request to the path of the directory ending with a '/', keeping its query string
+fn redirect_to_directory(request: &Request) -> Response {
+    let location = match request.uri().query() {
+        Some(query) => format!("{}/?{}", request.uri().path(), query),
+        None => format!("{}/", request.uri().path()),
+    };
+
+    ResponseBuilder::empty_301(&encoding::location(&location))
+        .build()
+        .unwrap()
+}
+
+/// Return an HTML page linking to the files of the directory, sorted by name with the directories first
+fn listing(path: &Path, target: &str, parent: bool) -> Result<String, FileError> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) if !name.starts_with('.') => name,
+            _ => continue,
+        };
+        let is_dir = entry.file_type()?.is_dir();
+        entries.push((!is_dir, name));
+    }
+    entries.sort();
+
+    let title = escape_html(target);
+    let mut page = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\
+         <body><h1>Index of {0}</h1><ul>",
+        title
+    );
+    if parent {
+        page.push_str("<li><a href=\"../\">../</a></li>");
+    }
+    for (is_file, name) in entries {
+        let slash = if is_file { "" } else { "/" };
+        page.push_str(&format!(
+            "<li><a href=\"{}{}\">{}{}</a></li>",
+            escape_html(&encoding::encode_path_segment(&name)),
+            slash,
+            escape_html(&name),
+            slash
+        ));
+    }
+    page.push_str("</ul></body></html>");
+
+    Ok(page)
+}
+
+/// Stream a file from the disk
+fn serve_file(path: &Path, request: &Request) -> Result<Response, FileError> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(FileError::NotFound);
+    }
+
+    let etag = entity_tag(&metadata);
+    if not_modified(request, &etag) {
+        return Ok(ResponseBuilder::not_modified()
+            .header("ETag", &etag)
+            .build()
+            .unwrap());
+    }
+
+    let builder = if *request.method() == Method::HEAD {
+        ResponseBuilder::empty_200()
+            .content_type(content_type(path))
+            .header("Content-Length", &metadata.len().to_string())
+    } else {
+        ResponseBuilder::file(path)?
+    };
+
+    Ok(builder.header("ETag", &etag).build().unwrap())
+}
+
+/// Return the entity tag of a file from its size and its modification time
+fn entity_tag(metadata: &fs::Metadata) -> String {
+    format!("\"{:x}-{:x}\"", metadata.len(), modified_micros(metadata))
+}
+
+fn modified_micros(metadata: &fs::Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_micros())
+}
+
+/// Return true if the "If-None-Match" header of the request holds the entity tag, weak or not, or `*`
+fn not_modified(request: &Request, etag: &str) -> bool {
+    let tags = match request.headers().get_header("If-None-Match") {
+        Some(tags) => tags,
+        None => return false,
+    };
+
+    tags.split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Return true if the "Accept-Encoding" header of the request accepts the content coding
+fn accepts_encoding(request: &Request, coding: &str) -> bool {
+    let accepted = match request.headers().get_header("Accept-Encoding") {
+        Some(accepted) => accepted,
+        None => return false,
+    };
+
+    accepted.split(',').any(|item| {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or("").trim();
+        let refused = params.any(|param| {
+            matches!(param.trim().strip_prefix("q=").map(str::parse::<f32>), Some(Ok(q)) if q <= 0.0)
+        });
+
+        (name.eq_ignore_ascii_case(coding) || name == "*") && !refused
+    })
+}
+
+/// File kept in memory with its precompressed variants
+struct CachedFile {
+    len: u64,
+    modified: u128,
+    etag: String,
+    content_type: &'static str,
+    body: Vec<u8>,
+    variants: Vec<(&'static str, Vec<u8>)>,
+}
+
+impl CachedFile {
+    fn load(path: &Path, metadata: &fs::Metadata) -> Result<CachedFile, FileError> {
+        let body = fs::read(path)?;
+        let mut variants = Vec::new();
+        for (coding, extension) in VARIANTS.iter() {
+            let mut variant = path.as_os_str().to_os_string();
+            variant.push(".");
+            variant.push(extension);
+            // A variant is optional, it is only sent if it is not older than the file
+            match fs::metadata(&variant) {
+                Ok(compressed) if compressed.modified().ok() >= metadata.modified().ok() => {
+                    variants.push((*coding, fs::read(&variant)?));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(CachedFile {
+            len: metadata.len(),
+            modified: modified_micros(metadata),
+            etag: entity_tag(metadata),
+            content_type: content_type(path),
+            body,
+            variants,
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .variants
+                .iter()
+                .map(|(_, body)| body.len())
+                .sum::<usize>()
+    }
+
+    fn is_current(&self, metadata: &fs::Metadata) -> bool {
+        metadata.is_file()
+            && metadata.len() == self.len
+            && modified_micros(metadata) == self.modified
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        let variant = self
+            .variants
+            .iter()
+            .find(|(coding, _)| accepts_encoding(request, coding));
+        // Each representation has its own tag so a cache does not mix them
+        let (etag, body) = match variant {
+            Some((coding, body)) => (
+                format!("{}-{}\"", self.etag.trim_end_matches('"'), coding),
+                body,
+            ),
+            None => (self.etag.clone(), &self.body),
+        };
+
+        let mut builder = if not_modified(request, &etag) {
+            ResponseBuilder::not_modified()
+        } else if *request.method() == Method::HEAD {
+            ResponseBuilder::empty_200()
+                .content_type(self.content_type)
+                .header("Content-Length", &body.len().to_string())
+        } else {
+            ResponseBuilder::empty_200().bytes(self.content_type, body)
+        };
+        builder = builder.header("ETag", &etag);
+        if !self.variants.is_empty() {
+            builder = builder.header("Vary", "Accept-Encoding");
+        }
+        if let Some((coding, _)) = variant {
+            builder = builder.header("Content-Encoding", coding);
+        }
+
+        builder.build().unwrap()
+    }
+}
+
+struct CacheEntry {
+    file: Arc<CachedFile>,
+    checked: Instant,
+    used: u64,
+}
+
+/// Least recently used files up to a total size
+struct FileCache {
+    files: HashMap<PathBuf, CacheEntry>,
+    size: usize,
+    max_size: usize,
+    max_file_size: usize,
+    clock: u64,
+}
+
+impl FileCache {
+    fn new(max_size: usize, max_file_size: usize) -> FileCache {
+        FileCache {
+            files: HashMap::new(),
+            size: 0,
+            max_size,
+            max_file_size,
+            clock: 0,
+        }
+    }
+
+    /// Return a cached file with the instant it was last checked
+    fn get(&mut self, path: &Path) -> Option<(Arc<CachedFile>, Instant)> {
+        self.clock += 1;
+        let entry = self.files.get_mut(path)?;
+        entry.used = self.clock;
+
+        Some((entry.file.clone(), entry.checked))
+    }
+
+    /// Mark the cached file as checked now
+    fn checked(&mut self, path: &Path) {
+        if let Some(entry) = self.files.get_mut(path) {
+            entry.checked = Instant::now();
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.files.remove(path) {
+            self.size -= entry.file.size();
+        }
+    }
+
+    /// Cache a file, evicting the least recently used ones to make room for it
+    fn insert(&mut self, path: &Path, file: Arc<CachedFile>) {
+        self.remove(path);
+        let size = file.size();
+        if size > self.max_size {
+            return;
+        }
+
+        while self.size + size > self.max_size {
+            let oldest = self
+                .files
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(path, _)| path.clone());
+            match oldest {
+                Some(oldest) => self.remove(&oldest),
+                None => break,
+            }
+        }
+
+        self.clock += 1;
+        self.size += size;
+        self.files.insert(
+            path.to_path_buf(),
+            CacheEntry {
+                file,
+                checked: Instant::now(),
+                used: self.clock,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Headers, RequestBuilder, Version};
+
+    fn request(method: Method, path: &str, headers: &[(&str, &str)]) -> Request {
+        let mut map = Headers::new();
+        for (name, value) in headers {
+            map.set_header(name, value);
+        }
+
+        RequestBuilder::new()
+            .method(method)
+            .path(String::from(path))
+            .version(Version::HTTP11)
+            .headers(map)
+            .build()
+            .unwrap()
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("mini_async_http_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn resolve() {
+        let files = StaticFiles::new("/assets/", "public");
+
+        assert_eq!(
+            files.resolve("/assets/css/a%20b.css"),
+            Some(PathBuf::from("public/css/a b.css"))
+        );
+        assert_eq!(files.resolve("/assets"), Some(PathBuf::from("public")));
+        assert_eq!(files.resolve("/assetsx/a.css"), None);
+        assert_eq!(files.resolve("/other/a.css"), None);
+        assert_eq!(files.resolve("/assets/%2e%2e/secret"), None);
+        assert_eq!(files.resolve("/assets/a%2Fb"), None);
+        assert_eq!(files.resolve("/assets/C:/secret"), None);
+        assert_eq!(files.resolve("/assets/c%3A%5Csecret"), None);
+
+        assert_eq!(files.resolve("/assets/.env"), None);
+        assert_eq!(files.resolve("/assets/.git/config"), None);
+        let files = files.serve_dotfiles(true);
+        assert_eq!(
+            files.resolve("/assets/.well-known/security.txt"),
+            Some(PathBuf::from("public/.well-known/security.txt"))
+        );
+        assert_eq!(files.resolve("/assets/../secret"), None);
+    }
+
+    #[test]
+    fn encodings() {
+        let accepts = |value: &str, coding: &str| {
+            accepts_encoding(
+                &request(Method::GET, "/", &[("Accept-Encoding", value)]),
+                coding,
+            )
+        };
+
+        assert!(accepts("gzip, br;q=0.5", "br"));
+        assert!(accepts("*", "gzip"));
+        assert!(!accepts("gzip, br;q=0", "br"));
+        assert!(!accepts("identity", "gzip"));
+    }
+
+    #[test]
+    fn revalidate() {
+        let dir = dir("static_revalidate");
+        let path = dir.join("style.css");
+        fs::write(&path, "a {}").unwrap();
+        fs::write(dir.join("style.css.gz"), "compressed").unwrap();
+        let files = StaticFiles::new("/", &dir).cache(1024, 1024);
+
+        let plain = files.handle(&request(Method::GET, "/style.css", &[]));
+        assert_eq!(plain.body().unwrap(), b"a {}");
+        assert_eq!(
+            plain.headers().get_header("Vary").unwrap(),
+            "Accept-Encoding"
+        );
+        let etag = plain.headers().get_header("ETag").unwrap().clone();
+
+        let gzip = files.handle(&request(
+            Method::GET,
+            "/style.css",
+            &[("Accept-Encoding", "gzip")],
+        ));
+        assert_eq!(gzip.body().unwrap(), b"compressed");
+        assert_eq!(
+            gzip.headers().get_header("Content-Encoding").unwrap(),
+            "gzip"
+        );
+        assert_ne!(gzip.headers().get_header("ETag").unwrap(), &etag);
+
+        let cached = files.handle(&request(
+            Method::GET,
+            "/style.css",
+            &[("If-None-Match", &etag)],
+        ));
+        assert_eq!(cached.code(), 304);
+
+        // The file is served from memory until it is checked again
+        fs::write(&path, "body {}").unwrap();
+        let stale = files.handle(&request(Method::GET, "/style.css", &[]));
+        assert_eq!(stale.body().unwrap(), b"a {}");
+
+        let cache = files.cache.as_ref().unwrap();
+        cache.lock().unwrap().files.get_mut(&path).unwrap().checked -= REVALIDATE_AFTER;
+        let fresh = files.handle(&request(Method::GET, "/style.css", &[]));
+        assert_eq!(fresh.body().unwrap(), b"body {}");
+        assert_ne!(fresh.headers().get_header("ETag").unwrap(), &etag);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn directories() {
+        let dir = dir("static_directories");
+        fs::create_dir_all(dir.join("docs/guide")).unwrap();
+        fs::create_dir_all(dir.join("site")).unwrap();
+        fs::create_dir_all(dir.join("Guides")).unwrap();
+        fs::write(dir.join("docs/a <b>.txt"), "a").unwrap();
+        fs::write(dir.join("docs/.hidden"), "").unwrap();
+        fs::write(dir.join("site/index.html"), "<p>home</p>").unwrap();
+        let files = StaticFiles::new("/", &dir)
+            .cache(1024, 1024)
+            .directory_listing(true)
+            .cache_control("HTML", CacheControl::new().no_cache());
+        let get = |path: &str| files.handle(&request(Method::GET, path, &[]));
+
+        let index = get("/site/");
+        assert_eq!(index.body().unwrap(), b"<p>home</p>");
+        assert_eq!(
+            index.headers().get_header("Cache-Control").unwrap(),
+            "no-cache"
+        );
+
+        let redirect = get("/site?lang=fr");
+        assert_eq!(redirect.code(), 301);
+        assert_eq!(
+            redirect.headers().get_header("Location").unwrap(),
+            "/site/?lang=fr"
+        );
+
+        // The paths are case sensitive, the redirection keeps them as requested
+        let redirect = get("/Guides?Lang=FR");
+        assert_eq!(redirect.code(), 301);
+        assert_eq!(
+            redirect.headers().get_header("Location").unwrap(),
+            "/Guides/?Lang=FR"
+        );
+
+        let listing = get("/docs/").body_as_string().unwrap();
+        assert!(listing.contains("<a href=\"../\">"));
+        assert!(listing.find("guide/").unwrap() < listing.find("a%20%3Cb%3E.txt").unwrap());
+        assert!(listing.contains(">a &lt;b&gt;.txt</a>"));
+        assert!(!listing.contains("hidden"));
+        assert!(!get("/").body_as_string().unwrap().contains("../"));
+
+        let files = StaticFiles::new("/", &dir).index_file(None);
+        assert_eq!(
+            files.handle(&request(Method::GET, "/site/", &[])).code(),
+            404
+        );
+        assert_eq!(
+            files.handle(&request(Method::GET, "/docs/", &[])).code(),
+            404
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn single_page_app() {
+        let dir = dir("static_spa");
+        fs::write(dir.join("index.html"), "<div id=app></div>").unwrap();
+        fs::write(dir.join("app.js"), "render()").unwrap();
+        let app = StaticFiles::single_page_app(&dir, &["/api/"]).cache(1024, 1024);
+        let browser = [("Accept", "text/html,*/*;q=0.8")];
+        let get =
+            |path: &str, headers: &[(&str, &str)]| app.handle(&request(Method::GET, path, headers));
+
+        assert_eq!(get("/app.js", &browser).body().unwrap(), b"render()");
+        assert_eq!(
+            get("/users/42", &browser).body().unwrap(),
+            b"<div id=app></div>"
+        );
+        assert_eq!(get("/apiary", &browser).code(), 200);
+
+        assert_eq!(get("/missing.js", &[("Accept", "*/*")]).code(), 404);
+        assert_eq!(get("/api", &browser).code(), 404);
+        assert_eq!(get("/api/users", &browser).code(), 404);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let dir = dir("static_evict");
+        for name in ["a", "b", "c", "large"].iter() {
+            let size = if *name == "large" { 64 } else { 10 };
+            fs::write(dir.join(name), vec![b'x'; size]).unwrap();
+        }
+        let files = StaticFiles::new("/", &dir).cache(25, 32);
+        let get = |name: &str| files.handle(&request(Method::GET, name, &[]));
+
+        get("/a");
+        get("/b");
+        get("/a");
+        get("/c");
+        // The large file is streamed from the disk
+        let large = get("/large");
+        assert!(large.body().is_none());
+        assert_eq!(large.headers().get_header("Content-Length").unwrap(), "64");
+
+        let cache = files.cache.as_ref().unwrap().lock().unwrap();
+        assert!(cache.files.contains_key(&dir.join("a")));
+        assert!(!cache.files.contains_key(&dir.join("b")));
+        assert!(cache.files.contains_key(&dir.join("c")));
+        assert!(!cache.files.contains_key(&dir.join("large")));
+        assert_eq!(cache.size, 20);
+        drop(cache);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,419 @@
+//! Circuit breaker answering right away with a 503 status code while the code it protects keeps failing.
+//!
+//! The breaker records the outcome of the last calls. Once the failures, errors or calls slower than the
+//! slow call threshold, reach the failure rate, the circuit opens and the calls are refused for the open
+//! duration. A few probe calls are then let through: the circuit closes if they all succeed and opens
+//! again otherwise. A failing downstream service is left alone to recover instead of piling up requests.
+//!
+//! The [`CircuitBreaker::middleware`] protects routes, counting the 5xx responses as failures, and
+//! [`CircuitBreaker::call`] protects the calls a handler makes to another service. The clones of a breaker
+//! share its state.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::{CircuitBreaker, ResponseBuilder};
+//! use std::time::Duration;
+//!
+//! let breaker = CircuitBreaker::new()
+//!     .failure_rate(0.5)
+//!     .slow_call(Duration::from_secs(2))
+//!     .open_duration(Duration::from_secs(30));
+//!
+//! let router = mini_async_http::router!(
+//!     scope "/reports" with [breaker.middleware()] {
+//!         "/daily", mini_async_http::Method::GET => |_, _| {
+//!             ResponseBuilder::empty_200().build().unwrap()
+//!         }
+//!     }
+//! );
+//! ```
+//!
+//! [`CircuitBreaker::middleware`]: struct.CircuitBreaker.html#method.middleware
+//! [`CircuitBreaker::call`]: struct.CircuitBreaker.html#method.call
+
+use crate::router::middleware::Next;
+use crate::{Request, Response, ResponseBuilder};
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_FAILURE_RATE: f64 = 0.5;
+const DEFAULT_WINDOW: usize = 20;
+const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(10);
+const DEFAULT_PROBES: usize = 3;
+
+/// State of a [`CircuitBreaker`]
+///
+/// [`CircuitBreaker`]: struct.CircuitBreaker.html
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    /// The calls go through and their outcome is recorded
+    Closed,
+    /// The calls are refused until the open duration is elapsed
+    Open,
+    /// A few probe calls go through to decide whether the circuit closes
+    HalfOpen,
+}
+
+/// Error of a call made through [`CircuitBreaker::call`]
+///
+/// [`CircuitBreaker::call`]: struct.CircuitBreaker.html#method.call
+#[derive(Debug, PartialEq)]
+pub enum CircuitError<E> {
+    /// The circuit is open, the call was not made
+    Open,
+    /// The call failed with the given error
+    Failed(E),
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed,
+    Open(Instant),
+    HalfOpen { started: usize, succeeded: usize },
+}
+
+#[derive(Debug)]
+struct Outcomes {
+    circuit: Circuit,
+    /// Outcome of the last calls while closed, true for a failure
+    last: VecDeque<bool>,
+    failures: usize,
+}
+
+/// Protect routes or calls to another service, see the [module documentation](circuit_breaker/index.html)
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    failure_rate: f64,
+    slow_call: Option<Duration>,
+    window: usize,
+    open_duration: Duration,
+    probes: usize,
+    outcomes: Arc<Mutex<Outcomes>>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker opening when half of the last 20 calls failed, for 10 seconds
+    pub fn new() -> CircuitBreaker {
+        CircuitBreaker {
+            failure_rate: DEFAULT_FAILURE_RATE,
+            slow_call: None,
+            window: DEFAULT_WINDOW,
+            open_duration: DEFAULT_OPEN_DURATION,
+            probes: DEFAULT_PROBES,
+            outcomes: Arc::new(Mutex::new(Outcomes {
+                circuit: Circuit::Closed,
+                last: VecDeque::new(),
+                failures: 0,
+            })),
+        }
+    }
+
+    /// Set the fraction of failed calls opening the circuit, 0.5 by default
+    pub fn failure_rate(mut self, rate: f64) -> CircuitBreaker {
+        self.failure_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Count the calls taking longer than the given duration as failures, even when they succeed
+    pub fn slow_call(mut self, threshold: Duration) -> CircuitBreaker {
+        self.slow_call = Some(threshold);
+        self
+    }
+
+    /// Set the number of last calls the failure rate is computed on, 20 by default.
+    /// The circuit does not open before that many calls are recorded.
+    pub fn window(mut self, calls: usize) -> CircuitBreaker {
+        self.window = calls.max(1);
+        self
+    }
+
+    /// Set how long the calls are refused once the circuit is open, 10 seconds by default
+    pub fn open_duration(mut self, duration: Duration) -> CircuitBreaker {
+        self.open_duration = duration;
+        self
+    }
+
+    /// Set the number of probe calls that must succeed to close the circuit again, 3 by default
+    pub fn half_open_probes(mut self, probes: usize) -> CircuitBreaker {
+        self.probes = probes.max(1);
+        self
+    }
+
+    /// Return the current state of the circuit
+    pub fn state(&self) -> CircuitState {
+        match self.outcomes.lock().unwrap().circuit {
+            Circuit::Closed => CircuitState::Closed,
+            Circuit::Open(until) if Instant::now() < until => CircuitState::Open,
+            Circuit::Open(_) | Circuit::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Make the call unless the circuit is open, an error or a slow call is counted as a failure
+    pub fn call<T, E, F>(&self, f: F) -> Result<T, CircuitError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let probe = self.acquire().map_err(|_| CircuitError::Open)?;
+
+        let mut guard = CallGuard::new(self, probe);
+        let result = f();
+        guard.failed = result.is_err();
+
+        result.map_err(CircuitError::Failed)
+    }
+
+    /// Create the middleware answering with a 503 status code while the circuit is open.
+    /// The responses with a 5xx status code are counted as failures.
+    pub fn middleware(&self) -> impl Send + Sync + 'static + Fn(&Request, Next<'_>) -> Response {
+        let breaker = self.clone();
+
+        move |request: &Request, next: Next<'_>| breaker.handle(request, next)
+    }
+
+    fn handle(&self, request: &Request, next: Next<'_>) -> Response {
+        let probe = match self.acquire() {
+            Ok(probe) => probe,
+            Err(retry) => {
+                // Retry-After is in whole seconds, a client retrying earlier would be refused again
+                let seconds = retry.as_secs() + u64::from(retry.subsec_nanos() > 0);
+                return ResponseBuilder::empty_503()
+                    .header("Retry-After", &seconds.max(1).to_string())
+                    .build()
+                    .unwrap();
+            }
+        };
+
+        let mut guard = CallGuard::new(self, probe);
+        let response = next.run(request);
+        guard.failed = response.code() >= 500;
+
+        response
+    }
+
+    fn is_slow(&self, start: Instant) -> bool {
+        matches!(self.slow_call, Some(threshold) if start.elapsed() > threshold)
+    }
+
+    /// Let a call through, returning whether it is a probe, or return the time until the circuit may close
+    fn acquire(&self) -> Result<bool, Duration> {
+        let mut outcomes = self.outcomes.lock().unwrap();
+        let now = Instant::now();
+
+        match outcomes.circuit {
+            Circuit::Closed => Ok(false),
+            Circuit::Open(until) if now < until => Err(until - now),
+            Circuit::Open(_) => {
+                outcomes.circuit = Circuit::HalfOpen {
+                    started: 1,
+                    succeeded: 0,
+                };
+                Ok(true)
+            }
+            Circuit::HalfOpen {
+                ref mut started, ..
+            } if *started < self.probes => {
+                *started += 1;
+                Ok(true)
+            }
+            Circuit::HalfOpen { .. } => Err(Duration::from_secs(0)),
+        }
+    }
+
+    fn record(&self, probe: bool, failed: bool) {
+        let mut outcomes = self.outcomes.lock().unwrap();
+
+        match outcomes.circuit {
+            Circuit::HalfOpen {
+                ref mut succeeded, ..
+            } if probe => {
+                *succeeded += 1;
+                if failed {
+                    self.open(&mut outcomes);
+                } else if *succeeded >= self.probes {
+                    outcomes.circuit = Circuit::Closed;
+                }
+            }
+            Circuit::Closed => {
+                outcomes.last.push_back(failed);
+                outcomes.failures += usize::from(failed);
+                if outcomes.last.len() > self.window {
+                    let oldest = outcomes.last.pop_front().unwrap_or_default();
+                    outcomes.failures -= usize::from(oldest);
+                }
+
+                let calls = outcomes.last.len();
+                if calls >= self.window
+                    && outcomes.failures as f64 >= self.failure_rate * calls as f64
+                {
+                    self.open(&mut outcomes);
+                }
+            }
+            // The calls started before the circuit opened do not count
+            _ => {}
+        }
+    }
+
+    fn open(&self, outcomes: &mut Outcomes) {
+        outcomes.circuit = Circuit::Open(Instant::now() + self.open_duration);
+        outcomes.last.clear();
+        outcomes.failures = 0;
+    }
+}
+
+/// Call let through by the breaker, its outcome is recorded when the guard is dropped.
+/// A call unwinding before it completes, as when it panics, is counted as a failure, otherwise a probe
+/// would never be recorded and the circuit would stay half open.
+struct CallGuard<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    start: Instant,
+    failed: bool,
+}
+
+impl<'a> CallGuard<'a> {
+    fn new(breaker: &'a CircuitBreaker, probe: bool) -> CallGuard<'a> {
+        CallGuard {
+            breaker,
+            probe,
+            start: Instant::now(),
+            failed: true,
+        }
+    }
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        let failed = self.failed || self.breaker.is_slow(self.start);
+        self.breaker.record(self.probe, failed);
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Method, Middleware, RequestBuilder, Route, Router};
+
+    fn request(path: &str) -> Request {
+        RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from(path))
+            .version(crate::Version::HTTP11)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn open_on_failures() {
+        let breaker = CircuitBreaker::new()
+            .window(4)
+            .open_duration(Duration::from_millis(20));
+
+        let mut router = Router::new();
+        router.add_routes_with(
+            vec![Route::new("/{code}", Method::GET).unwrap()],
+            &[Middleware::new(breaker.middleware())],
+            |_, parameters| match parameters["code"].as_str() {
+                "500" => ResponseBuilder::empty_500().build().unwrap(),
+                _ => ResponseBuilder::empty_200().build().unwrap(),
+            },
+        );
+
+        for code in &[200, 500, 200, 500] {
+            assert_eq!(router.exec(&request(&format!("/{}", code))).code(), *code);
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let refused = router.exec(&request("/200"));
+        assert_eq!(refused.code(), 503);
+        assert_eq!(refused.headers().get_header("Retry-After").unwrap(), "1");
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(router.exec(&request("/500")).code(), 500);
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn close_after_probes() {
+        let breaker = CircuitBreaker::new()
+            .window(2)
+            .half_open_probes(2)
+            .open_duration(Duration::from_millis(20));
+
+        for _ in 0..2 {
+            assert_eq!(
+                breaker.call(|| Err::<(), _>("down")),
+                Err(CircuitError::Failed("down"))
+            );
+        }
+        assert_eq!(breaker.call(|| Ok::<_, ()>(1)), Err(CircuitError::Open));
+
+        std::thread::sleep(Duration::from_millis(30));
+        let first = breaker.acquire().unwrap();
+        let second = breaker.acquire().unwrap();
+        // Only the probes go through while the circuit is half open
+        assert!(breaker.acquire().is_err());
+
+        breaker.record(first, false);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        breaker.record(second, false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.call(|| Ok::<_, ()>(1)), Ok(1));
+    }
+
+    #[test]
+    fn panicking_probe() {
+        let breaker = CircuitBreaker::new()
+            .window(1)
+            .half_open_probes(1)
+            .open_duration(Duration::from_millis(20));
+
+        let mut router = Router::new();
+        router.add_routes_with(
+            vec![Route::new("/{outcome}", Method::GET).unwrap()],
+            &[Middleware::new(breaker.middleware())],
+            |_, parameters| match parameters["outcome"].as_str() {
+                "panic" => panic!("The handler panicked"),
+                _ => ResponseBuilder::empty_200().build().unwrap(),
+            },
+        );
+        let exec = |path: &str| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| router.exec(&request(path))))
+        };
+
+        assert!(exec("/panic").is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // The panicking probe opens the circuit again instead of holding the only probe slot
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(exec("/panic").is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(exec("/ok").unwrap().code(), 200);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn slow_calls() {
+        let breaker = CircuitBreaker::new()
+            .window(1)
+            .slow_call(Duration::from_millis(5));
+
+        let result = breaker.call(|| {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok::<_, ()>(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+}
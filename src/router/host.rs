@@ -0,0 +1,98 @@
+use crate::Request;
+
+/// Host header pattern used to select the routes of a virtual host.
+/// The pattern is either a host name like `api.example.com` or a wildcard like `*.example.com`
+/// matching every subdomain. The port of the Host header is ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HostPattern {
+    pattern: String,
+}
+
+impl HostPattern {
+    pub(crate) fn new(pattern: &str) -> HostPattern {
+        HostPattern {
+            pattern: pattern.trim_end_matches('.').to_ascii_lowercase(),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Check the Host header of the request, a request without Host header never matches
+    pub(crate) fn is_match(&self, req: &Request) -> bool {
+        let host = match req.headers().get_header("Host") {
+            Some(host) => host_name(host).to_ascii_lowercase(),
+            None => return false,
+        };
+
+        match self.pattern.strip_prefix("*.") {
+            Some(domain) => {
+                host.len() > domain.len() + 1
+                    && host.ends_with(domain)
+                    && host[..host.len() - domain.len()].ends_with('.')
+            }
+            None => host == self.pattern,
+        }
+    }
+}
+
+/// Remove the port and the trailing dot of a Host header value, IPv6 addresses keep their brackets
+pub(crate) fn host_name(host: &str) -> &str {
+    let host = host.trim();
+    let name = if host.starts_with('[') {
+        match host.find(']') {
+            Some(end) => &host[..=end],
+            None => host,
+        }
+    } else {
+        host.split(':').next().unwrap()
+    };
+
+    name.trim_end_matches('.')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Method, RequestBuilder};
+
+    fn request(host: &str) -> Request {
+        let mut headers = crate::Headers::new();
+        headers.set_header("Host", host);
+
+        RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from("/"))
+            .version(crate::Version::HTTP11)
+            .headers(headers)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn exact_host() {
+        let pattern = HostPattern::new("API.example.com");
+
+        assert!(pattern.is_match(&request("api.example.com")));
+        assert!(pattern.is_match(&request("api.example.com:8080")));
+        assert!(!pattern.is_match(&request("www.example.com")));
+    }
+
+    #[test]
+    fn wildcard_host() {
+        let pattern = HostPattern::new("*.example.com");
+
+        assert!(pattern.is_match(&request("api.example.com")));
+        assert!(pattern.is_match(&request("a.b.example.com:80")));
+        assert!(!pattern.is_match(&request("example.com")));
+        assert!(!pattern.is_match(&request("badexample.com")));
+    }
+
+    #[test]
+    fn ipv6_host() {
+        let pattern = HostPattern::new("[::1]");
+
+        assert!(pattern.is_match(&request("[::1]:8080")));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{Request, Response};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type MiddlewareFn = Arc<dyn Send + Sync + 'static + Fn(&Request, Next<'_>) -> Response>;
+
+/// Code wrapping the handlers of a [`Router`], it is given the request and the rest of the chain.
+/// A middleware can answer by itself or call [`Next::run`] to continue with the next middleware or the handler.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{Middleware, ResponseBuilder};
+///
+/// let auth = Middleware::new(|req, next| match req.headers().get_header("Authorization") {
+///     Some(_) => next.run(req),
+///     None => ResponseBuilder::empty_400().build().unwrap(),
+/// });
+/// ```
+///
+/// [`Router`]: struct.Router.html
+/// [`Next::run`]: struct.Next.html#method.run
+#[derive(Clone)]
+pub struct Middleware {
+    inner: MiddlewareFn,
+}
+
+impl Middleware {
+    /// Create a middleware from a closure
+    pub fn new<T>(middleware: T) -> Middleware
+    where
+        T: Send + Sync + 'static + Fn(&Request, Next<'_>) -> Response,
+    {
+        Middleware {
+            inner: Arc::new(middleware),
+        }
+    }
+
+    pub(crate) fn handle(&self, request: &Request, next: Next<'_>) -> Response {
+        (self.inner)(request, next)
+    }
+}
+
+/// Rest of the handling chain given to a [`Middleware`]
+///
+/// [`Middleware`]: struct.Middleware.html
+pub struct Next<'a> {
+    handler: &'a (dyn Send + Sync + Fn(&Request, HashMap<String, String>) -> Response),
+    parameters: HashMap<String, String>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        handler: &'a (dyn Send + Sync + Fn(&Request, HashMap<String, String>) -> Response),
+        parameters: HashMap<String, String>,
+    ) -> Next<'a> {
+        Next {
+            handler,
+            parameters,
+        }
+    }
+
+    /// Return the parameters parsed from the route
+    pub fn parameters(&self) -> &HashMap<String, String> {
+        &self.parameters
+    }
+
+    /// Continue the chain with the given request
+    pub fn run(self, request: &Request) -> Response {
+        (self.handler)(request, self.parameters)
+    }
+}
@@ -1,20 +1,35 @@
+pub(crate) mod host;
+pub mod circuit_breaker;
+pub mod middleware;
+#[cfg(feature = "openapi")]
+pub mod openapi;
 pub mod route;
+#[cfg(feature = "session")]
+pub mod session;
+pub mod split;
+pub mod trace_context;
 
-use crate::{Request, Response, ResponseBuilder, Route};
+use crate::aioserver::deadline::with_timeout;
+use crate::{Headers, Middleware, Request, Response, ResponseBuilder, Route};
+use host::HostPattern;
+use middleware::Next;
+
+use futures::future::{BoxFuture, FutureExt};
 
 use std::collections::HashMap;
 use std::sync::Arc;
 
-type RouteList = Vec<(
-    route::Route,
-    Arc<dyn Send + Sync + 'static + Fn(&Request, HashMap<String, String>) -> Response>,
-)>;
+type RouteHandler = Arc<dyn Send + Sync + 'static + Fn(&Request, HashMap<String, String>) -> Response>;
+
+type RouteList = Vec<(route::Route, RouteHandler)>;
 
 /// Map http route to a specific handler
 #[derive(Clone)]
 pub struct Router {
     routes: RouteList,
+    hosts: Vec<(HostPattern, Router)>,
     not_found: Arc<dyn Send + Sync + 'static + Fn(&Request) -> Response>,
+    default_headers: Headers,
 }
 
 fn default_not_found(_: &Request) -> Response {
@@ -25,12 +40,51 @@ impl Router {
     /// Create a new empty Router
     pub fn new() -> Router {
         Router { routes: Vec::new(),
-            not_found: Arc::from(default_not_found)
+            hosts: Vec::new(),
+            not_found: Arc::from(default_not_found),
+            default_headers: Headers::new()
          }
     }
 
     pub(crate) fn is_matching(&self, req: &crate::Request) -> bool {
-        self.routes.iter().any(|(route, _)| route.is_match(&req))
+        self.hosts.iter().any(|(host, router)| host.is_match(req) && router.is_matching(req))
+            || self.routes.iter().any(|(route, _)| route.is_match(req))
+    }
+
+    /// Return the priority of the first route matching the request, the default one if none is matching
+    pub(crate) fn priority(&self, req: &crate::Request) -> crate::Priority {
+        if let Some((_, router)) = self.hosts.iter().find(|(host, _)| host.is_match(req)) {
+            return router.priority(req);
+        }
+
+        self.routes
+            .iter()
+            .find(|(route, _)| route.is_match(req))
+            .map(|(route, _)| route.priority_class())
+            .unwrap_or_default()
+    }
+
+    /// Serve the requests sent to the given host with another router, its not found handler included.
+    /// The pattern is a host name or a wildcard like `*.example.com` matching every subdomain,
+    /// the port of the Host header is ignored.
+    ///
+    /// The virtual hosts are checked in the order they are added, before the routes of this router.
+    /// The requests matching none of them are routed as usual.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Router,Method, ResponseBuilder};
+    ///
+    /// let mut api = Router::new();
+    /// api.add_route(Route::new("/users",Method::GET).unwrap(), |_,_|ResponseBuilder::empty_200().build().unwrap());
+    ///
+    /// let mut router = Router::new();
+    /// router.add_host("api.example.com", api);
+    /// router.add_route(Route::new("/home",Method::GET).unwrap(), |_,_|ResponseBuilder::empty_200().html("<h1>Home</h1>").build().unwrap());
+    /// ```
+    pub fn add_host(&mut self, pattern: &str, router: Router) {
+        self.hosts.push((HostPattern::new(pattern), router));
     }
 
     /// Add a new handler associated to a route to the router.
@@ -61,15 +115,126 @@ impl Router {
         self.routes.push((route, Arc::from(handler)));
     }
 
+    /// Add the same handler to several routes, for instance one path served with multiple methods.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Router,Method, ResponseBuilder};
+    ///
+    /// let mut router = Router::new();
+    ///
+    /// router.add_routes(
+    ///     vec![Route::new("/item",Method::GET).unwrap(), Route::new("/item",Method::HEAD).unwrap()],
+    ///     |_,_|ResponseBuilder::empty_200().build().unwrap()
+    /// );
+    /// ```
+    pub fn add_routes<T>(&mut self, routes: Vec<Route>, handler: T)
+    where
+        T: Send + Sync + 'static + std::ops::Fn(&Request, HashMap<String, String>) -> Response,
+    {
+        self.add_routes_with(routes, &[], handler);
+    }
+
+    /// Add the same handler to several routes, wrapped by the given middlewares.
+    /// The first middleware of the list is the first to be executed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Middleware,Route,Router,Method, ResponseBuilder};
+    ///
+    /// let auth = Middleware::new(|req, next| match req.headers().get_header("Authorization") {
+    ///     Some(_) => next.run(req),
+    ///     None => ResponseBuilder::empty_400().build().unwrap(),
+    /// });
+    ///
+    /// let mut router = Router::new();
+    ///
+    /// router.add_routes_with(
+    ///     vec![Route::new("/private",Method::GET).unwrap()],
+    ///     &[auth],
+    ///     |_,_|ResponseBuilder::empty_200().build().unwrap()
+    /// );
+    /// ```
+    pub fn add_routes_with<T>(&mut self, routes: Vec<Route>, middlewares: &[Middleware], handler: T)
+    where
+        T: Send + Sync + 'static + std::ops::Fn(&Request, HashMap<String, String>) -> Response,
+    {
+        let handler: RouteHandler = middlewares.iter().rev().fold(Arc::from(handler), |inner, middleware| {
+            let middleware = middleware.clone();
+            Arc::new(move |req: &Request, parameters: HashMap<String, String>| {
+                middleware.handle(req, Next::new(&*inner, parameters))
+            })
+        });
+
+        for route in routes {
+            if self.routes.iter().any(|(key_route, _)| &route == key_route) {
+                continue;
+            }
+            self.routes.push((route, handler.clone()));
+        }
+    }
+
+    /// Return the routes of the router in the order they are matched
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter().map(|(route, _)| route)
+    }
+
     /// Route the given request to a handler
     /// If no route match the given request, will execute the default handler
+    /// If the matching route limits the body size or the Content-Type, violating requests are answered with a 413 or 415 status code
+    /// If the handler answers with [`Response::pass`], the next matching route is tried
+    /// If the matching route has a timeout, its handler runs on a blocking thread and a 503 status code is answered once the timeout is reached, the handler itself is not interrupted
+    /// The request timeout of the server, for the routes without their own, is only checked once the handler returns
+    /// The default headers of the route and of the router are added to the response when it does not set them
+    ///
+    /// [`Response::pass`]: struct.Response.html#method.pass
     pub fn exec(&self, req: &crate::Request) -> Response {
-        if let Some((route, handler)) = self.routes.iter().find(|(route, _)| route.is_match(req)) {
+        futures::executor::block_on(self.respond(req))
+    }
+
+    /// Route the request like [`exec`](#method.exec), waiting for the handlers with a timeout without blocking the worker
+    pub(crate) fn respond<'a>(&'a self, req: &'a crate::Request) -> BoxFuture<'a, Response> {
+        async move {
+            let mut response = self.route(req).await;
+            response.headers.merge_defaults(&self.default_headers);
+
+            response
+        }
+        .boxed()
+    }
+
+    async fn route(&self, req: &crate::Request) -> Response {
+        if let Some((_, router)) = self.hosts.iter().find(|(host, _)| host.is_match(req)) {
+            return router.respond(req).await;
+        }
+
+        for (route, handler) in self.routes.iter().filter(|(route, _)| route.is_match(req)) {
+            #[cfg(feature = "tracing")]
+            tracing::Span::current().record("route", route.pattern());
+
+            if let Some(rejected) = route.check_guards(req) {
+                return rejected;
+            }
+
             let parameters = match route.parse_request(req) {
                 Some(param) => param,
                 None => return ResponseBuilder::empty_500().build().unwrap(),
             };
-            return handler(req, parameters);
+
+            let mut response = match route.handler_timeout() {
+                Some(timeout) => {
+                    let handler = handler.clone();
+                    let req = req.clone();
+                    with_timeout(timeout, move || handler(&req, parameters)).await
+                }
+                None => handler(req, parameters),
+            };
+            if !response.is_pass() {
+                response.headers.merge_defaults(route.response_headers());
+                return response;
+            }
         }
 
         (self.not_found)(req)
@@ -83,6 +248,21 @@ impl Router {
         self.not_found = Arc::from(handler);
     }
 
+    /// Add a header to every response of the router, not found responses included, unless the handler or the route sets it.
+    /// The routers of the virtual hosts add their own default headers first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.set_default_header("X-Frame-Options", "deny");
+    /// ```
+    pub fn set_default_header(&mut self, name: &str, value: &str) {
+        self.default_headers.set_header(name, value);
+    }
+
 }
 
 impl Default for Router {
@@ -93,6 +273,19 @@ impl Default for Router {
 
 /// Easier syntax to create a new router
 ///
+/// Each arm associates a path to a handler, the path can be followed by :
+/// * a single method : `"/path", Method::GET => handler`
+/// * a list of methods sharing the handler : `"/path", [Method::GET, Method::POST] => handler`
+/// * `ANY` to match every method : `"/path", ANY => handler`
+///
+/// The `_ => handler` arm sets the handler used when no route is matching.
+///
+/// Routes can be grouped under a common prefix with `scope "/prefix" { ... }`, adding `with [middleware, ...]`
+/// after the prefix wraps every route of the group with the given [`Middleware`] closures.
+///
+/// `host "api.example.com" { ... }` serves the requests sent to a virtual host with the routes of the block,
+/// see [`Router::add_host`].
+///
 /// # Example
 ///
 /// ```
@@ -100,18 +293,71 @@ impl Default for Router {
 ///
 /// let router = router!(
 ///     "/path/static", Method::GET => |_,_|ResponseBuilder::empty_200().body(b"GET").build().unwrap(),
-///     "/path/{param}", Method::GET => |_,param|ResponseBuilder::empty_200().body(param.get("parameter").unwrap().as_bytes()).build().unwrap()
+///     "/path/{param}", Method::GET => |_,param|ResponseBuilder::empty_200().body(param.get("parameter").unwrap().as_bytes()).build().unwrap(),
+///     "/items", [Method::GET, Method::HEAD] => |_,_|ResponseBuilder::empty_200().build().unwrap(),
+///     "/any", ANY => |req,_|ResponseBuilder::empty_200().body(req.method().as_str().as_bytes()).build().unwrap(),
+///     scope "/api/v1" with [|req, next| next.run(req)] {
+///         "/users", Method::GET => |_,_|ResponseBuilder::empty_200().body(b"users").build().unwrap(),
+///         "/users", Method::POST => |_,_|ResponseBuilder::empty_200().build().unwrap()
+///     },
+///     host "*.example.com" {
+///         "/status", Method::GET => |_,_|ResponseBuilder::empty_200().body(b"subdomain").build().unwrap()
+///     },
+///     _ => |_|ResponseBuilder::empty_404().body(b"Nothing here").build().unwrap()
 /// );
 ///
 /// ```
+///
+/// [`Middleware`]: struct.Middleware.html
+/// [`Router::add_host`]: struct.Router.html#method.add_host
 #[macro_export]
 macro_rules! router {
-    ( $( $path:expr, $method:expr => $handler:expr ),* ) => {
+    (@arms $router:ident;) => {};
+    (@arms $router:ident; _ => $handler:expr $(, $($rest:tt)*)?) => {
+        $router.set_not_found_handler($handler);
+        $crate::router!(@arms $router; $($($rest)*)?);
+    };
+    (@arms $router:ident; host $pattern:literal { $($inner:tt)* } $(, $($rest:tt)*)?) => {
+        $router.add_host($pattern, $crate::router!($($inner)*));
+        $crate::router!(@arms $router; $($($rest)*)?);
+    };
+    (@arms $router:ident; scope $prefix:literal $(with [$($middleware:expr),* $(,)?])? { $($inner:tt)* } $(, $($rest:tt)*)?) => {
         {
+            let middlewares: Vec<$crate::Middleware> = vec![$($($crate::Middleware::new($middleware)),*)?];
+            $crate::router!(@scope $router, $prefix, middlewares; $($inner)*);
+        }
+        $crate::router!(@arms $router; $($($rest)*)?);
+    };
+    (@scope $router:ident, $prefix:literal, $middlewares:ident;) => {};
+    (@scope $router:ident, $prefix:literal, $middlewares:ident; $path:expr, ANY => $handler:expr $(, $($rest:tt)*)?) => {
+        $router.add_routes_with(vec![$crate::Route::from_path(&format!("{}{}", $prefix, $path)).unwrap()], &$middlewares, $handler);
+        $crate::router!(@scope $router, $prefix, $middlewares; $($($rest)*)?);
+    };
+    (@scope $router:ident, $prefix:literal, $middlewares:ident; $path:expr, [$($method:expr),+ $(,)?] => $handler:expr $(, $($rest:tt)*)?) => {
+        $router.add_routes_with(vec![$($crate::Route::new(&format!("{}{}", $prefix, $path), $method).unwrap()),+], &$middlewares, $handler);
+        $crate::router!(@scope $router, $prefix, $middlewares; $($($rest)*)?);
+    };
+    (@scope $router:ident, $prefix:literal, $middlewares:ident; $path:expr, $method:expr => $handler:expr $(, $($rest:tt)*)?) => {
+        $router.add_routes_with(vec![$crate::Route::new(&format!("{}{}", $prefix, $path), $method).unwrap()], &$middlewares, $handler);
+        $crate::router!(@scope $router, $prefix, $middlewares; $($($rest)*)?);
+    };
+    (@arms $router:ident; $path:expr, ANY => $handler:expr $(, $($rest:tt)*)?) => {
+        $router.add_route($crate::Route::from_path($path).unwrap(), $handler);
+        $crate::router!(@arms $router; $($($rest)*)?);
+    };
+    (@arms $router:ident; $path:expr, [$($method:expr),+ $(,)?] => $handler:expr $(, $($rest:tt)*)?) => {
+        $router.add_routes(vec![$($crate::Route::new($path, $method).unwrap()),+], $handler);
+        $crate::router!(@arms $router; $($($rest)*)?);
+    };
+    (@arms $router:ident; $path:expr, $method:expr => $handler:expr $(, $($rest:tt)*)?) => {
+        $router.add_route($crate::Route::new($path, $method).unwrap(), $handler);
+        $crate::router!(@arms $router; $($($rest)*)?);
+    };
+    ( $($arms:tt)* ) => {
+        {
+            #[allow(unused_mut)]
             let mut router = $crate::Router::new();
-            $(
-                router.add_route($crate::Route::new($path, $method).unwrap(), $handler);
-            )*
+            $crate::router!(@arms router; $($arms)*);
             router
         }
     };
@@ -162,6 +408,32 @@ mod test {
         assert!(!router.is_matching(&req));
     }
 
+    #[test]
+    fn router_priority() {
+        let mut router = Router::new();
+
+        router.add_route(
+            route::Route::new("/upload", Method::POST).unwrap().priority(crate::Priority::Bulk),
+            |_req, _| ResponseBuilder::empty_200().build().unwrap(),
+        );
+
+        let upload = RequestBuilder::new()
+            .method(Method::POST)
+            .path(String::from("/upload"))
+            .version(crate::Version::HTTP11)
+            .build()
+            .unwrap();
+        let other = RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from("/upload"))
+            .version(crate::Version::HTTP11)
+            .build()
+            .unwrap();
+
+        assert_eq!(router.priority(&upload), crate::Priority::Bulk);
+        assert_eq!(router.priority(&other), crate::Priority::High);
+    }
+
     #[test]
     fn router_exec_single_route() {
         let mut router = Router::new();
@@ -262,6 +534,28 @@ mod test {
         assert_eq!(response.body().unwrap(), b"POST");
     }
 
+    #[test]
+    fn router_exec_patch() {
+        let mut router = Router::new();
+
+        router.add_route(
+            route::Route::new("/test", Method::PATCH).unwrap(),
+            move |_req, _| ResponseBuilder::empty_200().body(b"PATCH").build().unwrap(),
+        );
+
+        let req = RequestBuilder::new()
+            .method(Method::PATCH)
+            .path(String::from("/test"))
+            .version(crate::Version::HTTP11)
+            .build()
+            .expect("Error when building request");
+
+        let response = router.exec(&req);
+
+        assert_eq!(response.code(), 200);
+        assert_eq!(response.body().unwrap(), b"PATCH");
+    }
+
     #[test]
     fn router_add_same_route() {
         let mut router = Router::new();
@@ -456,4 +750,196 @@ mod test {
         assert_eq!(resp.body(),Some(&(b"Not Found".to_vec())));
 
     }
+
+    #[test]
+    fn router_macro_arms() {
+        let router = router!(
+            "/multi", [Method::GET, Method::POST] => |req,_|ResponseBuilder::empty_200().body(req.method().as_str().as_bytes()).build().unwrap(),
+            "/any", ANY => |_,_|ResponseBuilder::empty_200().body(b"ANY").build().unwrap(),
+            _ => |_|ResponseBuilder::empty_404().body(b"custom").build().unwrap(),
+        );
+
+        assert_eq!(router.routes.len(), 3);
+
+        let request = |method: Method, path: &str| {
+            RequestBuilder::new()
+                .method(method)
+                .path(String::from(path))
+                .version(crate::Version::HTTP11)
+                .build()
+                .expect("Error when building request")
+        };
+
+        assert_eq!(router.exec(&request(Method::GET, "/multi")).body().unwrap(), b"GET");
+        assert_eq!(router.exec(&request(Method::POST, "/multi")).body().unwrap(), b"POST");
+        assert_eq!(router.exec(&request(Method::PUT, "/multi")).body().unwrap(), b"custom");
+        assert_eq!(router.exec(&request(Method::DELETE, "/any")).body().unwrap(), b"ANY");
+        assert_eq!(router.exec(&request(Method::GET, "/missing")).code(), 404);
+    }
+
+    #[test]
+    fn router_macro_scope() {
+        let router = router!(
+            "/public", Method::GET => |_,_|ResponseBuilder::empty_200().body(b"public").build().unwrap(),
+            scope "/api/v1" with [
+                |req, next| match req.headers().get_header("Authorization") {
+                    Some(_) => next.run(req),
+                    None => ResponseBuilder::empty_400().build().unwrap(),
+                }
+            ] {
+                "/users/{id}", Method::GET => |_,params|ResponseBuilder::empty_200().body(params.get("id").unwrap().as_bytes()).build().unwrap(),
+                "/users", [Method::POST, Method::PUT] => |_,_|ResponseBuilder::empty_200().build().unwrap(),
+            }
+        );
+
+        assert_eq!(router.routes.len(), 4);
+
+        let request = |path: &str, authorized: bool| {
+            let mut headers = crate::Headers::new();
+            if authorized {
+                headers.set_header("Authorization", "token");
+            }
+
+            RequestBuilder::new()
+                .method(Method::GET)
+                .path(String::from(path))
+                .version(crate::Version::HTTP11)
+                .headers(headers)
+                .build()
+                .expect("Error when building request")
+        };
+
+        assert_eq!(router.exec(&request("/public", false)).body().unwrap(), b"public");
+        assert_eq!(router.exec(&request("/api/v1/users/42", false)).code(), 400);
+        assert_eq!(router.exec(&request("/api/v1/users/42", true)).body().unwrap(), b"42");
+        assert_eq!(router.exec(&request("/users/42", true)).code(), 404);
+    }
+
+    #[test]
+    fn middleware_order() {
+        let tag = |name: &'static str| {
+            Middleware::new(move |req, next| {
+                let response = next.run(req);
+                let mut body = response.body().unwrap().clone();
+                body.extend_from_slice(name.as_bytes());
+
+                ResponseBuilder::empty_200().body(&body).build().unwrap()
+            })
+        };
+
+        let mut router = Router::new();
+        router.add_routes_with(
+            vec![Route::new("/tagged", Method::GET).unwrap()],
+            &[tag("outer"), tag("inner")],
+            |_, _| ResponseBuilder::empty_200().body(b"handler,").build().unwrap(),
+        );
+
+        let req = RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from("/tagged"))
+            .version(crate::Version::HTTP11)
+            .build()
+            .expect("Error when building request");
+
+        assert_eq!(router.exec(&req).body().unwrap(), b"handler,innerouter");
+    }
+
+    #[test]
+    fn pass_to_next_route() {
+        let mut router = Router::new();
+        router.add_route(Route::new("/{file}", Method::GET).unwrap(), |_, params| {
+            match params.get("file").map(String::as_str) {
+                Some("style.css") => ResponseBuilder::empty_200().body(b"file").build().unwrap(),
+                _ => Response::pass(),
+            }
+        });
+        router.add_route(Route::from_path("/{page}").unwrap(), |_, _| {
+            ResponseBuilder::empty_200().body(b"index").build().unwrap()
+        });
+        router.add_route(Route::new("/other/{page}", Method::GET).unwrap(), |_, _| Response::pass());
+
+        let request = |path: &str| {
+            RequestBuilder::new()
+                .method(Method::GET)
+                .path(String::from(path))
+                .version(crate::Version::HTTP11)
+                .build()
+                .expect("Error when building request")
+        };
+
+        assert_eq!(router.exec(&request("/style.css")).body().unwrap(), b"file");
+        assert_eq!(router.exec(&request("/users")).body().unwrap(), b"index");
+
+        let response = router.exec(&request("/other/page"));
+        assert_eq!(response.code(), 404);
+        assert!(!response.is_pass());
+    }
+
+    #[test]
+    fn virtual_hosts() {
+        let router = crate::router!(
+            host "api.example.com" {
+                "/status", Method::GET => |_, _| ResponseBuilder::empty_200().body(b"api").build().unwrap(),
+                _ => |_| ResponseBuilder::empty_404().body(b"api not found").build().unwrap()
+            },
+            host "*.example.com" {
+                "/status", Method::GET => |_, _| ResponseBuilder::empty_200().body(b"subdomain").build().unwrap()
+            },
+            "/status", Method::GET => |_, _| ResponseBuilder::empty_200().body(b"default").build().unwrap()
+        );
+
+        let request = |host: &str, path: &str| {
+            let mut headers = crate::Headers::new();
+            headers.set_header("Host", host);
+
+            RequestBuilder::new()
+                .method(Method::GET)
+                .path(String::from(path))
+                .version(crate::Version::HTTP11)
+                .headers(headers)
+                .build()
+                .expect("Error when building request")
+        };
+
+        assert_eq!(router.exec(&request("api.example.com:8080", "/status")).body().unwrap(), b"api");
+        assert_eq!(router.exec(&request("api.example.com", "/missing")).body().unwrap(), b"api not found");
+        assert_eq!(router.exec(&request("www.example.com", "/status")).body().unwrap(), b"subdomain");
+        assert_eq!(router.exec(&request("localhost", "/status")).body().unwrap(), b"default");
+    }
+
+    #[test]
+    fn default_headers() {
+        let mut router = Router::new();
+        router.set_default_header("X-Frame-Options", "deny");
+        router.set_default_header("Api-Version", "1");
+        router.add_route(
+            route::Route::new("/v2", Method::GET).unwrap().response_header("Api-Version", "2"),
+            |_, _| ResponseBuilder::empty_200().build().unwrap(),
+        );
+        router.add_route(
+            route::Route::new("/custom", Method::GET).unwrap().response_header("Api-Version", "2"),
+            |_, _| ResponseBuilder::empty_200().header("X-Frame-Options", "sameorigin").header("Api-Version", "3").build().unwrap(),
+        );
+
+        let request = |path: &str| {
+            RequestBuilder::new()
+                .method(Method::GET)
+                .path(String::from(path))
+                .version(crate::Version::HTTP11)
+                .build()
+                .expect("Error when building request")
+        };
+
+        let response = router.exec(&request("/v2"));
+        assert_eq!(response.headers().get_header("Api-Version").unwrap(), "2");
+        assert_eq!(response.headers().get_header("X-Frame-Options").unwrap(), "deny");
+
+        let response = router.exec(&request("/custom"));
+        assert_eq!(response.headers().get_header("Api-Version").unwrap(), "3");
+        assert_eq!(response.headers().get_header("X-Frame-Options").unwrap(), "sameorigin");
+
+        let response = router.exec(&request("/missing"));
+        assert_eq!(response.code(), 404);
+        assert_eq!(response.headers().get_header("Api-Version").unwrap(), "1");
+    }
 }
@@ -0,0 +1,240 @@
+use crate::{Method, ResponseBuilder, Route, Router};
+
+use log::warn;
+use serde_json::{json, Map, Value};
+
+const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Methods documented for the routes matching any method
+const ANY_METHODS: [Method; 5] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::PATCH,
+];
+
+impl Router {
+    /// Generate an OpenAPI 3 document describing the routes of the router.
+    /// The documentation given with [`Route::summary`], [`Route::request_schema`] and [`Route::response_schema`] is included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route, Router, Method, ResponseBuilder};
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(
+    ///     Route::new("/users/{id}", Method::GET).unwrap().summary("Retrieve a user"),
+    ///     |_, _| ResponseBuilder::empty_200().build().unwrap(),
+    /// );
+    ///
+    /// let document = router.openapi("Users", "1.0.0");
+    /// assert!(document.contains("Retrieve a user"));
+    /// ```
+    ///
+    /// [`Route::summary`]: struct.Route.html#method.summary
+    /// [`Route::request_schema`]: struct.Route.html#method.request_schema
+    /// [`Route::response_schema`]: struct.Route.html#method.response_schema
+    pub fn openapi(&self, title: &str, version: &str) -> String {
+        document(self, title, version).to_string()
+    }
+
+    /// Serve the OpenAPI document of the router on the given path.
+    /// The document is generated from the routes added before this call.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route, Router, Method, ResponseBuilder};
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(Route::new("/users", Method::GET).unwrap(), |_, _| {
+    ///     ResponseBuilder::empty_200().build().unwrap()
+    /// });
+    /// router.serve_openapi("/openapi.json", "Users", "1.0.0");
+    /// ```
+    pub fn serve_openapi(&mut self, path: &str, title: &str, version: &str) {
+        let document = self.openapi(title, version);
+        let route = Route::new(path, Method::GET).unwrap();
+
+        self.add_route(route, move |_, _| {
+            ResponseBuilder::empty_200()
+                .bytes("application/json", document.as_bytes())
+                .build()
+                .unwrap()
+        });
+    }
+}
+
+fn document(router: &Router, title: &str, version: &str) -> Value {
+    let mut paths = Map::new();
+
+    for route in router.routes() {
+        let methods: Vec<&Method> = match route.method() {
+            Some(method) => vec![method],
+            None => ANY_METHODS.iter().collect(),
+        };
+
+        let item = paths
+            .entry(route.pattern().to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        for method in methods {
+            let key = match method {
+                Method::CONNECT | Method::Extension(_) => continue,
+                method => method.as_str().to_lowercase(),
+            };
+
+            if let Value::Object(item) = item {
+                item.entry(key).or_insert_with(|| operation(route));
+            }
+        }
+    }
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": title,
+            "version": version,
+        },
+        "paths": paths,
+    })
+}
+
+fn operation(route: &Route) -> Value {
+    let mut operation = Map::new();
+
+    if let Some(summary) = route.doc().summary() {
+        operation.insert(String::from("summary"), json!(summary));
+    }
+
+    if !route.parameters().is_empty() {
+        let parameters: Vec<Value> = route
+            .parameters()
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        operation.insert(String::from("parameters"), Value::Array(parameters));
+    }
+
+    if let Some(schema) = route.doc().request_schema() {
+        let content_type = route.required_content_type().unwrap_or("application/json");
+
+        operation.insert(
+            String::from("requestBody"),
+            json!({
+                "content": { content_type: { "schema": schema_value(route, schema) } },
+            }),
+        );
+    }
+
+    let mut success = json!({ "description": "Successful response" });
+    if let Some(schema) = route.doc().response_schema() {
+        success["content"] =
+            json!({ "application/json": { "schema": schema_value(route, schema) } });
+    }
+
+    let mut responses = Map::new();
+    responses.insert(String::from("200"), success);
+    if route.body_size_limit().is_some() {
+        responses.insert(
+            String::from("413"),
+            json!({ "description": "Payload too large" }),
+        );
+    }
+    if route.required_content_type().is_some() {
+        responses.insert(
+            String::from("415"),
+            json!({ "description": "Unsupported media type" }),
+        );
+    }
+    operation.insert(String::from("responses"), Value::Object(responses));
+
+    Value::Object(operation)
+}
+
+/// Parse a schema given as JSON text, an invalid schema is left out of the document
+fn schema_value(route: &Route, schema: &str) -> Value {
+    match serde_json::from_str(schema) {
+        Ok(schema) => schema,
+        Err(e) => {
+            warn!("Invalid schema for the route {} : {}", route.pattern(), e);
+            json!({})
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Request, RequestBuilder};
+
+    fn handler(_: &Request, _: std::collections::HashMap<String, String>) -> crate::Response {
+        ResponseBuilder::empty_200().build().unwrap()
+    }
+
+    #[test]
+    fn generate_document() {
+        let mut router = Router::new();
+        router.add_route(
+            Route::new("/users/{id}", Method::GET)
+                .unwrap()
+                .summary("Retrieve a user")
+                .response_schema(r#"{"type": "object"}"#),
+            handler,
+        );
+        router.add_route(
+            Route::new("/users", Method::POST)
+                .unwrap()
+                .content_type("application/json")
+                .request_schema(r#"{"type": "object"}"#),
+            handler,
+        );
+        router.add_route(Route::from_path("/any").unwrap(), handler);
+
+        let document = document(&router, "Users", "1.0.0");
+
+        assert_eq!(document["openapi"], OPENAPI_VERSION);
+        assert_eq!(document["info"]["title"], "Users");
+
+        let get = &document["paths"]["/users/{id}"]["get"];
+        assert_eq!(get["summary"], "Retrieve a user");
+        assert_eq!(get["parameters"][0]["name"], "id");
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"]["type"],
+            "object"
+        );
+
+        let post = &document["paths"]["/users"]["post"];
+        assert!(post["requestBody"]["content"]["application/json"].is_object());
+        assert!(post["responses"]["415"].is_object());
+
+        assert_eq!(document["paths"]["/any"].as_object().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn serve_document() {
+        let mut router = Router::new();
+        router.add_route(Route::new("/users", Method::GET).unwrap(), handler);
+        router.serve_openapi("/openapi.json", "Users", "1.0.0");
+
+        let request = RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from("/openapi.json"))
+            .version(crate::Version::HTTP11)
+            .build()
+            .unwrap();
+        let response = router.exec(&request);
+
+        let document: Value = serde_json::from_slice(response.body().unwrap()).unwrap();
+        assert!(document["paths"]["/users"]["get"].is_object());
+    }
+}
@@ -1,9 +1,70 @@
+use crate::router::host::HostPattern;
 use crate::Method;
+use crate::Priority;
 use crate::Request;
+use crate::{Headers, Response, ResponseBuilder};
 
 use regex::Regex;
 use std::collections::HashMap;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+type Predicate = Arc<dyn Send + Sync + 'static + Fn(&Request) -> bool>;
+
+/// Additional condition a request must fulfill to match a route
+#[derive(Clone)]
+enum Matcher {
+    Header(String, String),
+    Query(String),
+    Predicate(Predicate),
+}
+
+impl Matcher {
+    fn is_match(&self, req: &Request) -> bool {
+        match self {
+            Matcher::Header(name, expected) => match req.headers().get_header(name) {
+                Some(value) => value.split(',').any(|item| {
+                    item.split(';')
+                        .next()
+                        .unwrap()
+                        .trim()
+                        .eq_ignore_ascii_case(expected)
+                }),
+                None => false,
+            },
+            Matcher::Query(name) => match req.query() {
+                Some(query) => query
+                    .split('&')
+                    .any(|pair| pair.split('=').next().unwrap() == name),
+                None => false,
+            },
+            Matcher::Predicate(predicate) => predicate(req),
+        }
+    }
+}
+
+impl PartialEq for Matcher {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Matcher::Header(a, b), Matcher::Header(c, d)) => a == c && b == d,
+            (Matcher::Query(a), Matcher::Query(b)) => a == b,
+            (Matcher::Predicate(a), Matcher::Predicate(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Matcher::Header(name, value) => write!(f, "Header({}: {})", name, value),
+            Matcher::Query(name) => write!(f, "Query({})", name),
+            Matcher::Predicate(_) => write!(f, "Predicate"),
+        }
+    }
+}
 
 /// Representation of an HTTP route.
 /// Is used by a [`Router`] to match against incoming http request.
@@ -11,9 +72,48 @@ use std::hash::{Hash, Hasher};
 /// [`Router`]: struct.Router.html
 #[derive(Debug, Clone)]
 pub struct Route {
+    pattern: String,
     path: Regex,
     parameters: Vec<String>,
     method: Option<Method>,
+    max_body_size: Option<usize>,
+    content_type: Option<String>,
+    host: Option<HostPattern>,
+    matchers: Vec<Matcher>,
+    timeout: Option<Duration>,
+    priority: Priority,
+    response_headers: Headers,
+    doc: RouteDoc,
+}
+
+/// Description of a route used by the generated API documentation, set with [`Route::summary`],
+/// [`Route::request_schema`] and [`Route::response_schema`]
+///
+/// [`Route::summary`]: struct.Route.html#method.summary
+/// [`Route::request_schema`]: struct.Route.html#method.request_schema
+/// [`Route::response_schema`]: struct.Route.html#method.response_schema
+#[derive(Debug, Clone, Default)]
+pub struct RouteDoc {
+    summary: Option<String>,
+    request_schema: Option<String>,
+    response_schema: Option<String>,
+}
+
+impl RouteDoc {
+    /// Return the summary of the route
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    /// Return the JSON schema of the request body
+    pub fn request_schema(&self) -> Option<&str> {
+        self.request_schema.as_deref()
+    }
+
+    /// Return the JSON schema of the response body
+    pub fn response_schema(&self) -> Option<&str> {
+        self.response_schema.as_deref()
+    }
 }
 
 #[derive(Debug)]
@@ -84,23 +184,310 @@ impl Route {
         };
 
         Ok(Route {
+            pattern: String::from(path),
             path: reg,
             parameters,
             method: None,
+            max_body_size: None,
+            content_type: None,
+            host: None,
+            matchers: Vec::new(),
+            timeout: None,
+            priority: Priority::default(),
+            response_headers: Headers::new(),
+            doc: RouteDoc::default(),
         })
     }
 
+    /// Return the path the route was created from
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Return the HTTP method matched by the route, None if the route matches any method
+    pub fn method(&self) -> Option<&Method> {
+        self.method.as_ref()
+    }
+
+    /// Return the names of the parameters of the route path
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+
+    /// Return the Content-Type required by the route
+    pub fn required_content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Return the body size limit of the route
+    pub fn body_size_limit(&self) -> Option<usize> {
+        self.max_body_size
+    }
+
+    /// Return the Host pattern of the route
+    pub fn host_pattern(&self) -> Option<&str> {
+        self.host.as_ref().map(HostPattern::as_str)
+    }
+
+    /// Return the timeout of the route handler
+    pub fn handler_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Give the handler of the route its own time limit, replacing the request timeout of the server
+    /// so slow endpoints such as exports can get more time than the rest of the API.
+    /// The limit counts from the reception of the request, when used with a router the handler runs on a
+    /// blocking thread and a 503 status code is answered as soon as the limit is reached.
+    /// The handler itself is not interrupted, its late response is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    /// use std::time::Duration;
+    ///
+    /// let route = Route::new("/export",Method::GET).unwrap().timeout(Duration::from_secs(120));
+    /// ```
+    pub fn timeout(mut self, timeout: Duration) -> Route {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Return the priority of the route handler
+    pub fn priority_class(&self) -> Priority {
+        self.priority
+    }
+
+    /// Set the class of the workers running the handler of the route, [`Priority::High`] by default.
+    /// With [`Priority::Bulk`], the handler runs on the bulk workers of the server so slow transfers
+    /// do not hold the workers answering the other routes, see [`AIOServer::set_bulk_workers`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method,Priority};
+    ///
+    /// let route = Route::new("/upload",Method::POST).unwrap().priority(Priority::Bulk);
+    /// ```
+    ///
+    /// [`Priority::High`]: enum.Priority.html#variant.High
+    /// [`Priority::Bulk`]: enum.Priority.html#variant.Bulk
+    /// [`AIOServer::set_bulk_workers`]: struct.AIOServer.html#method.set_bulk_workers
+    pub fn priority(mut self, priority: Priority) -> Route {
+        self.priority = priority;
+        self
+    }
+
+    /// Add a header to the responses of the route, unless the handler sets it itself.
+    /// The headers of the route take precedence over the default headers of the router and of the server.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let route = Route::new("/v2/users",Method::GET).unwrap().response_header("Api-Version", "2");
+    /// ```
+    pub fn response_header(mut self, name: &str, value: &str) -> Route {
+        self.response_headers.set_header(name, value);
+        self
+    }
+
+    /// Return the headers added to the responses of the route
+    pub fn response_headers(&self) -> &Headers {
+        &self.response_headers
+    }
+
+    /// Only match the requests sent to the given host, `*.example.com` matches every subdomain of example.com.
+    /// The port of the Host header is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let route = Route::new("/users",Method::GET).unwrap().host("api.example.com");
+    /// ```
+    pub fn host(mut self, pattern: &str) -> Route {
+        self.host = Some(HostPattern::new(pattern));
+        self
+    }
+
+    /// Describe the route in a few words, the summary is used by the generated API documentation
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let route = Route::new("/users/{id}",Method::GET).unwrap().summary("Retrieve a user");
+    /// ```
+    pub fn summary(mut self, summary: &str) -> Route {
+        self.doc.summary = Some(summary.to_string());
+        self
+    }
+
+    /// Give the JSON schema of the request body, used by the generated API documentation
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let route = Route::new("/users",Method::POST).unwrap()
+    ///     .request_schema(r#"{"type": "object", "properties": {"name": {"type": "string"}}}"#);
+    /// ```
+    pub fn request_schema(mut self, schema: &str) -> Route {
+        self.doc.request_schema = Some(schema.to_string());
+        self
+    }
+
+    /// Give the JSON schema of the response body, used by the generated API documentation
+    pub fn response_schema(mut self, schema: &str) -> Route {
+        self.doc.response_schema = Some(schema.to_string());
+        self
+    }
+
+    /// Return the documentation of the route
+    pub fn doc(&self) -> &RouteDoc {
+        &self.doc
+    }
+
+    /// Limit the size of the body accepted by the route.
+    /// When used with a router, requests with a bigger body are answered with a 413 status code without calling the handler.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let route = Route::new("/upload",Method::POST).unwrap().max_body_size(1024 * 1024);
+    /// ```
+    pub fn max_body_size(mut self, size: usize) -> Route {
+        self.max_body_size = Some(size);
+        self
+    }
+
+    /// Only accept request with the given Content-Type, parameters such as the charset are ignored.
+    /// When used with a router, requests with another Content-Type are answered with a 415 status code without calling the handler.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let route = Route::new("/api",Method::POST).unwrap().content_type("application/json");
+    /// ```
+    pub fn content_type(mut self, content_type: &str) -> Route {
+        self.content_type = Some(content_type.to_lowercase());
+        self
+    }
+
+    /// Only match the requests having the header with the given value.
+    /// The value is compared without case to each item of a comma separated list, parameters like `;q=0.9` are ignored.
+    /// Unlike [`Route::content_type`], a request with another value is given to the next matching route.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// // Version the API with the Accept header
+    /// let v2 = Route::new("/users",Method::GET).unwrap().header("Accept", "application/vnd.users.v2+json");
+    /// let v1 = Route::new("/users",Method::GET).unwrap();
+    /// ```
+    ///
+    /// [`Route::content_type`]: struct.Route.html#method.content_type
+    pub fn header(mut self, name: &str, value: &str) -> Route {
+        self.matchers
+            .push(Matcher::Header(name.to_string(), value.to_lowercase()));
+        self
+    }
+
+    /// Only match the requests having the given parameter in their query string
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let search = Route::new("/users",Method::GET).unwrap().query("search");
+    /// ```
+    pub fn query(mut self, name: &str) -> Route {
+        self.matchers.push(Matcher::Query(name.to_string()));
+        self
+    }
+
+    /// Only match the requests for which the predicate returns true
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{Route,Method};
+    ///
+    /// let small = Route::new("/upload",Method::POST).unwrap()
+    ///     .predicate(|req| req.content_length().unwrap_or(0) < 1024);
+    /// ```
+    pub fn predicate<F>(mut self, predicate: F) -> Route
+    where
+        F: Send + Sync + 'static + Fn(&Request) -> bool,
+    {
+        self.matchers.push(Matcher::Predicate(Arc::new(predicate)));
+        self
+    }
+
+    /// Check the request against the body size limit and the expected Content-Type of the route.
+    /// Return the response to send if the request is rejected
+    pub(crate) fn check_guards(&self, req: &Request) -> Option<Response> {
+        if let Some(max) = self.max_body_size {
+            let size = match req.body() {
+                Some(body) => body.len(),
+                None => req.content_length().unwrap_or(0),
+            };
+
+            if size > max {
+                return Some(ResponseBuilder::empty_413().build().unwrap());
+            }
+        }
+
+        if let Some(expected) = &self.content_type {
+            let matching = match req.headers().get_header("Content-Type") {
+                Some(value) => value
+                    .split(';')
+                    .next()
+                    .unwrap()
+                    .trim()
+                    .eq_ignore_ascii_case(expected),
+                None => false,
+            };
+
+            if !matching {
+                return Some(ResponseBuilder::empty_415().build().unwrap());
+            }
+        }
+
+        None
+    }
+
     pub(crate) fn is_match(&self, req: &Request) -> bool {
-        let path = req.path().trim_end_matches('/');
+        if let Some(host) = &self.host {
+            if !host.is_match(req) {
+                return false;
+            }
+        }
+
+        let path = request_path(req);
         if let Some(method) = &self.method {
-            return method == req.method() && self.path.is_match(path);
+            if method != req.method() {
+                return false;
+            }
         }
 
-        self.path.is_match(path)
+        self.path.is_match(path) && self.matchers.iter().all(|matcher| matcher.is_match(req))
     }
 
     pub(crate) fn parse_request(&self, req: &Request) -> Option<HashMap<String, String>> {
-        let path = req.path().trim_end_matches('/');
+        let path = request_path(req);
         let caps = match self.path.captures(path) {
             Some(caps) => caps,
             None => return None,
@@ -123,9 +510,17 @@ impl Route {
     }
 }
 
+/// Return the path of the request without the query string and the trailing slashes
+fn request_path(req: &Request) -> &str {
+    req.uri().path().trim_end_matches('/')
+}
+
 impl PartialEq for Route {
     fn eq(&self, other: &Self) -> bool {
-        self.path.as_str() == other.path.as_str() && self.method == other.method
+        self.path.as_str() == other.path.as_str()
+            && self.method == other.method
+            && self.host == other.host
+            && self.matchers == other.matchers
     }
 }
 
@@ -188,6 +583,68 @@ mod test {
         assert!(route.is_match(&req));
     }
 
+    #[test]
+    fn route_host_match() {
+        let mut headers = crate::Headers::new();
+        headers.set_header("Host", "api.example.com:8080");
+
+        let req = RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from("/test"))
+            .version(crate::Version::HTTP11)
+            .headers(headers)
+            .build()
+            .expect("Error when building request");
+
+        assert!(Route::new("/test", Method::GET)
+            .unwrap()
+            .host("*.example.com")
+            .is_match(&req));
+        assert!(!Route::new("/test", Method::GET)
+            .unwrap()
+            .host("www.example.com")
+            .is_match(&req));
+        assert_ne!(
+            Route::new("/test", Method::GET)
+                .unwrap()
+                .host("api.example.com"),
+            Route::new("/test", Method::GET).unwrap()
+        );
+    }
+
+    #[test]
+    fn route_matchers() {
+        let mut headers = crate::Headers::new();
+        headers.set_header("Accept", "text/html, application/vnd.users.v2+json;q=0.9");
+
+        let req = RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from("/users?search=name&page"))
+            .version(crate::Version::HTTP11)
+            .headers(headers)
+            .build()
+            .expect("Error when building request");
+
+        let route = Route::new("/users", Method::GET).unwrap();
+        assert!(route
+            .clone()
+            .header("Accept", "application/vnd.users.v2+json")
+            .is_match(&req));
+        assert!(!route
+            .clone()
+            .header("Accept", "application/json")
+            .is_match(&req));
+        assert!(route.clone().query("search").query("page").is_match(&req));
+        assert!(!route.clone().query("sort").is_match(&req));
+        assert!(route
+            .clone()
+            .predicate(|req| req.query().is_some())
+            .is_match(&req));
+        assert!(!route.clone().predicate(|_| false).is_match(&req));
+
+        assert_ne!(route.clone().query("search"), route);
+    }
+
     #[test]
     fn route_path_not_match() {
         let req = RequestBuilder::new()
@@ -315,4 +772,48 @@ mod test {
 
         assert!(route.is_match(&req2));
     }
+
+    fn post(content_type: Option<&str>, body: &[u8]) -> Request {
+        let mut headers = crate::Headers::new();
+        if let Some(content_type) = content_type {
+            headers.set_header("Content-Type", content_type);
+        }
+
+        RequestBuilder::new()
+            .method(Method::POST)
+            .path(String::from("/upload"))
+            .version(crate::Version::HTTP11)
+            .headers(headers)
+            .body(body)
+            .build()
+            .expect("Error when building request")
+    }
+
+    #[test]
+    fn body_size_guard() {
+        let route = Route::new("/upload", Method::POST)
+            .unwrap()
+            .max_body_size(4);
+
+        assert!(route.check_guards(&post(None, b"1234")).is_none());
+
+        let response = route.check_guards(&post(None, b"12345")).unwrap();
+        assert_eq!(response.code(), 413);
+    }
+
+    #[test]
+    fn content_type_guard() {
+        let route = Route::new("/upload", Method::POST)
+            .unwrap()
+            .content_type("Application/JSON");
+
+        let request = post(Some("application/json; charset=utf-8"), b"{}");
+        assert!(route.check_guards(&request).is_none());
+
+        let response = route.check_guards(&post(Some("text/plain"), b"")).unwrap();
+        assert_eq!(response.code(), 415);
+
+        let response = route.check_guards(&post(None, b"")).unwrap();
+        assert_eq!(response.code(), 415);
+    }
 }
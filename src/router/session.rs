@@ -0,0 +1,492 @@
+//! Sessions keeping the data of a client between its requests, enabled with the `session` feature.
+//!
+//! The client only holds a random session identifier in a cookie, signed so it can not be forged.
+//! The data is kept by a [`SessionStore`], [`MemoryStore`] keeps it in the memory of the server
+//! and the trait can be implemented to use an external store shared by several servers.
+//!
+//! The middleware created by [`Sessions::middleware`] loads the session of the request and stores it in
+//! the task locals, handlers retrieve it with [`Session::current`]. Once the response is built, the
+//! modified session is saved and the cookie is sent again, so its expiration follows the one of the session.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::session::{MemoryStore, Session, Sessions};
+//! use mini_async_http::ResponseBuilder;
+//!
+//! let sessions = Sessions::new(MemoryStore::new(), b"a secret of at least 32 bytes....");
+//!
+//! let router = mini_async_http::router!(
+//!     scope "" with [sessions.middleware()] {
+//!         "/visits", mini_async_http::Method::GET => |_, _| {
+//!             let session = Session::current().unwrap();
+//!             let visits = session.get("visits").and_then(|v| v.parse().ok()).unwrap_or(0) + 1;
+//!             session.insert("visits", &visits.to_string());
+//!
+//!             ResponseBuilder::empty_200().text(&visits.to_string()).build().unwrap()
+//!         }
+//!     }
+//! );
+//! ```
+//!
+//! [`SessionStore`]: trait.SessionStore.html
+//! [`MemoryStore`]: struct.MemoryStore.html
+//! [`Sessions::middleware`]: struct.Sessions.html#method.middleware
+//! [`Session::current`]: struct.Session.html#method.current
+
+use crate::executor::task_local;
+use crate::router::middleware::Next;
+use crate::{Request, Response};
+
+use hmac::{Hmac, Mac};
+use log::warn;
+use sha2::Sha256;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_COOKIE: &str = "session";
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 3600);
+const ID_LENGTH: usize = 16;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+type HmacSha256 = Hmac<Sha256>;
+type Entries = HashMap<String, (HashMap<String, String>, Instant)>;
+
+/// Storage of the session data, indexed by session identifier
+pub trait SessionStore: Send + Sync + 'static {
+    /// Return the data of the session, None if it does not exist or has expired
+    fn load(&self, id: &str) -> Option<HashMap<String, String>>;
+
+    /// Save the data of the session, it expires after the given duration
+    fn save(&self, id: &str, data: HashMap<String, String>, ttl: Duration);
+
+    /// Delete the session
+    fn remove(&self, id: &str);
+}
+
+/// Store keeping the sessions in memory, they are lost when the server stops.
+/// The expired sessions are removed when they are loaded, the others by a sweep made at most
+/// once a minute when a session is saved.
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<Entries>,
+    next_sweep: Mutex<Option<Instant>>,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    /// Return the number of sessions stored, expired ones included
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Return true if no session is stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Option<HashMap<String, String>> {
+        let mut sessions = self.sessions.lock().unwrap();
+
+        match sessions.get(id) {
+            Some((_, expiration)) if *expiration <= Instant::now() => {
+                sessions.remove(id);
+                None
+            }
+            Some((data, _)) => Some(data.clone()),
+            None => None,
+        }
+    }
+
+    fn save(&self, id: &str, data: HashMap<String, String>, ttl: Duration) {
+        let now = Instant::now();
+        let sweep = {
+            let mut next_sweep = self.next_sweep.lock().unwrap();
+            match *next_sweep {
+                Some(next) if next > now => false,
+                _ => {
+                    *next_sweep = Some(now + SWEEP_INTERVAL);
+                    true
+                }
+            }
+        };
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if sweep {
+            sessions.retain(|_, (_, expiration)| *expiration > now);
+        }
+        sessions.insert(id.to_string(), (data, now + ttl));
+    }
+
+    fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    values: HashMap<String, String>,
+    changed: bool,
+    destroyed: bool,
+}
+
+/// Data of the client sending the request being handled.
+/// Clones share the same data, the changes are saved once the response is built.
+#[derive(Debug, Clone)]
+pub struct Session {
+    id: Option<String>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Session {
+    fn new(id: Option<String>, values: HashMap<String, String>) -> Session {
+        Session {
+            id,
+            state: Arc::new(Mutex::from(State {
+                values,
+                ..State::default()
+            })),
+        }
+    }
+
+    /// Return the session of the request being handled, stored by the session middleware
+    pub fn current() -> Option<Session> {
+        task_local::get::<Session>()
+    }
+
+    /// Return the identifier of the session, None for a new session
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Return the value stored under the key
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.state.lock().unwrap().values.get(key).cloned()
+    }
+
+    /// Store a value under the key, replacing the previous one
+    pub fn insert(&self, key: &str, value: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        state.values.insert(key.to_string(), value.to_string());
+        state.changed = true;
+    }
+
+    /// Remove the value stored under the key and return it
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+
+        let value = state.values.remove(key);
+        state.changed |= value.is_some();
+        value
+    }
+
+    /// Delete the session from the store and the cookie from the client, for instance on logout
+    pub fn destroy(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.values.clear();
+        state.destroyed = true;
+    }
+}
+
+/// Configuration of the sessions, creating the middleware
+#[derive(Clone)]
+pub struct Sessions {
+    store: Arc<dyn SessionStore>,
+    key: Vec<u8>,
+    cookie: String,
+    ttl: Duration,
+    secure: bool,
+}
+
+impl Sessions {
+    /// Create the configuration with the store and the secret key signing the session cookies.
+    /// The key should be random and at least 32 bytes long, changing it invalidates the existing sessions.
+    pub fn new<S: SessionStore>(store: S, key: &[u8]) -> Sessions {
+        Sessions {
+            store: Arc::new(store),
+            key: key.to_vec(),
+            cookie: String::from(DEFAULT_COOKIE),
+            ttl: DEFAULT_TTL,
+            secure: false,
+        }
+    }
+
+    /// Set the name of the session cookie, "session" by default
+    pub fn cookie_name(mut self, name: &str) -> Sessions {
+        self.cookie = name.to_string();
+        self
+    }
+
+    /// Set the lifetime of the sessions and of their cookie, one day by default
+    pub fn ttl(mut self, ttl: Duration) -> Sessions {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Only send the cookie over HTTPS
+    pub fn secure(mut self, secure: bool) -> Sessions {
+        self.secure = secure;
+        self
+    }
+
+    /// Create the middleware loading and saving the sessions
+    pub fn middleware(&self) -> impl Send + Sync + 'static + Fn(&Request, Next<'_>) -> Response {
+        let sessions = self.clone();
+
+        move |request: &Request, next: Next<'_>| sessions.handle(request, next)
+    }
+
+    fn handle(&self, request: &Request, next: Next<'_>) -> Response {
+        let session = match self.load(request) {
+            Some((id, values)) => Session::new(Some(id), values),
+            None => Session::new(None, HashMap::new()),
+        };
+
+        task_local::set(session.clone());
+        let mut response = next.run(request);
+        task_local::remove::<Session>();
+
+        let state = session.state.lock().unwrap();
+        if state.destroyed {
+            if let Some(ref id) = session.id {
+                self.store.remove(id);
+                self.set_cookie(&mut response, "", Duration::from_secs(0));
+            }
+        } else if state.changed {
+            let id = match session.id {
+                Some(ref id) => id.clone(),
+                None => match new_id() {
+                    Some(id) => id,
+                    None => return response,
+                },
+            };
+
+            // Sent on every save so the Max-Age of the cookie follows the expiration of the session
+            let value = format!("{}.{}", id, self.sign(&id));
+            self.set_cookie(&mut response, &value, self.ttl);
+            self.store.save(&id, state.values.clone(), self.ttl);
+        }
+
+        response
+    }
+
+    /// Return the identifier and the data of the session if the cookie is valid and the session exists
+    fn load(&self, request: &Request) -> Option<(String, HashMap<String, String>)> {
+        let cookie = request.cookie(&self.cookie)?;
+        let (id, signature) = cookie.split_once('.')?;
+
+        let mut mac = HmacSha256::new_from_slice(&self.key).ok()?;
+        mac.update(id.as_bytes());
+        mac.verify_slice(&from_hex(signature)?).ok()?;
+
+        self.store.load(id).map(|values| (id.to_string(), values))
+    }
+
+    fn sign(&self, id: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+        mac.update(id.as_bytes());
+
+        to_hex(&mac.finalize().into_bytes())
+    }
+
+    fn set_cookie(&self, response: &mut Response, value: &str, max_age: Duration) {
+        if response.headers().get_header("Set-Cookie").is_some() {
+            warn!("The Set-Cookie header of the response is replaced by the session cookie");
+        }
+
+        let mut cookie = format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+            self.cookie,
+            value,
+            max_age.as_secs()
+        );
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+
+        response.headers.set_header("Set-Cookie", &cookie);
+    }
+}
+
+/// Generate a random session identifier, None if the system has no source of randomness
+fn new_id() -> Option<String> {
+    let mut bytes = [0; ID_LENGTH];
+
+    match getrandom::getrandom(&mut bytes) {
+        Ok(()) => Some(to_hex(&bytes)),
+        Err(e) => {
+            warn!("Could not generate a session identifier : {}", e);
+            None
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => Some((hex_digit(*high)? << 4) | hex_digit(*low)?),
+            _ => None,
+        })
+        .collect()
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Method, Middleware, RequestBuilder, ResponseBuilder, Route, Router};
+
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn router(sessions: &Sessions) -> Router {
+        let mut router = Router::new();
+        router.add_routes_with(
+            vec![Route::new("/count", Method::GET).unwrap()],
+            &[Middleware::new(sessions.middleware())],
+            |_, _| {
+                let session = Session::current().unwrap();
+                let count: u32 = session
+                    .get("count")
+                    .and_then(|c| c.parse().ok())
+                    .unwrap_or(0);
+                session.insert("count", &(count + 1).to_string());
+
+                ResponseBuilder::empty_200()
+                    .body(count.to_string().as_bytes())
+                    .build()
+                    .unwrap()
+            },
+        );
+        router.add_routes_with(
+            vec![Route::new("/logout", Method::GET).unwrap()],
+            &[Middleware::new(sessions.middleware())],
+            |_, _| {
+                Session::current().unwrap().destroy();
+                ResponseBuilder::empty_200().build().unwrap()
+            },
+        );
+
+        router
+    }
+
+    fn request(path: &str, cookie: Option<&str>) -> Request {
+        let mut headers = crate::Headers::new();
+        if let Some(cookie) = cookie {
+            headers.set_header("Cookie", cookie);
+        }
+
+        RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from(path))
+            .version(crate::Version::HTTP11)
+            .headers(headers)
+            .build()
+            .unwrap()
+    }
+
+    /// Route the request inside task locals like the server does
+    fn exec(router: &Router, request: &Request) -> Response {
+        let locals = Arc::new(task_local::TaskLocals::default());
+        task_local::enter(&locals, || router.exec(request))
+    }
+
+    /// Return the name=value part of the Set-Cookie header
+    fn session_cookie(response: &Response) -> String {
+        let header = response.headers().get_header("Set-Cookie").unwrap();
+        header.split(';').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn keep_session() {
+        let sessions = Sessions::new(MemoryStore::new(), KEY);
+        let router = router(&sessions);
+
+        let response = exec(&router, &request("/count", None));
+        assert_eq!(response.body_as_string().unwrap(), "0");
+        let cookie = session_cookie(&response);
+
+        let response = exec(&router, &request("/count", Some(&cookie)));
+        assert_eq!(response.body_as_string().unwrap(), "1");
+        assert_eq!(session_cookie(&response), cookie);
+
+        let response = exec(&router, &request("/logout", Some(&cookie)));
+        assert!(response
+            .headers()
+            .get_header("Set-Cookie")
+            .unwrap()
+            .contains("Max-Age=0"));
+
+        let response = exec(&router, &request("/count", Some(&cookie)));
+        assert_eq!(response.body_as_string().unwrap(), "0");
+    }
+
+    #[test]
+    fn forged_cookie() {
+        let sessions = Sessions::new(MemoryStore::new(), KEY);
+        let router = router(&sessions);
+
+        let response = exec(&router, &request("/count", None));
+        let cookie = session_cookie(&response);
+        let (id, _) = cookie.split_once('.').unwrap();
+
+        let forged = format!("{}.{}", id, "00".repeat(32));
+        let response = exec(&router, &request("/count", Some(&forged)));
+        assert_eq!(response.body_as_string().unwrap(), "0");
+    }
+
+    #[test]
+    fn expired_session() {
+        let store = MemoryStore::new();
+        store.save("id", HashMap::new(), Duration::from_secs(0));
+
+        assert_eq!(store.load("id"), None);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn sweep_expired_sessions() {
+        let store = MemoryStore::new();
+        store.save("expired", HashMap::new(), Duration::from_secs(0));
+        store.save("other", HashMap::new(), Duration::from_secs(60));
+        assert_eq!(store.len(), 2);
+
+        *store.next_sweep.lock().unwrap() = None;
+        store.save("other", HashMap::new(), Duration::from_secs(60));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn refresh_cookie() {
+        let sessions = Sessions::new(MemoryStore::new(), KEY).ttl(Duration::from_secs(60));
+        let router = router(&sessions);
+
+        let response = exec(&router, &request("/count", None));
+        let cookie = session_cookie(&response);
+
+        let response = exec(&router, &request("/count", Some(&cookie)));
+        assert!(response
+            .headers()
+            .get_header("Set-Cookie")
+            .unwrap()
+            .contains("Max-Age=60"));
+    }
+}
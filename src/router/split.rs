@@ -0,0 +1,209 @@
+use crate::{Request, Response};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// How requests are assigned to the candidate handler
+#[derive(Debug, Clone)]
+enum Sticky {
+    Header(String),
+    Cookie(String),
+}
+
+/// Split the traffic of a route between a stable handler and a candidate one, to canary a new implementation.
+///
+/// A percentage of the requests is given to the candidate. Without stickiness, the requests are
+/// distributed in turn so exactly `percent` requests out of 100 reach the candidate. With a sticky header
+/// or cookie, its value is hashed so a client always reaches the same handler, even after a restart.
+/// A header can also force the candidate, for instance to test it before opening it to users.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{Method, ResponseBuilder, Route, Router, Split};
+///
+/// let split = Split::percent(10)
+///     .sticky_cookie("session")
+///     .force_header("X-Canary", "always");
+///
+/// let mut router = Router::new();
+/// router.add_route(
+///     Route::new("/search", Method::GET).unwrap(),
+///     split.between(
+///         |_, _| ResponseBuilder::empty_200().text("stable").build().unwrap(),
+///         |_, _| ResponseBuilder::empty_200().text("candidate").build().unwrap(),
+///     ),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Split {
+    percent: u8,
+    sticky: Option<Sticky>,
+    force: Option<(String, String)>,
+}
+
+impl Split {
+    /// Give the given percentage of the requests to the candidate, values above 100 are treated as 100
+    pub fn percent(percent: u8) -> Split {
+        Split {
+            percent: percent.min(100),
+            sticky: None,
+            force: None,
+        }
+    }
+
+    /// Assign the requests by the value of the header, requests without it are given to the stable handler
+    pub fn sticky_header(mut self, name: &str) -> Split {
+        self.sticky = Some(Sticky::Header(name.to_string()));
+        self
+    }
+
+    /// Assign the requests by the value of the cookie, requests without it are given to the stable handler
+    pub fn sticky_cookie(mut self, name: &str) -> Split {
+        self.sticky = Some(Sticky::Cookie(name.to_string()));
+        self
+    }
+
+    /// Always give the requests having the header with the given value to the candidate
+    pub fn force_header(mut self, name: &str, value: &str) -> Split {
+        self.force = Some((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Create the route handler splitting the traffic between the two handlers
+    pub fn between<A, B>(
+        self,
+        stable: A,
+        candidate: B,
+    ) -> impl Send + Sync + 'static + Fn(&Request, HashMap<String, String>) -> Response
+    where
+        A: Send + Sync + 'static + Fn(&Request, HashMap<String, String>) -> Response,
+        B: Send + Sync + 'static + Fn(&Request, HashMap<String, String>) -> Response,
+    {
+        let counter = Arc::new(AtomicU64::new(0));
+
+        move |request: &Request, parameters: HashMap<String, String>| {
+            if self.is_candidate(request, &counter) {
+                candidate(request, parameters)
+            } else {
+                stable(request, parameters)
+            }
+        }
+    }
+
+    fn is_candidate(&self, request: &Request, counter: &AtomicU64) -> bool {
+        if let Some((name, value)) = &self.force {
+            if matches!(request.headers().get_header(name), Some(header) if header.eq_ignore_ascii_case(value))
+            {
+                return true;
+            }
+        }
+
+        let bucket = match &self.sticky {
+            None => counter.fetch_add(1, Ordering::Relaxed) % 100,
+            Some(sticky) => {
+                let key = match sticky {
+                    Sticky::Header(name) => request.headers().get_header(name).cloned(),
+                    Sticky::Cookie(name) => request.cookie(name),
+                };
+
+                match key {
+                    Some(key) => fnv1a(key.as_bytes()) % 100,
+                    None => return false,
+                }
+            }
+        };
+
+        bucket < u64::from(self.percent)
+    }
+}
+
+/// 64 bits FNV-1a hash of the bytes. Unlike the hashers of the standard library, its result is fixed,
+/// so a client keeps reaching the same handler across restarts and Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Method, RequestBuilder, ResponseBuilder};
+
+    fn request(header: Option<(&str, &str)>) -> Request {
+        let mut headers = crate::Headers::new();
+        if let Some((name, value)) = header {
+            headers.set_header(name, value);
+        }
+
+        RequestBuilder::new()
+            .method(Method::GET)
+            .path(String::from("/"))
+            .version(crate::Version::HTTP11)
+            .headers(headers)
+            .build()
+            .unwrap()
+    }
+
+    fn handler(split: Split) -> impl Fn(&Request) -> String {
+        let handler = split.between(
+            |_, _| {
+                ResponseBuilder::empty_200()
+                    .body(b"stable")
+                    .build()
+                    .unwrap()
+            },
+            |_, _| {
+                ResponseBuilder::empty_200()
+                    .body(b"candidate")
+                    .build()
+                    .unwrap()
+            },
+        );
+
+        move |request| handler(request, HashMap::new()).body_as_string().unwrap()
+    }
+
+    #[test]
+    fn percentage() {
+        let handler = handler(Split::percent(25));
+
+        let candidates = (0..100)
+            .filter(|_| handler(&request(None)) == "candidate")
+            .count();
+        assert_eq!(candidates, 25);
+    }
+
+    #[test]
+    fn sticky() {
+        let handler = handler(Split::percent(50).sticky_cookie("session"));
+
+        for id in 0..20 {
+            let cookie = format!("theme=dark; session={}", id);
+            let first = handler(&request(Some(("Cookie", &cookie))));
+
+            for _ in 0..5 {
+                assert_eq!(handler(&request(Some(("Cookie", &cookie)))), first);
+            }
+        }
+
+        assert_eq!(handler(&request(None)), "stable");
+    }
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn forced() {
+        let handler = handler(Split::percent(0).force_header("X-Canary", "Always"));
+
+        assert_eq!(handler(&request(Some(("x-canary", "always")))), "candidate");
+        assert_eq!(handler(&request(None)), "stable");
+    }
+}
@@ -0,0 +1,324 @@
+//! Propagation of distributed tracing contexts through HTTP headers.
+//!
+//! The [`middleware`] extracts the context of the caller from the W3C `traceparent` and `tracestate`
+//! headers or from the B3 headers, starts the span of the server as its child and stores it in the
+//! task locals. Handlers retrieve it with [`TraceContext::current`] to give it to their tracing backend
+//! or to [`inject`] it into the requests they send to other services.
+//!
+//! # Example
+//!
+//! ```
+//! use mini_async_http::{trace_context, ResponseBuilder, TraceContext};
+//!
+//! let router = mini_async_http::router!(
+//!     scope "/api" with [trace_context::middleware] {
+//!         "/trace", mini_async_http::Method::GET => |_, _| {
+//!             let context = TraceContext::current().unwrap();
+//!
+//!             ResponseBuilder::empty_200()
+//!                 .body(context.trace_id().as_bytes())
+//!                 .build()
+//!                 .unwrap()
+//!         }
+//!     }
+//! );
+//! ```
+//!
+//! [`middleware`]: fn.middleware.html
+//! [`TraceContext::current`]: struct.TraceContext.html#method.current
+//! [`inject`]: struct.TraceContext.html#method.inject
+
+use crate::executor::task_local;
+use crate::router::middleware::Next;
+use crate::{Headers, Request, Response};
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+const B3: &str = "b3";
+const B3_TRACE_ID: &str = "X-B3-TraceId";
+const B3_SPAN_ID: &str = "X-B3-SpanId";
+const B3_SAMPLED: &str = "X-B3-Sampled";
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Position of a request in a distributed trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceContext {
+    trace_id: String,
+    span_id: String,
+    parent_id: Option<String>,
+    sampled: bool,
+    state: Option<String>,
+}
+
+impl TraceContext {
+    /// Start a new trace, the context has no parent
+    pub fn new_root() -> TraceContext {
+        TraceContext {
+            trace_id: format!("{:016x}{:016x}", random_id(), random_id()),
+            span_id: format!("{:016x}", random_id()),
+            parent_id: None,
+            sampled: true,
+            state: None,
+        }
+    }
+
+    /// Read the context sent by the caller, `traceparent` is used first and then the B3 headers.
+    /// None is returned if no header is present or if they are invalid.
+    pub fn from_headers(headers: &Headers) -> Option<TraceContext> {
+        if let Some(parent) = headers.get_header(TRACEPARENT) {
+            return parse_traceparent(parent).map(|context| TraceContext {
+                state: headers.get_header(TRACESTATE).cloned(),
+                ..context
+            });
+        }
+
+        if let Some(b3) = headers.get_header(B3) {
+            return parse_b3(b3);
+        }
+
+        let trace_id = headers.get_header(B3_TRACE_ID)?;
+        let span_id = headers.get_header(B3_SPAN_ID)?;
+        if !is_id(trace_id, 16) && !is_id(trace_id, 32) || !is_id(span_id, 16) {
+            return None;
+        }
+
+        Some(TraceContext {
+            trace_id: pad_trace_id(trace_id),
+            span_id: span_id.clone(),
+            parent_id: None,
+            sampled: headers.get_header(B3_SAMPLED).map(String::as_str) != Some("0"),
+            state: None,
+        })
+    }
+
+    /// Create the context of a new span in the same trace, having this span as parent
+    pub fn child(&self) -> TraceContext {
+        TraceContext {
+            trace_id: self.trace_id.clone(),
+            span_id: format!("{:016x}", random_id()),
+            parent_id: Some(self.span_id.clone()),
+            sampled: self.sampled,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Return the context of the request being handled, stored by the [`middleware`]
+    ///
+    /// [`middleware`]: trace_context/fn.middleware.html
+    pub fn current() -> Option<TraceContext> {
+        task_local::get::<TraceContext>()
+    }
+
+    /// Return the identifier of the trace as 32 hexadecimal characters
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Return the identifier of the span as 16 hexadecimal characters
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    /// Return the identifier of the parent span, None for a root span
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.as_deref()
+    }
+
+    /// Return true if the caller recorded the trace
+    pub fn sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Return the vendor specific `tracestate` sent by the caller
+    pub fn trace_state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    /// Return the value of the `traceparent` header for this context
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.span_id, self.sampled as u8
+        )
+    }
+
+    /// Write the context in the headers of a request sent to another service,
+    /// both the W3C and the single B3 headers are set
+    pub fn inject(&self, headers: &mut Headers) {
+        headers.set_header(TRACEPARENT, &self.traceparent());
+        if let Some(ref state) = self.state {
+            headers.set_header(TRACESTATE, state);
+        }
+        headers.set_header(
+            B3,
+            &format!("{}-{}-{}", self.trace_id, self.span_id, self.sampled as u8),
+        );
+    }
+}
+
+/// Middleware storing the context of the request in the task locals.
+/// The context is a child of the one sent by the caller, a new trace is started if there is none.
+pub fn middleware(request: &Request, next: Next<'_>) -> Response {
+    let context = match TraceContext::from_headers(request.headers()) {
+        Some(parent) => parent.child(),
+        None => TraceContext::new_root(),
+    };
+
+    task_local::set(context);
+    let response = next.run(request);
+    task_local::remove::<TraceContext>();
+
+    response
+}
+
+fn random_id() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+
+    // An identifier made of zeros is invalid
+    hasher.finish().max(1)
+}
+
+fn is_id(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value.bytes().all(|byte| byte.is_ascii_hexdigit())
+        && value.bytes().any(|byte| byte != b'0')
+}
+
+/// B3 trace ids can be 64 bits long, they are left padded to the W3C size
+fn pad_trace_id(trace_id: &str) -> String {
+    format!("{:0>32}", trace_id)
+}
+
+fn parse_traceparent(value: &str) -> Option<TraceContext> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    if parts.len() < 4 || parts[0].len() != 2 || parts[0] == "ff" {
+        return None;
+    }
+
+    let (trace_id, span_id) = (parts[1], parts[2]);
+    let flags = u8::from_str_radix(parts[3], 16).ok()?;
+    if !is_id(trace_id, 32) || !is_id(span_id, 16) || parts[3].len() != 2 {
+        return None;
+    }
+
+    Some(TraceContext {
+        trace_id: trace_id.to_string(),
+        span_id: span_id.to_string(),
+        parent_id: None,
+        sampled: flags & 1 == 1,
+        state: None,
+    })
+}
+
+/// Parse the single header format {trace_id}-{span_id}-{sampled}-{parent_id}
+fn parse_b3(value: &str) -> Option<TraceContext> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let (trace_id, span_id) = (parts[0], parts[1]);
+    if !is_id(trace_id, 16) && !is_id(trace_id, 32) || !is_id(span_id, 16) {
+        return None;
+    }
+
+    Some(TraceContext {
+        trace_id: pad_trace_id(trace_id),
+        span_id: span_id.to_string(),
+        parent_id: None,
+        sampled: parts.get(2) != Some(&"0"),
+        state: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    fn extract_traceparent() {
+        let mut headers = Headers::new();
+        headers.set_header(TRACEPARENT, &format!("00-{}-{}-01", TRACE_ID, SPAN_ID));
+        headers.set_header(TRACESTATE, "congo=t61rcWkgMzE,rojo=00f067aa0ba902b7");
+
+        let context = TraceContext::from_headers(&headers).unwrap();
+
+        assert_eq!(context.trace_id(), TRACE_ID);
+        assert_eq!(context.span_id(), SPAN_ID);
+        assert!(context.sampled());
+        assert_eq!(
+            context.trace_state(),
+            Some("congo=t61rcWkgMzE,rojo=00f067aa0ba902b7")
+        );
+        assert_eq!(
+            context.traceparent(),
+            format!("00-{}-{}-01", TRACE_ID, SPAN_ID)
+        );
+
+        // The vendor values are opaque, they are forwarded unchanged
+        let mut forwarded = Headers::new();
+        context.child().inject(&mut forwarded);
+        assert_eq!(
+            forwarded.get_header(TRACESTATE).unwrap(),
+            "congo=t61rcWkgMzE,rojo=00f067aa0ba902b7"
+        );
+    }
+
+    #[test]
+    fn invalid_traceparent() {
+        let mut headers = Headers::new();
+        headers.set_header(
+            TRACEPARENT,
+            &format!("00-{}-{}-01", "0".repeat(32), SPAN_ID),
+        );
+
+        assert_eq!(TraceContext::from_headers(&headers), None);
+    }
+
+    #[test]
+    fn extract_b3() {
+        let mut headers = Headers::new();
+        headers.set_header(B3, &format!("{}-{}-0", &TRACE_ID[16..], SPAN_ID));
+
+        let context = TraceContext::from_headers(&headers).unwrap();
+        assert_eq!(
+            context.trace_id(),
+            format!("{}{}", "0".repeat(16), &TRACE_ID[16..])
+        );
+        assert!(!context.sampled());
+
+        let mut headers = Headers::new();
+        headers.set_header(B3_TRACE_ID, TRACE_ID);
+        headers.set_header(B3_SPAN_ID, SPAN_ID);
+
+        let context = TraceContext::from_headers(&headers).unwrap();
+        assert_eq!(context.trace_id(), TRACE_ID);
+        assert!(context.sampled());
+    }
+
+    #[test]
+    fn child_inject() {
+        let root = TraceContext::new_root();
+        let child = root.child();
+
+        assert_eq!(child.trace_id(), root.trace_id());
+        assert_eq!(child.parent_id(), Some(root.span_id()));
+        assert_ne!(child.span_id(), root.span_id());
+
+        let mut headers = Headers::new();
+        child.inject(&mut headers);
+
+        let extracted = TraceContext::from_headers(&headers).unwrap();
+        assert_eq!(extracted.trace_id(), child.trace_id());
+        assert_eq!(extracted.span_id(), child.span_id());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::http::parser::ParseError;
+use crate::http::{Headers, Method, Version};
+use crate::request::{Request, RequestBuilder};
+use crate::response::response_parser::ResponseParser;
+use crate::response::Response;
+use crate::{AIOServer, Router};
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+
+type Handler = Arc<dyn Send + Sync + 'static + Fn(&Request) -> Response>;
+
+/// Client dispatching requests directly to a handler, a [`Router`] or a server, without opening any socket.
+/// It can also send its requests to a running server through TCP, see [`TestClient::connect`].
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::{router, Method, ResponseBuilder};
+/// use mini_async_http::test::TestClient;
+///
+/// let router = router!(
+///     "/hello", Method::GET => |_,_|ResponseBuilder::empty_200().body(b"Hello").build().unwrap()
+/// );
+/// let client = TestClient::from_router(router);
+///
+/// let response = client.get("/hello").header("Accept", "text/plain").send();
+///
+/// assert_eq!(response.code(), 200);
+/// assert_eq!(response.body().unwrap(), b"Hello");
+/// ```
+///
+/// [`Router`]: ../struct.Router.html
+/// [`TestClient::connect`]: struct.TestClient.html#method.connect
+#[derive(Clone)]
+pub struct TestClient {
+    handler: Handler,
+}
+
+impl TestClient {
+    /// Create a client sending its requests to the given handler
+    pub fn new<H>(handler: H) -> TestClient
+    where
+        H: Send + Sync + 'static + Fn(&Request) -> Response,
+    {
+        TestClient {
+            handler: Arc::from(handler),
+        }
+    }
+
+    /// Create a client sending its requests to the given router
+    pub fn from_router(router: Router) -> TestClient {
+        TestClient::new(move |request| router.exec(request))
+    }
+
+    /// Create a client sending its requests to the given server, as if they were received on a connection.
+    /// The server does not need to be started.
+    pub fn from_server(server: &AIOServer) -> TestClient {
+        let service = server.service();
+
+        TestClient::new(move |request| futures::executor::block_on(service.respond(request)))
+    }
+
+    /// Create a client sending every request on a new TCP connection to the given address.
+    ///
+    /// # Panics
+    ///
+    /// Sending a request panics if the connection fails or if the response is not valid.
+    pub fn connect(addr: SocketAddr) -> TestClient {
+        TestClient::new(move |request| send_tcp(addr, request))
+    }
+
+    /// Create a client sending its requests one after the other on the given connection, such as one end
+    /// of a [`duplex`] stream served by an [`HttpConnection`], so keep-alive is exercised without socket.
+    ///
+    /// # Panics
+    ///
+    /// Sending a request panics if the connection is closed or if the response is not valid.
+    ///
+    /// [`duplex`]: ../fn.duplex.html
+    /// [`HttpConnection`]: ../struct.HttpConnection.html
+    pub fn from_stream<S>(stream: S) -> TestClient
+    where
+        S: Read + Write + Send + 'static,
+    {
+        let stream = Mutex::new(stream);
+
+        TestClient::new(move |request| exchange(&mut *stream.lock().unwrap(), request))
+    }
+
+    /// Start a request with the given method and path
+    pub fn request(&self, method: Method, path: &str) -> TestRequest<'_> {
+        TestRequest {
+            client: self,
+            method,
+            path: String::from(path),
+            headers: Headers::new(),
+            body: None,
+        }
+    }
+
+    /// Start a GET request on the given path
+    pub fn get(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::GET, path)
+    }
+
+    /// Start a HEAD request on the given path
+    pub fn head(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::HEAD, path)
+    }
+
+    /// Start a POST request on the given path
+    pub fn post(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::POST, path)
+    }
+
+    /// Start a PUT request on the given path
+    pub fn put(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::PUT, path)
+    }
+
+    /// Start a PATCH request on the given path
+    pub fn patch(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::PATCH, path)
+    }
+
+    /// Start a DELETE request on the given path
+    pub fn delete(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::DELETE, path)
+    }
+
+    /// Start an OPTIONS request on the given path
+    pub fn options(&self, path: &str) -> TestRequest<'_> {
+        self.request(Method::OPTIONS, path)
+    }
+
+    /// Send an already built request
+    pub fn send(&self, request: &Request) -> Response {
+        (self.handler)(request)
+    }
+}
+
+fn send_tcp(addr: SocketAddr, request: &Request) -> Response {
+    let mut stream = TcpStream::connect(addr).expect("Could not connect to the server");
+
+    exchange(&mut stream, request)
+}
+
+/// Write the request to the stream and read its response
+fn exchange<S: Read + Write>(stream: &mut S, request: &Request) -> Response {
+    let mut buf = format!(
+        "{} {} {}\r\n",
+        request.method().as_str(),
+        request.path(),
+        request.version().as_str()
+    )
+    .into_bytes();
+    for (name, value) in request.headers().iter() {
+        buf.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    buf.extend_from_slice(b"\r\n");
+    if let Some(body) = request.body() {
+        buf.extend_from_slice(body);
+    }
+    stream.write_all(&buf).expect("Could not send the request");
+
+    let parser = ResponseParser::new();
+    let mut read = Vec::new();
+    let mut chunk = [0; 1024];
+    loop {
+        let n = stream
+            .read(&mut chunk)
+            .expect("Could not read the response");
+        read.extend_from_slice(&chunk[..n]);
+
+        match parser.parse_u8(&read) {
+            Ok((response, _)) => return response,
+            Err(ParseError::UnexpectedEnd) if n > 0 => continue,
+            Err(e) => panic!("Invalid response : {:?}", e),
+        }
+    }
+}
+
+/// Request being built by a [`TestClient`]
+///
+/// [`TestClient`]: struct.TestClient.html
+pub struct TestRequest<'a> {
+    client: &'a TestClient,
+    method: Method,
+    path: String,
+    headers: Headers,
+    body: Option<Vec<u8>>,
+}
+
+impl<'a> TestRequest<'a> {
+    /// Set a single header of the request
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.set_header(name, value);
+        self
+    }
+
+    /// Set the body of the request along with the "Content-Length" header
+    pub fn body(mut self, body: &[u8]) -> Self {
+        self.headers
+            .set_header("Content-Length", &body.len().to_string());
+        self.body = Some(body.to_vec());
+        self
+    }
+
+    /// Build the request and send it, returning the response
+    pub fn send(self) -> Response {
+        let mut builder = RequestBuilder::new()
+            .method(self.method)
+            .path(self.path)
+            .version(Version::HTTP11)
+            .headers(self.headers);
+
+        if let Some(body) = self.body {
+            builder = builder.body(&body);
+        }
+
+        self.client.send(&builder.build().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ResponseBuilder;
+
+    #[test]
+    fn handler() {
+        let client = TestClient::new(|request| {
+            ResponseBuilder::empty_200()
+                .body(request.body().unwrap())
+                .build()
+                .unwrap()
+        });
+
+        let response = client.post("/").body(b"body").send();
+
+        assert_eq!(response.code(), 200);
+        assert_eq!(response.body().unwrap(), b"body");
+    }
+
+    #[test]
+    fn router() {
+        let router = crate::router!(
+            "/header", Method::GET => |req,_| {
+                let value = req.headers().get_header("X-Test").unwrap();
+                ResponseBuilder::empty_200().body(value.as_bytes()).build().unwrap()
+            }
+        );
+        let client = TestClient::from_router(router);
+
+        let response = client.get("/header").header("X-Test", "value").send();
+        assert_eq!(response.body().unwrap(), b"value");
+
+        let response = client.post("/header").send();
+        assert_eq!(response.code(), 404);
+    }
+
+    #[test]
+    fn server() {
+        let mut server = AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+            ResponseBuilder::empty_200().build().unwrap()
+        });
+        server.set_trace_enabled(true);
+        let client = TestClient::from_server(&server);
+
+        let response = client.request(Method::TRACE, "/trace").send();
+
+        assert_eq!(
+            response.headers().get_header("Content-Type").unwrap(),
+            "message/http"
+        );
+        assert_eq!(client.get("/").send().code(), 200);
+    }
+}
@@ -0,0 +1,6 @@
+//! Utilities to test handlers, routers and servers
+mod client;
+mod server;
+
+pub use client::{TestClient, TestRequest};
+pub use server::TestServer;
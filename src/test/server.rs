@@ -0,0 +1,147 @@
+use crate::request::Request;
+use crate::response::Response;
+use crate::test::TestClient;
+use crate::{AIOServer, Router, ServerHandle};
+
+use std::net::SocketAddr;
+use std::thread::JoinHandle;
+
+const EPHEMERAL_ADDR: &str = "127.0.0.1:0";
+
+/// Server started in a background thread for the duration of a test.
+/// The server is shut down when the guard is dropped.
+///
+/// # Example
+///
+/// ```
+/// use mini_async_http::ResponseBuilder;
+/// use mini_async_http::test::TestServer;
+///
+/// let server = TestServer::new(|_| ResponseBuilder::empty_200().body(b"Hello").build().unwrap());
+///
+/// let response = server.client().get("/").send();
+///
+/// assert_eq!(response.body().unwrap(), b"Hello");
+/// ```
+pub struct TestServer {
+    handle: ServerHandle,
+    addr: SocketAddr,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    /// Start a server bound to a port picked by the system, executing the given handler
+    pub fn new<H>(handler: H) -> TestServer
+    where
+        H: Send + Sync + 'static + Fn(&Request) -> Response,
+    {
+        TestServer::spawn(AIOServer::new(EPHEMERAL_ADDR.parse().unwrap(), handler))
+    }
+
+    /// Start a server bound to a port picked by the system, executing the given router
+    pub fn from_router(router: Router) -> TestServer {
+        TestServer::spawn(AIOServer::from_router(
+            EPHEMERAL_ADDR.parse().unwrap(),
+            router,
+        ))
+    }
+
+    /// Start the given server in a background thread and wait for it to be ready.
+    /// To avoid port conflicts between tests, the server should be bound to the port 0.
+    pub fn spawn(mut server: AIOServer) -> TestServer {
+        let handle = server.handle();
+
+        let thread = std::thread::spawn(move || {
+            server.start();
+        });
+
+        assert!(handle.ready(), "Test server could not start");
+        let addr = handle
+            .local_addr()
+            .expect("Test server is ready without address");
+
+        TestServer {
+            handle,
+            addr,
+            thread: Some(thread),
+        }
+    }
+
+    /// Return the address the server is bound to
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Return the base url of the server, i.e. "http://127.0.0.1:port"
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Return a [`TestClient`] sending its requests to the server through TCP
+    ///
+    /// [`TestClient`]: struct.TestClient.html
+    pub fn client(&self) -> TestClient {
+        TestClient::connect(self.addr)
+    }
+
+    /// Return a [`ServerHandle`] to the server
+    ///
+    /// [`ServerHandle`]: ../struct.ServerHandle.html
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.handle.shutdown();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Method, ResponseBuilder};
+
+    #[test]
+    fn ephemeral_port() {
+        let a = TestServer::new(|_| ResponseBuilder::empty_200().build().unwrap());
+        let b = TestServer::new(|_| ResponseBuilder::empty_200().build().unwrap());
+
+        assert_ne!(a.addr().port(), 0);
+        assert_ne!(a.addr(), b.addr());
+        assert_eq!(a.url(), format!("http://127.0.0.1:{}", a.addr().port()));
+    }
+
+    #[test]
+    fn routed() {
+        let router = crate::router!(
+            "/routed/{param}", Method::POST => |req,param| {
+                let mut body = param.get("param").unwrap().as_bytes().to_vec();
+                body.extend_from_slice(req.body().unwrap());
+                ResponseBuilder::empty_200().body(&body).build().unwrap()
+            }
+        );
+        let server = TestServer::from_router(router);
+
+        let response = server.client().post("/routed/a").body(b"b").send();
+        assert_eq!(response.body().unwrap(), b"ab");
+
+        let response = server.client().get("/routed/a").send();
+        assert_eq!(response.code(), 404);
+    }
+
+    #[test]
+    fn shutdown_on_drop() {
+        let server = TestServer::new(|_| ResponseBuilder::empty_200().build().unwrap());
+        let addr = server.addr();
+
+        drop(server);
+
+        assert!(std::net::TcpStream::connect(addr).is_err());
+    }
+}
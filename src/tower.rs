@@ -0,0 +1,172 @@
+//! Adapters with the [tower](https://github.com/tower-rs/tower) `Service` trait, enabled with the `tower` feature.
+//!
+//! * An [`AIOServer`] can serve any `Service<http::Request<Vec<u8>>>` with [`AIOServer::from_service`]
+//! * A [`Router`] is a `Service<http::Request<Vec<u8>>>` and can be wrapped in tower middlewares
+//!
+//! [`AIOServer`]: struct.AIOServer.html
+//! [`AIOServer::from_service`]: struct.AIOServer.html#method.from_service
+//! [`Router`]: struct.Router.html
+
+use crate::request::Request;
+use crate::response::{Response, ResponseBuilder};
+use crate::{AIOServer, Router};
+
+use futures::future::{FutureExt, Ready};
+use log::error;
+use tower_service::Service;
+
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+impl AIOServer {
+    /// Create a new server executing the given tower `Service` for each request.
+    /// The service is cloned for every request.
+    ///
+    /// If the service fails, the error is logged and a response with status code 500 is sent.
+    /// The repeated headers of its responses are joined in one, except Set-Cookie of which only the last value is sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mini_async_http::{AIOServer, Method, ResponseBuilder};
+    ///
+    /// let router = mini_async_http::router!(
+    ///     "/example", Method::GET => |_,_|ResponseBuilder::empty_200().body(b"GET").build().unwrap()
+    /// );
+    ///
+    /// // Any tower middleware could wrap the router here
+    /// let server = AIOServer::from_service("127.0.0.1:7882".parse().unwrap(), router);
+    /// ```
+    pub fn from_service<S>(addr: SocketAddr, service: S) -> AIOServer
+    where
+        S: Service<http::Request<Vec<u8>>, Response = http::Response<Vec<u8>>>
+            + Clone
+            + Send
+            + 'static,
+        S::Future: Send,
+        S::Error: Debug,
+    {
+        let service = Mutex::new(service);
+
+        AIOServer::from_async(addr, move |request| {
+            let mut service = service.lock().unwrap().clone();
+
+            async move {
+                let request = match http::Request::try_from(request.clone()) {
+                    Ok(request) => request,
+                    Err(_) => return ResponseBuilder::empty_400().build().unwrap(),
+                };
+
+                if let Err(e) = futures::future::poll_fn(|cx| service.poll_ready(cx)).await {
+                    error!("Service is not ready : {:?}", e);
+                    return ResponseBuilder::empty_500().build().unwrap();
+                }
+
+                match service.call(request).await {
+                    Ok(response) => Response::from(response),
+                    Err(e) => {
+                        error!("Error when calling service : {:?}", e);
+                        ResponseBuilder::empty_500().build().unwrap()
+                    }
+                }
+            }
+            .boxed()
+        })
+    }
+}
+
+impl Service<http::Request<Vec<u8>>> for Router {
+    type Response = http::Response<Vec<u8>>;
+    type Error = http::Error;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Requests whose path is not a valid [`Uri`] are answered with a 400 status code
+    ///
+    /// [`Uri`]: struct.Uri.html
+    fn call(&mut self, request: http::Request<Vec<u8>>) -> Self::Future {
+        let response = match Request::try_from(request) {
+            Ok(request) => self.exec(&request),
+            Err(_) => ResponseBuilder::empty_400().build().unwrap(),
+        };
+
+        futures::future::ready(http::Response::try_from(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Method;
+
+    use std::io::{Read, Write};
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<http::Request<Vec<u8>>> for Echo {
+        type Response = http::Response<Vec<u8>>;
+        type Error = http::Error;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<Vec<u8>>) -> Self::Future {
+            let body = request.uri().path().as_bytes().to_vec();
+
+            futures::future::ready(
+                http::Response::builder()
+                    .header("Content-Length", body.len())
+                    .body(body),
+            )
+        }
+    }
+
+    #[test]
+    fn router_service() {
+        let mut router = crate::router!(
+            "/tower", Method::GET => |_,_|ResponseBuilder::empty_200().body(b"GET").build().unwrap()
+        );
+
+        let request = http::Request::get("/tower").body(Vec::new()).unwrap();
+        let response = futures::executor::block_on(router.call(request)).unwrap();
+
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.body(), b"GET");
+
+        let request = http::Request::get("/missing").body(Vec::new()).unwrap();
+        let response = futures::executor::block_on(router.call(request)).unwrap();
+
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+
+        let request = http::Request::get("/a%zz").body(Vec::new()).unwrap();
+        let response = futures::executor::block_on(router.call(request)).unwrap();
+
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn serve_service() {
+        let server = AIOServer::from_service("127.0.0.1:0".parse().unwrap(), Echo);
+        let server = crate::test::TestServer::spawn(server);
+
+        let mut stream = std::net::TcpStream::connect(server.addr()).unwrap();
+        stream
+            .write_all(b"GET /echo HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("/echo"));
+    }
+}
@@ -1,9 +1,10 @@
+use mini_async_http::test::TestServer;
 use mini_async_http::{router, AIOServer, Method, Request, Response, ResponseBuilder, Version};
 
-use std::sync::Mutex;
+use std::io::{Read, Write};
+use std::net::TcpStream;
 
-extern crate lazy_static;
-use lazy_static::lazy_static;
+const EPHEMERAL_ADDR: &str = "127.0.0.1:0";
 
 pub struct ServerConfig {
     pub addr: String,
@@ -19,51 +20,12 @@ impl Clone for ServerConfig {
     }
 }
 
-pub struct ServerGenerator {
-    port: Mutex<u32>,
-}
-
-lazy_static! {
-    static ref GENERATOR: ServerGenerator = {
-        ServerGenerator {
-            port: Mutex::from(12343),
+impl From<&TestServer> for ServerConfig {
+    fn from(server: &TestServer) -> ServerConfig {
+        ServerConfig {
+            addr: server.addr().to_string(),
+            http_addr: server.url(),
         }
-    };
-}
-
-impl ServerGenerator {
-    pub fn server(&self) -> (AIOServer, ServerConfig) {
-        let portstr = self.incr().to_string();
-
-        let server = server(portstr.as_str());
-
-        let config = ServerConfig {
-            addr: addr(portstr.as_str()),
-            http_addr: http_addr(portstr.as_str()),
-        };
-
-        (server, config)
-    }
-
-    pub fn routed_server(&self) -> (AIOServer, ServerConfig) {
-        let portstr = self.incr().to_string();
-
-        let server = router_server(portstr.as_str());
-
-        let config = ServerConfig {
-            addr: addr(portstr.as_str()),
-            http_addr: http_addr(portstr.as_str()),
-        };
-
-        (server, config)
-    }
-
-    fn incr(&self) -> u32 {
-        let mut port = self.port.lock().unwrap();
-        let val = *port;
-
-        *port = *port + 1;
-        val
     }
 }
 
@@ -86,14 +48,11 @@ pub fn handler_basic(request: &Request) -> Response {
     return response;
 }
 
-fn server(port: &str) -> AIOServer {
-    let addr = format!("127.0.0.1:{}", port);
-    AIOServer::new(addr.as_str().parse().unwrap(), Box::new(handler_basic))
+fn server() -> AIOServer {
+    AIOServer::new(EPHEMERAL_ADDR.parse().unwrap(), Box::new(handler_basic))
 }
 
-fn router_server(port: &str) -> AIOServer {
-    let addr = format!("127.0.0.1:{}", port);
-
+fn router_server() -> AIOServer {
     let router = router!(
         "/router/parametrized/{parameter}", Method::GET => |_req, params| {
             let val = params.get("parameter").unwrap();
@@ -139,51 +98,55 @@ fn router_server(port: &str) -> AIOServer {
         }
     );
 
-    AIOServer::from_router(addr.as_str().parse().unwrap(), router)
+    AIOServer::from_router(EPHEMERAL_ADDR.parse().unwrap(), router)
 }
 
-fn addr(port: &str) -> String {
-    format!("127.0.0.1:{}", port)
-}
+/// Send raw bytes to the server and read the answer until the connection is closed
+pub fn raw_request(addr: &str, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request).unwrap();
 
-fn http_addr(port: &str) -> String {
-    format!("http://{}", addr(port))
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
 }
 
-pub fn run_test<T>(test: T) -> ()
+fn run<T>(server: AIOServer, test: T)
 where
-    T: FnOnce(ServerConfig) -> () + std::panic::UnwindSafe,
+    T: FnOnce(ServerConfig) + std::panic::UnwindSafe,
 {
-    let (mut server, config) = GENERATOR.server();
-    let handle = server.handle();
-    std::thread::spawn(move || {
-        server.start();
-    });
-
-    handle.ready();
+    let server = TestServer::spawn(server);
+    let config = ServerConfig::from(&server);
 
     let result = std::panic::catch_unwind(|| test(config));
 
-    handle.shutdown();
+    drop(server);
 
     assert!(result.is_ok())
 }
 
-pub fn run_test_routed_server<T>(test: T) -> ()
+pub fn run_test<T>(test: T) -> ()
 where
     T: FnOnce(ServerConfig) -> () + std::panic::UnwindSafe,
 {
-    let (mut server, config) = GENERATOR.routed_server();
-    let handle = server.handle();
-    std::thread::spawn(move || {
-        server.start();
-    });
-
-    handle.ready();
+    run(server(), test)
+}
 
-    let result = std::panic::catch_unwind(|| test(config));
+pub fn run_test_routed_server<T>(test: T) -> ()
+where
+    T: FnOnce(ServerConfig) -> () + std::panic::UnwindSafe,
+{
+    run(router_server(), test)
+}
 
-    handle.shutdown();
+pub fn run_test_with<S, T>(configure: S, test: T)
+where
+    S: FnOnce(&mut AIOServer),
+    T: FnOnce(ServerConfig) + std::panic::UnwindSafe,
+{
+    let mut server = server();
+    configure(&mut server);
 
-    assert!(result.is_ok())
+    run(server, test)
 }
@@ -1,4 +1,11 @@
+use std::io::{Read, Write};
 use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mini_async_http::{ConnectionInfo, ConnectionObserver, ConnectionStats, Request};
 
 mod common;
 
@@ -138,6 +145,36 @@ fn close_connection() {
     })
 }
 
+#[test]
+fn close_connection_tokens() {
+    run_test(|config| {
+        let response = raw_request(
+            config.addr.as_str(),
+            b"GET / HTTP/1.1\r\nConnection: TE, Close\r\n\r\nPOST / HTTP/1.1\r\n\r\n",
+        );
+
+        assert_eq!(response.matches("HTTP/1.1 200").count(), 1);
+        assert!(response.contains("connection: close\r\n"));
+    })
+}
+
+#[test]
+fn http10_connection() {
+    run_test(|config| {
+        let response = raw_request(
+            config.addr.as_str(),
+            b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nPOST / HTTP/1.0\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        );
+
+        assert_eq!(response.matches("HTTP/1.1 200").count(), 2);
+
+        let (first, second) = response.split_at(response.rfind("HTTP/1.1").unwrap());
+        assert!(first.contains("connection: keep-alive\r\n"));
+        assert!(second.contains("connection: close\r\n"));
+        assert!(second.ends_with("POST"));
+    })
+}
+
 #[test]
 fn simple_get_request_routed() {
     run_test_routed_server(|config| {
@@ -205,3 +242,1975 @@ fn parametrized_request_routed() {
         assert_eq!("myParam", body);
     })
 }
+
+struct CountingObserver {
+    requests: Arc<AtomicUsize>,
+    closed: Mutex<mpsc::Sender<usize>>,
+}
+
+impl ConnectionObserver for CountingObserver {
+    fn on_request(&self, _info: &ConnectionInfo, _request: &Request) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_close(&self, _info: &ConnectionInfo, stats: &ConnectionStats) {
+        self.closed.lock().unwrap().send(stats.requests()).unwrap();
+    }
+}
+
+#[test]
+fn observer_events() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let observer = CountingObserver {
+        requests: requests.clone(),
+        closed: Mutex::new(sender),
+    };
+
+    run_test_with(
+        |server| server.set_observer(observer),
+        move |config| {
+            let mut writer = Vec::new();
+            let _res = http_req::request::get(config.http_addr.as_str(), &mut writer).unwrap();
+
+            let served = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+
+            assert_eq!(1, served);
+            assert_eq!(1, requests.load(Ordering::SeqCst));
+        },
+    )
+}
+
+#[test]
+fn trace_disabled() {
+    run_test(|config| {
+        let response = raw_request(
+            config.addr.as_str(),
+            b"TRACE / HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("TRACE"));
+    })
+}
+
+#[test]
+fn trace_enabled() {
+    run_test_with(
+        |server| server.set_trace_enabled(true),
+        |config| {
+            let response = raw_request(
+                config.addr.as_str(),
+                b"TRACE /echo HTTP/1.1\r\nConnection: close\r\n\r\n",
+            );
+
+            assert!(response.contains("message/http"));
+            assert!(response.contains("TRACE /echo HTTP/1.1"));
+        },
+    )
+}
+
+#[test]
+fn connect_not_allowed() {
+    run_test(|config| {
+        let response = raw_request(
+            config.addr.as_str(),
+            b"CONNECT example.com:443 HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+
+        assert!(response.starts_with("HTTP/1.1 405"));
+    })
+}
+
+#[test]
+fn connect_tunnel() {
+    run_test_with(
+        |server| {
+            server.set_connect_handler(|request, mut tunnel| {
+                assert_eq!(request.path(), "example.com:443");
+                std::thread::spawn(move || {
+                    let mut buf = [0; 4];
+                    tunnel.read_exact(&mut buf).unwrap();
+                    tunnel.write_all(&buf).unwrap();
+                });
+            })
+        },
+        |config| {
+            let mut stream = TcpStream::connect(config.addr.as_str()).unwrap();
+            stream
+                .write_all(b"CONNECT example.com:443 HTTP/1.1\r\n\r\nping")
+                .unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+
+            assert_eq!(response, "HTTP/1.1 200 Connection Established\r\n\r\nping");
+        },
+    )
+}
+
+#[test]
+fn connect_tunnels_keep_workers_free() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    // The handler relays the tunnel until it is closed instead of moving it to a thread
+    server.set_connect_handler(|_, mut tunnel| {
+        let mut buf = [0; 4];
+        while tunnel.read_exact(&mut buf).is_ok() && tunnel.write_all(&buf).is_ok() {}
+    });
+    let server = mini_async_http::test::TestServer::spawn(server);
+    assert!(server.handle().resize_pool(1));
+    let addr = server.addr().to_string();
+
+    let tunnels: Vec<TcpStream> = (0..3)
+        .map(|_| {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(b"CONNECT example.com:443 HTTP/1.1\r\n\r\nping")
+                .unwrap();
+
+            let mut response = [0; 43];
+            stream.read_exact(&mut response).unwrap();
+            assert_eq!(
+                &response[..],
+                &b"HTTP/1.1 200 Connection Established\r\n\r\nping"[..]
+            );
+            stream
+        })
+        .collect();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    drop(tunnels);
+}
+
+#[test]
+fn handle_state() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+    let handle = server.handle();
+
+    futures::executor::block_on(handle.ready_async());
+    assert!(handle.is_ready());
+    assert!(handle.is_running());
+    assert_eq!(0, handle.connection_count());
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+    let mut buf = [0; 256];
+    let _ = stream.read(&mut buf).unwrap();
+
+    assert_eq!(1, handle.connection_count());
+
+    drop(stream);
+    let start = std::time::Instant::now();
+    while handle.connection_count() != 0 && start.elapsed() < Duration::from_secs(1) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(0, handle.connection_count());
+
+    handle.shutdown();
+    assert!(!handle.is_ready());
+}
+
+#[test]
+fn restart() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    let handle = server.handle();
+
+    for _ in 0..2 {
+        let thread = std::thread::spawn(move || {
+            server.start();
+            server
+        });
+        handle.ready();
+
+        let addr = handle.local_addr().unwrap().to_string();
+        let response = raw_request(
+            addr.as_str(),
+            b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.ends_with("GET"));
+
+        handle.shutdown();
+        futures::executor::block_on(handle.wait_async());
+        assert!(!handle.is_running());
+
+        server = thread.join().unwrap();
+    }
+}
+
+#[test]
+fn overload_reject() {
+    run_test_with(
+        |server| server.set_queue_capacity(0, mini_async_http::OverloadPolicy::Reject),
+        |config| {
+            let response = raw_request(config.addr.as_str(), b"GET / HTTP/1.1\r\n\r\n");
+
+            assert!(response.starts_with("HTTP/1.1 503"));
+        },
+    )
+}
+
+#[test]
+fn pipelined_requests() {
+    run_test(|config| {
+        let response = raw_request(
+            config.addr.as_str(),
+            b"GET / HTTP/1.1\r\n\r\nPOST / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+
+        assert_eq!(response.matches("HTTP/1.1 200").count(), 3);
+        assert!(response.find("GET").unwrap() < response.find("POST").unwrap());
+    })
+}
+
+#[test]
+fn pipeline_limit() {
+    run_test_with(
+        |server| server.set_pipeline_limit(1),
+        |config| {
+            let response = raw_request(
+                config.addr.as_str(),
+                b"GET / HTTP/1.1\r\n\r\nPOST / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+            );
+
+            assert_eq!(response.matches("HTTP/1.1 200").count(), 3);
+            assert!(response.find("GET").unwrap() < response.find("POST").unwrap());
+        },
+    )
+}
+
+#[test]
+fn pipelined_requests_multiple_workers() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+    assert!(server.handle().resize_pool(4));
+    let addr = server.addr().to_string();
+
+    let mut request = b"GET / HTTP/1.1\r\n\r\n".repeat(49);
+    request.extend_from_slice(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+    // The connections yield between their pipelined requests while the other workers are idle
+    let clients: Vec<_> = (0..8)
+        .map(|_| {
+            let addr = addr.clone();
+            let request = request.clone();
+            std::thread::spawn(move || {
+                let mut stream = TcpStream::connect(addr).unwrap();
+                stream
+                    .set_read_timeout(Some(Duration::from_secs(5)))
+                    .unwrap();
+                stream.write_all(&request).unwrap();
+
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                response.matches("HTTP/1.1 200").count()
+            })
+        })
+        .collect();
+
+    for client in clients {
+        assert_eq!(client.join().unwrap(), 50);
+    }
+}
+
+#[test]
+fn thread_name() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        let name = std::thread::current().name().unwrap_or("").to_string();
+
+        mini_async_http::ResponseBuilder::empty_200()
+            .body(name.as_bytes())
+            .build()
+            .unwrap()
+    });
+    server.set_thread_name("named");
+    server.set_thread_stack_size(1 << 20);
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let response = server.client().get("/").send();
+
+    assert!(response.body().unwrap().starts_with(b"named-worker-"));
+}
+
+#[test]
+fn resize_pool() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+    let handle = server.handle();
+    let client = server.client();
+
+    assert!(handle.resize_pool(4));
+    assert_eq!(handle.pool_size(), 4);
+    assert_eq!(client.get("/").send().body().unwrap(), b"GET");
+
+    assert!(handle.resize_pool(1));
+    assert_eq!(handle.pool_size(), 1);
+    assert_eq!(client.get("/").send().body().unwrap(), b"GET");
+
+    assert!(!handle.resize_pool(0));
+}
+
+#[test]
+fn socket_options() {
+    run_test_with(
+        |server| {
+            server.set_tcp_nodelay(false);
+            server.set_tcp_keepalive(Duration::from_secs(60), Duration::from_secs(10));
+            server.set_tcp_linger(Some(Duration::from_secs(1)));
+            server.set_listen_backlog(16);
+        },
+        |config| {
+            let response = raw_request(
+                config.addr.as_str(),
+                b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+            );
+
+            assert!(response.starts_with("HTTP/1.1 200"));
+        },
+    )
+}
+
+#[test]
+fn multiple_addresses() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    server.add_address("127.0.0.1:0".parse().unwrap());
+    let handle = server.handle();
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let addrs = handle.local_addrs();
+    assert_eq!(addrs.len(), 2);
+    assert_eq!(handle.local_addr(), Some(addrs[0]));
+
+    for addr in addrs {
+        let response = raw_request(
+            addr.to_string().as_str(),
+            b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.ends_with("GET"));
+    }
+
+    drop(server);
+}
+
+#[test]
+fn busy_address() {
+    use mini_async_http::{ServerEvent, ShutdownReason};
+
+    let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    server.add_address(busy.local_addr().unwrap());
+    let handle = server.handle();
+    let events = handle.subscribe();
+    let waiting = std::thread::spawn(move || handle.ready());
+
+    // The server stops instead of panicking when one of its addresses is taken
+    server.start();
+    assert!(!waiting.join().unwrap());
+
+    let events: Vec<ServerEvent> = events.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![ServerEvent::Stopped {
+            reason: ShutdownReason::ListenersFailed,
+            abandoned: 0
+        }]
+    );
+    assert!(!server.handle().is_running());
+}
+
+#[test]
+fn dual_stack() {
+    let port = std::net::TcpListener::bind("0.0.0.0:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    // Both unspecified addresses can share the port
+    let mut server = mini_async_http::AIOServer::new(
+        format!("0.0.0.0:{}", port).parse().unwrap(),
+        handler_basic,
+    );
+    server.add_address(format!("[::]:{}", port).parse().unwrap());
+    let handle = server.handle();
+    let _server = mini_async_http::test::TestServer::spawn(server);
+
+    assert_eq!(handle.local_addrs().len(), 2);
+    for addr in &[format!("127.0.0.1:{}", port), format!("[::1]:{}", port)] {
+        let response = raw_request(addr, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(response.ends_with("GET"));
+    }
+}
+
+#[test]
+fn trace_context_propagation() {
+    use mini_async_http::{trace_context, Method, ResponseBuilder, TraceContext};
+
+    let router = mini_async_http::router!(
+        scope "/api" with [trace_context::middleware] {
+            "/trace", Method::GET => |_, _| {
+                let context = TraceContext::current().unwrap();
+                let body = format!("{} {}", context.trace_id(), context.parent_id().unwrap_or(""));
+
+                ResponseBuilder::empty_200().body(body.as_bytes()).build().unwrap()
+            }
+        }
+    );
+    let server = mini_async_http::test::TestServer::spawn(mini_async_http::AIOServer::from_router(
+        "127.0.0.1:0".parse().unwrap(),
+        router,
+    ));
+
+    let response = raw_request(
+        server.addr().to_string().as_str(),
+        b"GET /api/trace HTTP/1.1\r\nConnection: close\r\n\
+        traceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\r\n\r\n",
+    );
+
+    assert!(response.ends_with("4bf92f3577b34da6a3ce929d0e0e4736 00f067aa0ba902b7"));
+}
+
+#[test]
+fn bounded_queue() {
+    run_test_with(
+        |server| server.set_queue_capacity(64, mini_async_http::OverloadPolicy::ShedOldest),
+        |config| {
+            let response = raw_request(
+                config.addr.as_str(),
+                b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+            );
+
+            assert!(response.starts_with("HTTP/1.1 200"));
+        },
+    )
+}
+
+#[test]
+fn request_id_task_local() {
+    let server = mini_async_http::test::TestServer::new(|_| {
+        let id = mini_async_http::task_local::get::<mini_async_http::RequestId>().unwrap();
+
+        mini_async_http::ResponseBuilder::empty_200()
+            .body(id.to_string().as_bytes())
+            .build()
+            .unwrap()
+    });
+
+    let client = server.client();
+    let first = client.get("/").send();
+    let second = client.get("/").send();
+
+    assert_ne!(first.body().unwrap(), second.body().unwrap());
+}
+
+#[test]
+fn connection_id_task_local() {
+    let server = mini_async_http::test::TestServer::new(|_| {
+        let info = mini_async_http::task_local::get::<mini_async_http::ConnectionInfo>().unwrap();
+
+        mini_async_http::ResponseBuilder::empty_200()
+            .body(format!("[{}]", info.id()).as_bytes())
+            .build()
+            .unwrap()
+    });
+    let addr = server.addr().to_string();
+    let ids = |response: String| -> Vec<String> {
+        response
+            .split('[')
+            .skip(1)
+            .map(|part| part.split(']').next().unwrap().to_string())
+            .collect()
+    };
+
+    let pipelined = ids(raw_request(
+        &addr,
+        b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    ));
+    let other = ids(raw_request(
+        &addr,
+        b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    ));
+
+    assert_eq!(pipelined.len(), 2);
+    assert_eq!(pipelined[0], pipelined[1]);
+    assert_ne!(pipelined[0], other[0]);
+}
+
+#[test]
+fn slow_poll_stats() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        std::thread::sleep(Duration::from_millis(20));
+        mini_async_http::ResponseBuilder::empty_200()
+            .build()
+            .unwrap()
+    });
+    server.set_slow_poll_threshold(Duration::from_millis(10));
+
+    let server = mini_async_http::test::TestServer::spawn(server);
+    server.client().get("/").send();
+
+    // The poll writing the response may still be running when the response is received
+    let start = std::time::Instant::now();
+    while server.handle().executor_stats().slow_polls() == 0
+        && start.elapsed() < Duration::from_secs(1)
+    {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let stats = server.handle().executor_stats();
+    assert!(stats.polls() > 0);
+    assert!(stats.slow_polls() >= 1);
+}
+
+#[test]
+fn early_hints() {
+    use mini_async_http::{send_informational, ResponseBuilder};
+
+    let server = mini_async_http::test::TestServer::new(|request| {
+        let hints = ResponseBuilder::early_hints()
+            .header("Link", "</style.css>; rel=preload; as=style")
+            .build()
+            .unwrap();
+        let sent = send_informational(&hints).is_ok();
+
+        ResponseBuilder::empty_200()
+            .text(&format!("{} {}", request.version().as_str(), sent))
+            .build()
+            .unwrap()
+    });
+    let addr = server.addr().to_string();
+
+    let response = raw_request(
+        addr.as_str(),
+        b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 103 Early Hints\r\n"));
+    assert!(response.contains("link: </style.css>; rel=preload; as=style\r\n"));
+    assert!(response.contains("HTTP/1.1 200"));
+    assert!(response.ends_with("HTTP/1.1 true"));
+
+    let response = raw_request(addr.as_str(), b"GET / HTTP/1.0\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("HTTP/1.0 false"));
+}
+
+#[test]
+fn large_response_slow_reader() {
+    const SIZE: usize = 8 * 1024 * 1024;
+
+    let server = mini_async_http::test::TestServer::new(|_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .body(&vec![b'a'; SIZE])
+            .build()
+            .unwrap()
+    });
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+
+    // Let the send buffer fill up before reading
+    std::thread::sleep(Duration::from_millis(100));
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    assert!(response.starts_with(b"HTTP/1.1 200"));
+    assert!(response.ends_with(&vec![b'a'; SIZE]));
+}
+
+#[test]
+fn invalid_request() {
+    run_test(|config| {
+        let response = raw_request(
+            config.addr.as_str(),
+            b"GET / HTTP/1.1\r\nBad Header\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.contains("connection: close\r\n"));
+
+        let mut request = String::from("GET / HTTP/1.1\r\n");
+        for i in 0..100 {
+            request.push_str(&format!("X-Header-{}: value\r\n", i));
+        }
+        request.push_str("\r\n");
+
+        let response = raw_request(config.addr.as_str(), request.as_bytes());
+        assert!(response.starts_with("HTTP/1.1 431"));
+    })
+}
+
+#[test]
+fn parse_error_handler() {
+    run_test_with(
+        |server| {
+            server.set_parse_error_handler(|error, received| {
+                let body = format!("{:?} {}", error, received.len());
+                Some(
+                    mini_async_http::ResponseBuilder::empty_400()
+                        .text(&body)
+                        .build()
+                        .unwrap(),
+                )
+            })
+        },
+        |config| {
+            let response = raw_request(
+                config.addr.as_str(),
+                b"GET / HTTP/1.1\r\nBad Header\r\n\r\n",
+            );
+
+            assert!(response.starts_with("HTTP/1.1 400"));
+            assert!(response.ends_with("HeaderName 30"));
+        },
+    )
+}
+
+#[test]
+fn listener_handover() {
+    let old = mini_async_http::test::TestServer::new(|_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .text("old")
+            .build()
+            .unwrap()
+    });
+    let old_handle = old.handle();
+    let addr = old.addr().to_string();
+
+    let mut connection = TcpStream::connect(addr.as_str()).unwrap();
+    connection.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut buf = [0; 1024];
+    assert!(connection.read(&mut buf).unwrap() > 0);
+
+    let listener = old_handle.export_listener().unwrap();
+    let new = mini_async_http::test::TestServer::spawn(mini_async_http::AIOServer::from_listener(
+        listener,
+        |_| {
+            mini_async_http::ResponseBuilder::empty_200()
+                .text("new")
+                .build()
+                .unwrap()
+        },
+    ));
+    assert_eq!(new.addr(), old.addr());
+
+    let shutdown = std::thread::spawn(move || {
+        old_handle.shutdown_graceful(Duration::from_secs(10));
+        old_handle
+    });
+    // The listeners are released once the server is draining
+    while old.handle().export_listener().is_some() {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    // The open connection is served by the old server and closed afterwards
+    connection.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    connection.read_to_string(&mut response).unwrap();
+    assert!(response.contains("connection: close\r\n"));
+    assert!(response.ends_with("old"));
+
+    let old_handle = shutdown.join().unwrap();
+    assert!(!old_handle.is_running());
+
+    let response = raw_request(
+        addr.as_str(),
+        b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.ends_with("new"));
+}
+
+struct ByteObserver {
+    events: Mutex<mpsc::Sender<(u64, u64)>>,
+    transfers: Mutex<mpsc::Sender<mini_async_http::TransferStats>>,
+}
+
+impl ConnectionObserver for ByteObserver {
+    fn on_response(
+        &self,
+        _info: &ConnectionInfo,
+        _request: &Request,
+        _response: &mini_async_http::Response,
+        transfer: &mini_async_http::TransferStats,
+    ) {
+        self.transfers.lock().unwrap().send(*transfer).unwrap();
+    }
+
+    fn on_close(&self, _info: &ConnectionInfo, stats: &ConnectionStats) {
+        self.events
+            .lock()
+            .unwrap()
+            .send((stats.bytes_read(), stats.bytes_written()))
+            .unwrap();
+    }
+}
+
+#[test]
+fn byte_accounting() {
+    let (events, closed) = mpsc::channel();
+    let (transfers, responses) = mpsc::channel();
+    let observer = ByteObserver {
+        events: Mutex::new(events),
+        transfers: Mutex::new(transfers),
+    };
+
+    run_test_with(
+        |server| server.set_observer(observer),
+        move |config| {
+            let request = b"POST / HTTP/1.1\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello";
+            let response = raw_request(config.addr.as_str(), request);
+
+            let transfer = responses.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(transfer.request_head(), request.len() - 5);
+            assert_eq!(transfer.request_body(), 5);
+            assert_eq!(transfer.response_body(), 4);
+            assert_eq!(transfer.response_bytes(), response.len());
+
+            let (read, written) = closed.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(read, request.len() as u64);
+            assert_eq!(written, response.len() as u64);
+        },
+    )
+}
+
+#[test]
+fn slow_request_threshold() {
+    run_test_with(
+        |server| server.set_slow_request_threshold(Duration::from_millis(0)),
+        |config| {
+            let response = raw_request(
+                config.addr.as_str(),
+                b"GET /slow HTTP/1.1\r\nConnection: close\r\n\r\n",
+            );
+
+            assert!(response.starts_with("HTTP/1.1 200"));
+        },
+    )
+}
+
+#[test]
+fn route_timeout() {
+    let handler = |_: &Request, _| {
+        std::thread::sleep(Duration::from_millis(50));
+        mini_async_http::ResponseBuilder::empty_200()
+            .build()
+            .unwrap()
+    };
+    let mut router = mini_async_http::Router::new();
+    router.add_route(
+        mini_async_http::Route::new("/export", mini_async_http::Method::GET)
+            .unwrap()
+            .timeout(Duration::from_secs(60)),
+        handler,
+    );
+    router.add_route(
+        mini_async_http::Route::new("/fast", mini_async_http::Method::GET)
+            .unwrap()
+            .timeout(Duration::from_millis(0)),
+        handler,
+    );
+    router.add_route(
+        mini_async_http::Route::new("/api", mini_async_http::Method::GET).unwrap(),
+        handler,
+    );
+
+    let mut server =
+        mini_async_http::AIOServer::from_router("127.0.0.1:0".parse().unwrap(), router);
+    server.set_request_timeout(Duration::from_millis(10));
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let client = server.client();
+
+    assert_eq!(client.get("/export").send().code(), 200);
+    assert_eq!(client.get("/fast").send().code(), 503);
+    assert_eq!(client.get("/api").send().code(), 503);
+}
+
+#[test]
+fn route_timeout_stops_waiting() {
+    let mut router = mini_async_http::Router::new();
+    router.add_route(
+        mini_async_http::Route::new("/stuck", mini_async_http::Method::GET)
+            .unwrap()
+            .timeout(Duration::from_millis(50)),
+        |_, _| {
+            std::thread::sleep(Duration::from_secs(5));
+            mini_async_http::ResponseBuilder::empty_200()
+                .build()
+                .unwrap()
+        },
+    );
+
+    let server = mini_async_http::AIOServer::from_router("127.0.0.1:0".parse().unwrap(), router);
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let client = server.client();
+
+    let start = std::time::Instant::now();
+    assert_eq!(client.get("/stuck").send().code(), 503);
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn request_deadline() {
+    let handler = |request: &Request, _| {
+        let remaining = match request.deadline() {
+            Some(deadline) => deadline.saturating_duration_since(std::time::Instant::now()),
+            None => {
+                return mini_async_http::ResponseBuilder::empty_500()
+                    .build()
+                    .unwrap()
+            }
+        };
+
+        mini_async_http::ResponseBuilder::empty_200()
+            .text(&remaining.as_secs().to_string())
+            .build()
+            .unwrap()
+    };
+    let mut router = mini_async_http::Router::new();
+    router.add_route(
+        mini_async_http::Route::new("/export", mini_async_http::Method::GET)
+            .unwrap()
+            .timeout(Duration::from_secs(120)),
+        handler,
+    );
+    router.add_route(
+        mini_async_http::Route::new("/api", mini_async_http::Method::GET).unwrap(),
+        handler,
+    );
+
+    let mut server =
+        mini_async_http::AIOServer::from_router("127.0.0.1:0".parse().unwrap(), router);
+    server.set_request_timeout(Duration::from_secs(60));
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let client = server.client();
+
+    assert_eq!(client.get("/api").send().body().unwrap(), b"59");
+    assert_eq!(client.get("/export").send().body().unwrap(), b"119");
+}
+
+#[test]
+fn async_middleware() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .text("handler")
+            .build()
+            .unwrap()
+    });
+    server.add_middleware(mini_async_http::AsyncMiddleware::new(|req, next| {
+        Box::pin(async move {
+            mini_async_http::yield_now().await;
+            match req.headers().get_header("Authorization") {
+                Some(_) => next.run(req).await,
+                None => mini_async_http::ResponseBuilder::empty_403()
+                    .build()
+                    .unwrap(),
+            }
+        })
+    }));
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let client = server.client();
+
+    assert_eq!(client.get("/").send().code(), 403);
+
+    let response = client.get("/").header("Authorization", "token").send();
+    assert_eq!(response.code(), 200);
+    assert_eq!(response.body().unwrap(), b"handler");
+}
+
+#[test]
+fn file_response() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/http_body.txt");
+    let server = mini_async_http::test::TestServer::new(move |request| {
+        let file = match request.path().as_str() {
+            "/file.txt" => path,
+            _ => "missing.txt",
+        };
+
+        match mini_async_http::ResponseBuilder::file(file) {
+            Ok(builder) => builder.build().unwrap(),
+            Err(e) => e.response(),
+        }
+    });
+    let client = server.client();
+
+    let response = client.get("/file.txt").send();
+    assert_eq!(response.code(), 200);
+    assert_eq!(
+        response.headers().get_header("Content-Type").unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(response.body().unwrap(), &std::fs::read(path).unwrap());
+
+    assert_eq!(client.get("/missing").send().code(), 404);
+}
+
+#[test]
+fn static_files_cache() {
+    let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+    let files = mini_async_http::StaticFiles::new("/static", root).cache(64 * 1024, 16 * 1024);
+    let server = mini_async_http::test::TestServer::new(move |request| files.handle(request));
+    let client = server.client();
+
+    let response = client.get("/static/http_body.txt").send();
+    assert_eq!(response.code(), 200);
+    assert_eq!(
+        response.body().unwrap(),
+        &std::fs::read(format!("{}/http_body.txt", root)).unwrap()
+    );
+    let etag = response.headers().get_header("ETag").unwrap().clone();
+
+    let response = client
+        .get("/static/http_body.txt")
+        .header("If-None-Match", &etag)
+        .send();
+    assert_eq!(response.code(), 304);
+    assert_eq!(response.body().map_or(0, Vec::len), 0);
+
+    assert_eq!(client.get("/static/../Cargo.toml").send().code(), 404);
+    assert_eq!(client.get("/static/missing.txt").send().code(), 404);
+    assert_eq!(client.post("/static/http_body.txt").send().code(), 405);
+
+    // Without index file nor listing a directory is not found, once redirected to its path ending with a '/'
+    let response = client.get("/static").send();
+    assert_eq!(response.code(), 301);
+    assert_eq!(
+        response.headers().get_header("Location").unwrap(),
+        "/static/"
+    );
+    assert_eq!(client.get("/static/").send().code(), 404);
+}
+
+#[test]
+fn content_length_mismatch() {
+    use mini_async_http::LengthMismatchPolicy;
+
+    let serve = |policy| {
+        let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+            mini_async_http::ResponseBuilder::empty_200()
+                .body(b"abc")
+                .header("Content-Length", "10")
+                .build()
+                .unwrap()
+        });
+        server.set_length_mismatch_policy(policy);
+        let server = mini_async_http::test::TestServer::spawn(server);
+
+        raw_request(
+            &server.addr().to_string(),
+            b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+        )
+    };
+
+    let corrected = serve(LengthMismatchPolicy::Correct);
+    assert_eq!(corrected.matches("content-length: 3\r\n").count(), 2);
+    assert!(corrected.ends_with("\r\n\r\nabc"));
+
+    let closed = serve(LengthMismatchPolicy::CloseDelimited);
+    assert_eq!(closed.matches("HTTP/1.1 200").count(), 1);
+    assert!(!closed.contains("content-length"));
+    assert!(closed.contains("connection: close\r\n"));
+    assert!(closed.ends_with("\r\n\r\nabc"));
+
+    let failed = serve(LengthMismatchPolicy::InternalError);
+    assert_eq!(failed.matches("HTTP/1.1 500").count(), 2);
+    assert!(!failed.contains("abc"));
+}
+
+#[test]
+fn bodiless_responses() {
+    let server = mini_async_http::test::TestServer::new(|request| {
+        let builder = match request.path().as_str() {
+            "/empty" => mini_async_http::ResponseBuilder::no_content(),
+            _ => mini_async_http::ResponseBuilder::empty_200(),
+        };
+        builder.body(b"abc").build().unwrap()
+    });
+
+    let response = raw_request(
+        &server.addr().to_string(),
+        b"GET /empty HTTP/1.1\r\n\r\nHEAD / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    let responses: Vec<&str> = response.split("HTTP/1.1 ").skip(1).collect();
+
+    assert_eq!(responses.len(), 3);
+    assert!(responses[0].starts_with("204"));
+    assert!(!responses[0].contains("content-length"));
+    assert!(responses[1].starts_with("200"));
+    assert!(responses[1].contains("content-length: 3\r\n"));
+    assert!(responses[1].ends_with("\r\n\r\n"));
+    assert!(responses[2].ends_with("\r\n\r\nabc"));
+}
+
+#[test]
+fn date_header() {
+    let server = mini_async_http::test::TestServer::new(|request| {
+        let builder = mini_async_http::ResponseBuilder::empty_200();
+        match request.path().as_str() {
+            "/fixed" => builder.header("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            _ => builder,
+        }
+        .build()
+        .unwrap()
+    });
+    let client = server.client();
+
+    let response = client.get("/").send();
+    let date = response.headers().get_header("Date").unwrap();
+    assert!(date.ends_with(" GMT"));
+    assert_eq!(date.len(), "Sun, 06 Nov 1994 08:49:37 GMT".len());
+
+    let response = client.get("/fixed").send();
+    assert_eq!(
+        response.headers().get_header("Date").unwrap(),
+        "Sun, 06 Nov 1994 08:49:37 GMT"
+    );
+}
+
+#[test]
+fn max_requests_per_connection() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .build()
+            .unwrap()
+    });
+    server.set_max_requests_per_connection(Some(2));
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let response = raw_request(
+        server.addr().to_string().as_str(),
+        b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+    );
+    assert_eq!(response.matches("HTTP/1.1 200").count(), 2);
+    assert_eq!(response.matches("connection: close").count(), 1);
+
+    raw_request(
+        server.addr().to_string().as_str(),
+        b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+
+    let start = std::time::Instant::now();
+    while server.handle().keep_alive_stats().connections() < 2 {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let stats = server.handle().keep_alive_stats();
+    assert_eq!(stats.requests(), 3);
+    assert_eq!(stats.reused_connections(), 1);
+    assert_eq!(stats.limited_connections(), 1);
+    assert_eq!(stats.requests_per_connection(), 1.5);
+}
+
+#[test]
+fn bulk_priority() {
+    let (release, blocked) = std::sync::mpsc::channel::<()>();
+    let blocked = Arc::new(std::sync::Mutex::new(blocked));
+    let (started_sender, started) = std::sync::mpsc::channel();
+    let started_sender = std::sync::Mutex::new(started_sender);
+
+    let mut router = mini_async_http::Router::new();
+    router.add_route(
+        mini_async_http::Route::new("/health", mini_async_http::Method::GET).unwrap(),
+        |_, _| {
+            mini_async_http::ResponseBuilder::empty_200()
+                .build()
+                .unwrap()
+        },
+    );
+    router.add_route(
+        mini_async_http::Route::new("/export", mini_async_http::Method::GET)
+            .unwrap()
+            .priority(mini_async_http::Priority::Bulk),
+        move |_, _| {
+            let name = std::thread::current().name().map(String::from);
+            started_sender.lock().unwrap().send(name).unwrap();
+            // Hold the bulk worker until the health check is answered
+            let _ = blocked.lock().unwrap().recv_timeout(Duration::from_secs(5));
+            mini_async_http::ResponseBuilder::empty_200()
+                .build()
+                .unwrap()
+        },
+    );
+
+    let mut server =
+        mini_async_http::AIOServer::from_router("127.0.0.1:0".parse().unwrap(), router);
+    server.set_bulk_workers(1);
+    server.set_thread_name("priority");
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let addr = server.addr().to_string();
+    let export = std::thread::spawn(move || {
+        raw_request(&addr, b"GET /export HTTP/1.1\r\nConnection: close\r\n\r\n")
+    });
+    let name = started.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(name.unwrap().starts_with("priority-bulk-"));
+
+    assert_eq!(server.client().get("/health").send().code(), 200);
+    release.send(()).unwrap();
+    assert!(export.join().unwrap().starts_with("HTTP/1.1 200"));
+}
+
+#[test]
+fn adaptive_concurrency() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .build()
+            .unwrap()
+    });
+    server.set_adaptive_concurrency(Duration::from_secs(10), 1);
+
+    // The slow request holds the only slot until it is released, without blocking the worker
+    let started = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let released = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (started_flag, released_flag) = (started.clone(), released.clone());
+    server.add_middleware(mini_async_http::AsyncMiddleware::new(move |req, next| {
+        let (started, released) = (started_flag.clone(), released_flag.clone());
+        Box::pin(async move {
+            if req.path() == "/slow" {
+                started.store(true, Ordering::SeqCst);
+                let start = std::time::Instant::now();
+                while !released.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(5) {
+                    mini_async_http::yield_now().await;
+                }
+            }
+            next.run(req).await
+        })
+    }));
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let addr = server.addr().to_string();
+    let slow = std::thread::spawn(move || {
+        raw_request(&addr, b"GET /slow HTTP/1.1\r\nConnection: close\r\n\r\n")
+    });
+    while !started.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(server.client().get("/").send().code(), 503);
+    let stats = server.handle().concurrency_stats().unwrap();
+    assert_eq!((stats.limit(), stats.in_flight(), stats.shed()), (1, 1, 1));
+
+    released.store(true, Ordering::SeqCst);
+    assert!(slow.join().unwrap().starts_with("HTTP/1.1 200"));
+    assert_eq!(server.client().get("/").send().code(), 200);
+}
+
+#[test]
+fn large_file_response() {
+    let path = std::env::temp_dir().join(format!("mini_async_http_{}.bin", std::process::id()));
+    let content: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &content).unwrap();
+
+    let served = path.clone();
+    let server = mini_async_http::test::TestServer::new(move |_| {
+        mini_async_http::ResponseBuilder::file(&served)
+            .unwrap()
+            .build()
+            .unwrap()
+    });
+
+    let response = server.client().get("/").send();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        response.headers().get_header("Content-Type").unwrap(),
+        "application/octet-stream"
+    );
+    assert!(response.body().unwrap() == &content);
+}
+
+#[test]
+fn header_section_too_large() {
+    run_test(|config| {
+        let request = format!(
+            "GET / HTTP/1.1\r\nX-Large: {}\r\n\r\n",
+            "a".repeat(17 * 1024)
+        );
+        let response = raw_request(config.addr.as_str(), request.as_bytes());
+
+        assert!(response.starts_with("HTTP/1.1 431"));
+        assert!(response.contains("connection: close\r\n"));
+    })
+}
+
+#[test]
+fn max_headers() {
+    let mut request = String::from("GET / HTTP/1.1\r\nConnection: close\r\n");
+    for i in 0..80 {
+        request.push_str(&format!("X-Header-{}: {}\r\n", i, i));
+    }
+    request.push_str("\r\n");
+
+    run_test(|config| {
+        let response = raw_request(config.addr.as_str(), request.as_bytes());
+        assert!(response.starts_with("HTTP/1.1 431"));
+    });
+
+    run_test_with(
+        |server| server.set_max_headers(128),
+        |config| {
+            let response = raw_request(config.addr.as_str(), request.as_bytes());
+            assert!(response.starts_with("HTTP/1.1 200"));
+        },
+    )
+}
+
+#[test]
+fn object_pools() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+    let before = server.handle().object_pool_stats();
+
+    let client = server.client();
+    for _ in 0..20 {
+        assert_eq!(client.get("/").send().code(), 200);
+    }
+
+    let stats = server.handle().object_pool_stats();
+    assert!(stats.reused() > before.reused());
+}
+
+#[test]
+fn concurrent_pipeline() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |request| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .text(request.path())
+            .build()
+            .unwrap()
+    });
+    server.set_concurrent_pipeline(true);
+
+    // The first request only completes once the second one is being handled
+    let second_started = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    server.add_middleware(mini_async_http::AsyncMiddleware::new(move |req, next| {
+        let second_started = second_started.clone();
+        Box::pin(async move {
+            if req.path() == "/second" {
+                second_started.store(true, Ordering::SeqCst);
+                return next.run(req).await;
+            }
+
+            let start = std::time::Instant::now();
+            while !second_started.load(Ordering::SeqCst) {
+                if start.elapsed() > Duration::from_secs(5) {
+                    return mini_async_http::ResponseBuilder::empty_503()
+                        .build()
+                        .unwrap();
+                }
+                mini_async_http::yield_now().await;
+            }
+
+            next.run(req).await
+        })
+    }));
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let response = raw_request(
+        server.addr().to_string().as_str(),
+        b"GET /first HTTP/1.1\r\n\r\nGET /second HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+
+    assert_eq!(response.matches("HTTP/1.1 200").count(), 2);
+    assert!(response.find("/first").unwrap() < response.find("/second").unwrap());
+}
+
+#[test]
+fn pause_accepting() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+    let addr = server.addr();
+
+    let mut established = TcpStream::connect(addr).unwrap();
+    established.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut buf = [0; 1024];
+    assert!(established.read(&mut buf).unwrap() > 0);
+
+    server.handle().pause_accepting();
+    assert!(server.handle().is_accepting_paused());
+
+    // A new connection waits in the backlog, the established one is still served
+    let mut pending = TcpStream::connect(addr).unwrap();
+    pending
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    pending
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    assert!(pending.read(&mut buf).is_err());
+
+    established.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    assert!(established.read(&mut buf).unwrap() > 0);
+
+    server.handle().resume_accepting();
+    pending.set_read_timeout(None).unwrap();
+    let mut response = String::new();
+    pending.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+}
+
+#[test]
+fn drain_mode() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut buf = [0; 1024];
+    let read = stream.read(&mut buf).unwrap();
+    let response = String::from_utf8_lossy(&buf[..read]).to_string();
+    assert!(!response.contains("connection: close"));
+
+    server.handle().start_draining();
+    assert!(server.handle().is_draining());
+
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("connection: close"));
+
+    server.handle().stop_draining();
+    let response = server.client().get("/").send();
+    assert_eq!(response.code(), 200);
+}
+
+#[test]
+fn default_headers() {
+    run_test_with(
+        |server| {
+            server.set_default_header("Server", "mini-async-http");
+            server.set_default_header("Content-Type", "application/octet-stream");
+        },
+        |config| {
+            let response = raw_request(
+                config.addr.as_str(),
+                b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+            );
+
+            assert!(response.contains("server: mini-async-http"));
+            assert!(response.contains("content-type: text/plain"));
+            assert!(!response.contains("application/octet-stream"));
+        },
+    )
+}
+
+#[test]
+fn error_pages() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |request| {
+        match request.path().as_str() {
+            "/panic" => panic!("Handler failure"),
+            "/text" => mini_async_http::ResponseBuilder::empty_404()
+                .text("missing")
+                .build()
+                .unwrap(),
+            _ => mini_async_http::ResponseBuilder::empty_404()
+                .build()
+                .unwrap(),
+        }
+    });
+    server.set_error_renderer(|page| page.render());
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let addr = server.addr().to_string();
+
+    let response = raw_request(&addr, b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(response.contains("content-type: application/json"));
+    assert!(response.ends_with(r#"{"status":404,"reason":"Not Found"}"#));
+
+    let response = raw_request(
+        &addr,
+        b"GET /missing HTTP/1.1\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.contains("content-type: text/html"));
+    assert!(response.contains("<h1>404 Not Found</h1>"));
+
+    let response = raw_request(&addr, b"GET /text HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(response.ends_with("missing"));
+
+    // The worker survives the panic
+    let response = raw_request(&addr, b"GET /panic HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 500"));
+    assert!(response.ends_with(r#"{"status":500,"reason":"Internal Server Error"}"#));
+
+    let response = raw_request(&addr, b"GET / HTTP/1.1\r\nInvalid Header\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert!(response.contains(r#""details":"#));
+}
+
+#[test]
+fn invalid_request_target() {
+    run_test(|config| {
+        let response = raw_request(config.addr.as_str(), b"GET /page#top HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 400"));
+
+        let response = raw_request(
+            config.addr.as_str(),
+            b"GET http://localhost/page?id=1 HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 200"));
+    });
+}
+
+#[test]
+fn max_connections_per_ip() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    server.set_max_connections_per_ip(1, mini_async_http::LimitAction::Reject);
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let addr = server.addr().to_string();
+
+    let mut first = TcpStream::connect(&addr).unwrap();
+    first.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut buf = [0; 1024];
+    assert!(first.read(&mut buf).unwrap() > 0);
+
+    let response = raw_request(&addr, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 503"));
+
+    // The slot of the client is released once its connection is closed
+    drop(first);
+    let start = std::time::Instant::now();
+    loop {
+        let response = raw_request(&addr, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        if response.starts_with("HTTP/1.1 200") {
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    server.set_max_connections_per_ip(1, mini_async_http::LimitAction::Close);
+    server.add_allowed_ip("127.0.0.1".parse().unwrap());
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let addr = server.addr().to_string();
+
+    let _first = TcpStream::connect(&addr).unwrap();
+    let response = raw_request(&addr, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200"));
+}
+
+#[test]
+fn accept_per_worker() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    server.set_accept_per_worker(true);
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let addr = server.addr().to_string();
+
+    for _ in 0..20 {
+        let response = raw_request(&addr, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"));
+    }
+
+    // Every accept loop is paused
+    server.handle().pause_accepting();
+    let mut pending = TcpStream::connect(&addr).unwrap();
+    pending
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    pending
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    let mut buf = [0; 1024];
+    assert!(pending.read(&mut buf).is_err());
+
+    server.handle().resume_accepting();
+    pending.set_read_timeout(None).unwrap();
+    let mut response = String::new();
+    pending.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+}
+
+#[test]
+fn background_jobs() {
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    let counter = ticks.clone();
+    server.add_background_job(move || {
+        let counter = counter.clone();
+        async move {
+            let mut interval = mini_async_http::runtime::interval(Duration::from_millis(10));
+            loop {
+                interval.tick().await;
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    });
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let start = std::time::Instant::now();
+    while ticks.load(Ordering::SeqCst) < 3 {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // The job is stopped with the server
+    drop(server);
+    let stopped = ticks.load(Ordering::SeqCst);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(ticks.load(Ordering::SeqCst), stopped);
+}
+
+#[test]
+fn cancelled_on_shutdown() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    let (started, wait_started) = mpsc::channel();
+    let started = Mutex::new(started);
+    let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let flag = cancelled.clone();
+    server.add_middleware(mini_async_http::AsyncMiddleware::new(move |req, _| {
+        let token = req.cancellation().unwrap();
+        let started = started.lock().unwrap().clone();
+        let flag = flag.clone();
+        Box::pin(async move {
+            started.send(()).unwrap();
+            token.cancelled().await;
+            flag.store(true, Ordering::SeqCst);
+            mini_async_http::ResponseBuilder::empty_503()
+                .build()
+                .unwrap()
+        })
+    }));
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let addr = server.addr().to_string();
+    let client = std::thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        response
+    });
+    wait_started.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    // The handler waiting for its token ends with the server
+    drop(server);
+    assert!(cancelled.load(Ordering::SeqCst));
+    assert!(client.join().unwrap().starts_with("HTTP/1.1 503"));
+}
+
+#[test]
+fn half_closed_client() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler_basic);
+    // The handler is still running when the end of the requests is read
+    server.add_middleware(mini_async_http::AsyncMiddleware::new(|req, next| {
+        Box::pin(async move {
+            mini_async_http::runtime::sleep(Duration::from_millis(50)).await;
+            next.run(req).await
+        })
+    }));
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\n\r\nPOST / HTTP/1.1\r\nContent-Length: 2\r\n\r\nab")
+        .unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 2);
+    assert!(response.ends_with("POST"));
+}
+
+#[test]
+fn half_closed_client_concurrent() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .body(&vec![b'a'; 4 * 1024 * 1024])
+            .build()
+            .unwrap()
+    });
+    server.set_concurrent_pipeline(true);
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    // The responses are sent while the client does not read
+    std::thread::sleep(Duration::from_millis(100));
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    assert!(response.len() > 8 * 1024 * 1024);
+}
+
+#[test]
+fn close_with_unread_requests() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    let mut requests = b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n".to_vec();
+    requests.extend(b"GET / HTTP/1.1\r\n\r\n".repeat(2000));
+    stream.write_all(&requests).unwrap();
+    // The server closes the connection before the client reads
+    std::thread::sleep(Duration::from_millis(20));
+
+    // The ignored requests do not reset the connection
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("GET"));
+}
+
+#[test]
+fn https_redirect() {
+    let server = mini_async_http::AIOServer::https_redirect("127.0.0.1:0".parse().unwrap(), 8443);
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let addr = server.addr().to_string();
+
+    let response = raw_request(
+        &addr,
+        b"GET /Docs/Page?Token=AbC HTTP/1.1\r\nHost: Example.com:80\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 301 Moved Permanently"));
+    // The path and the query are case sensitive, they are kept as sent
+    assert!(response.contains("\r\nlocation: https://Example.com:8443/Docs/Page?Token=AbC\r\n"));
+
+    // A 301 would let the client repeat the POST as a GET, the characters unsafe in a URL are encoded
+    let response = raw_request(
+        &addr,
+        b"POST /form|send HTTP/1.1\r\nHost: example.com\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 308 Permanent Redirect"));
+    assert!(response.contains("\r\nlocation: https://example.com:8443/form%7Csend\r\n"));
+
+    let response = raw_request(&addr, b"GET / HTTP/1.0\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 400"));
+}
+
+#[test]
+fn upgrade_protocol() {
+    let server = mini_async_http::test::TestServer::new(|request| {
+        if request.upgrade() != Some("echo") {
+            return handler_basic(request);
+        }
+
+        mini_async_http::ResponseBuilder::switching_protocols("echo")
+            .upgrade(|mut tunnel| {
+                std::thread::spawn(move || {
+                    let mut buf = [0; 4];
+                    tunnel.read_exact(&mut buf).unwrap();
+                    tunnel.write_all(&buf).unwrap();
+                });
+            })
+            .build()
+            .unwrap()
+    });
+    let addr = server.addr().to_string();
+
+    // The bytes sent after the request belong to the new protocol
+    let response = raw_request(
+        &addr,
+        b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\nGET ",
+    );
+    assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(response.contains("\r\nupgrade: echo\r\n"));
+    assert!(response.ends_with("\r\n\r\nGET "));
+
+    // A refused upgrade keeps the connection on HTTP
+    let response = raw_request(
+        &addr,
+        b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: other\r\n\r\nPOST / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 2);
+    assert!(response.ends_with("POST"));
+}
+
+#[test]
+fn upgrades_keep_workers_free() {
+    let server = mini_async_http::test::TestServer::new(|request| {
+        if request.upgrade() != Some("echo") {
+            return handler_basic(request);
+        }
+
+        // The handler serves the protocol until the connection is closed instead of moving it to a thread
+        mini_async_http::ResponseBuilder::switching_protocols("echo")
+            .upgrade(|mut tunnel| {
+                let mut buf = [0; 4];
+                while tunnel.read_exact(&mut buf).is_ok() && tunnel.write_all(&buf).is_ok() {}
+            })
+            .build()
+            .unwrap()
+    });
+    assert!(server.handle().resize_pool(1));
+    let addr = server.addr().to_string();
+
+    let upgraded: Vec<TcpStream> = (0..3)
+        .map(|_| {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\nping")
+                .unwrap();
+
+            let mut response = Vec::new();
+            let mut buf = [0; 256];
+            while !response.ends_with(b"\r\n\r\nping") {
+                let n = stream.read(&mut buf).unwrap();
+                assert!(n > 0);
+                response.extend_from_slice(&buf[..n]);
+            }
+            stream
+        })
+        .collect();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    drop(upgraded);
+}
+
+#[test]
+fn websocket_handshake() {
+    let server = mini_async_http::test::TestServer::new(|request| {
+        // The protocol names are compared ignoring case
+        let websocket = matches!(request.upgrade(), Some(protocol) if protocol.eq_ignore_ascii_case("websocket"));
+        // The key of the RFC 6455 example, any change to its case would give another accept value
+        let key = request.headers().get_header("Sec-WebSocket-Key");
+        if !websocket || key.map(String::as_str) != Some("dGhlIHNhbXBsZSBub25jZQ==") {
+            return mini_async_http::ResponseBuilder::empty_400()
+                .build()
+                .unwrap();
+        }
+
+        mini_async_http::ResponseBuilder::switching_protocols("websocket")
+            .header("Sec-WebSocket-Accept", "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+            .upgrade(|mut tunnel| {
+                std::thread::spawn(move || {
+                    let mut frame = [0; 6];
+                    tunnel.read_exact(&mut frame).unwrap();
+                    // Close frame with an empty payload, sent unmasked by the server
+                    tunnel.write_all(&[0x88, 0x00]).unwrap();
+                });
+            })
+            .build()
+            .unwrap()
+    });
+    let addr = server.addr().to_string();
+
+    // The key and the accept value are base64, their case must be kept (RFC 6455 section 1.3)
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .write_all(
+            b"GET /chat HTTP/1.1\r\nHost: server.example.com\r\nUpgrade: WebSocket\r\nConnection: Upgrade\r\n\
+              Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        )
+        .unwrap();
+    // Close frame with an empty payload and a zero mask, as a client masks its frames
+    stream.write_all(&[0x88, 0x80, 0, 0, 0, 0]).unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let (head, frame) = response.split_at(response.len() - 2);
+    let head = String::from_utf8(head.to_vec()).unwrap();
+    assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(head.contains("\r\nupgrade: websocket\r\n"));
+    assert!(head.contains("\r\nsec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    assert!(head.ends_with("\r\n\r\n"));
+    assert_eq!(frame, [0x88, 0x00]);
+}
+
+#[test]
+fn smuggling_rejected() {
+    let server = mini_async_http::test::TestServer::new(handler_basic);
+    let addr = server.addr().to_string();
+
+    // The second request hidden in the body would be answered by a server reading the chunked body as empty
+    let response = raw_request(
+        &addr,
+        b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\n0\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert_eq!(response.matches("HTTP/1.1").count(), 1);
+
+    let response = raw_request(
+        &addr,
+        b"GET / HTTP/1.1\r\nX-Header: a\r\n\tb\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert_eq!(response.matches("HTTP/1.1").count(), 1);
+}
+
+#[test]
+fn normalized_paths() {
+    let handler = |request: &Request| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .text(request.path())
+            .build()
+            .unwrap()
+    };
+    let server = mini_async_http::test::TestServer::new(handler);
+    let client = server.client();
+
+    let response = client
+        .get("/public/%2e%2e/admin//users/./42?next=/../x")
+        .send();
+    assert_eq!(response.body().unwrap(), b"/admin/users/42?next=/../x");
+
+    let response = raw_request(
+        &server.addr().to_string(),
+        b"GET /a%zz HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 400"));
+
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), handler);
+    server.set_merge_slashes(false);
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let response = server.client().get("//a/../b").send();
+    assert_eq!(response.body().unwrap(), b"//b");
+}
+
+#[test]
+fn reload_config() {
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .build()
+            .unwrap()
+    });
+    server.set_default_header("Server", "first");
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let response = server.client().get("/").send();
+    assert_eq!(response.headers().get_header("Server").unwrap(), "first");
+
+    let config = server
+        .handle()
+        .config()
+        .default_header("Server", "second")
+        .max_requests_per_connection(Some(1));
+    server.handle().reload_config(config);
+
+    let response = raw_request(
+        server.addr().to_string().as_str(),
+        b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+    );
+    assert_eq!(response.matches("HTTP/1.1 200").count(), 1);
+    assert!(response.contains("server: second"));
+    assert!(response.contains("connection: close"));
+}
+
+#[test]
+fn duplex_connection() {
+    let (mut client, server) = mini_async_http::duplex(64);
+
+    let served = std::thread::spawn(move || {
+        futures::executor::block_on(async {
+            let mut connection = mini_async_http::HttpConnection::new(server);
+            let mut served = 0;
+            while let Some(request) = connection.next_request().await.unwrap() {
+                served += 1;
+                let response = mini_async_http::ResponseBuilder::empty_200()
+                    .body(request.path().as_bytes())
+                    .build()
+                    .unwrap();
+                connection.send_response(&request, response).await.unwrap();
+            }
+            served
+        })
+    });
+
+    client
+        .write_all(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\nGET /c HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+
+    assert_eq!(response.matches("HTTP/1.1 200").count(), 3);
+    assert!(response.ends_with("/c"));
+    assert_eq!(served.join().unwrap(), 3);
+}
+
+#[test]
+fn transcript() {
+    let dir = std::env::temp_dir().join(format!("transcripts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |request| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .body(request.path().as_bytes())
+            .build()
+            .unwrap()
+    });
+    server.set_transcript_dir(&dir);
+    let server = mini_async_http::test::TestServer::spawn(server);
+
+    let response = raw_request(
+        server.addr().to_string().as_str(),
+        b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+
+    let path = std::fs::read_dir(&dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let transcript = mini_async_http::Transcript::load(&path).unwrap();
+    assert_eq!(
+        transcript.received(),
+        b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\nConnection: close\r\n\r\n"
+    );
+    assert_eq!(transcript.sent(), response.as_bytes());
+
+    let replayed = transcript.replay_to(server.addr()).unwrap();
+    assert_eq!(
+        String::from_utf8(replayed)
+            .unwrap()
+            .matches("HTTP/1.1 200")
+            .count(),
+        2
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn lifecycle_events() {
+    use mini_async_http::{ServerEvent, ShutdownReason};
+
+    let server = mini_async_http::AIOServer::new("127.0.0.1:0".parse().unwrap(), |_| {
+        mini_async_http::ResponseBuilder::empty_200()
+            .build()
+            .unwrap()
+    });
+    let events = server.handle().subscribe();
+    let server = mini_async_http::test::TestServer::spawn(server);
+    let addr = server.addr();
+    drop(server);
+
+    let events: Vec<ServerEvent> = events.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            ServerEvent::Ready { addrs: vec![addr] },
+            ServerEvent::Draining {
+                connections: 0,
+                timeout: Duration::from_secs(0)
+            },
+            ServerEvent::Stopped {
+                reason: ShutdownReason::Requested,
+                abandoned: 0
+            },
+        ]
+    );
+}